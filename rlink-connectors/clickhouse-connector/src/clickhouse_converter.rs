@@ -0,0 +1,493 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use chrono::TimeZone;
+use chrono_tz::{Asia, Tz};
+use clickhouse_rs::types::Value;
+use clickhouse_rs::{Options, Pool};
+use rlink::api::element::{types, Record};
+use rlink::utils::get_runtime;
+
+use crate::clickhouse_sink::{CkBlock, ClickhouseBatch, ClickhouseConverter};
+
+/// the type of a clickhouse column, it's parsed from the type name of `DESCRIBE TABLE`
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnType {
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+    String,
+    /// converted from the timestamp of milliseconds
+    Date,
+    /// converted from the timestamp of milliseconds in the time zone of the column,
+    /// the time zone of the converter is used if it's not declared
+    DateTime(Option<Tz>),
+    /// the empty `BYTES` field is written as `NULL`
+    Nullable(Box<ColumnType>),
+    /// the values are written as the inner type, the server converts them to the dictionary
+    LowCardinality(Box<ColumnType>),
+}
+
+impl ColumnType {
+    /// the column type of the record field without conversion
+    pub fn from_data_type(data_type: u8) -> anyhow::Result<Self> {
+        match data_type {
+            types::I32 => Ok(ColumnType::Int32),
+            types::U32 => Ok(ColumnType::UInt32),
+            types::I64 => Ok(ColumnType::Int64),
+            types::U64 => Ok(ColumnType::UInt64),
+            types::F64 => Ok(ColumnType::Float64),
+            types::BYTES => Ok(ColumnType::String),
+            _ => Err(anyhow!("unsupported data type {}", data_type)),
+        }
+    }
+
+    /// the type without `Nullable` and `LowCardinality`, and whether it's nullable
+    fn get_value_type(&self) -> (&ColumnType, bool) {
+        match self {
+            ColumnType::Nullable(inner) => (inner.get_value_type().0, true),
+            ColumnType::LowCardinality(inner) => inner.get_value_type(),
+            _ => (self, false),
+        }
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(inner) = strip_wrapper(s, "Nullable") {
+            return Ok(ColumnType::Nullable(Box::new(inner.parse()?)));
+        }
+        if let Some(inner) = strip_wrapper(s, "LowCardinality") {
+            return Ok(ColumnType::LowCardinality(Box::new(inner.parse()?)));
+        }
+        // `DateTime('Asia/Shanghai')`
+        if let Some(inner) = strip_wrapper(s, "DateTime") {
+            let timezone = inner.trim().trim_matches('\'');
+            let timezone = Tz::from_str(timezone)
+                .map_err(|e| anyhow!("unsupported time zone {}. {}", inner, e))?;
+            return Ok(ColumnType::DateTime(Some(timezone)));
+        }
+
+        match s {
+            "UInt8" => Ok(ColumnType::UInt8),
+            "UInt16" => Ok(ColumnType::UInt16),
+            "UInt32" => Ok(ColumnType::UInt32),
+            "UInt64" => Ok(ColumnType::UInt64),
+            "Int8" => Ok(ColumnType::Int8),
+            "Int16" => Ok(ColumnType::Int16),
+            "Int32" => Ok(ColumnType::Int32),
+            "Int64" => Ok(ColumnType::Int64),
+            "Float32" => Ok(ColumnType::Float32),
+            "Float64" => Ok(ColumnType::Float64),
+            "String" => Ok(ColumnType::String),
+            "Date" => Ok(ColumnType::Date),
+            "DateTime" => Ok(ColumnType::DateTime(None)),
+            _ => Err(anyhow!("unsupported clickhouse type {}", s)),
+        }
+    }
+}
+
+fn strip_wrapper<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    s.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClickhouseColumn {
+    pub name: String,
+    pub column_type: ColumnType,
+}
+
+impl ClickhouseColumn {
+    pub fn new(name: &str, column_type: ColumnType) -> Self {
+        ClickhouseColumn {
+            name: name.to_string(),
+            column_type,
+        }
+    }
+}
+
+/// the insertable columns of the table in the order of the table,
+/// the `MATERIALIZED` and `ALIAS` columns are skipped
+pub async fn describe_table(url: &str, table: &str) -> anyhow::Result<Vec<ClickhouseColumn>> {
+    let pool = Pool::new(Options::from_str(url)?);
+    let mut client = pool.get_handle().await?;
+    let block = client
+        .query(format!("DESCRIBE TABLE {}", table))
+        .fetch_all()
+        .await?;
+
+    let mut columns = Vec::new();
+    for row in block.rows() {
+        let name: String = row.get("name")?;
+        let type_name: String = row.get("type")?;
+        let default_type: String = row.get("default_type")?;
+        if default_type == "MATERIALIZED" || default_type == "ALIAS" {
+            continue;
+        }
+
+        columns.push(ClickhouseColumn::new(name.as_str(), type_name.parse()?));
+    }
+
+    Ok(columns)
+}
+
+/// a `ClickhouseConverter` driven by the schema of the record, the n-th field of the record
+/// is written to the n-th column
+pub struct SchemaConverter {
+    data_types: Arc<Vec<u8>>,
+    /// it's empty until the table is described if the columns are not declared
+    columns: Arc<Vec<ClickhouseColumn>>,
+    timezone: Tz,
+}
+
+impl SchemaConverter {
+    pub fn new(data_types: &[u8], columns: Vec<ClickhouseColumn>) -> Self {
+        assert_eq!(
+            data_types.len(),
+            columns.len(),
+            "the number of columns is not equal to the number of fields"
+        );
+
+        SchemaConverter {
+            data_types: Arc::new(data_types.to_vec()),
+            columns: Arc::new(columns),
+            timezone: Asia::Shanghai,
+        }
+    }
+
+    /// the types of the columns are the same as the fields of the record
+    pub fn with_column_names(data_types: &[u8], column_names: &[&str]) -> Self {
+        assert_eq!(
+            data_types.len(),
+            column_names.len(),
+            "the number of columns is not equal to the number of fields"
+        );
+
+        let columns = data_types
+            .iter()
+            .zip(column_names.iter())
+            .map(|(data_type, name)| {
+                let column_type = ColumnType::from_data_type(*data_type)
+                    .expect("unsupported data type of the record");
+                ClickhouseColumn::new(name, column_type)
+            })
+            .collect();
+        SchemaConverter::new(data_types, columns)
+    }
+
+    /// the columns are introspected by `DESCRIBE TABLE` when the sink is opened
+    pub fn describe(data_types: &[u8]) -> Self {
+        SchemaConverter {
+            data_types: Arc::new(data_types.to_vec()),
+            columns: Arc::new(Vec::new()),
+            timezone: Asia::Shanghai,
+        }
+    }
+
+    /// the time zone to convert the timestamp to the `Date` columns and
+    /// the `DateTime` columns without time zone
+    pub fn set_timezone(&mut self, timezone: Tz) {
+        self.timezone = timezone;
+    }
+}
+
+impl ClickhouseConverter for SchemaConverter {
+    fn open(&mut self, url: &str, table: &str) {
+        if !self.columns.is_empty() {
+            return;
+        }
+
+        let columns = get_runtime()
+            .block_on(describe_table(url, table))
+            .expect("describe clickhouse table error");
+        info!("describe clickhouse table {}: {:?}", table, columns);
+
+        assert_eq!(
+            self.data_types.len(),
+            columns.len(),
+            "the number of columns is not equal to the number of fields"
+        );
+        self.columns = Arc::new(columns);
+    }
+
+    fn create_batch(&self, batch_size: usize) -> Box<dyn ClickhouseBatch> {
+        Box::new(SchemaBatch {
+            data_types: self.data_types.clone(),
+            columns: self.columns.clone(),
+            timezone: self.timezone,
+            batch_size,
+            block: CkBlock::with_capacity(batch_size),
+        })
+    }
+}
+
+/// a field of the record
+enum Field<'a> {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bytes(&'a [u8]),
+}
+
+impl<'a> Field<'a> {
+    fn to_i64(&self) -> anyhow::Result<Option<i64>> {
+        match self {
+            Field::Int(v) => Ok(Some(*v)),
+            Field::UInt(v) => Ok(Some(i64::try_from(*v)?)),
+            // the upper bound 2^63 is exclusive
+            Field::Float(v) if v.is_finite() && *v >= -(2f64.powi(63)) && *v < 2f64.powi(63) => {
+                Ok(Some(*v as i64))
+            }
+            Field::Float(v) => Err(anyhow!("{} is out of the range of Int64", v)),
+            Field::Bytes(v) => parse(v),
+        }
+    }
+
+    fn to_u64(&self) -> anyhow::Result<Option<u64>> {
+        match self {
+            Field::Int(v) => Ok(Some(u64::try_from(*v)?)),
+            Field::UInt(v) => Ok(Some(*v)),
+            // the upper bound 2^64 is exclusive
+            Field::Float(v) if v.is_finite() && *v >= 0f64 && *v < 2f64.powi(64) => {
+                Ok(Some(*v as u64))
+            }
+            Field::Float(v) => Err(anyhow!("{} is out of the range of UInt64", v)),
+            Field::Bytes(v) => parse(v),
+        }
+    }
+
+    fn to_f32(&self) -> anyhow::Result<Option<f32>> {
+        match self.to_f64()? {
+            Some(v) if v.is_finite() && v.abs() > f32::MAX as f64 => {
+                Err(anyhow!("{} is out of the range of Float32", v))
+            }
+            v => Ok(v.map(|v| v as f32)),
+        }
+    }
+
+    fn to_f64(&self) -> anyhow::Result<Option<f64>> {
+        match self {
+            Field::Int(v) => Ok(Some(*v as f64)),
+            Field::UInt(v) => Ok(Some(*v as f64)),
+            Field::Float(v) => Ok(Some(*v)),
+            Field::Bytes(v) => parse(v),
+        }
+    }
+
+    fn to_text(&self, nullable: bool) -> anyhow::Result<Option<String>> {
+        match self {
+            Field::Int(v) => Ok(Some(v.to_string())),
+            Field::UInt(v) => Ok(Some(v.to_string())),
+            Field::Float(v) => Ok(Some(v.to_string())),
+            Field::Bytes(v) if v.is_empty() && nullable => Ok(None),
+            Field::Bytes(v) => Ok(Some(String::from_utf8(v.to_vec())?)),
+        }
+    }
+}
+
+/// narrow the value to the type of the column, the out of range value is an error
+fn narrow<S, T>(value: Option<S>) -> anyhow::Result<Option<T>>
+where
+    T: TryFrom<S>,
+    T::Error: std::error::Error + Send + Sync + 'static,
+{
+    Ok(value.map(T::try_from).transpose()?)
+}
+
+/// the empty bytes is `None`
+fn parse<T>(bytes: &[u8]) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+    let value = std::str::from_utf8(bytes)?.trim().parse()?;
+    Ok(Some(value))
+}
+
+fn to_value<T>(value: Option<T>, nullable: bool) -> anyhow::Result<Value>
+where
+    Value: From<T> + From<Option<T>>,
+{
+    if nullable {
+        return Ok(Value::from(value));
+    }
+    value
+        .map(Value::from)
+        .ok_or_else(|| anyhow!("the value of non-nullable column is empty"))
+}
+
+struct SchemaBatch {
+    data_types: Arc<Vec<u8>>,
+    columns: Arc<Vec<ClickhouseColumn>>,
+    timezone: Tz,
+    batch_size: usize,
+    block: CkBlock,
+}
+
+impl SchemaBatch {
+    fn to_row(&self, record: &mut Record) -> anyhow::Result<Vec<(String, Value)>> {
+        let mut reader = record.get_reader(self.data_types.as_slice());
+        let mut row = Vec::with_capacity(self.columns.len());
+        for (index, column) in self.columns.iter().enumerate() {
+            let field = match self.data_types[index] {
+                types::I32 => Field::Int(reader.get_i32(index)? as i64),
+                types::U32 => Field::UInt(reader.get_u32(index)? as u64),
+                types::I64 => Field::Int(reader.get_i64(index)?),
+                types::U64 => Field::UInt(reader.get_u64(index)?),
+                types::F64 => Field::Float(reader.get_f64(index)?),
+                types::BYTES => Field::Bytes(reader.get_bytes(index)?),
+                data_type => return Err(anyhow!("unsupported data type {}", data_type)),
+            };
+
+            let value = self
+                .convert(&field, &column.column_type)
+                .map_err(|e| anyhow!("convert the column {} error. {}", column.name, e))?;
+            row.push((column.name.clone(), value));
+        }
+
+        Ok(row)
+    }
+
+    fn convert(&self, field: &Field, column_type: &ColumnType) -> anyhow::Result<Value> {
+        let (value_type, nullable) = column_type.get_value_type();
+        match value_type {
+            ColumnType::UInt8 => to_value(narrow::<_, u8>(field.to_u64()?)?, nullable),
+            ColumnType::UInt16 => to_value(narrow::<_, u16>(field.to_u64()?)?, nullable),
+            ColumnType::UInt32 => to_value(narrow::<_, u32>(field.to_u64()?)?, nullable),
+            ColumnType::UInt64 => to_value(field.to_u64()?, nullable),
+            ColumnType::Int8 => to_value(narrow::<_, i8>(field.to_i64()?)?, nullable),
+            ColumnType::Int16 => to_value(narrow::<_, i16>(field.to_i64()?)?, nullable),
+            ColumnType::Int32 => to_value(narrow::<_, i32>(field.to_i64()?)?, nullable),
+            ColumnType::Int64 => to_value(field.to_i64()?, nullable),
+            ColumnType::Float32 => to_value(field.to_f32()?, nullable),
+            ColumnType::Float64 => to_value(field.to_f64()?, nullable),
+            ColumnType::String => to_value(field.to_text(nullable)?, nullable),
+            ColumnType::Date => {
+                let date = field
+                    .to_i64()?
+                    .map(|ts| self.timezone.timestamp_millis(ts).date());
+                to_value(date, nullable)
+            }
+            ColumnType::DateTime(timezone) => {
+                let timezone = timezone.unwrap_or(self.timezone);
+                let date_time = field.to_i64()?.map(|ts| timezone.timestamp_millis(ts));
+                to_value(date_time, nullable)
+            }
+            ColumnType::Nullable(_) | ColumnType::LowCardinality(_) => unreachable!(),
+        }
+    }
+}
+
+impl ClickhouseBatch for SchemaBatch {
+    fn append(&mut self, mut record: Record) -> anyhow::Result<()> {
+        let row = self.to_row(&mut record)?;
+        self.block.push(row)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> CkBlock {
+        std::mem::replace(&mut self.block, CkBlock::with_capacity(self.batch_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clickhouse_converter::{ClickhouseColumn, ColumnType, SchemaConverter};
+    use crate::clickhouse_sink::ClickhouseConverter;
+    use chrono_tz::Europe;
+    use rlink::api::element::{types, Record};
+
+    #[test]
+    pub fn column_type_test() {
+        assert_eq!("Int64".parse::<ColumnType>().unwrap(), ColumnType::Int64);
+        assert_eq!(
+            "DateTime('Europe/London')".parse::<ColumnType>().unwrap(),
+            ColumnType::DateTime(Some(Europe::London))
+        );
+        assert_eq!(
+            "DateTime".parse::<ColumnType>().unwrap(),
+            ColumnType::DateTime(None)
+        );
+        assert!("DateTime('Mars/Olympus')".parse::<ColumnType>().is_err());
+        assert_eq!(
+            "LowCardinality(Nullable(String))"
+                .parse::<ColumnType>()
+                .unwrap(),
+            ColumnType::LowCardinality(Box::new(ColumnType::Nullable(Box::new(
+                ColumnType::String
+            ))))
+        );
+        assert!("DateTime64(3)".parse::<ColumnType>().is_err());
+        assert!("Array(String)".parse::<ColumnType>().is_err());
+    }
+
+    #[test]
+    pub fn schema_converter_test() {
+        let data_types = [types::I64, types::U32, types::BYTES, types::BYTES];
+        let columns = vec![
+            ClickhouseColumn::new("ts", "DateTime".parse().unwrap()),
+            ClickhouseColumn::new("code", ColumnType::UInt16),
+            ClickhouseColumn::new("name", "LowCardinality(String)".parse().unwrap()),
+            ClickhouseColumn::new("score", "Nullable(Float64)".parse().unwrap()),
+        ];
+        let converter = SchemaConverter::new(&data_types, columns);
+        let mut batch = converter.create_batch(10);
+
+        for n in 0..3 {
+            let mut record = Record::new();
+            let mut writer = record.get_writer(&data_types);
+            writer.set_i64(1609430400000 + n * 1000).unwrap();
+            writer.set_u32(200).unwrap();
+            writer.set_str(format!("name-{}", n).as_str()).unwrap();
+            writer.set_str(if n == 0 { "" } else { "0.5" }).unwrap();
+            batch.append(record).unwrap();
+        }
+
+        let block = batch.flush();
+        assert_eq!(block.row_count(), 3);
+        assert_eq!(block.column_count(), 4);
+        assert_eq!(batch.flush().row_count(), 0);
+    }
+
+    #[test]
+    pub fn out_of_range_test() {
+        let data_types = [types::I64, types::F64];
+        let columns = vec![
+            ClickhouseColumn::new("code", ColumnType::UInt8),
+            ClickhouseColumn::new("score", ColumnType::Int16),
+        ];
+        let converter = SchemaConverter::new(&data_types, columns);
+        let mut batch = converter.create_batch(10);
+
+        let create_record = |code: i64, score: f64| {
+            let mut record = Record::new();
+            let mut writer = record.get_writer(&data_types);
+            writer.set_i64(code).unwrap();
+            writer.set_f64(score).unwrap();
+            record
+        };
+
+        assert!(batch.append(create_record(255, -32768f64)).is_ok());
+        // the negative and out of range values are never wrapped
+        assert!(batch.append(create_record(-1, 0f64)).is_err());
+        assert!(batch.append(create_record(256, 0f64)).is_err());
+        assert!(batch.append(create_record(0, 32768f64)).is_err());
+        assert!(batch.append(create_record(0, f64::NAN)).is_err());
+        assert_eq!(batch.flush().row_count(), 1);
+    }
+}
@@ -1,7 +1,8 @@
 use std::borrow::BorrowMut;
 use std::str::FromStr;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use clickhouse_rs::{ClientHandle, Options, Pool};
 use rlink::api::element::Record;
@@ -9,31 +10,113 @@ use rlink::api::function::Context;
 use rlink::api::function::Function;
 use rlink::api::output::OutputFormat;
 use rlink::channel::mb;
-use rlink::metrics::Tag;
+use rlink::metrics::{register_counter, Tag};
 use rlink::utils;
 use rlink::utils::get_runtime;
 use rlink::utils::handover::Handover;
 
 pub type CkBlock = clickhouse_rs::Block;
 
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+const DEFAULT_FLUSH_TIMEOUT: Duration = Duration::from_secs(120);
+
 pub trait ClickhouseConverter: Send + Sync {
+    /// called once when the sink is opened, before any batch is created
+    fn open(&mut self, _url: &str, _table: &str) {}
+
     fn create_batch(&self, batch_size: usize) -> Box<dyn ClickhouseBatch>;
 }
 
 pub trait ClickhouseBatch: Send + Sync {
-    fn append(&mut self, record: Record);
+    /// the record is not appended if it can't be converted
+    fn append(&mut self, record: Record) -> anyhow::Result<()>;
     fn flush(&mut self) -> CkBlock;
 }
 
-#[derive(Function)]
-pub struct ClickhouseSink {
-    url: String,
+/// the retries of a failed insert, the backoff is doubled after each retry
+#[derive(Clone, Debug)]
+pub(crate) struct RetryBackoff {
+    max_retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryBackoff {
+    /// the backoff before the `retries + 1` retry
+    fn get_backoff(&self, retries: u32) -> Duration {
+        let backoff = self.backoff * 2u32.saturating_pow(retries.min(31));
+        backoff.min(self.max_backoff)
+    }
+}
+
+/// the options of the background tasks
+#[derive(Clone, Debug)]
+pub(crate) struct SinkOptions {
     table: String,
     batch_size: usize,
     batch_timeout: Duration,
+    retry_backoff: RetryBackoff,
+}
+
+/// the progress of the records handed over to the background tasks
+#[derive(Debug, Default)]
+pub(crate) struct SinkProgress {
+    /// the number of the inserted records
+    acknowledged: AtomicU64,
+    /// the barrier is waiting, the partial batches are inserted without waiting the timeout
+    flush_requested: AtomicBool,
+    /// the number of the running background tasks
+    running_tasks: AtomicUsize,
+    /// the batches failed after all retries, they are kept and retried before the new records,
+    /// so the checkpoint can't be completed until they are inserted
+    failed_batches: Mutex<Vec<Vec<Record>>>,
+    failed_rows: Arc<AtomicU64>,
+    /// the records can't be converted are dropped, they're acknowledged without inserting
+    dropped_rows: Arc<AtomicU64>,
+    retried_rows: Arc<AtomicU64>,
+}
+
+impl SinkProgress {
+    fn take_failed_batch(&self) -> Option<Vec<Record>> {
+        self.failed_batches.lock().unwrap().pop()
+    }
+
+    fn add_failed_batch(&self, records: Vec<Record>) {
+        self.failed_rows
+            .fetch_add(records.len() as u64, Ordering::Relaxed);
+        self.failed_batches.lock().unwrap().push(records);
+    }
+}
+
+/// decrease the running tasks when the background task is exited or panicked
+struct RunningTask {
+    progress: Arc<SinkProgress>,
+}
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        self.progress.running_tasks.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// insert the records to clickhouse by the background tasks, the records before the barrier
+/// are inserted before the checkpoint, so it's at-least-once.
+/// the records can't be converted by the `ClickhouseConverter` are dropped and counted
+/// by `ClickhouseSink_DroppedRows`, they're never retried
+#[derive(Function)]
+pub struct ClickhouseSink {
+    url: String,
     tasks: usize,
+    options: SinkOptions,
     converter: Arc<Box<dyn ClickhouseConverter>>,
     handover: Option<Handover>,
+
+    flush_timeout: Duration,
+    /// the number of the records handed over
+    written: u64,
+    progress: Arc<SinkProgress>,
 }
 
 impl ClickhouseSink {
@@ -47,12 +130,64 @@ impl ClickhouseSink {
     ) -> Self {
         ClickhouseSink {
             url: url.to_string(),
-            table: table.to_string(),
-            batch_size,
-            batch_timeout,
             tasks,
+            options: SinkOptions {
+                table: table.to_string(),
+                batch_size,
+                batch_timeout,
+                retry_backoff: RetryBackoff {
+                    max_retries: DEFAULT_MAX_RETRIES,
+                    backoff: DEFAULT_RETRY_BACKOFF,
+                    max_backoff: DEFAULT_MAX_RETRY_BACKOFF,
+                },
+            },
             converter: Arc::new(builder),
             handover: None,
+            flush_timeout: DEFAULT_FLUSH_TIMEOUT,
+            written: 0,
+            progress: Arc::new(SinkProgress::default()),
+        }
+    }
+
+    /// retry a failed insert `max_retries` times, the backoff begins with `backoff`
+    /// and is doubled after each retry until `max_backoff`
+    pub fn set_retry(&mut self, max_retries: u32, backoff: Duration, max_backoff: Duration) {
+        self.options.retry_backoff = RetryBackoff {
+            max_retries,
+            backoff,
+            max_backoff,
+        };
+    }
+
+    /// the checkpoint is failed if the records are not inserted in `flush_timeout`
+    pub fn set_flush_timeout(&mut self, flush_timeout: Duration) {
+        self.flush_timeout = flush_timeout;
+    }
+
+    /// wait until all the records handed over are inserted
+    fn wait_acknowledged(&self) -> anyhow::Result<()> {
+        let begin = Instant::now();
+        loop {
+            let acknowledged = self.progress.acknowledged.load(Ordering::SeqCst);
+            if acknowledged >= self.written {
+                return Ok(());
+            }
+
+            if self.progress.running_tasks.load(Ordering::SeqCst) == 0 {
+                return Err(anyhow::Error::msg(
+                    "all background tasks of the clickhouse sink are exited",
+                ));
+            }
+
+            if begin.elapsed() > self.flush_timeout {
+                return Err(anyhow::Error::msg(format!(
+                    "{} records are not inserted in {:?}",
+                    self.written - acknowledged,
+                    self.flush_timeout
+                )));
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
         }
     }
 }
@@ -66,7 +201,23 @@ impl OutputFormat for ClickhouseSink {
                 format!("{}", context.task_number),
             ),
         ];
-        self.handover = Some(Handover::new(self.get_name(), tags, 100000, mb(10)));
+        self.handover = Some(Handover::new(self.get_name(), tags.clone(), 100000, mb(10)));
+
+        register_counter(
+            "ClickhouseSink_FailedRows",
+            tags.clone(),
+            self.progress.failed_rows.clone(),
+        );
+        register_counter(
+            "ClickhouseSink_RetriedRows",
+            tags.clone(),
+            self.progress.retried_rows.clone(),
+        );
+        register_counter(
+            "ClickhouseSink_DroppedRows",
+            tags,
+            self.progress.dropped_rows.clone(),
+        );
 
         let urls: Vec<&str> = self.url.split(",").collect();
         let url = if urls.len() > 1 {
@@ -78,15 +229,20 @@ impl OutputFormat for ClickhouseSink {
         };
         info!("location clickhouse database url:{} from {}", url, self.url);
 
+        Arc::get_mut(&mut self.converter)
+            .expect("the converter is shared before open")
+            .open(url.as_str(), self.options.table.as_str());
+
         let mut task = ClickhouseSinkTask::new(
             url.as_str(),
-            self.table.clone(),
-            self.batch_size,
-            self.batch_timeout,
+            self.options.clone(),
             self.converter.clone(),
             self.handover.as_ref().unwrap().clone(),
+            self.progress.clone(),
         );
         let tasks = self.tasks;
+        // counted before spawning, so the tasks are never seen as exited before they start
+        self.progress.running_tasks.store(tasks, Ordering::SeqCst);
         utils::spawn("clickhouse-sink-block", move || {
             get_runtime().block_on(async {
                 task.run(tasks).await;
@@ -95,40 +251,53 @@ impl OutputFormat for ClickhouseSink {
     }
 
     fn write_record(&mut self, record: Record) {
+        if self.progress.running_tasks.load(Ordering::SeqCst) == 0 {
+            panic!("all background tasks of the clickhouse sink are exited");
+        }
+
         self.handover.as_ref().unwrap().produce_always(record);
+        self.written += 1;
     }
 
     fn close(&mut self) {}
+
+    /// wait until all the records handed over are inserted, the checkpoint is failed if
+    /// they are not inserted in `flush_timeout` or the background tasks are exited.
+    /// the failed batches are retried, so a later checkpoint is completed after they're inserted
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.progress.flush_requested.store(true, Ordering::SeqCst);
+        let result = self.wait_acknowledged();
+        self.progress.flush_requested.store(false, Ordering::SeqCst);
+
+        result
+    }
 }
 
 #[derive(Clone)]
 pub struct ClickhouseSinkTask {
     pool: Pool,
-    table: String,
-    batch_size: usize,
-    batch_timeout: Duration,
+    options: SinkOptions,
     converter: Arc<Box<dyn ClickhouseConverter>>,
     handover: Handover,
+    progress: Arc<SinkProgress>,
 }
 
 impl ClickhouseSinkTask {
-    pub fn new(
+    pub(crate) fn new(
         url: &str,
-        table: String,
-        batch_size: usize,
-        batch_timeout: Duration,
-        builder: Arc<Box<dyn ClickhouseConverter>>,
+        options: SinkOptions,
+        converter: Arc<Box<dyn ClickhouseConverter>>,
         handover: Handover,
+        progress: Arc<SinkProgress>,
     ) -> Self {
         let opts = Options::from_str(url).expect("parse clickhouse url error");
         let pool = Pool::new(opts);
         ClickhouseSinkTask {
             pool,
-            table,
-            batch_size,
-            batch_timeout,
-            converter: builder,
+            options,
+            converter,
             handover,
+            progress,
         }
     }
 
@@ -138,96 +307,202 @@ impl ClickhouseSinkTask {
             let mut self_clone = self.clone();
 
             let handler = tokio::spawn(async move {
-                match self_clone.run0().await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("run task error. {}", e);
-                    }
-                }
+                let _running_task = RunningTask {
+                    progress: self_clone.progress.clone(),
+                };
+                self_clone.run0().await;
             });
 
             join_handlers.push(handler);
         }
 
         for handler in join_handlers {
-            handler.await.unwrap();
+            if let Err(e) = handler.await {
+                error!("clickhouse sink task is exited. {}", e);
+            }
         }
     }
 
-    pub async fn run0(&mut self) -> anyhow::Result<()> {
-        let mut client = self.pool.get_handle().await?;
+    pub async fn run0(&mut self) {
+        let mut client = self.get_handle().await;
         loop {
-            match self.batch_send(client.borrow_mut()).await {
-                Ok(len) => {
-                    if len == 0 {
-                        tokio::time::delay_for(Duration::from_secs(1)).await;
-                    }
-                }
-                Err(e) => {
-                    error!("write clickhouse error. {}", e);
-
-                    // todo reconnection
-                    self.reconnection(client.borrow_mut()).await?;
-                }
+            let len = self.batch_send(client.borrow_mut()).await;
+            if len == 0 {
+                tokio::time::delay_for(Duration::from_secs(1)).await;
             }
         }
     }
 
-    async fn reconnection(&mut self, client: &mut ClientHandle) -> anyhow::Result<()> {
-        let mut err = None;
-        for _ in 0..180 {
-            tokio::time::delay_for(Duration::from_secs(1)).await;
-            match client.check_connection().await {
-                Ok(_) => {
-                    err = None;
-                    break;
-                }
+    /// connect to clickhouse, retry until it's connected
+    async fn get_handle(&self) -> ClientHandle {
+        let mut retries = 0;
+        loop {
+            match self.pool.get_handle().await {
+                Ok(client) => return client,
                 Err(e) => {
-                    error!("reconnection error. {}", e);
-                    err = Some(e)
+                    let backoff = self.options.retry_backoff.get_backoff(retries);
+                    error!("connect clickhouse error, retry after {:?}. {}", backoff, e);
+                    retries += 1;
+                    tokio::time::delay_for(backoff).await;
                 }
             }
         }
+    }
+
+    async fn batch_send(&mut self, client: &mut ClientHandle) -> usize {
+        let mut records = match self.progress.take_failed_batch() {
+            Some(records) => records,
+            None => self.poll_batch().await,
+        };
+
+        let size = records.len();
+        if size == 0 {
+            return 0;
+        }
+
+        let block = self.create_block(&mut records);
+        let rows = records.len();
+        if rows == 0 {
+            return size;
+        }
+
+        match self.insert(client, records.as_slice(), block).await {
+            Ok(_) => {
+                self.progress
+                    .acknowledged
+                    .fetch_add(rows as u64, Ordering::SeqCst);
+            }
+            Err(e) => {
+                error!(
+                    "write clickhouse error after {} retries, {} rows are kept to retry. {}",
+                    self.options.retry_backoff.max_retries, rows, e
+                );
+                self.progress.add_failed_batch(records);
+            }
+        }
+
+        size
+    }
 
-        match err {
-            Some(e) => Err(anyhow::Error::from(e)),
-            None => Ok(()),
+    /// convert the records to a block, the records can't be converted are removed,
+    /// they're counted as dropped and acknowledged
+    fn create_block(&self, records: &mut Vec<Record>) -> CkBlock {
+        let size = records.len();
+        let mut batch = self.converter.create_batch(size);
+        records.retain(|record| match batch.append(record.clone()) {
+            Ok(_) => true,
+            Err(e) => {
+                error!(
+                    "convert record to clickhouse row error, it's dropped. {}",
+                    e
+                );
+                false
+            }
+        });
+
+        let dropped = (size - records.len()) as u64;
+        if dropped > 0 {
+            self.progress
+                .dropped_rows
+                .fetch_add(dropped, Ordering::Relaxed);
+            self.progress
+                .acknowledged
+                .fetch_add(dropped, Ordering::SeqCst);
         }
+
+        batch.flush()
     }
 
-    async fn batch_send(&mut self, client: &mut ClientHandle) -> anyhow::Result<usize> {
-        let mut batch_block = self.converter.create_batch(self.batch_size);
+    /// rebuild the block consumed by the failed insert, the records have been converted
+    fn rebuild_block(&self, records: &[Record]) -> anyhow::Result<CkBlock> {
+        let mut batch = self.converter.create_batch(records.len());
+        for record in records {
+            batch.append(record.clone())?;
+        }
+        Ok(batch.flush())
+    }
+
+    async fn poll_batch(&mut self) -> Vec<Record> {
+        let mut records = Vec::new();
         let begin_timestamp = utils::date_time::current_timestamp();
-        let mut size = 0;
-        for n in 0..self.batch_size {
+        while records.len() < self.options.batch_size {
             match self.handover.poll_next() {
-                Ok(record) => {
-                    batch_block.append(record);
-                    size = n;
-                }
+                Ok(record) => records.push(record),
                 Err(_e) => {
+                    if self.progress.flush_requested.load(Ordering::SeqCst) {
+                        break;
+                    }
+
                     tokio::time::delay_for(Duration::from_millis(100)).await;
                     let current_timestamp = utils::date_time::current_timestamp();
-                    if current_timestamp - begin_timestamp > self.batch_timeout {
+                    if current_timestamp - begin_timestamp > self.options.batch_timeout {
                         break;
                     }
                 }
             }
         }
 
-        if size > 0 {
-            let block = batch_block.flush();
-            client.insert(self.table.as_str(), block).await?;
-        }
+        records
+    }
 
-        Ok(size)
+    /// insert the block of the records, the block is rebuilt for each retry
+    /// since it's consumed by the insert
+    async fn insert(
+        &mut self,
+        client: &mut ClientHandle,
+        records: &[Record],
+        mut block: CkBlock,
+    ) -> anyhow::Result<()> {
+        let mut retries = 0;
+        loop {
+            match client.insert(self.options.table.as_str(), block).await {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    if retries >= self.options.retry_backoff.max_retries {
+                        return Err(anyhow::Error::from(e));
+                    }
+
+                    let backoff = self.options.retry_backoff.get_backoff(retries);
+                    warn!(
+                        "write clickhouse error, retry {} after {:?}. {}",
+                        retries + 1,
+                        backoff,
+                        e
+                    );
+                    retries += 1;
+                    self.progress
+                        .retried_rows
+                        .fetch_add(records.len() as u64, Ordering::Relaxed);
+
+                    tokio::time::delay_for(backoff).await;
+                    if let Err(e) = client.check_connection().await {
+                        error!("reconnection error. {}", e);
+                    }
+                    block = self.rebuild_block(records)?;
+                }
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::clickhouse_sink::{
+        ClickhouseBatch, ClickhouseConverter, ClickhouseSink, RetryBackoff,
+    };
     use clickhouse_rs::Options;
+    use rlink::api::output::OutputFormat;
     use std::str::FromStr;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    struct TestConverter {}
+
+    impl ClickhouseConverter for TestConverter {
+        fn create_batch(&self, _batch_size: usize) -> Box<dyn ClickhouseBatch> {
+            unimplemented!()
+        }
+    }
 
     #[test]
     pub fn options_test() {
@@ -237,4 +512,45 @@ mod tests {
         .unwrap();
         println!("{:?}", opt);
     }
+
+    #[test]
+    pub fn retry_backoff_test() {
+        let retry_backoff = RetryBackoff {
+            max_retries: 5,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+        };
+        assert_eq!(retry_backoff.get_backoff(0), Duration::from_secs(1));
+        assert_eq!(retry_backoff.get_backoff(1), Duration::from_secs(2));
+        assert_eq!(retry_backoff.get_backoff(3), Duration::from_secs(8));
+        assert_eq!(retry_backoff.get_backoff(4), Duration::from_secs(10));
+        assert_eq!(retry_backoff.get_backoff(100), Duration::from_secs(10));
+    }
+
+    #[test]
+    pub fn flush_test() {
+        let mut sink = ClickhouseSink::new(
+            "tcp://localhost:9000",
+            "rlink_test",
+            1000,
+            Duration::from_secs(1),
+            1,
+            Box::new(TestConverter {}),
+        );
+        sink.set_flush_timeout(Duration::from_millis(100));
+        assert!(sink.flush().is_ok());
+
+        sink.written = 10;
+        // the background tasks are exited
+        assert!(sink.flush().is_err());
+
+        // the records are not inserted in the flush timeout
+        sink.progress.running_tasks.store(1, Ordering::SeqCst);
+        assert!(sink.flush().is_err());
+        assert!(!sink.progress.flush_requested.load(Ordering::SeqCst));
+
+        // the next checkpoint is not affected by the failed one
+        sink.progress.acknowledged.store(10, Ordering::SeqCst);
+        assert!(sink.flush().is_ok());
+    }
 }
@@ -3,6 +3,7 @@ extern crate log;
 #[macro_use]
 extern crate rlink_derive;
 
+pub mod clickhouse_converter;
 pub mod clickhouse_sink;
 
 use chrono::{DateTime, NaiveDateTime, TimeZone};
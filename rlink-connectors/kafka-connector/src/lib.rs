@@ -13,7 +13,14 @@ pub mod source;
 pub(crate) mod state;
 
 pub use sink::output_format::KafkaOutputFormat;
+pub use sink::partitioner::{KafkaPartitioner, KeyHashPartitioner};
+pub use sink::serializer::{KafkaMessage, KafkaRecordSerializer, KafkaSerializer};
+pub use source::deserializer::{
+    AvroDeserializer, Column, JsonDeserializer, KafkaDeserializer, ProtobufDeserializer,
+};
 pub use source::input_format::KafkaInputFormat;
+pub use source::startup_mode::StartupMode;
+pub use source::watermark::{KafkaPartitionWatermarkAssigner, KafkaTimestampAssigner};
 
 use std::collections::HashMap;
 
@@ -25,6 +32,14 @@ use rlink::api::properties::Properties;
 pub const BOOTSTRAP_SERVERS: &str = "bootstrap.servers";
 pub const TOPICS: &str = "topics";
 pub const GROUP_ID: &str = "group.id";
+/// enable the transactional sink, the value is the prefix of the transactional ids.
+/// the records between two barriers are a transaction, it's pre-committed when the barrier
+/// reached and committed after the checkpoint is completed, so the records of a failed attempt
+/// are never seen by the `read_committed` consumers.
+/// librdkafka is unable to resume a transaction of another producer, the pre-committed
+/// transaction is committed after restore only if its producer is kept by the worker process,
+/// it's aborted by the broker if the process is lost before committing it
+pub const TRANSACTIONAL_ID: &str = "transactional.id";
 
 pub const SOURCE_CHANNEL_SIZE: usize = 100000;
 pub const SINK_CHANNEL_SIZE: usize = 50000;
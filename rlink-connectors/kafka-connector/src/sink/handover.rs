@@ -1,17 +1,17 @@
 use std::time::Duration;
 
-use rlink::api::element::Record;
 use rlink::channel::receiver::ChannelReceiver;
 use rlink::channel::sender::ChannelSender;
 use rlink::channel::{mb, named_bounded, TryRecvError};
 use rlink::metrics::Tag;
 
+use crate::sink::serializer::KafkaMessage;
 use crate::SINK_CHANNEL_SIZE;
 
 #[derive(Clone)]
 pub struct Handover {
-    sender: ChannelSender<Record>,
-    receiver: ChannelReceiver<Record>,
+    sender: ChannelSender<KafkaMessage>,
+    receiver: ChannelReceiver<KafkaMessage>,
 }
 
 impl Handover {
@@ -26,12 +26,12 @@ impl Handover {
     }
 
     #[inline]
-    pub fn poll_next(&self) -> Result<Record, TryRecvError> {
+    pub fn poll_next(&self) -> Result<KafkaMessage, TryRecvError> {
         self.receiver.try_recv()
     }
 
     #[inline]
-    pub fn produce(&self, element: Record) {
+    pub fn produce(&self, element: KafkaMessage) {
         self.sender.try_send_loop(element, Duration::from_secs(1))
     }
 }
@@ -1,3 +1,6 @@
 pub mod handover;
 pub mod output_format;
+pub mod partitioner;
 pub mod producer;
+pub mod serializer;
+pub mod transaction;
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
 use rdkafka::ClientConfig;
 use rlink::api::element::Record;
 use rlink::api::function::Context;
@@ -7,27 +10,155 @@ use rlink::utils;
 use rlink::utils::get_runtime;
 
 use crate::sink::handover::Handover;
+use crate::sink::partitioner::{KafkaPartitioner, TopicPartitions};
 use crate::sink::producer::KafkaProducerThread;
+use crate::sink::serializer::{KafkaMessage, KafkaRecordSerializer, KafkaSerializer};
+use crate::sink::transaction::TransactionalProducer;
+use crate::TRANSACTIONAL_ID;
+
+const DEFAULT_TRANSACTIONAL_PRODUCERS: usize = 3;
+
+lazy_static! {
+    /// the producers of the pre-committed transactions of the closed sinks, keyed by the
+    /// transactional id. the restored sink in the same process commits them
+    static ref PRE_COMMITTED_PRODUCERS: Mutex<HashMap<String, (String, TransactionalProducer)>> =
+        Mutex::new(HashMap::new());
+}
 
 #[derive(Function)]
 pub struct KafkaOutputFormat {
     client_config: ClientConfig,
     topic: String,
     handover: Option<Handover>,
+
+    serializer: Box<dyn KafkaSerializer>,
+    partitioner: Option<Box<dyn KafkaPartitioner>>,
+    topic_partitions: Option<TopicPartitions>,
+
+    /// the `transactional.id` of the `client_config` is the prefix of the transactional id
+    /// of each task, the transactional mode is enabled if it's set. see `TRANSACTIONAL_ID`
+    transactional_id: Option<String>,
+    /// the n-th producer owns the transactional id `{transactional_id}-{n}`, so a
+    /// pre-committed transaction is kept open while the next one begins on another producer.
+    /// the producers fence the producers of the task before the restart when they're created
+    producers: Vec<TransactionalProducer>,
+    /// the open or pre-committed transaction of each producer
+    transactions: HashMap<usize, String>,
+    /// the producer of the open transaction
+    current_producer: Option<usize>,
+    /// the transactions of the restored producers are not recovered by the checkpoint yet
+    recovering: bool,
 }
 
 impl KafkaOutputFormat {
     pub fn new(client_config: ClientConfig, topic: String) -> Self {
+        let transactional_id = client_config.get(TRANSACTIONAL_ID).map(|x| x.to_string());
         KafkaOutputFormat {
             client_config,
             topic,
             handover: None,
+            serializer: Box::new(KafkaRecordSerializer::new()),
+            partitioner: None,
+            topic_partitions: None,
+            transactional_id,
+            producers: Vec::new(),
+            transactions: HashMap::new(),
+            current_producer: None,
+            recovering: false,
+        }
+    }
+
+    /// convert the records to the kafka messages, the default is `KafkaRecordSerializer`
+    pub fn set_serializer<S>(&mut self, serializer: S)
+    where
+        S: KafkaSerializer + 'static,
+    {
+        self.serializer = Box::new(serializer);
+    }
+
+    /// choose the partitions of the messages, the partitioner of the kafka client is used
+    /// if not set
+    pub fn set_partitioner<P>(&mut self, partitioner: P)
+    where
+        P: KafkaPartitioner + 'static,
+    {
+        self.partitioner = Some(Box::new(partitioner));
+    }
+
+    fn to_message(&mut self, mut record: Record) -> KafkaMessage {
+        let mut message = self.serializer.serialize(&mut record);
+        if message.partition.is_some() {
+            return message;
+        }
+
+        if let Some(partitioner) = self.partitioner.as_mut() {
+            let topic = message.topic.as_deref().unwrap_or(self.topic.as_str());
+            let num_partitions = self
+                .topic_partitions
+                .as_mut()
+                .unwrap()
+                .get_num_partitions(topic);
+            message.partition = partitioner.partition(&message, num_partitions);
+        }
+
+        message
+    }
+
+    /// create the producers of the task, the producers of the pre-committed transactions
+    /// are restored if the sink is reopened in the same process, they're committed or
+    /// aborted by the recovery of the checkpoint
+    fn open_producers(&mut self, transactional_id: &str) {
+        let mut pre_committed = PRE_COMMITTED_PRODUCERS.lock().unwrap();
+        for index in 0..DEFAULT_TRANSACTIONAL_PRODUCERS {
+            let id = format!("{}-{}", transactional_id, index);
+            let producer = match pre_committed.remove(&id) {
+                Some((transaction, producer)) => {
+                    info!("restore the producer of transaction({})", transaction);
+                    self.transactions.insert(index, transaction);
+                    producer
+                }
+                None => TransactionalProducer::new(&self.client_config, id.as_str())
+                    .expect("init transactional producer error"),
+            };
+            self.producers.push(producer);
+        }
+        self.recovering = !self.transactions.is_empty();
+    }
+
+    fn find_producer(&self, transaction: &str) -> Option<usize> {
+        self.transactions
+            .iter()
+            .find(|(_index, t)| t.as_str() == transaction)
+            .map(|(index, _t)| *index)
+    }
+
+    /// abort the transactions of the restored producers which are not recovered by the
+    /// checkpoint, they're began after the restored checkpoint and written again
+    fn abort_unrecovered(&mut self) {
+        self.recovering = false;
+        let transactions: Vec<String> = self.transactions.values().cloned().collect();
+        for transaction in transactions {
+            info!("abort transaction({}) after the checkpoint", transaction);
+            self.abort(transaction.as_str());
         }
     }
 }
 
 impl OutputFormat for KafkaOutputFormat {
     fn open(&mut self, context: &Context) {
+        if self.partitioner.is_some() {
+            self.topic_partitions = Some(TopicPartitions::new(&self.client_config));
+        }
+
+        if let Some(prefix) = &self.transactional_id {
+            // the transactional id is stable across the restarts of the job
+            let transactional_id =
+                format!("{}-{}-{}", prefix, context.chain_id, context.task_number);
+            self.open_producers(transactional_id.as_str());
+            self.transactional_id = Some(transactional_id);
+            return;
+        }
+
         self.handover = Some(Handover::new(
             self.get_name(),
             self.topic.as_str(),
@@ -47,8 +178,226 @@ impl OutputFormat for KafkaOutputFormat {
     }
 
     fn write_record(&mut self, record: Record) {
-        self.handover.as_ref().unwrap().produce(record);
+        let message = self.to_message(record);
+        match self.current_producer {
+            Some(index) => self.producers[index].send(self.topic.as_str(), &message),
+            None => self.handover.as_ref().unwrap().produce(message),
+        }
+    }
+
+    fn close(&mut self) {
+        // the records of the open transaction are written again after restore
+        if let Some(index) = self.current_producer {
+            let transaction = self.transactions.get(&index).unwrap().clone();
+            self.abort(transaction.as_str());
+        }
+
+        // the pre-committed transactions are committed by the sink restored in the process
+        let mut pre_committed = PRE_COMMITTED_PRODUCERS.lock().unwrap();
+        for (index, producer) in self.producers.drain(..).enumerate() {
+            if let Some(transaction) = self.transactions.remove(&index) {
+                let transactional_id = producer.get_transactional_id().to_string();
+                pre_committed.insert(transactional_id, (transaction, producer));
+            }
+        }
     }
 
-    fn close(&mut self) {}
+    fn begin_transaction(&mut self, checkpoint_id: u64) -> Option<String> {
+        if self.transactional_id.is_none() {
+            return None;
+        }
+        if self.recovering {
+            self.abort_unrecovered();
+        }
+
+        let index = (0..self.producers.len())
+            .find(|index| !self.transactions.contains_key(index))
+            .unwrap_or_else(|| {
+                panic!(
+                    "all {} transactional producers have pre-committed transactions, the checkpoints are not completed",
+                    self.producers.len()
+                )
+            });
+
+        let producer = &self.producers[index];
+        producer.begin().expect("begin transaction error");
+
+        let transaction = format!("{}@{}", producer.get_transactional_id(), checkpoint_id);
+        info!("begin transaction({})", transaction);
+
+        self.transactions.insert(index, transaction.clone());
+        self.current_producer = Some(index);
+        Some(transaction)
+    }
+
+    /// flush the records of the transaction, it's kept open by the producer
+    /// until the checkpoint is completed
+    fn prepare_commit(&mut self, transaction: &str) {
+        let index = match self.current_producer {
+            Some(index)
+                if self.transactions.get(&index).map(|t| t.as_str()) == Some(transaction) =>
+            {
+                index
+            }
+            _ => {
+                warn!("transaction({}) is not in progress", transaction);
+                return;
+            }
+        };
+        self.current_producer = None;
+
+        self.producers[index]
+            .flush()
+            .expect("flush transaction error");
+        debug!("pre-commit transaction({})", transaction);
+    }
+
+    fn commit(&mut self, transaction: &str) {
+        let index = match self.find_producer(transaction) {
+            Some(index) => index,
+            None => {
+                warn!("transaction({}) is not pre-committed", transaction);
+                return;
+            }
+        };
+        self.transactions.remove(&index);
+
+        self.producers[index]
+            .commit()
+            .expect("commit transaction error");
+        debug!("commit transaction({})", transaction);
+    }
+
+    fn abort(&mut self, transaction: &str) {
+        let index = match self.find_producer(transaction) {
+            Some(index) => index,
+            None => {
+                warn!("transaction({}) is not in progress", transaction);
+                return;
+            }
+        };
+        self.transactions.remove(&index);
+        if self.current_producer == Some(index) {
+            self.current_producer = None;
+        }
+
+        if let Err(e) = self.producers[index].abort() {
+            error!("abort transaction({}) error. {}", transaction, e);
+        }
+    }
+
+    /// commit the transaction kept by the producer restored in the process, the transaction
+    /// of a lost producer can't be resumed by librdkafka, see `TRANSACTIONAL_ID`
+    fn recover_and_commit(&mut self, transaction: &str) {
+        if self.find_producer(transaction).is_some() {
+            info!(
+                "commit transaction({}) of the restored producer",
+                transaction
+            );
+            self.commit(transaction);
+        } else {
+            error!(
+                "transaction({}) is committed before or its producer is lost, it's aborted by the broker if it's not committed",
+                transaction
+            );
+        }
+    }
+
+    fn recover_and_abort(&mut self, transaction: &str) {
+        if self.find_producer(transaction).is_some() {
+            self.abort(transaction);
+        } else {
+            // the producers fence the producers before the restart, the transaction is aborted
+            info!(
+                "transaction({}) of the previous producer is aborted",
+                transaction
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use rdkafka::ClientConfig;
+    use rlink::api::function::Context;
+    use rlink::api::output::OutputFormat;
+    use rlink::api::properties::Properties;
+
+    use crate::build_kafka_record;
+    use crate::sink::output_format::KafkaOutputFormat;
+    use crate::TRANSACTIONAL_ID;
+
+    fn create_context() -> Context {
+        Context {
+            job_id: "".to_string(),
+            job_name: "".to_string(),
+            job_properties: Properties::new(),
+            task_id: "".to_string(),
+            task_number: 1,
+            num_tasks: 2,
+            chain_id: 3,
+            dependency_chain_id: 0,
+            checkpoint_id: 0,
+            checkpoint_handle: None,
+        }
+    }
+
+    fn write_records(output_format: &mut KafkaOutputFormat) {
+        for n in 0..10 {
+            let record = build_kafka_record(0, "key".as_bytes(), "v".as_bytes(), "", 0, n).unwrap();
+            output_format.write_record(record);
+        }
+    }
+
+    #[test]
+    pub fn transactional_output_format_test() {
+        // the mock cluster of librdkafka
+        let mut client_config = ClientConfig::new();
+        client_config.set("test.mock.num.brokers", "3");
+        client_config.set(TRANSACTIONAL_ID, "rlink-test");
+
+        let mut output_format =
+            KafkaOutputFormat::new(client_config.clone(), "rlink-test".to_string());
+        output_format.open(&create_context());
+
+        // the transaction left open before the restart is aborted by the new producers
+        output_format.recover_and_abort("rlink-test-3-1-0@0");
+
+        let txn0 = output_format.begin_transaction(1000).unwrap();
+        assert_eq!(txn0, "rlink-test-3-1-0@1000");
+        write_records(&mut output_format);
+        output_format.prepare_commit(txn0.as_str());
+        assert!(output_format.current_producer.is_none());
+
+        // the pre-committed transaction is kept open while the next one begins
+        let txn1 = output_format.begin_transaction(2000).unwrap();
+        assert_eq!(txn1, "rlink-test-3-1-1@2000");
+        write_records(&mut output_format);
+        output_format.commit(txn0.as_str());
+        assert_eq!(output_format.transactions.len(), 1);
+
+        output_format.prepare_commit(txn1.as_str());
+        let txn2 = output_format.begin_transaction(3000).unwrap();
+        assert_eq!(txn2, "rlink-test-3-1-0@3000");
+
+        // the open transaction is aborted when the sink is closed,
+        // the pre-committed one is committed by the restored sink
+        output_format.close();
+        assert!(output_format.producers.is_empty());
+
+        let mut output_format = KafkaOutputFormat::new(client_config, "rlink-test".to_string());
+        output_format.open(&create_context());
+        output_format.recover_and_commit(txn1.as_str());
+        output_format.recover_and_abort(txn2.as_str());
+        assert!(output_format.transactions.is_empty());
+
+        let txn3 = output_format.begin_transaction(3000).unwrap();
+        assert_eq!(txn3, "rlink-test-3-1-0@3000");
+        output_format.abort(txn3.as_str());
+        assert!(output_format.current_producer.is_none());
+        output_format.close();
+    }
 }
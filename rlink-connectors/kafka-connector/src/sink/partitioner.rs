@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::ClientConfig;
+use rlink::utils;
+use rlink::utils::hash::hash_code;
+
+use crate::sink::serializer::KafkaMessage;
+
+/// the partitions of a topic may be increased, the metadata is refreshed in the interval
+const METADATA_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+const METADATA_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// choose the partition of the message if it's not chosen by the `KafkaSerializer`,
+/// the partitioner of the kafka client is used if `None` returned
+pub trait KafkaPartitioner
+where
+    Self: Send + Sync,
+{
+    fn partition(&mut self, message: &KafkaMessage, num_partitions: i32) -> Option<i32>;
+}
+
+/// partition by the hash of the key, it's the same hash of the `key_by`.
+/// the messages are partitioned consistently with the upstream if the key is
+/// the values of the record returned by the `KeySelectorFunction`
+#[derive(Debug, Default)]
+pub struct KeyHashPartitioner {}
+
+impl KeyHashPartitioner {
+    pub fn new() -> Self {
+        KeyHashPartitioner {}
+    }
+}
+
+impl KafkaPartitioner for KeyHashPartitioner {
+    fn partition(&mut self, message: &KafkaMessage, num_partitions: i32) -> Option<i32> {
+        match &message.key {
+            Some(key) if num_partitions > 0 => {
+                let hash_code = hash_code(key.as_slice()).unwrap_or(0);
+                Some((hash_code % num_partitions as u32) as i32)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// the number of partitions of the topics, it's provided to the `KafkaPartitioner`.
+///
+/// the metadata of a topic is fetched when the first message of it is sent, then it's
+/// refreshed in a background thread, so the write path is never blocked by the refresh.
+/// the last known number is kept if the metadata can't be fetched
+pub(crate) struct TopicPartitions {
+    consumer: BaseConsumer,
+    num_partitions: Arc<RwLock<HashMap<String, i32>>>,
+}
+
+impl TopicPartitions {
+    pub fn new(client_config: &ClientConfig) -> Self {
+        let consumer: BaseConsumer = client_config.create().expect("Consumer creation failed");
+        let num_partitions = Arc::new(RwLock::new(HashMap::new()));
+
+        let refresh_consumer: BaseConsumer =
+            client_config.create().expect("Consumer creation failed");
+        let refresh_partitions = Arc::downgrade(&num_partitions);
+        utils::spawn("kafka-sink-metadata", move || loop {
+            std::thread::sleep(METADATA_REFRESH_INTERVAL);
+
+            // the sink is closed
+            let num_partitions = match refresh_partitions.upgrade() {
+                Some(num_partitions) => num_partitions,
+                None => break,
+            };
+            let topics: Vec<String> = num_partitions.read().unwrap().keys().cloned().collect();
+            for topic in topics {
+                if let Some(n) = fetch_num_partitions(&refresh_consumer, topic.as_str()) {
+                    num_partitions.write().unwrap().insert(topic, n);
+                }
+            }
+        });
+
+        TopicPartitions {
+            consumer,
+            num_partitions,
+        }
+    }
+
+    /// 0 if the metadata of the topic has never been fetched,
+    /// then the partitioner of the kafka client is used
+    pub fn get_num_partitions(&mut self, topic: &str) -> i32 {
+        if let Some(num_partitions) = self.num_partitions.read().unwrap().get(topic) {
+            return *num_partitions;
+        }
+
+        // it's refreshed in the background if the metadata can't be fetched
+        let num_partitions = fetch_num_partitions(&self.consumer, topic).unwrap_or(0);
+        self.num_partitions
+            .write()
+            .unwrap()
+            .insert(topic.to_string(), num_partitions);
+        num_partitions
+    }
+}
+
+fn fetch_num_partitions(consumer: &BaseConsumer, topic: &str) -> Option<i32> {
+    match consumer.fetch_metadata(Some(topic), METADATA_TIMEOUT) {
+        Ok(metadata) => Some(
+            metadata
+                .topics()
+                .get(0)
+                .map(|metadata_topic| metadata_topic.partitions().len() as i32)
+                .unwrap_or(0),
+        ),
+        Err(e) => {
+            warn!("fetch the metadata of topic({}) error. {}", topic, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rlink::utils::hash::hash_code;
+
+    use crate::sink::partitioner::{KafkaPartitioner, KeyHashPartitioner};
+    use crate::sink::serializer::KafkaMessage;
+
+    #[test]
+    pub fn key_hash_partitioner_test() {
+        let mut partitioner = KeyHashPartitioner::new();
+
+        let key = "rlink".as_bytes().to_vec();
+        let message = KafkaMessage {
+            key: Some(key.clone()),
+            ..Default::default()
+        };
+        let partition = partitioner.partition(&message, 6).unwrap();
+        assert_eq!(partition as u32, hash_code(key.as_slice()).unwrap() % 6);
+
+        let message = KafkaMessage::default();
+        assert!(partitioner.partition(&message, 6).is_none());
+    }
+}
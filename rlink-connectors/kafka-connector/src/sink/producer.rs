@@ -1,15 +1,12 @@
-use std::borrow::BorrowMut;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::producer::FutureProducer;
 use rdkafka::ClientConfig;
 use rlink::channel::TryRecvError;
-use rlink::utils::EMPTY_SLICE;
 
 use crate::sink::handover::Handover;
-use crate::KafkaRecord;
 
 #[derive(Clone)]
 pub struct KafkaProducerThread {
@@ -46,18 +43,8 @@ impl KafkaProducerThread {
             let mut discard_counter = 0;
             for _n in 0..batch {
                 match self.handover.poll_next() {
-                    Ok(mut record) => {
-                        let mut reader = KafkaRecord::new(record.borrow_mut());
-
-                        let timestamp = reader.get_kafka_timestamp().unwrap_or_default();
-                        let key = reader.get_kafka_key().unwrap_or(&EMPTY_SLICE).to_vec();
-                        let payload = reader.get_kafka_payload().unwrap_or(&EMPTY_SLICE);
-
-                        let future_record = FutureRecord::to(self.topic.as_str())
-                            .payload(payload)
-                            .timestamp(timestamp as i64)
-                            .key(&key);
-
+                    Ok(message) => {
+                        let future_record = message.to_future_record(self.topic.as_str());
                         match self.producer.send_result(future_record) {
                             Ok(delivery_future) => future_queue.push(delivery_future),
                             Err((e, _future_record)) => {
@@ -119,14 +106,14 @@ impl KafkaProducerThread {
 mod tests {
     use crate::sink::handover::Handover;
     use crate::sink::producer::KafkaProducerThread;
+    use crate::sink::serializer::{KafkaMessage, KafkaRecordSerializer, KafkaSerializer};
     use crate::{build_kafka_record, BOOTSTRAP_SERVERS};
     use rdkafka::ClientConfig;
-    use rlink::api::element::Record;
     use rlink::utils::date_time::current_timestamp_millis;
     use std::sync::atomic::Ordering;
 
-    fn get_record() -> Record {
-        build_kafka_record(
+    fn get_message() -> KafkaMessage {
+        let mut record = build_kafka_record(
             current_timestamp_millis() as i64,
             "abc".as_bytes(),
             "bbbbbbbbbbbbbbbbbbbbbbbbbbb".as_bytes(),
@@ -134,7 +121,8 @@ mod tests {
             0,
             0,
         )
-        .unwrap()
+        .unwrap();
+        KafkaRecordSerializer::new().serialize(&mut record)
     }
 
     #[tokio::test(threaded_scheduler)]
@@ -151,9 +139,9 @@ mod tests {
 
         let handover_c = handover.clone();
         std::thread::spawn(move || {
-            let record = get_record();
+            let message = get_message();
             for _n in 0..1000000 {
-                handover_c.produce(record.clone());
+                handover_c.produce(message.clone());
             }
             println!("finish");
         });
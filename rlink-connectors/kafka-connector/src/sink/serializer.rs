@@ -0,0 +1,111 @@
+use std::borrow::BorrowMut;
+
+use rdkafka::message::OwnedHeaders;
+use rdkafka::producer::{BaseRecord, FutureRecord};
+use rlink::api::element::Record;
+use rlink::utils::EMPTY_SLICE;
+
+use crate::KafkaRecord;
+
+/// the message sent by the kafka sink, the `topic` and `partition` of the sink are used if `None`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KafkaMessage {
+    pub topic: Option<String>,
+    pub partition: Option<i32>,
+    pub timestamp: Option<i64>,
+    pub key: Option<Vec<u8>>,
+    pub payload: Vec<u8>,
+    pub headers: Vec<(String, Vec<u8>)>,
+}
+
+impl KafkaMessage {
+    fn get_headers(&self) -> Option<OwnedHeaders> {
+        if self.headers.is_empty() {
+            return None;
+        }
+
+        let mut headers = OwnedHeaders::new_with_capacity(self.headers.len());
+        for (name, value) in &self.headers {
+            headers = headers.add(name.as_str(), value.as_slice());
+        }
+        Some(headers)
+    }
+
+    pub(crate) fn to_future_record<'a>(&'a self, topic: &'a str) -> FutureRecord<'a, [u8], [u8]> {
+        let mut future_record = FutureRecord::to(self.topic.as_deref().unwrap_or(topic))
+            .payload(self.payload.as_slice());
+        if let Some(key) = &self.key {
+            future_record = future_record.key(key.as_slice());
+        }
+        if let Some(partition) = self.partition {
+            future_record = future_record.partition(partition);
+        }
+        if let Some(timestamp) = self.timestamp {
+            future_record = future_record.timestamp(timestamp);
+        }
+        if let Some(headers) = self.get_headers() {
+            future_record = future_record.headers(headers);
+        }
+        future_record
+    }
+
+    pub(crate) fn to_base_record<'a>(&'a self, topic: &'a str) -> BaseRecord<'a, [u8], [u8]> {
+        let mut base_record =
+            BaseRecord::to(self.topic.as_deref().unwrap_or(topic)).payload(self.payload.as_slice());
+        if let Some(key) = &self.key {
+            base_record = base_record.key(key.as_slice());
+        }
+        if let Some(partition) = self.partition {
+            base_record = base_record.partition(partition);
+        }
+        if let Some(timestamp) = self.timestamp {
+            base_record = base_record.timestamp(timestamp);
+        }
+        if let Some(headers) = self.get_headers() {
+            base_record = base_record.headers(headers);
+        }
+        base_record
+    }
+}
+
+/// convert the `Record` to the kafka message, the topic, partition, key and headers
+/// can be chosen by the fields of the record
+pub trait KafkaSerializer
+where
+    Self: Send + Sync,
+{
+    fn serialize(&mut self, record: &mut Record) -> KafkaMessage;
+}
+
+/// the serializer of the records in `KAFKA_DATA_TYPES` layout,
+/// they are sent to the topic of the sink.
+///
+/// the layout has no headers, so the messages are sent without headers. the headers,
+/// the topic and the partition of the messages are carried by a custom `KafkaSerializer`.
+/// the records of the source with a deserializer are not in the layout, see
+/// `KafkaInputFormat::set_deserializer`
+#[derive(Debug, Default)]
+pub struct KafkaRecordSerializer {}
+
+impl KafkaRecordSerializer {
+    pub fn new() -> Self {
+        KafkaRecordSerializer {}
+    }
+}
+
+impl KafkaSerializer for KafkaRecordSerializer {
+    fn serialize(&mut self, record: &mut Record) -> KafkaMessage {
+        let mut reader = KafkaRecord::new(record.borrow_mut());
+
+        let timestamp = reader.get_kafka_timestamp().unwrap_or_default();
+        let key = reader.get_kafka_key().unwrap_or(&EMPTY_SLICE).to_vec();
+        let payload = reader.get_kafka_payload().unwrap_or(&EMPTY_SLICE).to_vec();
+
+        KafkaMessage {
+            timestamp: Some(timestamp),
+            key: Some(key),
+            payload,
+            ..Default::default()
+        }
+    }
+}
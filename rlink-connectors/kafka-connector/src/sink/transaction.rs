@@ -0,0 +1,147 @@
+use std::ffi::CStr;
+use std::time::Duration;
+
+use rdkafka::bindings as rdsys;
+use rdkafka::error::{KafkaError, RDKafkaError};
+use rdkafka::producer::{BaseProducer, Producer};
+use rdkafka::ClientConfig;
+
+use crate::sink::serializer::KafkaMessage;
+use crate::TRANSACTIONAL_ID;
+
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// a producer owns one transaction at a time, the `transactional_id` is the handle of it.
+///
+/// the transaction api is not provided by `rdkafka` 0.24, so the librdkafka functions are used.
+pub struct TransactionalProducer {
+    transactional_id: String,
+    producer: BaseProducer,
+}
+
+impl TransactionalProducer {
+    /// create the producer and fence the previous producer with the same `transactional_id`,
+    /// the transaction left open by it is aborted
+    pub fn new(client_config: &ClientConfig, transactional_id: &str) -> std::io::Result<Self> {
+        let mut client_config = client_config.clone();
+        client_config.set(TRANSACTIONAL_ID, transactional_id);
+
+        let producer: BaseProducer = client_config
+            .create()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        let transactional_producer = TransactionalProducer {
+            transactional_id: transactional_id.to_string(),
+            producer,
+        };
+
+        let timeout_ms = TRANSACTION_TIMEOUT.as_millis() as i32;
+        transactional_producer
+            .check(|rk| unsafe { rdsys::rd_kafka_init_transactions(rk, timeout_ms) })?;
+
+        Ok(transactional_producer)
+    }
+
+    pub fn get_transactional_id(&self) -> &str {
+        self.transactional_id.as_str()
+    }
+
+    pub fn begin(&self) -> std::io::Result<()> {
+        self.check(|rk| unsafe { rdsys::rd_kafka_begin_transaction(rk) })
+    }
+
+    pub fn send(&self, topic: &str, message: &KafkaMessage) {
+        let mut base_record = message.to_base_record(topic);
+        loop {
+            match self.producer.send(base_record) {
+                Ok(_) => break,
+                Err((KafkaError::MessageProduction(RDKafkaError::QueueFull), r)) => {
+                    base_record = r;
+                    self.producer.poll(Duration::from_millis(100));
+                }
+                Err((e, _r)) => panic!(
+                    "send to transaction({}) error. {}",
+                    self.transactional_id, e
+                ),
+            }
+        }
+
+        self.producer.poll(Duration::from_millis(0));
+    }
+
+    /// flush all the records of the transaction to the broker
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.producer.flush(TRANSACTION_TIMEOUT);
+        match self.producer.in_flight_count() {
+            0 => Ok(()),
+            n => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("{} records are not flushed", n),
+            )),
+        }
+    }
+
+    pub fn commit(&self) -> std::io::Result<()> {
+        let timeout_ms = TRANSACTION_TIMEOUT.as_millis() as i32;
+        self.check(|rk| unsafe { rdsys::rd_kafka_commit_transaction(rk, timeout_ms) })
+    }
+
+    pub fn abort(&self) -> std::io::Result<()> {
+        let timeout_ms = TRANSACTION_TIMEOUT.as_millis() as i32;
+        self.check(|rk| unsafe { rdsys::rd_kafka_abort_transaction(rk, timeout_ms) })
+    }
+
+    fn check<F>(&self, f: F) -> std::io::Result<()>
+    where
+        F: FnOnce(*mut rdsys::rd_kafka_t) -> *mut rdsys::rd_kafka_error_t,
+    {
+        let error = f(self.producer.client().native_ptr());
+        if error.is_null() {
+            return Ok(());
+        }
+
+        let message = unsafe {
+            let message = CStr::from_ptr(rdsys::rd_kafka_error_string(error))
+                .to_string_lossy()
+                .to_string();
+            rdsys::rd_kafka_error_destroy(error);
+            message
+        };
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("transaction({}) error. {}", self.transactional_id, message),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sink::serializer::KafkaMessage;
+    use crate::sink::transaction::TransactionalProducer;
+    use rdkafka::ClientConfig;
+
+    #[test]
+    pub fn transactional_producer_test() {
+        // the mock cluster of librdkafka
+        let mut client_config = ClientConfig::new();
+        client_config.set("test.mock.num.brokers", "3");
+
+        let producer = TransactionalProducer::new(&client_config, "rlink-test-0").unwrap();
+        producer.begin().unwrap();
+        for n in 0..10 {
+            let message = KafkaMessage {
+                key: Some(format!("key-{}", n).into_bytes()),
+                payload: "v".as_bytes().to_vec(),
+                headers: vec![("h".to_string(), "v".as_bytes().to_vec())],
+                ..Default::default()
+            };
+            producer.send("rlink-test", &message);
+        }
+        producer.flush().unwrap();
+        producer.commit().unwrap();
+
+        producer.begin().unwrap();
+        producer.abort().unwrap();
+    }
+}
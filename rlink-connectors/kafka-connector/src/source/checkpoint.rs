@@ -1,35 +1,120 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use rlink::api::backend::{CheckpointBackend, OperatorState, OperatorStateBackend};
 use rlink::api::checkpoint::{CheckpointHandle, CheckpointedFunction, FunctionSnapshotContext};
+use rlink::api::properties::{Properties, SystemProperties};
+
+use crate::source::committer::OffsetCommitter;
+use crate::state::{KafkaSourceStateCache, OffsetMetadata, PartitionMetadata};
 
-use crate::state::{KafkaSourceStateCache, OffsetMetadata};
+/// the operator state backend of the offsets. the `DFSStateBackendWorkDir` is local to the
+/// worker, so the offsets written by the tasks on the other hosts are missing after restart.
+/// it's replaced by the path of the `CheckpointBackend::FileSystem` if configured
+pub(crate) fn get_offset_state_backend(job_properties: &Properties) -> OperatorStateBackend {
+    match job_properties.get_operator_state_backend() {
+        Ok(OperatorStateBackend::DFSStateBackendWorkDir) => match job_properties.get_checkpoint() {
+            Ok(CheckpointBackend::FileSystem { path }) => OperatorStateBackend::DFS {
+                path: format!("{}/operator_state", path.trim_end_matches('/')),
+            },
+            _ => {
+                warn!("the kafka offsets are kept in the local work space, the offsets of the partitions reassigned from another host are not restored");
+                OperatorStateBackend::DFSStateBackendWorkDir
+            }
+        },
+        Ok(state_backend) => state_backend,
+        Err(_e) => OperatorStateBackend::None,
+    }
+}
+
+fn parse_offset(value: &str) -> std::io::Result<OffsetMetadata> {
+    serde_json::from_str(value).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("illegal offset state({}). {}", value, e),
+        )
+    })
+}
 
 #[derive(Debug)]
 pub struct KafkaCheckpointed {
     pub(crate) state_cache: Option<KafkaSourceStateCache>,
-    pub(crate) job_id: String,
+    /// the offsets of the previous run are found by the `job_name` after restart
+    pub(crate) job_name: String,
     pub(crate) chain_id: u32,
     pub(crate) task_number: u16,
-    // pub(crate) state_mode: OperatorStateBackend,
+    pub(crate) state_mode: OperatorStateBackend,
+    /// the restored offsets of all tasks, taken by the partitions assigned to the task
+    restored_offsets: HashMap<PartitionMetadata, OffsetMetadata>,
+    /// persist the offsets of each checkpoint, besides the coordinator handle
+    pub(crate) operator_state: Option<Box<dyn OperatorState>>,
+    /// commit the offsets to the consumer group if enabled
+    pub(crate) offset_committer: Option<OffsetCommitter>,
 }
 
 impl KafkaCheckpointed {
     pub fn new(
-        job_id: String,
+        job_name: String,
         chain_id: u32,
         task_number: u16,
-        // state_mode: OperatorStateBackend,
+        state_mode: OperatorStateBackend,
     ) -> Self {
         KafkaCheckpointed {
             state_cache: None,
-            job_id,
+            job_name,
             chain_id,
             task_number,
-            // state_mode,
+            state_mode,
+            restored_offsets: HashMap::new(),
+            operator_state: None,
+            offset_committer: None,
         }
     }
 
     pub fn get_state(&mut self) -> &mut KafkaSourceStateCache {
         self.state_cache.as_mut().unwrap()
     }
+
+    /// take the restored offset of the `partition` when it's assigned to the task,
+    /// so the snapshot only contains the partitions of the task
+    pub(crate) fn take_restored_offset(
+        &mut self,
+        partition: &PartitionMetadata,
+    ) -> Option<OffsetMetadata> {
+        self.restored_offsets.remove(partition)
+    }
+
+    /// load the offsets of all tasks of the job from the operator state, the partitions may be
+    /// assigned to the other tasks after restart.
+    /// returns `None` if the checkpoint is not found in the operator state
+    fn load_operator_state(
+        &self,
+        checkpoint_id: u64,
+    ) -> std::io::Result<Option<Vec<OffsetMetadata>>> {
+        let operator_state = self.operator_state.as_ref().unwrap();
+        let state_values = operator_state.load_latest(checkpoint_id)?;
+        if state_values.is_empty() {
+            return Ok(None);
+        }
+
+        let mut offsets = Vec::new();
+        for state_value in state_values.values() {
+            for value in &state_value.values {
+                offsets.push(parse_offset(value.as_str())?);
+            }
+        }
+        Ok(Some(offsets))
+    }
+
+    /// the offsets of the task in the checkpoint handle reported to the coordinator
+    fn load_handle(handle: &CheckpointHandle) -> std::io::Result<Vec<OffsetMetadata>> {
+        serde_json::from_str(handle.handle.as_str()).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("illegal checkpoint handle({}). {}", handle.handle, e),
+            )
+        })
+    }
 }
 
 impl CheckpointedFunction for KafkaCheckpointed {
@@ -41,23 +126,46 @@ impl CheckpointedFunction for KafkaCheckpointed {
         self.state_cache = Some(KafkaSourceStateCache::new());
         info!("Checkpoint initialize, context: {:?}", context);
 
+        let operator_state = context.create_state(
+            self.chain_id,
+            self.state_mode.clone(),
+            self.job_name.clone(),
+            self.task_number,
+        );
+        self.operator_state = Some(operator_state);
+
         if context.checkpoint_id > 0 && handle.is_some() {
             let data = handle.as_ref().unwrap();
 
-            let offsets: Vec<OffsetMetadata> = serde_json::from_str(data.handle.as_str()).unwrap();
-            for offset in offsets {
-                let OffsetMetadata {
-                    topic,
-                    partition,
-                    offset,
-                } = offset;
-                let state_cache = self.state_cache.as_mut().unwrap();
-                state_cache.update(topic, partition, offset);
-
-                info!(
-                    "load state value from checkpoint({}): {:?}",
-                    context.checkpoint_id, data
-                );
+            let offsets = match self.load_operator_state(context.checkpoint_id) {
+                Ok(Some(offsets)) => Ok(offsets),
+                Ok(None) => KafkaCheckpointed::load_handle(data),
+                Err(e) => {
+                    error!(
+                        "load operator state error, checkpoint_id={}, fallback to the checkpoint handle. {}",
+                        context.checkpoint_id, e
+                    );
+                    KafkaCheckpointed::load_handle(data)
+                }
+            };
+            match offsets {
+                Ok(offsets) => {
+                    info!(
+                        "load state value from checkpoint({}): {:?}",
+                        context.checkpoint_id, offsets
+                    );
+                    for offset in offsets {
+                        let partition = PartitionMetadata {
+                            topic: offset.topic.clone(),
+                            partition: offset.partition,
+                        };
+                        self.restored_offsets.insert(partition, offset);
+                    }
+                }
+                Err(e) => error!(
+                    "load offsets from checkpoint({}) error, the partitions start from the startup mode. {}",
+                    context.checkpoint_id, e
+                ),
             }
         }
     }
@@ -72,8 +180,31 @@ impl CheckpointedFunction for KafkaCheckpointed {
         let snapshot_serial: Vec<OffsetMetadata> =
             offset_snapshot.values().map(|x| x.clone()).collect();
 
+        if let Some(offset_committer) = self.offset_committer.as_mut() {
+            offset_committer.add(context.checkpoint_id, snapshot_serial.clone());
+        }
+
+        let values: Vec<String> = snapshot_serial
+            .iter()
+            .map(|x| serde_json::to_string(x).unwrap())
+            .collect();
+        let operator_state = self.operator_state.as_mut().unwrap();
+        operator_state.update(context.checkpoint_id, values);
+        if let Err(e) = operator_state.snapshot() {
+            error!(
+                "snapshot operator state error, checkpoint_id={}. {}",
+                context.checkpoint_id, e
+            );
+        }
+
         let json = serde_json::to_string(&snapshot_serial).unwrap();
 
         CheckpointHandle { handle: json }
     }
+
+    fn notify_checkpoint_complete(&mut self, checkpoint_id: u64) {
+        if let Some(offset_committer) = self.offset_committer.as_mut() {
+            offset_committer.commit(checkpoint_id);
+        }
+    }
 }
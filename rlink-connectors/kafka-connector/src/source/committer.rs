@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Debug, Formatter};
+
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer};
+use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+use rlink::api::checkpoint::SAVEPOINT_FLAG;
+
+use crate::state::{OffsetMetadata, PartitionMetadata};
+
+/// the offsets of the checkpoints which are never completed are given up
+const MAX_PENDING_CHECKPOINTS: usize = 10;
+
+/// commit the checkpointed offsets to the consumer group after the checkpoint is completed,
+/// so the lag of the group can be monitored by the external tools.
+/// the offsets are never used to restore the job, the checkpoint is preferred
+pub(crate) struct OffsetCommitter {
+    consumer: BaseConsumer,
+    /// the partitions consumed by the task, the state may contain the offsets of other tasks
+    assigned_partitions: HashSet<PartitionMetadata>,
+    /// the offsets of the checkpoints not completed, keyed by the timestamp of the checkpoint
+    pending_offsets: BTreeMap<u64, Vec<OffsetMetadata>>,
+}
+
+impl OffsetCommitter {
+    pub fn new(client_config: &ClientConfig) -> Self {
+        let consumer: BaseConsumer = client_config.create().expect("Consumer creation failed");
+        OffsetCommitter {
+            consumer,
+            assigned_partitions: HashSet::new(),
+            pending_offsets: BTreeMap::new(),
+        }
+    }
+
+    pub fn assign(&mut self, partition: PartitionMetadata) {
+        self.assigned_partitions.insert(partition);
+    }
+
+    pub fn add(&mut self, checkpoint_id: u64, offsets: Vec<OffsetMetadata>) {
+        let offsets = offsets
+            .into_iter()
+            .filter(|x| {
+                self.assigned_partitions.contains(&PartitionMetadata {
+                    topic: x.topic.clone(),
+                    partition: x.partition,
+                })
+            })
+            .collect();
+        self.pending_offsets
+            .insert(checkpoint_id & !SAVEPOINT_FLAG, offsets);
+
+        while self.pending_offsets.len() > MAX_PENDING_CHECKPOINTS {
+            let checkpoint_id = *self.pending_offsets.keys().next().unwrap();
+            self.pending_offsets.remove(&checkpoint_id);
+        }
+    }
+
+    /// take the offsets of the latest checkpoint before the completed one,
+    /// the offsets of the older checkpoints are removed
+    fn take_completed(&mut self, completed_checkpoint_id: u64) -> Option<Vec<OffsetMetadata>> {
+        let completed_checkpoint_id = completed_checkpoint_id & !SAVEPOINT_FLAG;
+
+        let mut offsets = None;
+        let checkpoint_ids: Vec<u64> = self
+            .pending_offsets
+            .range(..=completed_checkpoint_id)
+            .map(|(checkpoint_id, _)| *checkpoint_id)
+            .collect();
+        for checkpoint_id in checkpoint_ids {
+            offsets = self.pending_offsets.remove(&checkpoint_id);
+        }
+        offsets
+    }
+
+    pub fn commit(&mut self, completed_checkpoint_id: u64) {
+        let offsets = match self.take_completed(completed_checkpoint_id) {
+            Some(offsets) => offsets,
+            None => return,
+        };
+
+        let mut topic_partition_list = TopicPartitionList::new();
+        for offset in &offsets {
+            // the startup offsets like `Offset::End` are not committed
+            if offset.offset >= 0 {
+                // the committed offset is the next offset to consume
+                topic_partition_list.add_partition_offset(
+                    offset.topic.as_str(),
+                    offset.partition,
+                    Offset::Offset(offset.offset + 1),
+                );
+            }
+        }
+        if topic_partition_list.count() == 0 {
+            return;
+        }
+
+        match self
+            .consumer
+            .commit(&topic_partition_list, CommitMode::Async)
+        {
+            Ok(_) => debug!(
+                "commit offsets of checkpoint({}): {:?}",
+                completed_checkpoint_id, offsets
+            ),
+            Err(e) => error!(
+                "commit offsets of checkpoint({}) error. {}",
+                completed_checkpoint_id, e
+            ),
+        }
+    }
+}
+
+impl Debug for OffsetCommitter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OffsetCommitter")
+            .field("assigned_partitions", &self.assigned_partitions)
+            .field("pending_offsets", &self.pending_offsets)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rdkafka::ClientConfig;
+
+    use crate::source::committer::OffsetCommitter;
+    use crate::state::{OffsetMetadata, PartitionMetadata};
+
+    fn offset(partition: i32, offset: i64) -> OffsetMetadata {
+        OffsetMetadata {
+            topic: "rlink-test".to_string(),
+            partition,
+            offset,
+        }
+    }
+
+    #[test]
+    pub fn offset_committer_test() {
+        let mut committer = OffsetCommitter::new(&ClientConfig::new());
+        committer.assign(PartitionMetadata {
+            topic: "rlink-test".to_string(),
+            partition: 0,
+        });
+
+        // the partition 1 is consumed by another task
+        committer.add(1000, vec![offset(0, 10), offset(1, 10)]);
+        committer.add(2000, vec![offset(0, 20), offset(1, 10)]);
+        committer.add(3000, vec![offset(0, 30), offset(1, 10)]);
+
+        assert!(committer.take_completed(500).is_none());
+        assert_eq!(committer.take_completed(2000), Some(vec![offset(0, 20)]));
+        assert!(committer.take_completed(2000).is_none());
+        assert_eq!(committer.pending_offsets.len(), 1);
+    }
+}
@@ -1,17 +1,23 @@
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use futures::StreamExt;
-use rdkafka::consumer::{Consumer, DefaultConsumerContext, StreamConsumer};
+use rdkafka::consumer::{BaseConsumer, Consumer, DefaultConsumerContext, StreamConsumer};
+use rdkafka::message::BorrowedMessage;
 use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
-use rlink::channel::TrySendError;
+use rlink::channel::{Receiver, TrySendError};
+use rlink::metrics::{register_gauge, Tag};
 use rlink::utils;
 use rlink::utils::get_runtime;
 
 use crate::build_kafka_record;
 use crate::source::handover::Handover;
-use crate::state::OffsetMetadata;
+use crate::state::{OffsetMetadata, PartitionMetadata};
+
+/// the interval to fetch the high watermarks of the partitions to compute the lag
+const LAG_INTERVAL: Duration = Duration::from_secs(10);
 
 struct TaskHandover {
     task_number: u16,
@@ -38,6 +44,7 @@ pub(crate) fn create_kafka_consumer(
     task_number: u16,
     client_config: ClientConfig,
     partition_offsets: Vec<OffsetMetadata>,
+    new_partitions: Receiver<OffsetMetadata>,
     handover: Handover,
 ) {
     let kafka_consumer: &Mutex<HashMap<u32, Vec<TaskHandover>>> = &*KAFKA_CONSUMERS;
@@ -55,8 +62,12 @@ pub(crate) fn create_kafka_consumer(
     let handover_clone = handover.clone();
     utils::spawn("kafka-source-block", move || {
         get_runtime().block_on(async {
-            let mut kafka_consumer =
-                KafkaConsumerThread::new(client_config, partition_offsets, handover_clone);
+            let mut kafka_consumer = KafkaConsumerThread::new(
+                client_config,
+                partition_offsets,
+                new_partitions,
+                handover_clone,
+            );
             kafka_consumer.run().await;
         });
     });
@@ -91,20 +102,30 @@ pub(crate) fn get_kafka_consumer_handover(chain_id: u32) -> Option<Handover> {
 pub struct KafkaConsumerThread {
     client_config: ClientConfig,
     partition_offsets: Vec<OffsetMetadata>,
+    /// the partitions discovered by the source task, they're added to the assignment
+    new_partitions: Receiver<OffsetMetadata>,
 
     handover: Handover,
+    /// the lag of each assigned partition, it's the high watermark minus the position
+    lag_gauges: Arc<Mutex<HashMap<PartitionMetadata, Arc<AtomicI64>>>>,
+    /// the lag is being updated by a blocking task
+    lag_updating: Arc<AtomicBool>,
 }
 
 impl KafkaConsumerThread {
     pub fn new(
         client_config: ClientConfig,
         partition_offsets: Vec<OffsetMetadata>,
+        new_partitions: Receiver<OffsetMetadata>,
         handover: Handover,
     ) -> Self {
         KafkaConsumerThread {
             client_config,
             partition_offsets,
+            new_partitions,
             handover,
+            lag_gauges: Arc::new(Mutex::new(HashMap::new())),
+            lag_updating: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -134,49 +155,177 @@ impl KafkaConsumerThread {
             self.client_config, self.partition_offsets
         );
 
+        // the high watermarks are fetched by blocking, they're kept away from the message loop
+        let watermark_consumer: Arc<BaseConsumer> = Arc::new(
+            self.client_config
+                .create()
+                .expect("Consumer creation failed"),
+        );
+
         let mut message_stream = consumer.start();
+        let mut discovery_interval = tokio::time::interval(Duration::from_secs(1));
+        let mut lag_interval = tokio::time::interval(LAG_INTERVAL);
+
+        loop {
+            tokio::select! {
+                message = message_stream.next() => match message {
+                    Some(Ok(borrowed_message)) => self.send_to_handover(&borrowed_message).await,
+                    Some(Err(e)) => warn!("Kafka error: {}", e),
+                    None => break,
+                },
+                _ = discovery_interval.tick() => self.assign_new_partitions(&consumer, &mut assignment),
+                _ = lag_interval.tick() => self.update_lag(&consumer, &watermark_consumer),
+            }
+        }
+    }
+
+    async fn send_to_handover(&self, borrowed_message: &BorrowedMessage<'_>) {
+        let topic = borrowed_message.topic();
+        let partition = borrowed_message.partition();
+        let offset = borrowed_message.offset();
+        let timestamp = borrowed_message.timestamp().to_millis().unwrap_or(0);
+        let key = borrowed_message.key().unwrap_or(&utils::EMPTY_SLICE);
+        let payload = borrowed_message.payload().unwrap_or(&utils::EMPTY_SLICE);
+
+        let mut record = build_kafka_record(timestamp, key, payload, topic, partition, offset)
+            .expect("kafka message writer to Record error");
+
+        let mut loops = 0;
+        loop {
+            match self.handover.produce(record) {
+                Ok(_) => {
+                    break;
+                }
+                Err(TrySendError::Full(r)) => {
+                    record = r;
 
-        while let Some(message) = message_stream.next().await {
-            match message {
-                Ok(borrowed_message) => {
-                    let topic = borrowed_message.topic();
-                    let partition = borrowed_message.partition();
-                    let offset = borrowed_message.offset();
-                    let timestamp = borrowed_message.timestamp().to_millis().unwrap_or(0);
-                    let key = borrowed_message.key().unwrap_or(&utils::EMPTY_SLICE);
-                    let payload = borrowed_message.payload().unwrap_or(&utils::EMPTY_SLICE);
-
-                    let mut record =
-                        build_kafka_record(timestamp, key, payload, topic, partition, offset)
-                            .expect("kafka message writer to Record error");
-
-                    let mut loops = 0;
-                    loop {
-                        match self.handover.produce(record) {
-                            Ok(_) => {
-                                break;
-                            }
-                            Err(TrySendError::Full(r)) => {
-                                record = r;
-
-                                if loops == 5 {
-                                    warn!("Handover produce `Full`");
-                                } else if loops == 1000 {
-                                    error!("Handover produce `Full` and try with 1000 times");
-                                    loops = 0;
-                                }
-                                loops += 1;
-
-                                tokio::time::delay_for(Duration::from_millis(100)).await;
-                            }
-                            Err(TrySendError::Disconnected(_r)) => {
-                                panic!("handover produce `Disconnected`");
-                            }
-                        }
+                    if loops == 5 {
+                        warn!("Handover produce `Full`");
+                    } else if loops == 1000 {
+                        error!("Handover produce `Full` and try with 1000 times");
+                        loops = 0;
                     }
+                    loops += 1;
+
+                    tokio::time::delay_for(Duration::from_millis(100)).await;
+                }
+                Err(TrySendError::Disconnected(_r)) => {
+                    panic!("handover produce `Disconnected`");
                 }
-                Err(e) => warn!("Kafka error: {}", e),
             }
         }
     }
+
+    /// the positions are taken from the consumer, and the lag is computed in a blocking task,
+    /// the update is skipped if the update of the previous interval is not finished
+    fn update_lag(
+        &self,
+        consumer: &StreamConsumer<DefaultConsumerContext>,
+        watermark_consumer: &Arc<BaseConsumer>,
+    ) {
+        if self.lag_updating.swap(true, Ordering::SeqCst) {
+            debug!("the lag of the previous interval is updating");
+            return;
+        }
+
+        let positions = match consumer.position() {
+            Ok(positions) => positions,
+            Err(e) => {
+                warn!("get the consumer position error. {}", e);
+                self.lag_updating.store(false, Ordering::SeqCst);
+                return;
+            }
+        };
+
+        let watermark_consumer = watermark_consumer.clone();
+        let lag_gauges = self.lag_gauges.clone();
+        let lag_updating = self.lag_updating.clone();
+        tokio::task::spawn_blocking(move || {
+            update_lag(watermark_consumer.as_ref(), &positions, lag_gauges.as_ref());
+            lag_updating.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// add the discovered partitions to the assignment, the assigned partitions
+    /// continue from the current positions
+    fn assign_new_partitions(
+        &self,
+        consumer: &StreamConsumer<DefaultConsumerContext>,
+        assignment: &mut TopicPartitionList,
+    ) {
+        let mut new_partitions = Vec::new();
+        while let Ok(partition_offset) = self.new_partitions.try_recv() {
+            new_partitions.push(partition_offset);
+        }
+        if new_partitions.is_empty() {
+            return;
+        }
+
+        let positions = consumer
+            .position()
+            .expect("Can't get the consumer position");
+        let mut new_assignment = TopicPartitionList::new();
+        for element in assignment.elements() {
+            // the position is invalid if no record of the partition is consumed
+            let offset = match positions.find_partition(element.topic(), element.partition()) {
+                Some(position) if position.offset() != Offset::Invalid => position.offset(),
+                _ => element.offset(),
+            };
+            new_assignment.add_partition_offset(element.topic(), element.partition(), offset);
+        }
+        for po in &new_partitions {
+            new_assignment.add_partition_offset(
+                po.topic.as_str(),
+                po.partition,
+                Offset::from_raw(po.offset),
+            );
+        }
+
+        consumer
+            .assign(&new_assignment)
+            .expect("Can't subscribe to specified topics");
+        info!("assign new partitions: {:?}", new_partitions);
+
+        *assignment = new_assignment;
+    }
+}
+
+fn update_lag(
+    consumer: &BaseConsumer,
+    positions: &TopicPartitionList,
+    lag_gauges: &Mutex<HashMap<PartitionMetadata, Arc<AtomicI64>>>,
+) {
+    for element in positions.elements() {
+        let position = match element.offset() {
+            Offset::Offset(position) => position,
+            _ => continue,
+        };
+        let high_watermark = match consumer.fetch_watermarks(
+            element.topic(),
+            element.partition(),
+            Duration::from_secs(1),
+        ) {
+            Ok((_low, high)) => high,
+            Err(e) => {
+                warn!("fetch watermarks error. {}", e);
+                continue;
+            }
+        };
+
+        let partition = PartitionMetadata {
+            topic: element.topic().to_string(),
+            partition: element.partition(),
+        };
+        let mut lag_gauges = lag_gauges.lock().unwrap();
+        let lag_gauge = lag_gauges.entry(partition).or_insert_with(|| {
+            let lag_gauge = Arc::new(AtomicI64::new(0));
+            let tags = vec![
+                Tag("topic".to_string(), element.topic().to_string()),
+                Tag("partition".to_string(), element.partition().to_string()),
+            ];
+            register_gauge("KafkaSource_Lag", tags, lag_gauge.clone());
+            lag_gauge
+        });
+        lag_gauge.store(high_watermark - position, Ordering::Relaxed);
+    }
 }
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use rdkafka::error::{KafkaError, RDKafkaError};
+use rdkafka::producer::BaseProducer;
+use rdkafka::ClientConfig;
+use rlink::api::element::Record;
+use rlink::utils::EMPTY_SLICE;
+
+use crate::sink::serializer::KafkaMessage;
+use crate::KafkaRecord;
+
+pub const HEADER_SOURCE_TOPIC: &str = "rlink.source.topic";
+pub const HEADER_SOURCE_PARTITION: &str = "rlink.source.partition";
+pub const HEADER_SOURCE_OFFSET: &str = "rlink.source.offset";
+pub const HEADER_ERROR: &str = "rlink.error";
+
+/// send the messages which can't be deserialized to the dead letter topic as they are,
+/// the source position and the error are kept in the headers
+pub(crate) struct DeadLetterProducer {
+    topic: String,
+    producer: BaseProducer,
+}
+
+impl DeadLetterProducer {
+    pub fn new(client_config: &ClientConfig, topic: &str) -> Self {
+        let producer: BaseProducer = client_config.create().expect("Producer creation failed");
+        DeadLetterProducer {
+            topic: topic.to_string(),
+            producer,
+        }
+    }
+
+    pub fn send(&self, record: &mut Record, error: &std::io::Error) {
+        let mut reader = KafkaRecord::new(record);
+        let message = KafkaMessage {
+            topic: None,
+            partition: None,
+            timestamp: Some(reader.get_kafka_timestamp().unwrap_or_default()),
+            key: Some(reader.get_kafka_key().unwrap_or(&EMPTY_SLICE).to_vec()),
+            payload: reader.get_kafka_payload().unwrap_or(&EMPTY_SLICE).to_vec(),
+            headers: vec![
+                (
+                    HEADER_SOURCE_TOPIC.to_string(),
+                    reader.get_kafka_topic().unwrap_or_default().into_bytes(),
+                ),
+                (
+                    HEADER_SOURCE_PARTITION.to_string(),
+                    reader
+                        .get_kafka_partition()
+                        .unwrap_or_default()
+                        .to_string()
+                        .into_bytes(),
+                ),
+                (
+                    HEADER_SOURCE_OFFSET.to_string(),
+                    reader
+                        .get_kafka_offset()
+                        .unwrap_or_default()
+                        .to_string()
+                        .into_bytes(),
+                ),
+                (HEADER_ERROR.to_string(), error.to_string().into_bytes()),
+            ],
+        };
+
+        let mut base_record = message.to_base_record(self.topic.as_str());
+        loop {
+            match self.producer.send(base_record) {
+                Ok(_) => break,
+                Err((KafkaError::MessageProduction(RDKafkaError::QueueFull), r)) => {
+                    base_record = r;
+                    self.producer.poll(Duration::from_millis(100));
+                }
+                Err((e, _r)) => {
+                    error!("send to dead letter topic({}) error. {}", self.topic, e);
+                    break;
+                }
+            }
+        }
+
+        self.producer.poll(Duration::from_millis(0));
+    }
+
+    /// wait until the messages in flight are delivered
+    pub fn flush(&self) {
+        self.producer.flush(Duration::from_secs(10));
+
+        let in_flight_count = self.producer.in_flight_count();
+        if in_flight_count > 0 {
+            error!(
+                "{} messages are not delivered to the dead letter topic({})",
+                in_flight_count, self.topic
+            );
+        }
+    }
+}
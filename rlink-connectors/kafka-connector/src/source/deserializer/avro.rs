@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use rlink::api::element::Record;
+
+use crate::source::deserializer::{
+    build_record, get_schema_types, invalid_data, Column, KafkaDeserializer, Value,
+};
+
+/// the magic byte of the schema registry wire format, followed by the 4 bytes schema id
+const MAGIC_BYTE: u8 = 0;
+const HEADER_LEN: usize = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+enum AvroType {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Enum(Vec<String>),
+    Fixed(usize),
+    Union(Vec<AvroType>),
+}
+
+impl AvroType {
+    fn parse(schema: &serde_json::Value) -> std::io::Result<Self> {
+        match schema {
+            serde_json::Value::String(name) => match name.as_str() {
+                "null" => Ok(AvroType::Null),
+                "boolean" => Ok(AvroType::Boolean),
+                "int" => Ok(AvroType::Int),
+                "long" => Ok(AvroType::Long),
+                "float" => Ok(AvroType::Float),
+                "double" => Ok(AvroType::Double),
+                "bytes" => Ok(AvroType::Bytes),
+                "string" => Ok(AvroType::String),
+                _ => Err(invalid_data(format!("unsupported avro type {}", name))),
+            },
+            serde_json::Value::Array(types) => {
+                let types: std::io::Result<Vec<AvroType>> =
+                    types.iter().map(|x| AvroType::parse(x)).collect();
+                Ok(AvroType::Union(types?))
+            }
+            serde_json::Value::Object(object) => match object.get("type") {
+                Some(serde_json::Value::String(name)) if name.eq("enum") => {
+                    let symbols = object
+                        .get("symbols")
+                        .and_then(|x| x.as_array())
+                        .ok_or_else(|| invalid_data("the symbols of enum not found"))?;
+                    let symbols = symbols
+                        .iter()
+                        .map(|x| x.as_str().unwrap_or_default().to_string())
+                        .collect();
+                    Ok(AvroType::Enum(symbols))
+                }
+                Some(serde_json::Value::String(name)) if name.eq("fixed") => {
+                    let size = object
+                        .get("size")
+                        .and_then(|x| x.as_u64())
+                        .ok_or_else(|| invalid_data("the size of fixed not found"))?;
+                    Ok(AvroType::Fixed(size as usize))
+                }
+                // the primitive type with the logical type
+                Some(primitive) => AvroType::parse(primitive),
+                None => Err(invalid_data("the type of avro schema not found")),
+            },
+            _ => Err(invalid_data(format!("illegal avro schema {}", schema))),
+        }
+    }
+
+    fn decode(&self, reader: &mut AvroReader) -> std::io::Result<Value> {
+        match self {
+            AvroType::Null => Ok(Value::Null),
+            AvroType::Boolean => Ok(Value::Bool(reader.read_bytes(1)?[0] != 0)),
+            AvroType::Int | AvroType::Long => Ok(Value::Long(reader.read_long()?)),
+            AvroType::Float => {
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(reader.read_bytes(4)?);
+                Ok(Value::Double(f32::from_le_bytes(bytes) as f64))
+            }
+            AvroType::Double => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(reader.read_bytes(8)?);
+                Ok(Value::Double(f64::from_le_bytes(bytes)))
+            }
+            AvroType::Bytes | AvroType::String => {
+                let len = reader.read_long()?;
+                if len < 0 {
+                    return Err(invalid_data("negative length of avro bytes"));
+                }
+                Ok(Value::Bytes(reader.read_bytes(len as usize)?.to_vec()))
+            }
+            AvroType::Enum(symbols) => {
+                let index = reader.read_long()?;
+                let symbol = symbols
+                    .get(index as usize)
+                    .ok_or_else(|| invalid_data(format!("illegal enum index {}", index)))?;
+                Ok(Value::Bytes(symbol.as_bytes().to_vec()))
+            }
+            AvroType::Fixed(size) => Ok(Value::Bytes(reader.read_bytes(*size)?.to_vec())),
+            AvroType::Union(types) => {
+                let index = reader.read_long()?;
+                let avro_type = types
+                    .get(index as usize)
+                    .ok_or_else(|| invalid_data(format!("illegal union index {}", index)))?;
+                avro_type.decode(reader)
+            }
+        }
+    }
+}
+
+struct AvroReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> AvroReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        // the length is read from the message, it may be malformed
+        let end = match self.position.checked_add(len) {
+            Some(end) if end <= self.data.len() => end,
+            _ => return Err(invalid_data("unexpected end of avro data")),
+        };
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// the zigzag encoded variable-length long
+    fn read_long(&mut self) -> std::io::Result<i64> {
+        let mut value = 0u64;
+        for shift in (0..70).step_by(7) {
+            let b = self.read_bytes(1)?[0];
+            value |= ((b & 0x7F) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        Err(invalid_data("avro long overflow"))
+    }
+}
+
+/// the fields of a flat avro record schema
+#[derive(Clone, Debug)]
+struct RecordSchema {
+    fields: Vec<(String, AvroType)>,
+}
+
+impl RecordSchema {
+    fn parse(schema: &str) -> std::io::Result<Self> {
+        let schema: serde_json::Value = serde_json::from_str(schema)?;
+        let fields = schema
+            .get("fields")
+            .and_then(|x| x.as_array())
+            .ok_or_else(|| invalid_data("the fields of avro record not found"))?;
+
+        let mut record_fields = Vec::new();
+        for field in fields {
+            let name = field
+                .get("name")
+                .and_then(|x| x.as_str())
+                .ok_or_else(|| invalid_data("the name of avro field not found"))?;
+            let field_type = field
+                .get("type")
+                .ok_or_else(|| invalid_data("the type of avro field not found"))?;
+            record_fields.push((name.to_string(), AvroType::parse(field_type)?));
+        }
+
+        Ok(RecordSchema {
+            fields: record_fields,
+        })
+    }
+}
+
+/// deserialize the avro binary of a flat record, the columns are the fields by name.
+///
+/// the payload is decoded by the writer schema. if the schemas of the registry ids are
+/// registered, the payload must start with the schema registry header(magic byte and
+/// the schema id), the writer schema is chosen by the id
+#[derive(Clone, Debug)]
+pub struct AvroDeserializer {
+    columns: Vec<Column>,
+    schema: RecordSchema,
+    registry_schemas: HashMap<u32, RecordSchema>,
+}
+
+impl AvroDeserializer {
+    pub fn new(schema: &str, columns: Vec<Column>) -> std::io::Result<Self> {
+        Ok(AvroDeserializer {
+            columns,
+            schema: RecordSchema::parse(schema)?,
+            registry_schemas: HashMap::new(),
+        })
+    }
+
+    /// register the writer schema of the `schema_id` in the schema registry
+    pub fn register_schema(&mut self, schema_id: u32, schema: &str) -> std::io::Result<()> {
+        self.registry_schemas
+            .insert(schema_id, RecordSchema::parse(schema)?);
+        Ok(())
+    }
+
+    fn get_writer_schema<'a>(
+        &'a self,
+        payload: &'a [u8],
+    ) -> std::io::Result<(&'a RecordSchema, &'a [u8])> {
+        if self.registry_schemas.is_empty() {
+            return Ok((&self.schema, payload));
+        }
+
+        if payload.len() < HEADER_LEN || payload[0] != MAGIC_BYTE {
+            return Err(invalid_data("the schema registry header not found"));
+        }
+        let mut schema_id = [0u8; 4];
+        schema_id.copy_from_slice(&payload[1..HEADER_LEN]);
+        let schema_id = u32::from_be_bytes(schema_id);
+
+        let schema = self
+            .registry_schemas
+            .get(&schema_id)
+            .ok_or_else(|| invalid_data(format!("schema({}) not registered", schema_id)))?;
+        Ok((schema, &payload[HEADER_LEN..]))
+    }
+}
+
+impl KafkaDeserializer for AvroDeserializer {
+    fn get_schema_types(&self) -> Vec<u8> {
+        get_schema_types(self.columns.as_slice())
+    }
+
+    fn deserialize(&mut self, payload: &[u8]) -> std::io::Result<Record> {
+        let (schema, data) = self.get_writer_schema(payload)?;
+
+        let mut reader = AvroReader { data, position: 0 };
+        let mut field_values = HashMap::with_capacity(schema.fields.len());
+        for (name, avro_type) in &schema.fields {
+            field_values.insert(name.as_str(), avro_type.decode(&mut reader)?);
+        }
+
+        let values = self
+            .columns
+            .iter()
+            .map(|column| {
+                field_values
+                    .remove(column.name.as_str())
+                    .unwrap_or(Value::Null)
+            })
+            .collect();
+        build_record(self.columns.as_slice(), values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rlink::api::element::types;
+
+    use crate::source::deserializer::avro::AvroReader;
+    use crate::source::deserializer::{AvroDeserializer, Column, KafkaDeserializer};
+
+    const SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "user",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "name", "type": ["null", "string"]},
+            {"name": "score", "type": "double"},
+            {"name": "level", "type": {"type": "enum", "name": "level", "symbols": ["LOW", "HIGH"]}}
+        ]
+    }"#;
+
+    fn encode() -> Vec<u8> {
+        let mut data = Vec::new();
+        // id = -2, zigzag encoded
+        data.push(3);
+        // union index 1, the string "rlink"
+        data.push(2);
+        data.push(10);
+        data.extend_from_slice("rlink".as_bytes());
+        data.extend_from_slice(&9.5f64.to_le_bytes());
+        // enum index 1
+        data.push(2);
+        data
+    }
+
+    #[test]
+    pub fn avro_deserializer_test() {
+        let columns = vec![
+            Column::new("level", types::BYTES),
+            Column::new("id", types::I64),
+            Column::new("name", types::BYTES),
+            Column::new("score", types::F64),
+        ];
+        let mut deserializer = AvroDeserializer::new(SCHEMA, columns).unwrap();
+        let data_types = deserializer.get_schema_types();
+
+        let mut record = deserializer.deserialize(encode().as_slice()).unwrap();
+        let mut reader = record.get_reader(data_types.as_slice());
+        assert_eq!(reader.get_str(0).unwrap(), "HIGH");
+        assert_eq!(reader.get_i64(1).unwrap(), -2);
+        assert_eq!(reader.get_str(2).unwrap(), "rlink");
+        assert_eq!(reader.get_f64(3).unwrap(), 9.5);
+
+        // the payload with the schema registry header
+        deserializer.register_schema(12, SCHEMA).unwrap();
+        assert!(deserializer.deserialize(encode().as_slice()).is_err());
+
+        let mut payload = vec![0u8];
+        payload.extend_from_slice(&12u32.to_be_bytes());
+        payload.extend_from_slice(encode().as_slice());
+        let mut record = deserializer.deserialize(payload.as_slice()).unwrap();
+        let mut reader = record.get_reader(data_types.as_slice());
+        assert_eq!(reader.get_i64(1).unwrap(), -2);
+    }
+
+    #[test]
+    pub fn malformed_length_test() {
+        let data = [0u8; 4];
+        let mut reader = AvroReader {
+            data: &data,
+            position: 1,
+        };
+        assert!(reader.read_bytes(usize::MAX).is_err());
+        assert!(reader.read_bytes(4).is_err());
+        assert_eq!(reader.read_bytes(3).unwrap().len(), 3);
+    }
+}
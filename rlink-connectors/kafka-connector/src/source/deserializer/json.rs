@@ -0,0 +1,89 @@
+use rlink::api::element::Record;
+
+use crate::source::deserializer::{
+    build_record, get_schema_types, invalid_data, Column, KafkaDeserializer, Value,
+};
+
+/// deserialize the json object, the columns are the fields of the object by name.
+/// the nested objects and arrays are kept as the json string
+#[derive(Clone, Debug)]
+pub struct JsonDeserializer {
+    columns: Vec<Column>,
+}
+
+impl JsonDeserializer {
+    pub fn new(columns: Vec<Column>) -> Self {
+        JsonDeserializer { columns }
+    }
+}
+
+impl KafkaDeserializer for JsonDeserializer {
+    fn get_schema_types(&self) -> Vec<u8> {
+        get_schema_types(self.columns.as_slice())
+    }
+
+    fn deserialize(&mut self, payload: &[u8]) -> std::io::Result<Record> {
+        let json: serde_json::Value = serde_json::from_slice(payload)?;
+        let object = json
+            .as_object()
+            .ok_or_else(|| invalid_data("the payload is not a json object"))?;
+
+        let values = self
+            .columns
+            .iter()
+            .map(|column| match object.get(column.name.as_str()) {
+                None | Some(serde_json::Value::Null) => Value::Null,
+                Some(serde_json::Value::Bool(v)) => Value::Bool(*v),
+                Some(serde_json::Value::Number(v)) => match (v.as_i64(), v.as_u64()) {
+                    (Some(v), _) => Value::Long(v),
+                    (None, Some(v)) => Value::UnsignedLong(v),
+                    (None, None) => Value::Double(v.as_f64().unwrap_or_default()),
+                },
+                Some(serde_json::Value::String(v)) => Value::Bytes(v.as_bytes().to_vec()),
+                Some(v) => Value::Bytes(v.to_string().into_bytes()),
+            })
+            .collect();
+
+        build_record(self.columns.as_slice(), values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rlink::api::element::types;
+
+    use crate::source::deserializer::{Column, JsonDeserializer, KafkaDeserializer};
+
+    #[test]
+    pub fn json_deserializer_test() {
+        let mut deserializer = JsonDeserializer::new(vec![
+            Column::new("id", types::I64),
+            Column::new("name", types::BYTES),
+            Column::new("score", types::F64),
+        ]);
+
+        let payload = r#"{"id": 7, "name": "rlink", "score": 9.5, "tags": [1, 2]}"#;
+        let mut record = deserializer.deserialize(payload.as_bytes()).unwrap();
+
+        let data_types = deserializer.get_schema_types();
+        let mut reader = record.get_reader(data_types.as_slice());
+        assert_eq!(reader.get_i64(0).unwrap(), 7);
+        assert_eq!(reader.get_str(1).unwrap(), "rlink");
+        assert_eq!(reader.get_f64(2).unwrap(), 9.5);
+
+        assert!(deserializer.deserialize("[1, 2]".as_bytes()).is_err());
+        assert!(deserializer
+            .deserialize(r#"{"id": 7, "score": "x"}"#.as_bytes())
+            .is_err());
+        // the missing, null and fractional numbers are never written as the integer
+        assert!(deserializer
+            .deserialize(r#"{"name": "rlink", "score": 9.5}"#.as_bytes())
+            .is_err());
+        assert!(deserializer
+            .deserialize(r#"{"id": null, "score": 9.5}"#.as_bytes())
+            .is_err());
+        assert!(deserializer
+            .deserialize(r#"{"id": 7.5, "score": 9.5}"#.as_bytes())
+            .is_err());
+    }
+}
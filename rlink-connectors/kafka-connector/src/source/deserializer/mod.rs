@@ -0,0 +1,216 @@
+use std::convert::TryFrom;
+
+use rlink::api::element::{types, Record};
+
+pub mod avro;
+pub mod json;
+pub mod protobuf;
+
+pub use avro::AvroDeserializer;
+pub use json::JsonDeserializer;
+pub use protobuf::ProtobufDeserializer;
+
+/// a column of the deserialized `Record`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub name: String,
+    /// one of `I32`, `U32`, `I64`, `U64`, `F64` and `BYTES` in `rlink::api::element::types`
+    pub data_type: u8,
+    /// the field number of the protobuf message, it's unused by the other formats
+    pub field_number: u32,
+}
+
+impl Column {
+    pub fn new(name: &str, data_type: u8) -> Self {
+        Column {
+            name: name.to_string(),
+            data_type,
+            field_number: 0,
+        }
+    }
+
+    pub fn with_field_number(name: &str, field_number: u32, data_type: u8) -> Self {
+        Column {
+            name: name.to_string(),
+            data_type,
+            field_number,
+        }
+    }
+}
+
+/// convert the payload of the kafka message to the typed `Record` of the declared columns.
+/// the message is sent to the dead letter topic if it can't be deserialized
+pub trait KafkaDeserializer
+where
+    Self: Send + Sync,
+{
+    /// the data types of the deserialized records
+    fn get_schema_types(&self) -> Vec<u8>;
+
+    fn deserialize(&mut self, payload: &[u8]) -> std::io::Result<Record>;
+}
+
+/// the decoded value of a field, it's converted to the data type of the column
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Long(i64),
+    /// the unsigned integer out of the range of `Long`, such as the protobuf `uint64`
+    UnsignedLong(u64),
+    Double(f64),
+    Bytes(Vec<u8>),
+}
+
+pub(crate) fn invalid_data<E>(error: E) -> std::io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+pub(crate) fn get_schema_types(columns: &[Column]) -> Vec<u8> {
+    columns.iter().map(|column| column.data_type).collect()
+}
+
+/// write the values in the order of the columns, the `Null` of the `BYTES` column is written
+/// as the empty bytes. the value which can't be converted to the data type of the column
+/// without loss, such as the `Null` number, the fractional or the out of range integer, is an error
+pub(crate) fn build_record(columns: &[Column], values: Vec<Value>) -> std::io::Result<Record> {
+    let data_types = get_schema_types(columns);
+
+    // 4 = len(bytes), 8 = the max len of number
+    let capacity = values
+        .iter()
+        .map(|value| match value {
+            Value::Bytes(bytes) => bytes.len() + 4,
+            _ => 8,
+        })
+        .sum();
+    let mut record = Record::with_capacity(capacity);
+    let mut writer = record.get_writer(data_types.as_slice());
+    for (column, value) in columns.iter().zip(values.into_iter()) {
+        match column.data_type {
+            types::BYTES => match value {
+                Value::Bytes(bytes) => writer.set_bytes(bytes.as_slice())?,
+                Value::Null => writer.set_bytes(&[])?,
+                Value::Bool(v) => writer.set_str(v.to_string().as_str())?,
+                Value::Long(v) => writer.set_str(v.to_string().as_str())?,
+                Value::UnsignedLong(v) => writer.set_str(v.to_string().as_str())?,
+                Value::Double(v) => writer.set_str(v.to_string().as_str())?,
+            },
+            types::F64 => writer.set_f64(to_f64(column, &value)?)?,
+            types::I32 => writer.set_i32(to_integer(column, &value)?)?,
+            types::U32 => writer.set_u32(to_integer(column, &value)?)?,
+            types::I64 => writer.set_i64(to_integer(column, &value)?)?,
+            types::U64 => writer.set_u64(to_integer(column, &value)?)?,
+            data_type => {
+                return Err(invalid_data(format!(
+                    "unsupported data type {} of column {}",
+                    data_type, column.name
+                )))
+            }
+        }
+    }
+
+    Ok(record)
+}
+
+fn to_f64(column: &Column, value: &Value) -> std::io::Result<f64> {
+    match value {
+        Value::Bool(v) => Ok(if *v { 1f64 } else { 0f64 }),
+        Value::Long(v) => Ok(*v as f64),
+        Value::UnsignedLong(v) => Ok(*v as f64),
+        Value::Double(v) => Ok(*v),
+        Value::Null | Value::Bytes(_) => Err(mismatch(column)),
+    }
+}
+
+/// narrow the value to the integer type of the column, the out of range value is an error
+fn to_integer<T>(column: &Column, value: &Value) -> std::io::Result<T>
+where
+    T: TryFrom<i128>,
+{
+    let integer = match value {
+        Value::Bool(v) => i128::from(*v),
+        Value::Long(v) => i128::from(*v),
+        Value::UnsignedLong(v) => i128::from(*v),
+        // the fractional, infinite and NaN values are never truncated,
+        // the range is checked by the `TryFrom`
+        Value::Double(v) if v.fract() == 0f64 && v.abs() < 2f64.powi(64) => *v as i128,
+        Value::Double(v) => {
+            return Err(invalid_data(format!(
+                "the value {} of column {} is not an integer",
+                v, column.name
+            )))
+        }
+        Value::Null | Value::Bytes(_) => return Err(mismatch(column)),
+    };
+
+    T::try_from(integer).map_err(|_| {
+        invalid_data(format!(
+            "the value {} of column {} is out of range",
+            integer, column.name
+        ))
+    })
+}
+
+fn mismatch(column: &Column) -> std::io::Error {
+    invalid_data(format!(
+        "the value of column {} is not a number",
+        column.name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use rlink::api::element::types;
+
+    use crate::source::deserializer::{build_record, Column, Value};
+
+    #[test]
+    pub fn build_record_test() {
+        let columns = vec![
+            Column::new("i32", types::I32),
+            Column::new("u32", types::U32),
+            Column::new("u64", types::U64),
+            Column::new("f64", types::F64),
+        ];
+        let data_types = vec![types::I32, types::U32, types::U64, types::F64];
+
+        let values = vec![
+            Value::Double(-3f64),
+            Value::Bool(true),
+            Value::UnsignedLong(u64::MAX),
+            Value::Long(7),
+        ];
+        let mut record = build_record(columns.as_slice(), values).unwrap();
+        let mut reader = record.get_reader(data_types.as_slice());
+        assert_eq!(reader.get_i32(0).unwrap(), -3);
+        assert_eq!(reader.get_u32(1).unwrap(), 1);
+        assert_eq!(reader.get_u64(2).unwrap(), u64::MAX);
+        assert_eq!(reader.get_f64(3).unwrap(), 7f64);
+
+        let build = |index: usize, value: Value| {
+            let mut values = vec![
+                Value::Long(0),
+                Value::Long(0),
+                Value::Long(0),
+                Value::Long(0),
+            ];
+            values[index] = value;
+            build_record(columns.as_slice(), values)
+        };
+        // the out of range values are never wrapped
+        assert!(build(0, Value::Long(i32::MAX as i64 + 1)).is_err());
+        assert!(build(1, Value::Long(-1)).is_err());
+        assert!(build(2, Value::Long(-1)).is_err());
+        // the fractional values are never truncated
+        assert!(build(0, Value::Double(1.5)).is_err());
+        assert!(build(2, Value::Double(f64::NAN)).is_err());
+        assert!(build(2, Value::Double(2f64.powi(64))).is_err());
+        // the missing numbers are never written as 0
+        assert!(build(0, Value::Null).is_err());
+        assert!(build(3, Value::Null).is_err());
+    }
+}
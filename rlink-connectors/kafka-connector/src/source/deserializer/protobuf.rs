@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use rlink::api::element::{types, Record};
+
+use crate::source::deserializer::{
+    build_record, get_schema_types, invalid_data, Column, KafkaDeserializer, Value,
+};
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// deserialize the protobuf message without the generated code,
+/// the columns are the fields by the `field_number`.
+///
+/// the `varint` fields are decoded as the `int32`, `int64`, `uint32`, `uint64`, `bool` and `enum`,
+/// the `sint32` and `sint64` are not supported. the `fixed64` fields are decoded as the `double`
+/// for the `F64` column, otherwise as the `fixed64`, so does the `fixed32` as the `float`.
+/// the length-delimited fields are kept as the bytes, including the nested messages.
+/// the last value wins if the field is repeated, and the absent field is the default value of proto3
+#[derive(Clone, Debug)]
+pub struct ProtobufDeserializer {
+    columns: Vec<Column>,
+    /// field number -> the index of the column
+    column_index: HashMap<u32, usize>,
+}
+
+impl ProtobufDeserializer {
+    pub fn new(columns: Vec<Column>) -> Self {
+        let column_index = columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| (column.field_number, index))
+            .collect();
+        ProtobufDeserializer {
+            columns,
+            column_index,
+        }
+    }
+}
+
+struct WireReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> WireReader<'a> {
+    fn is_end(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        // the length is read from the message, it may be malformed
+        let end = match self.position.checked_add(len) {
+            Some(end) if end <= self.data.len() => end,
+            _ => return Err(invalid_data("unexpected end of protobuf data")),
+        };
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_varint(&mut self) -> std::io::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..70).step_by(7) {
+            let b = self.read_bytes(1)?[0];
+            value |= ((b & 0x7F) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data("protobuf varint overflow"))
+    }
+}
+
+impl KafkaDeserializer for ProtobufDeserializer {
+    fn get_schema_types(&self) -> Vec<u8> {
+        get_schema_types(self.columns.as_slice())
+    }
+
+    fn deserialize(&mut self, payload: &[u8]) -> std::io::Result<Record> {
+        let mut values: Vec<Value> = self
+            .columns
+            .iter()
+            .map(|column| default_value(column.data_type))
+            .collect();
+
+        let mut reader = WireReader {
+            data: payload,
+            position: 0,
+        };
+        while !reader.is_end() {
+            let key = reader.read_varint()?;
+            let field_number = (key >> 3) as u32;
+            let wire_type = key & 0x07;
+
+            let column_index = self.column_index.get(&field_number).map(|x| *x);
+            let data_type = column_index.map(|index| self.columns[index].data_type);
+            let value = match wire_type {
+                WIRE_VARINT => {
+                    // the `int32` and `int64` are the two's complement of the `uint64`
+                    let v = reader.read_varint()?;
+                    match data_type {
+                        Some(types::U64) => Value::UnsignedLong(v),
+                        _ => Value::Long(v as i64),
+                    }
+                }
+                WIRE_FIXED64 => {
+                    let mut bytes = [0u8; 8];
+                    bytes.copy_from_slice(reader.read_bytes(8)?);
+                    match data_type {
+                        Some(types::F64) => Value::Double(f64::from_le_bytes(bytes)),
+                        Some(types::U64) => Value::UnsignedLong(u64::from_le_bytes(bytes)),
+                        _ => Value::Long(i64::from_le_bytes(bytes)),
+                    }
+                }
+                WIRE_LENGTH_DELIMITED => {
+                    let len = reader.read_varint()? as usize;
+                    Value::Bytes(reader.read_bytes(len)?.to_vec())
+                }
+                WIRE_FIXED32 => {
+                    let mut bytes = [0u8; 4];
+                    bytes.copy_from_slice(reader.read_bytes(4)?);
+                    match data_type {
+                        Some(types::F64) => Value::Double(f32::from_le_bytes(bytes) as f64),
+                        Some(types::U32) | Some(types::U64) => {
+                            Value::UnsignedLong(u32::from_le_bytes(bytes) as u64)
+                        }
+                        _ => Value::Long(i32::from_le_bytes(bytes) as i64),
+                    }
+                }
+                _ => {
+                    return Err(invalid_data(format!(
+                        "unsupported wire type {} of field {}",
+                        wire_type, field_number
+                    )))
+                }
+            };
+
+            if let Some(index) = column_index {
+                values[index] = value;
+            }
+        }
+
+        build_record(self.columns.as_slice(), values)
+    }
+}
+
+/// the default value of the absent field in proto3
+fn default_value(data_type: u8) -> Value {
+    match data_type {
+        types::BYTES => Value::Bytes(vec![]),
+        types::F64 => Value::Double(0f64),
+        _ => Value::Long(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rlink::api::element::types;
+
+    use crate::source::deserializer::protobuf::WireReader;
+    use crate::source::deserializer::{Column, KafkaDeserializer, ProtobufDeserializer};
+
+    #[test]
+    pub fn protobuf_deserializer_test() {
+        let mut deserializer = ProtobufDeserializer::new(vec![
+            Column::with_field_number("id", 1, types::I64),
+            Column::with_field_number("name", 2, types::BYTES),
+            Column::with_field_number("score", 4, types::F64),
+            Column::with_field_number("missing", 5, types::U32),
+        ]);
+        let data_types = deserializer.get_schema_types();
+
+        let mut payload = vec![];
+        // field 1, varint 300
+        payload.extend_from_slice(&[0x08, 0xAC, 0x02]);
+        // field 2, string "rlink"
+        payload.extend_from_slice(&[0x12, 0x05]);
+        payload.extend_from_slice("rlink".as_bytes());
+        // field 3 is unknown, varint 1
+        payload.extend_from_slice(&[0x18, 0x01]);
+        // field 4, double 9.5
+        payload.push(0x21);
+        payload.extend_from_slice(&9.5f64.to_le_bytes());
+
+        let mut record = deserializer.deserialize(payload.as_slice()).unwrap();
+        let mut reader = record.get_reader(data_types.as_slice());
+        assert_eq!(reader.get_i64(0).unwrap(), 300);
+        assert_eq!(reader.get_str(1).unwrap(), "rlink");
+        assert_eq!(reader.get_f64(2).unwrap(), 9.5);
+        assert_eq!(reader.get_u32(3).unwrap(), 0);
+
+        // truncated message
+        assert!(deserializer.deserialize(&payload[..4]).is_err());
+
+        // field 5, varint -1 of the `int32` can't be written to the `U32` column
+        let payload = [
+            0x28, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01,
+        ];
+        assert!(deserializer.deserialize(&payload).is_err());
+
+        // field 1, varint `u64::MAX` of the `uint64`
+        let mut deserializer =
+            ProtobufDeserializer::new(vec![Column::with_field_number("id", 1, types::U64)]);
+        let payload = [
+            0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01,
+        ];
+        let mut record = deserializer.deserialize(&payload).unwrap();
+        let mut reader = record.get_reader(&[types::U64]);
+        assert_eq!(reader.get_u64(0).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    pub fn malformed_length_test() {
+        let data = [0u8; 4];
+        let mut reader = WireReader {
+            data: &data,
+            position: 1,
+        };
+        assert!(reader.read_bytes(usize::MAX).is_err());
+        assert!(reader.read_bytes(4).is_err());
+        assert_eq!(reader.read_bytes(3).unwrap().len(), 3);
+
+        // field 2 with the length of `u64::MAX`
+        let mut deserializer =
+            ProtobufDeserializer::new(vec![Column::with_field_number("name", 2, types::BYTES)]);
+        let mut payload = vec![0x12];
+        payload.extend_from_slice(&[0xFF; 9]);
+        payload.push(0x01);
+        assert!(deserializer.deserialize(payload.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::error::{KafkaError, KafkaResult, RDKafkaError};
+use rdkafka::ClientConfig;
+use regex::Regex;
+use rlink::channel::Receiver;
+use rlink::utils;
+
+use crate::state::PartitionMetadata;
+
+const METADATA_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// the topics subscribed by the source, the topics fully matching the `topic_pattern`
+/// are subscribed besides the `topics`
+#[derive(Clone, Debug)]
+pub(crate) struct TopicSubscription {
+    topics: Vec<String>,
+    topic_regex: Option<Regex>,
+}
+
+impl TopicSubscription {
+    pub fn new(topics: Vec<String>, topic_pattern: Option<String>) -> Self {
+        // the pattern is anchored, so the pattern `orders` never matches `orders_dlq`
+        let topic_regex = topic_pattern.map(|topic_pattern| {
+            Regex::new(format!("^(?:{})$", topic_pattern).as_str())
+                .expect(format!("illegal topic pattern {}", topic_pattern).as_str())
+        });
+        TopicSubscription {
+            topics,
+            topic_regex,
+        }
+    }
+
+    pub fn is_subscribed(&self, topic: &str) -> bool {
+        self.topics.iter().any(|x| x.eq(topic))
+            || self
+                .topic_regex
+                .as_ref()
+                .map(|regex| regex.is_match(topic))
+                .unwrap_or(false)
+    }
+
+    /// fetch all the partitions of the subscribed topics, sorted by topic and partition
+    pub fn fetch_partitions(&self, consumer: &BaseConsumer) -> KafkaResult<Vec<PartitionMetadata>> {
+        let mut partitions = Vec::new();
+        match &self.topic_regex {
+            Some(_topic_regex) => {
+                let metadata = consumer.fetch_metadata(None, METADATA_TIMEOUT)?;
+                for metadata_topic in metadata.topics() {
+                    let topic = metadata_topic.name();
+                    if self.is_subscribed(topic) {
+                        for partition in metadata_topic.partitions() {
+                            partitions.push(PartitionMetadata {
+                                topic: topic.to_string(),
+                                partition: partition.id(),
+                            });
+                        }
+                    }
+                }
+            }
+            None => {
+                for topic in &self.topics {
+                    let metadata =
+                        consumer.fetch_metadata(Some(topic.as_str()), METADATA_TIMEOUT)?;
+                    let metadata_topic = metadata.topics().get(0).ok_or_else(|| {
+                        KafkaError::MetadataFetch(RDKafkaError::UnknownTopicOrPartition)
+                    })?;
+                    for partition in metadata_topic.partitions() {
+                        partitions.push(PartitionMetadata {
+                            topic: topic.clone(),
+                            partition: partition.id(),
+                        });
+                    }
+                }
+            }
+        }
+
+        partitions.sort_by(|a, b| {
+            a.topic
+                .cmp(&b.topic)
+                .then_with(|| a.partition.cmp(&b.partition))
+        });
+        Ok(partitions)
+    }
+}
+
+/// the consumer which owns a partition not assigned by the input splits,
+/// the partitions of a topic are spread across the consumers from a start index of the topic
+pub(crate) fn get_partition_owner(partition: &PartitionMetadata, num_consumers: u32) -> u32 {
+    let hash = partition.topic.as_bytes().iter().fold(0u32, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(*b as u32)
+    });
+    let start_index = (hash.wrapping_mul(31) & 0x7FFFFFFF) % num_consumers;
+    (start_index + partition.partition as u32) % num_consumers
+}
+
+/// a partition discovered by the `PartitionDiscoverer`
+#[derive(Clone, Debug)]
+pub(crate) struct DiscoveredPartition {
+    pub partition: PartitionMetadata,
+    /// the first discovery takes the partitions beyond the input splits,
+    /// they are not new partitions of the topics
+    pub first: bool,
+}
+
+/// discover the new partitions of the subscribed topics periodically,
+/// the partitions owned by the `consumer_index` are returned
+pub(crate) struct PartitionDiscoverer {
+    subscription: TopicSubscription,
+    consumer: BaseConsumer,
+
+    consumer_index: u32,
+    num_consumers: u32,
+    interval: Duration,
+
+    /// the partitions assigned by the input splits or discovered
+    known_partitions: HashSet<PartitionMetadata>,
+    first: bool,
+}
+
+impl PartitionDiscoverer {
+    pub fn new(
+        client_config: &ClientConfig,
+        subscription: TopicSubscription,
+        consumer_index: u32,
+        num_consumers: u32,
+        interval: Duration,
+        known_partitions: Vec<PartitionMetadata>,
+    ) -> Self {
+        let consumer: BaseConsumer = client_config.create().expect("Consumer creation failed");
+        PartitionDiscoverer {
+            subscription,
+            consumer,
+            consumer_index,
+            num_consumers,
+            interval,
+            known_partitions: known_partitions.into_iter().collect(),
+            first: true,
+        }
+    }
+
+    /// discover the partitions every `interval` in a background thread, the metadata is
+    /// fetched by blocking, so it's kept away from the thread of the source task.
+    /// the first discovery is not delayed
+    pub fn start(mut self) -> Receiver<DiscoveredPartition> {
+        let (sender, receiver) = rlink::channel::unbounded();
+        utils::spawn("kafka-partition-discovery", move || loop {
+            for partition in self.discover() {
+                if sender.send(partition).is_err() {
+                    info!("the source is closed, stop the partition discovery");
+                    return;
+                }
+            }
+            std::thread::sleep(self.interval);
+        });
+        receiver
+    }
+
+    /// return the new partitions owned by this consumer, the partitions are discovered
+    /// again later if the metadata can't be fetched
+    fn discover(&mut self) -> Vec<DiscoveredPartition> {
+        let partitions = match self.subscription.fetch_partitions(&self.consumer) {
+            Ok(partitions) => partitions,
+            Err(e) => {
+                warn!("fetch the partitions of the subscribed topics error. {}", e);
+                return vec![];
+            }
+        };
+
+        let first = self.first;
+        self.first = false;
+
+        let mut new_partitions = Vec::new();
+        for partition in partitions {
+            if self.known_partitions.contains(&partition) {
+                continue;
+            }
+
+            self.known_partitions.insert(partition.clone());
+            if get_partition_owner(&partition, self.num_consumers) == self.consumer_index {
+                info!("discover new partition {:?}", partition);
+                new_partitions.push(DiscoveredPartition { partition, first });
+            }
+        }
+
+        new_partitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::discovery::{get_partition_owner, TopicSubscription};
+    use crate::state::PartitionMetadata;
+
+    #[test]
+    pub fn partition_owner_test() {
+        let owners: Vec<u32> = (0..6)
+            .map(|partition| {
+                let partition = PartitionMetadata {
+                    topic: "rlink-test".to_string(),
+                    partition,
+                };
+                get_partition_owner(&partition, 4)
+            })
+            .collect();
+
+        // the partitions of a topic are assigned round-robin
+        for n in 1..owners.len() {
+            assert_eq!(owners[n], (owners[n - 1] + 1) % 4);
+        }
+    }
+
+    #[test]
+    pub fn topic_subscription_test() {
+        let subscription = TopicSubscription::new(
+            vec!["payments".to_string()],
+            Some("orders|refunds-.*".to_string()),
+        );
+
+        assert!(subscription.is_subscribed("payments"));
+        assert!(subscription.is_subscribed("orders"));
+        assert!(subscription.is_subscribed("refunds-eu"));
+
+        // the pattern matches the whole topic name
+        assert!(!subscription.is_subscribed("orders_dlq"));
+        assert!(!subscription.is_subscribed("old_orders"));
+        assert!(!subscription.is_subscribed("payments_dlq"));
+    }
+}
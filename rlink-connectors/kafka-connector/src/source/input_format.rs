@@ -1,8 +1,10 @@
 use std::borrow::BorrowMut;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
-use rdkafka::consumer::{BaseConsumer, Consumer};
-use rdkafka::{ClientConfig, Offset};
+use rdkafka::consumer::BaseConsumer;
+use rdkafka::ClientConfig;
 use rlink::api::backend::OperatorStateBackend;
 use rlink::api::checkpoint::CheckpointedFunction;
 use rlink::api::element::Record;
@@ -10,21 +12,47 @@ use rlink::api::function::Context;
 use rlink::api::input::{InputFormat, InputSplitSource};
 use rlink::api::properties::{Properties, SystemProperties};
 use rlink::api::split::{InputSplit, InputSplitAssigner};
-use rlink::channel::TryRecvError;
+use rlink::api::watermark::TimestampAssigner;
+use rlink::channel::{Receiver, Sender, TryRecvError};
+use rlink::metrics::{register_counter, Tag};
 
-use crate::source::checkpoint::KafkaCheckpointed;
+use crate::source::checkpoint::{get_offset_state_backend, KafkaCheckpointed};
+use crate::source::committer::OffsetCommitter;
 use crate::source::consumer::{create_kafka_consumer, get_kafka_consumer_handover};
+use crate::source::dead_letter::DeadLetterProducer;
+use crate::source::deserializer::KafkaDeserializer;
+use crate::source::discovery::{DiscoveredPartition, PartitionDiscoverer, TopicSubscription};
 use crate::source::handover::Handover;
-use crate::KafkaRecord;
+use crate::source::startup_mode::StartupMode;
+use crate::source::watermark::{KafkaPartitionWatermarkAssigner, PartitionWatermarks, SystemClock};
+use crate::state::{OffsetMetadata, PartitionMetadata};
+use crate::{KafkaRecord, GROUP_ID};
 
 #[derive(Function)]
 pub struct KafkaInputFormat {
     client_config: ClientConfig,
     topics: Vec<String>,
+    topic_pattern: Option<String>,
+    startup_mode: StartupMode,
     handover: Option<Handover>,
 
+    /// the partition discovery is disabled if `None`
+    partition_discovery_interval: Option<Duration>,
+    discovered_partitions: Option<Receiver<DiscoveredPartition>>,
+    new_partitions: Option<Sender<OffsetMetadata>>,
+
     state_mode: Option<OperatorStateBackend>,
     checkpoint: Option<KafkaCheckpointed>,
+    commit_offsets: bool,
+
+    /// the records are in `KAFKA_DATA_TYPES` layout if `None`
+    deserializer: Option<Box<dyn KafkaDeserializer>>,
+    dead_letter_topic: Option<String>,
+    dead_letter_producer: Option<DeadLetterProducer>,
+    undecodable_counter: Arc<AtomicU64>,
+
+    /// the watermarks of the consumed partitions, see `create_watermark_assigner`
+    partition_watermarks: Option<PartitionWatermarks>,
 
     counter: u64,
 }
@@ -34,40 +62,246 @@ impl KafkaInputFormat {
         KafkaInputFormat {
             client_config,
             topics,
+            topic_pattern: None,
+            startup_mode: StartupMode::default(),
             handover: None,
+            partition_discovery_interval: None,
+            discovered_partitions: None,
+            new_partitions: None,
             state_mode: None,
             checkpoint: None,
+            commit_offsets: false,
+            deserializer: None,
+            dead_letter_topic: None,
+            dead_letter_producer: None,
+            undecodable_counter: Arc::new(AtomicU64::new(0)),
+            partition_watermarks: None,
             counter: 0,
         }
     }
+
+    /// the start position of the partitions without checkpoint, the default is `Latest`
+    pub fn set_startup_mode(&mut self, startup_mode: StartupMode) {
+        self.startup_mode = startup_mode;
+    }
+
+    /// subscribe the topics fully matching the regex `topic_pattern` besides the `topics`,
+    /// the partition discovery should be enabled to subscribe the topics created later
+    pub fn set_topic_pattern(&mut self, topic_pattern: &str) {
+        self.topic_pattern = Some(topic_pattern.to_string());
+    }
+
+    /// discover the new partitions and topics every `interval`,
+    /// they are assigned to the tasks which own the kafka consumers
+    pub fn set_partition_discovery_interval(&mut self, interval: Duration) {
+        self.partition_discovery_interval = Some(interval);
+    }
+
+    /// commit the offsets to the consumer group of `group.id` after each completed checkpoint
+    pub fn set_commit_offsets_on_checkpoint(&mut self, commit_offsets: bool) {
+        self.commit_offsets = commit_offsets;
+    }
+
+    /// deserialize the payloads to the typed records, the data types of the records
+    /// are the `get_schema_types` of the deserializer.
+    ///
+    /// the records are no longer in `KAFKA_DATA_TYPES` layout, the topic, partition, offset
+    /// and timestamp of the messages are dropped. so the functions reading the records by
+    /// `KafkaRecord`, such as `KafkaTimestampAssigner` and `KafkaRecordSerializer`, can't be
+    /// used with them, the timestamp should be extracted from the columns of the deserializer.
+    /// the watermarks of `create_watermark_assigner` still follow the partitions of the messages
+    pub fn set_deserializer<D>(&mut self, deserializer: D)
+    where
+        D: KafkaDeserializer + 'static,
+    {
+        self.deserializer = Some(Box::new(deserializer));
+    }
+
+    /// send the messages which can't be deserialized to the `topic`,
+    /// they are discarded if the dead letter topic is not set
+    pub fn set_dead_letter_topic(&mut self, topic: &str) {
+        self.dead_letter_topic = Some(topic.to_string());
+    }
+
+    /// generate the watermark of each partition consumed by the source, the returned
+    /// assigner must be assigned to the stream of this source, and the emitted watermark
+    /// is the minimum of the partitions. the partitions are tracked from the assignment
+    /// of the source, so a partition holds back the watermark before its first record,
+    /// until it's idle for `idle_timeout`
+    pub fn create_watermark_assigner<E>(
+        &mut self,
+        max_out_of_orderness: Duration,
+        idle_timeout: Duration,
+        extract_timestamp: E,
+    ) -> KafkaPartitionWatermarkAssigner<E>
+    where
+        E: TimestampAssigner,
+    {
+        let partition_watermarks = PartitionWatermarks::new(Arc::new(SystemClock::default()));
+        self.partition_watermarks = Some(partition_watermarks.clone());
+        KafkaPartitionWatermarkAssigner::new(
+            max_out_of_orderness,
+            idle_timeout,
+            partition_watermarks,
+            extract_timestamp,
+        )
+    }
+
+    /// return `None` if the payload of the record can't be deserialized
+    fn deserialize(&mut self, mut record: Record) -> Option<Record> {
+        let deserializer = match self.deserializer.as_mut() {
+            Some(deserializer) => deserializer,
+            None => return Some(record),
+        };
+
+        let result = {
+            let mut reader = KafkaRecord::new(record.borrow_mut());
+            match reader.get_kafka_payload() {
+                Ok(payload) => deserializer.deserialize(payload),
+                Err(e) => Err(e),
+            }
+        };
+        match result {
+            Ok(record) => Some(record),
+            Err(e) => {
+                let n = self.undecodable_counter.fetch_add(1, Ordering::Relaxed);
+                // 8388605 = 8 * 1024 * 1024 -1
+                if n & 8388605 == 0 {
+                    warn!("deserialize kafka message error. {}", e);
+                }
+
+                if let Some(dead_letter_producer) = self.dead_letter_producer.as_ref() {
+                    dead_letter_producer.send(record.borrow_mut(), &e);
+                }
+                None
+            }
+        }
+    }
+
+    fn get_subscription(&self) -> TopicSubscription {
+        TopicSubscription::new(self.topics.clone(), self.topic_pattern.clone())
+    }
+
+    /// the offset in the checkpoint is preferred, the partitions without checkpoint
+    /// start from the `startup_mode`
+    fn get_start_offset(
+        &mut self,
+        partition: &PartitionMetadata,
+        startup_mode: &StartupMode,
+    ) -> OffsetMetadata {
+        let checkpoint = self.checkpoint.as_mut().unwrap();
+        if let Some(offset_committer) = checkpoint.offset_committer.as_mut() {
+            offset_committer.assign(partition.clone());
+        }
+
+        if let Some(partition_offset) = checkpoint
+            .get_state()
+            .get(partition.topic.as_str(), partition.partition)
+        {
+            return partition_offset;
+        }
+
+        let partition_offset = match checkpoint.take_restored_offset(partition) {
+            Some(partition_offset) => partition_offset,
+            None => startup_mode.get_offset(
+                &self.client_config,
+                partition.topic.as_str(),
+                partition.partition,
+            ),
+        };
+        checkpoint.get_state().add(partition_offset.clone());
+        partition_offset
+    }
+
+    /// take the partitions discovered by the background discoverer
+    fn discover_partitions(&mut self) {
+        let discovered_partitions: Vec<DiscoveredPartition> =
+            match self.discovered_partitions.as_ref() {
+                Some(discovered_partitions) => discovered_partitions.try_iter().collect(),
+                None => return,
+            };
+
+        let mut partition_offsets = Vec::new();
+        for discovered_partition in discovered_partitions {
+            if let Some(partition_watermarks) = self.partition_watermarks.as_ref() {
+                partition_watermarks.assign(discovered_partition.partition.clone());
+            }
+
+            // the new partitions are consumed from the beginning, all of their records are new
+            let startup_mode = if discovered_partition.first {
+                self.startup_mode.clone()
+            } else {
+                StartupMode::Earliest
+            };
+            partition_offsets
+                .push(self.get_start_offset(&discovered_partition.partition, &startup_mode));
+        }
+
+        if let Some(new_partitions) = self.new_partitions.as_ref() {
+            for partition_offset in partition_offsets {
+                new_partitions
+                    .send(partition_offset)
+                    .expect("send new partition error");
+            }
+        }
+    }
 }
 
 impl InputFormat for KafkaInputFormat {
     fn open(&mut self, input_split: InputSplit, context: &Context) {
         info!("kafka source open");
 
+        if self.deserializer.is_some() {
+            let tags = vec![
+                Tag("chain_id".to_string(), context.chain_id.to_string()),
+                Tag("task_number".to_string(), context.task_number.to_string()),
+            ];
+            register_counter(
+                "KafkaSource_Undecodable",
+                tags,
+                self.undecodable_counter.clone(),
+            );
+
+            if let Some(topic) = &self.dead_letter_topic {
+                self.dead_letter_producer =
+                    Some(DeadLetterProducer::new(&self.client_config, topic.as_str()));
+            }
+        }
+
+        // the follower shares the consumer of the partition of the input split
+        if let Some(partition_watermarks) = self.partition_watermarks.as_ref() {
+            let properties = input_split.get_properties();
+            partition_watermarks.assign(PartitionMetadata {
+                topic: properties.get_string("topic").unwrap(),
+                partition: properties.get_i32("partition").unwrap(),
+            });
+        }
+
         let can_create_consumer = input_split
             .get_properties()
             .get_string("create_kafka_connection")
             .unwrap();
         if can_create_consumer.to_lowercase().eq("true") {
-            let state_backend = context
-                .job_properties
-                .get_operator_state_backend()
-                .unwrap_or(OperatorStateBackend::None);
+            let state_backend = get_offset_state_backend(&context.job_properties);
             self.state_mode = Some(state_backend);
 
             let mut kafka_checkpoint = KafkaCheckpointed::new(
-                context.job_id.clone(),
+                context.job_name.clone(),
                 context.chain_id,
                 context.task_number,
-                // self.state_mode.as_ref().unwrap().clone(),
+                self.state_mode.as_ref().unwrap().clone(),
             );
             // todo provide the data from coordinator
             kafka_checkpoint.initialize_state(
                 &context.get_checkpoint_context(),
                 &context.checkpoint_handle,
             );
+            if self.commit_offsets {
+                if self.client_config.get(GROUP_ID).is_none() {
+                    panic!("`group.id` is required to commit offsets");
+                }
+                kafka_checkpoint.offset_committer = Some(OffsetCommitter::new(&self.client_config));
+            }
             self.checkpoint = Some(kafka_checkpoint);
 
             let topic = input_split.get_properties().get_string("topic").unwrap();
@@ -75,12 +309,35 @@ impl InputFormat for KafkaInputFormat {
 
             self.handover = Some(Handover::new(topic.as_str(), partition));
 
-            let partition_offset =
-                self.checkpoint
-                    .as_mut()
-                    .unwrap()
-                    .get_state()
-                    .get(topic, partition, Offset::End);
+            let startup_mode = self.startup_mode.clone();
+            let partition_offset = self.get_start_offset(
+                &PartitionMetadata {
+                    topic: topic.clone(),
+                    partition,
+                },
+                &startup_mode,
+            );
+
+            let (sender, receiver) = rlink::channel::unbounded();
+            self.new_partitions = Some(sender);
+            if let Some(interval) = self.partition_discovery_interval {
+                let properties = input_split.get_properties();
+                let num_consumers = properties.get_u32("num_consumers").unwrap();
+                let initial_partitions = properties.get_string("initial_partitions").unwrap();
+                let initial_partitions: Vec<PartitionMetadata> =
+                    serde_json::from_str(initial_partitions.as_str()).unwrap();
+
+                let partition_discoverer = PartitionDiscoverer::new(
+                    &self.client_config,
+                    self.get_subscription(),
+                    input_split.get_split_number(),
+                    num_consumers,
+                    interval,
+                    initial_partitions,
+                );
+                // the partitions beyond the input splits are taken by the first discovery
+                self.discovered_partitions = Some(partition_discoverer.start());
+            }
 
             let client_config = self.client_config.clone();
             let handover = self.handover.as_ref().unwrap().clone();
@@ -90,6 +347,7 @@ impl InputFormat for KafkaInputFormat {
                 context.task_number,
                 client_config,
                 partition_offsets,
+                receiver,
                 handover,
             );
 
@@ -106,45 +364,63 @@ impl InputFormat for KafkaInputFormat {
     }
 
     fn next_record(&mut self) -> Option<Record> {
-        match self.handover.as_ref() {
-            Some(handover) => {
-                match handover.poll_next() {
-                    Ok(mut record) => {
-                        // save to state
-                        let mut reader = KafkaRecord::new(record.borrow_mut());
-
-                        // same as `self.counter % 4096`
-                        if self.counter & 4095 == 0 {
-                            match self.checkpoint.as_mut() {
-                                Some(checkpoint) => {
-                                    checkpoint.get_state().update(
-                                        reader.get_kafka_topic().unwrap(),
-                                        reader.get_kafka_partition().unwrap(),
-                                        reader.get_kafka_offset().unwrap(),
-                                    );
-                                }
-                                None => {}
-                            }
-                        }
-
-                        self.counter += 1;
-
-                        Some(record)
-                    }
+        if self.counter & 4095 == 0 {
+            self.discover_partitions();
+        }
+
+        loop {
+            let mut record = match self.handover.as_ref() {
+                Some(handover) => match handover.poll_next() {
+                    Ok(record) => record,
                     Err(TryRecvError::Empty) => {
                         self.counter = 0;
-                        None
+                        return None;
                     }
                     Err(TryRecvError::Disconnected) => {
                         panic!("kafka input recv channel disconnected");
                     }
+                },
+                None => return None,
+            };
+
+            // save to state
+            let mut reader = KafkaRecord::new(record.borrow_mut());
+
+            // same as `self.counter % 4096`
+            if self.counter & 4095 == 0 {
+                match self.checkpoint.as_mut() {
+                    Some(checkpoint) => {
+                        checkpoint.get_state().update(
+                            reader.get_kafka_topic().unwrap(),
+                            reader.get_kafka_partition().unwrap(),
+                            reader.get_kafka_offset().unwrap(),
+                        );
+                    }
+                    None => {}
                 }
             }
-            None => None,
+
+            self.counter += 1;
+
+            if let Some(partition_watermarks) = self.partition_watermarks.as_ref() {
+                partition_watermarks.set_current_partition(PartitionMetadata {
+                    topic: reader.get_kafka_topic().unwrap(),
+                    partition: reader.get_kafka_partition().unwrap(),
+                });
+            }
+
+            // the undecodable message is skipped
+            if let Some(record) = self.deserialize(record) {
+                return Some(record);
+            }
         }
     }
 
-    fn close(&mut self) {}
+    fn close(&mut self) {
+        if let Some(dead_letter_producer) = self.dead_letter_producer.as_ref() {
+            dead_letter_producer.flush();
+        }
+    }
 
     fn get_checkpoint(&mut self) -> Option<Box<&mut dyn CheckpointedFunction>> {
         match self.checkpoint.as_mut() {
@@ -156,44 +432,39 @@ impl InputFormat for KafkaInputFormat {
 
 impl InputSplitSource for KafkaInputFormat {
     fn create_input_splits(&self, min_num_splits: u32) -> Vec<InputSplit> {
-        let timeout = Duration::from_secs(3);
-
         info!("kafka config {:?}", self.client_config);
 
         let consumer: BaseConsumer = self
             .client_config
             .create()
             .expect("Consumer creation failed");
+        let partitions = self
+            .get_subscription()
+            .fetch_partitions(&consumer)
+            .expect("Failed to fetch metadata");
 
-        let mut input_splits = Vec::new();
-        let mut index = 0;
-        for topic in &self.topics {
-            let metadata = consumer
-                .fetch_metadata(Some(topic.as_str()), timeout)
-                .expect("Failed to fetch metadata");
-            let metadata_topic = metadata
-                .topics()
-                .get(0)
-                .expect(format!("Topic({}) not found", topic).as_str());
-
-            for partition in metadata_topic.partitions() {
-                let mut properties = Properties::new();
-                properties.set_str("topic", topic.as_str());
-                properties.set_i32("partition", partition.id());
-                properties.set_str("create_kafka_connection", "true");
-
-                let input_split = InputSplit::new(index, properties);
-                index += 1;
-
-                input_splits.push(input_split);
-                if index == min_num_splits {
-                    break;
-                }
-            }
+        if partitions.len() > min_num_splits as usize && self.partition_discovery_interval.is_none()
+        {
+            panic!("kafka `input_splits.len()` != `min_num_splits`")
         }
 
-        if input_splits.len() > min_num_splits as usize {
-            panic!("kafka `input_splits.len()` != `min_num_splits`")
+        // the partitions beyond the input splits are taken by the partition discovery
+        let initial_partitions: Vec<PartitionMetadata> = partitions
+            .into_iter()
+            .take(min_num_splits as usize)
+            .collect();
+        let initial_partitions_json = serde_json::to_string(&initial_partitions).unwrap();
+
+        let mut input_splits = Vec::new();
+        for (index, partition) in initial_partitions.iter().enumerate() {
+            let mut properties = Properties::new();
+            properties.set_str("topic", partition.topic.as_str());
+            properties.set_i32("partition", partition.partition);
+            properties.set_str("create_kafka_connection", "true");
+            properties.set_u32("num_consumers", initial_partitions.len() as u32);
+            properties.set_str("initial_partitions", initial_partitions_json.as_str());
+
+            input_splits.push(InputSplit::new(index as u32, properties));
         }
 
         if input_splits.len() < min_num_splits as usize {
@@ -1,4 +1,10 @@
 pub mod checkpoint;
+pub(crate) mod committer;
 pub mod consumer;
+pub(crate) mod dead_letter;
+pub mod deserializer;
+pub(crate) mod discovery;
 pub mod handover;
 pub mod input_format;
+pub mod startup_mode;
+pub mod watermark;
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+
+use crate::state::OffsetMetadata;
+
+/// the start position of the partitions without checkpoint
+#[derive(Clone, Debug, PartialEq)]
+pub enum StartupMode {
+    /// from the earliest offset of the partition
+    Earliest,
+    /// from the latest offset of the partition, only the new records are consumed
+    Latest,
+    /// from the offsets committed by the `group.id`, `auto.offset.reset` is applied
+    /// if there is no committed offset
+    GroupOffsets,
+    /// from the specified offsets, keyed by `(topic, partition)`.
+    /// the partitions not specified start from the group offsets
+    SpecificOffsets(HashMap<(String, i32), i64>),
+    /// from the earliest offset whose timestamp(ms) is greater than or equal to the given one.
+    /// the partitions without such a record start from the latest offset
+    Timestamp(i64),
+}
+
+impl Default for StartupMode {
+    fn default() -> Self {
+        StartupMode::Latest
+    }
+}
+
+impl StartupMode {
+    pub(crate) fn get_offset(
+        &self,
+        client_config: &ClientConfig,
+        topic: &str,
+        partition: i32,
+    ) -> OffsetMetadata {
+        let offset = match self {
+            StartupMode::Earliest => Offset::Beginning,
+            StartupMode::Latest => Offset::End,
+            StartupMode::GroupOffsets => Offset::Stored,
+            StartupMode::SpecificOffsets(offsets) => {
+                match offsets.get(&(topic.to_string(), partition)) {
+                    Some(offset) => Offset::Offset(*offset),
+                    None => Offset::Stored,
+                }
+            }
+            StartupMode::Timestamp(timestamp) => {
+                offset_for_timestamp(client_config, topic, partition, *timestamp)
+            }
+        };
+
+        info!(
+            "{:?} start from {:?} by {:?}",
+            (topic, partition),
+            offset,
+            self
+        );
+        OffsetMetadata {
+            topic: topic.to_string(),
+            partition,
+            offset: offset.to_raw(),
+        }
+    }
+}
+
+fn offset_for_timestamp(
+    client_config: &ClientConfig,
+    topic: &str,
+    partition: i32,
+    timestamp: i64,
+) -> Offset {
+    let consumer: BaseConsumer = client_config.create().expect("Consumer creation failed");
+
+    let mut timestamps = TopicPartitionList::new();
+    timestamps.add_partition_offset(topic, partition, Offset::Offset(timestamp));
+
+    let offsets = consumer
+        .offsets_for_times(timestamps, Duration::from_secs(10))
+        .expect(format!("seek ({}, {}) by timestamp error", topic, partition).as_str());
+    match offsets.find_partition(topic, partition) {
+        Some(element) => match element.offset() {
+            Offset::Offset(offset) => Offset::Offset(offset),
+            _ => Offset::End,
+        },
+        None => Offset::End,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rdkafka::{ClientConfig, Offset};
+
+    use crate::source::startup_mode::StartupMode;
+
+    #[test]
+    pub fn startup_mode_offset_test() {
+        let client_config = ClientConfig::new();
+
+        let offset = StartupMode::Earliest.get_offset(&client_config, "t", 0);
+        assert_eq!(offset.offset, Offset::Beginning.to_raw());
+
+        let offset = StartupMode::default().get_offset(&client_config, "t", 0);
+        assert_eq!(offset.offset, Offset::End.to_raw());
+
+        let mut offsets = HashMap::new();
+        offsets.insert(("t".to_string(), 1), 100);
+        let startup_mode = StartupMode::SpecificOffsets(offsets);
+
+        let offset = startup_mode.get_offset(&client_config, "t", 1);
+        assert_eq!(offset.offset, 100);
+        let offset = startup_mode.get_offset(&client_config, "t", 0);
+        assert_eq!(offset.offset, Offset::Stored.to_raw());
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rlink::api::element::{Element, Record};
+use rlink::api::function::Function;
+use rlink::api::watermark::{TimestampAssigner, Watermark, WatermarkAssigner};
+use rlink::utils::date_time::{current_timestamp_millis, timestamp_str};
+
+use crate::state::PartitionMetadata;
+use crate::KafkaRecord;
+
+/// extract the timestamp of the kafka message.
+///
+/// the record must be in `KAFKA_DATA_TYPES` layout, so it can't be used with the
+/// deserializer of the `KafkaInputFormat`
+#[derive(Debug, Default, Function)]
+pub struct KafkaTimestampAssigner {}
+
+impl KafkaTimestampAssigner {
+    pub fn new() -> Self {
+        KafkaTimestampAssigner {}
+    }
+}
+
+impl TimestampAssigner for KafkaTimestampAssigner {
+    fn extract_timestamp(&mut self, row: &mut Record, _previous_element_timestamp: u64) -> u64 {
+        let mut reader = KafkaRecord::new(row);
+        reader.get_kafka_timestamp().unwrap_or_default() as u64
+    }
+}
+
+/// the processing time(ms) to detect the idle partitions
+pub(crate) trait Clock: Debug + Send + Sync {
+    fn now(&self) -> u64;
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SystemClock {}
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        current_timestamp_millis()
+    }
+}
+
+#[derive(Debug)]
+struct PartitionWatermark {
+    max_timestamp: u64,
+    /// the partition has records since the last watermark
+    active: bool,
+    last_active: u64,
+}
+
+#[derive(Debug, Default)]
+struct PartitionWatermarkState {
+    partitions: HashMap<PartitionMetadata, PartitionWatermark>,
+    /// the partition of the record emitted by the source
+    current_partition: Option<PartitionMetadata>,
+}
+
+/// the watermarks of the partitions consumed by a source task, shared by the
+/// `KafkaInputFormat` and the `KafkaPartitionWatermarkAssigner` of the task.
+///
+/// the source tracks the assigned partitions and the partition of each emitted record,
+/// the assigner is chained with the source, so the timestamp extracted by it belongs to
+/// the partition of the latest emitted record
+#[derive(Clone, Debug)]
+pub(crate) struct PartitionWatermarks {
+    state: Arc<Mutex<PartitionWatermarkState>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl PartitionWatermarks {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        PartitionWatermarks {
+            state: Arc::new(Mutex::new(PartitionWatermarkState::default())),
+            clock,
+        }
+    }
+
+    /// track the partition assigned to the source before its first record,
+    /// it holds back the watermark until it has records or it's idle
+    pub fn assign(&self, partition: PartitionMetadata) {
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        state
+            .partitions
+            .entry(partition)
+            .or_insert_with(|| PartitionWatermark {
+                max_timestamp: 0,
+                active: false,
+                last_active: now,
+            });
+    }
+
+    pub fn set_current_partition(&self, partition: PartitionMetadata) {
+        let mut state = self.state.lock().unwrap();
+        state.current_partition = Some(partition);
+    }
+
+    fn update(&self, timestamp: u64) {
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        let partition = match state.current_partition.clone() {
+            Some(partition) => partition,
+            None => return,
+        };
+
+        let partition_watermark =
+            state
+                .partitions
+                .entry(partition)
+                .or_insert_with(|| PartitionWatermark {
+                    max_timestamp: 0,
+                    active: true,
+                    last_active: now,
+                });
+        partition_watermark.active = true;
+        if timestamp > partition_watermark.max_timestamp {
+            partition_watermark.max_timestamp = timestamp;
+        }
+    }
+
+    /// the minimum of the active partitions, `None` if all partitions are idle
+    fn get_min_watermark(&self, max_out_of_orderness: u64, idle_timeout: u64) -> Option<u64> {
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+
+        let mut min_watermark = None;
+        for (partition, partition_watermark) in &mut state.partitions {
+            if partition_watermark.active {
+                partition_watermark.active = false;
+                partition_watermark.last_active = now;
+            } else if now.saturating_sub(partition_watermark.last_active) > idle_timeout {
+                debug!("partition {:?} is idle", partition);
+                continue;
+            }
+
+            let watermark = partition_watermark
+                .max_timestamp
+                .saturating_sub(max_out_of_orderness);
+            min_watermark = match min_watermark {
+                Some(min) if min <= watermark => Some(min),
+                _ => Some(watermark),
+            };
+        }
+
+        min_watermark
+    }
+}
+
+/// Generate the watermark of each kafka partition consumed by the source, the emitted
+/// watermark is the minimum of the active partitions, so a lagging partition holds back
+/// the watermark. it's created by `KafkaInputFormat::create_watermark_assigner`.
+///
+/// the partition which has no records in `idle_timeout` is idle and excluded,
+/// it becomes active again by the next record.
+#[derive(Debug)]
+pub struct KafkaPartitionWatermarkAssigner<E>
+where
+    E: TimestampAssigner,
+{
+    max_out_of_orderness: u64,
+    idle_timeout: u64,
+    watermarks: PartitionWatermarks,
+    last_emitted_watermark: u64,
+    extract_timestamp: E,
+}
+
+impl<E> KafkaPartitionWatermarkAssigner<E>
+where
+    E: TimestampAssigner,
+{
+    pub(crate) fn new(
+        max_out_of_orderness: Duration,
+        idle_timeout: Duration,
+        watermarks: PartitionWatermarks,
+        extract_timestamp: E,
+    ) -> Self {
+        KafkaPartitionWatermarkAssigner {
+            max_out_of_orderness: max_out_of_orderness.as_millis() as u64,
+            idle_timeout: idle_timeout.as_millis() as u64,
+            watermarks,
+            last_emitted_watermark: 0,
+            extract_timestamp,
+        }
+    }
+
+    fn get_min_watermark(&self) -> Option<u64> {
+        self.watermarks
+            .get_min_watermark(self.max_out_of_orderness, self.idle_timeout)
+    }
+}
+
+impl<E> WatermarkAssigner for KafkaPartitionWatermarkAssigner<E>
+where
+    E: TimestampAssigner,
+{
+    fn get_watermark(&mut self, element: &Element) -> Option<Watermark> {
+        if !element.is_stream_status() {
+            return None;
+        }
+
+        match self.get_min_watermark() {
+            Some(watermark) if watermark > self.last_emitted_watermark => {
+                self.last_emitted_watermark = watermark;
+                debug!("Create Watermark: {}", timestamp_str(watermark));
+                Some(Watermark::new(watermark))
+            }
+            _ => None,
+        }
+    }
+
+    fn get_current_watermark(&self) -> Option<Watermark> {
+        if self.last_emitted_watermark == 0 {
+            None
+        } else {
+            Some(Watermark::new(self.last_emitted_watermark))
+        }
+    }
+}
+
+impl<E> TimestampAssigner for KafkaPartitionWatermarkAssigner<E>
+where
+    E: TimestampAssigner,
+{
+    fn extract_timestamp(&mut self, row: &mut Record, previous_element_timestamp: u64) -> u64 {
+        let timestamp = self
+            .extract_timestamp
+            .extract_timestamp(row, previous_element_timestamp);
+        self.watermarks.update(timestamp);
+
+        timestamp
+    }
+}
+
+impl<E> Function for KafkaPartitionWatermarkAssigner<E>
+where
+    E: TimestampAssigner,
+{
+    fn get_name(&self) -> &str {
+        "KafkaPartitionWatermarkAssigner"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use rlink::api::watermark::TimestampAssigner;
+
+    use crate::build_kafka_record;
+    use crate::source::watermark::{
+        Clock, KafkaPartitionWatermarkAssigner, KafkaTimestampAssigner, PartitionWatermarks,
+    };
+    use crate::state::PartitionMetadata;
+
+    #[derive(Debug, Default)]
+    struct ManualClock {
+        now: AtomicU64,
+    }
+
+    impl ManualClock {
+        fn advance(&self, millis: u64) {
+            self.now.fetch_add(millis, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> u64 {
+            self.now.load(Ordering::SeqCst)
+        }
+    }
+
+    fn partition(partition: i32) -> PartitionMetadata {
+        PartitionMetadata {
+            topic: "rlink-test".to_string(),
+            partition,
+        }
+    }
+
+    fn extract(
+        assigner: &mut dyn TimestampAssigner,
+        watermarks: &PartitionWatermarks,
+        partition_num: i32,
+        timestamp: i64,
+    ) {
+        // the source sets the partition of the emitted record
+        watermarks.set_current_partition(partition(partition_num));
+        let mut record =
+            build_kafka_record(timestamp, &[], &[], "rlink-test", partition_num, 0).unwrap();
+        assigner.extract_timestamp(&mut record, 0);
+    }
+
+    #[test]
+    pub fn partition_watermark_test() {
+        let clock = Arc::new(ManualClock::default());
+        let watermarks = PartitionWatermarks::new(clock.clone());
+        let mut assigner = KafkaPartitionWatermarkAssigner::new(
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+            watermarks.clone(),
+            KafkaTimestampAssigner::new(),
+        );
+
+        // the assigned partitions are tracked before their first records
+        watermarks.assign(partition(0));
+        watermarks.assign(partition(1));
+        extract(&mut assigner, &watermarks, 0, 1000);
+        assert_eq!(assigner.get_min_watermark(), Some(0));
+
+        extract(&mut assigner, &watermarks, 1, 500);
+        // the lagging partition 1 holds back the watermark
+        assert_eq!(assigner.get_min_watermark(), Some(490));
+
+        extract(&mut assigner, &watermarks, 0, 2000);
+        clock.advance(100);
+        extract(&mut assigner, &watermarks, 0, 3000);
+        // the partition 1 is idle
+        assert_eq!(assigner.get_min_watermark(), Some(2990));
+
+        clock.advance(100);
+        // all partitions are idle
+        assert_eq!(assigner.get_min_watermark(), None);
+    }
+
+    #[test]
+    pub fn idle_assigned_partition_test() {
+        let clock = Arc::new(ManualClock::default());
+        let watermarks = PartitionWatermarks::new(clock.clone());
+        let mut assigner = KafkaPartitionWatermarkAssigner::new(
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+            watermarks.clone(),
+            KafkaTimestampAssigner::new(),
+        );
+
+        watermarks.assign(partition(0));
+        watermarks.assign(partition(1));
+        extract(&mut assigner, &watermarks, 0, 1000);
+        assert_eq!(assigner.get_min_watermark(), Some(0));
+
+        // the partition 1 never has records, it's idle after the timeout
+        clock.advance(100);
+        extract(&mut assigner, &watermarks, 0, 2000);
+        assert_eq!(assigner.get_min_watermark(), Some(1990));
+    }
+}
@@ -3,13 +3,13 @@ use std::collections::HashMap;
 use rdkafka::Offset;
 use serde::Serialize;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PartitionMetadata {
     pub(crate) topic: String,
     pub(crate) partition: i32,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct OffsetMetadata {
     pub(crate) topic: String,
     pub(crate) partition: i32,
@@ -42,22 +42,24 @@ impl KafkaSourceStateCache {
         self.partition_offsets.insert(key, val);
     }
 
+    /// keep the start offset of a discovered partition until its records are consumed
+    pub fn add(&mut self, offset_metadata: OffsetMetadata) {
+        let key = PartitionMetadata {
+            topic: offset_metadata.topic.clone(),
+            partition: offset_metadata.partition,
+        };
+        self.partition_offsets.entry(key).or_insert(offset_metadata);
+    }
+
     pub fn snapshot(&self) -> HashMap<PartitionMetadata, OffsetMetadata> {
         self.partition_offsets.clone()
     }
 
-    pub fn get(&self, topic: String, partition: i32, default_offset: Offset) -> OffsetMetadata {
+    pub fn get(&self, topic: &str, partition: i32) -> Option<OffsetMetadata> {
         let key = PartitionMetadata {
-            topic: topic.clone(),
+            topic: topic.to_string(),
             partition,
         };
-        match self.partition_offsets.get(&key) {
-            Some(offset_metadata) => offset_metadata.clone(),
-            None => OffsetMetadata {
-                topic,
-                partition,
-                offset: default_offset.to_raw(),
-            },
-        }
+        self.partition_offsets.get(&key).map(|x| x.clone())
     }
 }
@@ -6,7 +6,13 @@ use std::fmt::{Debug, Display, Formatter};
 #[serde(tag = "type", content = "param")]
 pub enum CheckpointBackend {
     Memory,
-    MySql { endpoint: String },
+    MySql {
+        endpoint: String,
+    },
+    /// keep the completed checkpoints as files under the `path`
+    FileSystem {
+        path: String,
+    },
 }
 
 impl Display for CheckpointBackend {
@@ -14,6 +20,7 @@ impl Display for CheckpointBackend {
         match self {
             CheckpointBackend::Memory => write!(f, "Memory"),
             CheckpointBackend::MySql { endpoint } => write!(f, "MySql{{endpoint={}}}", endpoint),
+            CheckpointBackend::FileSystem { path } => write!(f, "FileSystem{{path={}}}", path),
         }
     }
 }
@@ -23,7 +30,13 @@ impl Display for CheckpointBackend {
 pub enum KeyedStateBackend {
     Memory,
     // FsStateBackend(String),
-    // RocksDBStateBackend(String),
+    /// keep the window states in the RocksDB under the `path`, requires the `rocksdb` feature.
+    /// the window states are not restored from the checkpoints, so this backend has no fault
+    /// tolerance, the windows in progress are lost when the task is restarted
+    #[cfg(feature = "rocksdb")]
+    RocksDB {
+        path: String,
+    },
 }
 
 impl Display for KeyedStateBackend {
@@ -31,9 +44,8 @@ impl Display for KeyedStateBackend {
         match self {
             KeyedStateBackend::Memory => write!(f, "Memory"),
             // StateBackend::FsStateBackend(path) => write!(f, "FsStateBackend{{path={}}}", path),
-            // KeyedStateBackend::RocksDBStateBackend(path) => {
-            //     write!(f, "RocksDBStateBackend{{path={}}}", path)
-            // }
+            #[cfg(feature = "rocksdb")]
+            KeyedStateBackend::RocksDB { path } => write!(f, "RocksDB{{path={}}}", path),
         }
     }
 }
@@ -42,19 +54,20 @@ impl Display for KeyedStateBackend {
 #[serde(tag = "type", content = "param")]
 pub enum OperatorStateBackend {
     None,
-    // DFSStateBackendWorkDir,
-    // DFSStateBackend(String),
+    /// keep the operator states as files under the work space of the worker
+    DFSStateBackendWorkDir,
+    /// keep the operator states as files under the `path`, eg: a mounted distributed file system
+    DFS {
+        path: String,
+    },
 }
 
 impl Display for OperatorStateBackend {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             OperatorStateBackend::None => write!(f, "None"),
-            // StateBackend::FsStateBackend(path) => write!(f, "FsStateBackend{{path={}}}", path),
-            // OperatorStateBackend::DFSStateBackendWorkDir => write!(f, "DFSStateBackendWorkDir"),
-            // OperatorStateBackend::DFSStateBackend(path) => {
-            //     write!(f, "DFSStateBackend{{path={}}}", path)
-            // }
+            OperatorStateBackend::DFSStateBackendWorkDir => write!(f, "DFSStateBackendWorkDir"),
+            OperatorStateBackend::DFS { path } => write!(f, "DFS{{path={}}}", path),
         }
     }
 }
@@ -1,8 +1,33 @@
 use crate::api::backend::{OperatorState, OperatorStateBackend};
 use crate::runtime::ChainId;
 use crate::storage::operator_state::{OperatorStateManager, OperatorStateManagerWrap};
+use crate::utils::date_time::current_timestamp_millis;
 use std::fmt::Debug;
 
+/// the highest bit of the `checkpoint_id` is reserved for the savepoints,
+/// so a savepoint never collides with the periodic checkpoints whose id is a timestamp
+pub const SAVEPOINT_FLAG: u64 = 1 << 63;
+
+/// returns `true` if the `checkpoint_id` is created by a savepoint
+pub fn is_savepoint(checkpoint_id: u64) -> bool {
+    checkpoint_id & SAVEPOINT_FLAG == SAVEPOINT_FLAG
+}
+
+pub(crate) fn new_savepoint_id() -> u64 {
+    SAVEPOINT_FLAG | current_timestamp_millis()
+}
+
+/// how the barriers of a checkpoint are handled by a task with multiple upstream tasks
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum CheckpointMode {
+    /// the elements of the upstream tasks whose barrier has reached are held back
+    /// until the barriers of all upstream tasks reached, the snapshot is exactly once
+    Aligned,
+    /// the elements are never held back, so the snapshot may include the elements after
+    /// the barrier and they are processed again after restore. lower latency, at least once
+    Unaligned,
+}
+
 #[derive(Clone, Debug)]
 pub struct FunctionSnapshotContext {
     pub chain_id: u32,
@@ -23,10 +48,10 @@ impl FunctionSnapshotContext {
         &self,
         chain_id: ChainId,
         state: OperatorStateBackend,
-        job_id: String,
+        job_name: String,
         task_number: u16,
     ) -> Box<dyn OperatorState> {
-        OperatorStateManagerWrap::new(chain_id, state).create_state(job_id, task_number)
+        OperatorStateManagerWrap::new(chain_id, state).create_state(job_name, task_number)
     }
 }
 
@@ -62,4 +87,9 @@ where
 
     /// trigger the method when the `operator` operate a `Barrier` event
     fn snapshot_state(&mut self, context: &FunctionSnapshotContext) -> CheckpointHandle;
+
+    /// trigger the method when the checkpoint has been acknowledged by all tasks of the job.
+    /// the notification may be skipped if a later checkpoint completes first, so it confirms
+    /// all the snapshots not later than the `checkpoint_id`. the savepoints are not notified
+    fn notify_checkpoint_complete(&mut self, _checkpoint_id: u64) {}
 }
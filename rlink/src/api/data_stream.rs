@@ -1,10 +1,16 @@
-use crate::api::function::{FilterFunction, KeySelectorFunction, MapFunction, ReduceFunction};
+use crate::api::evictor::Evictor;
+use crate::api::function::{
+    AggregateFunction, AggregateReduceFunction, FilterFunction, KeySelectorFunction,
+    KeyedProcessFunction, MapFunction, ProcessWindowFunction, ReduceFunction,
+};
 use crate::api::input::InputFormat;
 use crate::api::operator::{FunctionCreator, StreamOperatorWrap};
 use crate::api::output::OutputFormat;
+use crate::api::trigger::Trigger;
 use crate::api::watermark::WatermarkAssigner;
-use crate::api::window::WindowAssigner;
+use crate::api::window::{WindowAssigner, WindowOptions};
 use std::fmt::Debug;
+use std::time::Duration;
 
 pub(crate) const ROOT_ID: u32 = 100;
 
@@ -38,15 +44,60 @@ pub trait TKeyedStream {
     fn window<W>(self, window_assigner: W) -> WindowedStream
     where
         W: WindowAssigner + 'static;
+    /// process each record of the key by the `KeyedProcessFunction` without window,
+    /// the function can keep the state of the key and register the timers.
+    fn process<F>(self, process: F, parallelism: u32) -> DataStream
+    where
+        F: KeyedProcessFunction + 'static;
     fn add_sink<O>(self, output_format: O) -> SinkStream
     where
         O: OutputFormat + 'static;
 }
 
 pub trait TWindowedStream {
+    /// Sets the time by which elements are allowed to be late. the window state is kept
+    /// until the watermark passes the end of the window plus the `lateness`, and the
+    /// window result is emitted again when the window is updated by the late elements.
+    fn allowed_lateness(self, lateness: Duration) -> WindowedStream;
+
+    /// Send the late elements, which arrive after the window is purged, to the `output_format`
+    /// instead of dropping them.
+    ///
+    /// it's a side sink rather than a side output stream, the `output_format` is written in
+    /// the window task and is never flushed or committed by the checkpoint, so the late
+    /// elements are delivered at most once.
+    fn side_output_late_data<O>(self, output_format: O) -> WindowedStream
+    where
+        O: OutputFormat + 'static;
+
+    /// Sets the `Trigger` that is consulted on each element and watermark to fire or purge
+    /// the windows before the end of the window.
+    fn trigger<T>(self, trigger: T) -> WindowedStream
+    where
+        T: Trigger + 'static;
+
+    /// Adds an `Evictor` to remove the buffered records of the window before the
+    /// `ProcessWindowFunction` is evaluated. only supported by the `process`.
+    fn evictor<E>(self, evictor: E) -> WindowedStream
+    where
+        E: Evictor + 'static;
+
     fn reduce<F>(self, reduce: F, parallelism: u32) -> DataStream
     where
         F: ReduceFunction + 'static;
+
+    /// Applies the `AggregateFunction` incrementally to each window of the key,
+    /// only the accumulator is kept in the window state and the output value is
+    /// materialized by the `AggregateFunction::get_result` when the window is emitted.
+    fn aggregate<F>(self, aggregate: F, parallelism: u32) -> DataStream
+    where
+        F: AggregateFunction + 'static;
+
+    /// Applies the `ProcessWindowFunction` to all the buffered records of each window of the key.
+    /// the merging windows(eg: session windows) are not supported.
+    fn process<F>(self, process: F, parallelism: u32) -> DataStream
+    where
+        F: ProcessWindowFunction + 'static;
 }
 
 pub trait TEndStream {}
@@ -122,6 +173,17 @@ impl TKeyedStream for KeyedStream {
         }
     }
 
+    fn process<F>(self, process: F, parallelism: u32) -> DataStream
+    where
+        F: KeyedProcessFunction + 'static,
+    {
+        match self {
+            KeyedStream::DefaultKeyedStream(keyed_stream) => {
+                TKeyedStream::process(keyed_stream, process, parallelism)
+            }
+        }
+    }
+
     fn add_sink<O>(self, output_format: O) -> SinkStream
     where
         O: OutputFormat + 'static,
@@ -140,6 +202,47 @@ pub enum WindowedStream {
 }
 
 impl TWindowedStream for WindowedStream {
+    fn allowed_lateness(self, lateness: Duration) -> WindowedStream {
+        match self {
+            WindowedStream::DefaultWindowedStream(windowed_stream) => {
+                windowed_stream.allowed_lateness(lateness)
+            }
+        }
+    }
+
+    fn side_output_late_data<O>(self, output_format: O) -> WindowedStream
+    where
+        O: OutputFormat + 'static,
+    {
+        match self {
+            WindowedStream::DefaultWindowedStream(windowed_stream) => {
+                windowed_stream.side_output_late_data(output_format)
+            }
+        }
+    }
+
+    fn trigger<T>(self, trigger: T) -> WindowedStream
+    where
+        T: Trigger + 'static,
+    {
+        match self {
+            WindowedStream::DefaultWindowedStream(windowed_stream) => {
+                windowed_stream.trigger(trigger)
+            }
+        }
+    }
+
+    fn evictor<E>(self, evictor: E) -> WindowedStream
+    where
+        E: Evictor + 'static,
+    {
+        match self {
+            WindowedStream::DefaultWindowedStream(windowed_stream) => {
+                windowed_stream.evictor(evictor)
+            }
+        }
+    }
+
     fn reduce<F>(self, reduce: F, parallelism: u32) -> DataStream
     where
         F: ReduceFunction + 'static,
@@ -150,6 +253,28 @@ impl TWindowedStream for WindowedStream {
             }
         }
     }
+
+    fn aggregate<F>(self, aggregate: F, parallelism: u32) -> DataStream
+    where
+        F: AggregateFunction + 'static,
+    {
+        match self {
+            WindowedStream::DefaultWindowedStream(windowed_stream) => {
+                windowed_stream.aggregate(aggregate, parallelism)
+            }
+        }
+    }
+
+    fn process<F>(self, process: F, parallelism: u32) -> DataStream
+    where
+        F: ProcessWindowFunction + 'static,
+    {
+        match self {
+            WindowedStream::DefaultWindowedStream(windowed_stream) => {
+                TWindowedStream::process(windowed_stream, process, parallelism)
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -192,6 +317,17 @@ impl DataStreamSource {
     }
 }
 
+impl DataStreamSource {
+    fn window_options_mut(&mut self) -> &mut WindowOptions {
+        match self.operators.last_mut() {
+            Some(StreamOperatorWrap::StreamWindowAssigner(_stream_window, window_options)) => {
+                window_options
+            }
+            _ => panic!("the latest operator is not a `StreamWindowAssigner`"),
+        }
+    }
+}
+
 impl StreamGraph for DataStreamSource {
     fn into_operators(self) -> Vec<StreamOperatorWrap> {
         self.operators
@@ -303,6 +439,23 @@ impl TKeyedStream for DataStreamSource {
         WindowedStream::DefaultWindowedStream(self)
     }
 
+    fn process<F>(mut self, process: F, parallelism: u32) -> DataStream
+    where
+        F: KeyedProcessFunction + 'static,
+    {
+        let parent_id = self.current_id;
+        self.current_id += 1;
+        let id = self.current_id;
+
+        let process_func = Box::new(process);
+        let stream_keyed_process =
+            StreamOperatorWrap::new_keyed_process(id, parent_id, parallelism, process_func);
+
+        self.operators.push(stream_keyed_process);
+
+        DataStream::DefaultDataStream(self)
+    }
+
     fn add_sink<O>(mut self, output_format: O) -> SinkStream
     where
         O: OutputFormat + 'static,
@@ -322,10 +475,50 @@ impl TKeyedStream for DataStreamSource {
 }
 
 impl TWindowedStream for DataStreamSource {
+    fn allowed_lateness(mut self, lateness: Duration) -> WindowedStream {
+        self.window_options_mut().allowed_lateness = lateness.as_millis() as u64;
+
+        WindowedStream::DefaultWindowedStream(self)
+    }
+
+    fn side_output_late_data<O>(mut self, output_format: O) -> WindowedStream
+    where
+        O: OutputFormat + 'static,
+    {
+        let late_data_output: Box<dyn OutputFormat> = Box::new(output_format);
+        self.window_options_mut().late_data_output = Some(late_data_output);
+
+        WindowedStream::DefaultWindowedStream(self)
+    }
+
+    fn trigger<T>(mut self, trigger: T) -> WindowedStream
+    where
+        T: Trigger + 'static,
+    {
+        let trigger: Box<dyn Trigger> = Box::new(trigger);
+        self.window_options_mut().trigger = Some(trigger);
+
+        WindowedStream::DefaultWindowedStream(self)
+    }
+
+    fn evictor<E>(mut self, evictor: E) -> WindowedStream
+    where
+        E: Evictor + 'static,
+    {
+        let evictor: Box<dyn Evictor> = Box::new(evictor);
+        self.window_options_mut().evictors.push(evictor);
+
+        WindowedStream::DefaultWindowedStream(self)
+    }
+
     fn reduce<F>(mut self, reduce: F, parallelism: u32) -> DataStream
     where
         F: ReduceFunction + 'static,
     {
+        if self.window_options_mut().evictors.len() > 0 {
+            panic!("the `Evictor` is only supported by the `process`");
+        }
+
         let parent_id = self.current_id;
         self.current_id += 1;
         let id = self.current_id;
@@ -337,6 +530,35 @@ impl TWindowedStream for DataStreamSource {
 
         DataStream::DefaultDataStream(self)
     }
+
+    fn aggregate<F>(self, aggregate: F, parallelism: u32) -> DataStream
+    where
+        F: AggregateFunction + 'static,
+    {
+        let aggregate_func = AggregateReduceFunction::new(Box::new(aggregate));
+        self.reduce(aggregate_func, parallelism)
+    }
+
+    fn process<F>(mut self, process: F, parallelism: u32) -> DataStream
+    where
+        F: ProcessWindowFunction + 'static,
+    {
+        if self.window_options_mut().merging {
+            panic!("the merging windows are not supported by the `process`");
+        }
+
+        let parent_id = self.current_id;
+        self.current_id += 1;
+        let id = self.current_id;
+
+        let process_func = Box::new(process);
+        let stream_process =
+            StreamOperatorWrap::new_process_window(id, parent_id, parallelism, process_func);
+
+        self.operators.push(stream_process);
+
+        DataStream::DefaultDataStream(self)
+    }
 }
 
 impl TEndStream for DataStreamSource {}
@@ -344,17 +566,21 @@ impl TEndStream for DataStreamSource {}
 #[cfg(test)]
 mod tests {
     use crate::api::data_stream::{DataStream, TDataStream, TWindowedStream};
-    use crate::api::data_stream::{DataStreamSource, TKeyedStream};
+    use crate::api::data_stream::{DataStreamSource, StreamGraph, TKeyedStream};
     use crate::api::element::Record;
     use crate::api::function::{
-        Context, Function, KeySelectorFunction, MapFunction, ReduceFunction,
+        Context, Function, KeySelectorFunction, KeyedProcessFunction, MapFunction, ReduceFunction,
     };
     use crate::api::input::{InputFormat, InputSplitSource};
+    use crate::api::keyed_process::KeyedProcessContext;
+    use crate::api::operator::StreamOperatorWrap;
     use crate::api::output::OutputFormat;
     use crate::api::properties::Properties;
     use crate::api::split::{InputSplit, InputSplitAssigner};
+    use crate::api::trigger::CountTrigger;
     use crate::api::watermark::{BoundedOutOfOrdernessTimestampExtractor, TimestampAssigner};
     use crate::api::window::SlidingEventTimeWindows;
+    use crate::graph::job_graph::build_job_graph;
     use std::time::Duration;
 
     #[test]
@@ -380,6 +606,67 @@ mod tests {
         println!("{:?}", end_stream);
     }
 
+    #[test]
+    pub fn windowed_stream_options_test() {
+        let n = DataStreamSource::new(Box::new(MyInputFormat::new()), 10);
+        let data_stream = DataStream::DefaultDataStream(n);
+
+        let end_stream = data_stream
+            .key_by(MyKeySelectorFunction::new())
+            .window(SlidingEventTimeWindows::new(
+                Duration::from_secs(60),
+                Duration::from_secs(20),
+                None,
+            ))
+            .allowed_lateness(Duration::from_secs(30))
+            .side_output_late_data(MyOutputFormat::new(Properties::new()))
+            .trigger(CountTrigger::of(100))
+            .reduce(MyReduceFunction::new(), 10)
+            .add_sink(MyOutputFormat::new(Properties::new()));
+
+        let window_options = end_stream
+            .into_operators()
+            .into_iter()
+            .find_map(|operator| match operator {
+                StreamOperatorWrap::StreamWindowAssigner(_, window_options) => Some(window_options),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(window_options.allowed_lateness, 30000);
+        assert!(window_options.late_data_output.is_some());
+        assert!(window_options.trigger.is_some());
+        assert!(window_options.event_time);
+        assert!(!window_options.merging);
+    }
+
+    #[test]
+    pub fn keyed_process_graph_test() {
+        let n = DataStreamSource::new(Box::new(MyInputFormat::new()), 10);
+        let data_stream = DataStream::DefaultDataStream(n);
+
+        let end_stream = data_stream
+            .key_by(MyKeySelectorFunction::new())
+            .process(MyKeyedProcessFunction::new(), 5)
+            .add_sink(MyOutputFormat::new(Properties::new()));
+
+        let job_graph = build_job_graph(end_stream.into_operators());
+        assert_eq!(job_graph.chain_map.len(), 2);
+
+        let source_chain = job_graph.chain_map.get(&1).unwrap();
+        assert_eq!(source_chain.nodes.len(), 2);
+        assert_eq!(source_chain.follower_parallelism, 5);
+
+        let keyed_process_chain = job_graph.chain_map.get(&2).unwrap();
+        assert_eq!(keyed_process_chain.parallelism, 5);
+        assert_eq!(keyed_process_chain.dependency_parallelism, 10);
+        let node_names: Vec<&str> = keyed_process_chain
+            .nodes
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(node_names, vec!["MyKeyedProcessFunction", "MyOutputFormat"]);
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     pub struct MyInputFormat {}
 
@@ -520,6 +807,40 @@ mod tests {
         }
     }
 
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct MyKeyedProcessFunction {}
+
+    impl MyKeyedProcessFunction {
+        pub fn new() -> Self {
+            MyKeyedProcessFunction {}
+        }
+    }
+
+    impl KeyedProcessFunction for MyKeyedProcessFunction {
+        fn open(&mut self, _context: &Context) {}
+
+        fn process_element(
+            &mut self,
+            record: &mut Record,
+            ctx: &mut KeyedProcessContext,
+        ) -> Vec<Record> {
+            ctx.register_event_time_timer(record.timestamp + 1000);
+            vec![record.clone()]
+        }
+
+        fn on_timer(&mut self, _timestamp: u64, _ctx: &mut KeyedProcessContext) -> Vec<Record> {
+            vec![]
+        }
+
+        fn close(&mut self) {}
+    }
+
+    impl Function for MyKeyedProcessFunction {
+        fn get_name(&self) -> &str {
+            "MyKeyedProcessFunction"
+        }
+    }
+
     #[derive(Debug)]
     pub struct MyOutputFormat {
         properties: Properties,
@@ -2,6 +2,7 @@ use crate::api::window::WindowWrap;
 use bytes::{Buf, BufMut, BytesMut};
 use std::borrow::BorrowMut;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
 
 lazy_static! {
     static ref EMPTY_VEC: Vec<WindowWrap> = Vec::with_capacity(0);
@@ -34,9 +35,11 @@ pub(crate) trait Serde {
     fn deserialize(bytes: &mut BytesMut) -> Self;
 }
 
-#[derive(Clone, Debug, Hash)]
+#[derive(Clone, Debug)]
 pub struct Record {
     pub(crate) partition_num: u16,
+    // for align, the task number of the upstream task
+    pub(crate) task_number: u16,
     pub(crate) timestamp: u64,
 
     pub(crate) location_windows: Option<Vec<WindowWrap>>,
@@ -49,6 +52,7 @@ impl Record {
     pub fn new() -> Self {
         Record {
             partition_num: 0,
+            task_number: 0,
             timestamp: 0,
             location_windows: None,
             trigger_window: None,
@@ -59,6 +63,7 @@ impl Record {
     pub fn with_capacity(capacity: usize) -> Self {
         Record {
             partition_num: 0,
+            task_number: 0,
             timestamp: 0,
             location_windows: None,
             trigger_window: None,
@@ -135,7 +140,7 @@ impl Partition for Record {
 
 impl Serde for Record {
     fn capacity(&self) -> usize {
-        15 + self.values.len()
+        17 + self.values.len()
     }
 
     fn serialize(&self, bytes: &mut BytesMut) {
@@ -143,6 +148,7 @@ impl Serde for Record {
 
         bytes.put_u8(SER_DE_RECORD);
         bytes.put_u16(self.partition_num);
+        bytes.put_u16(self.task_number);
         bytes.put_u64(self.timestamp);
 
         bytes.put_u32(value_len as u32);
@@ -154,6 +160,7 @@ impl Serde for Record {
         assert_eq!(flag, SER_DE_RECORD, "Invalid `Record` flag");
 
         let partition_num = bytes.get_u16();
+        let task_number = bytes.get_u16();
         let timestamp = bytes.get_u64();
 
         let value_len = bytes.get_u32() as usize;
@@ -161,6 +168,7 @@ impl Serde for Record {
 
         Record {
             partition_num,
+            task_number,
             timestamp,
             location_windows: None,
             trigger_window: None,
@@ -177,6 +185,14 @@ impl PartialEq for Record {
     }
 }
 
+/// only the `values` are hashed, the same as `PartialEq`.
+/// the `task_number` of the upstream task and the other metadata never affect the key
+impl Hash for Record {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.values.hash(state);
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
 pub struct Watermark {
     // for partition routing
@@ -194,6 +210,7 @@ pub struct Watermark {
     pub(crate) location_windows: Option<Vec<WindowWrap>>,
     pub(crate) downstream: bool,
     pub(crate) drop_windows: Option<Vec<WindowWrap>>,
+    pub(crate) window_firing: Option<WindowFiring>,
 }
 
 impl Watermark {
@@ -212,6 +229,7 @@ impl Watermark {
             location_windows: None,
             downstream: false,
             drop_windows: None,
+            window_firing: None,
         }
     }
 
@@ -266,6 +284,24 @@ impl Serde for Watermark {
             location_windows: None,
             downstream: false,
             drop_windows: None,
+            window_firing: None,
+        }
+    }
+}
+
+/// a firing of the window operator, which takes the `drop_windows` of the `Watermark` downstream.
+/// the firings are ordered by the checkpoint before them and the sequence after the checkpoint
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct WindowFiring {
+    pub(crate) checkpoint_id: u64,
+    pub(crate) sequence: u64,
+}
+
+impl WindowFiring {
+    pub fn new(checkpoint_id: u64, sequence: u64) -> Self {
+        WindowFiring {
+            checkpoint_id,
+            sequence,
         }
     }
 }
@@ -324,6 +360,11 @@ impl Serde for StreamStatus {
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
 pub struct Barrier {
     pub(crate) partition_num: u16,
+
+    // for align
+    pub(crate) task_number: u16,
+    pub(crate) num_tasks: u16,
+
     pub(crate) checkpoint_id: u64,
 }
 
@@ -331,6 +372,8 @@ impl Barrier {
     pub fn new(checkpoint_id: u64) -> Self {
         Barrier {
             partition_num: 0,
+            task_number: 0,
+            num_tasks: 0,
             checkpoint_id,
         }
     }
@@ -344,12 +387,14 @@ impl Partition for Barrier {
 
 impl Serde for Barrier {
     fn capacity(&self) -> usize {
-        11
+        15
     }
 
     fn serialize(&self, bytes: &mut BytesMut) {
         bytes.put_u8(SER_DE_BARRIER);
         bytes.put_u16(self.partition_num);
+        bytes.put_u16(self.task_number);
+        bytes.put_u16(self.num_tasks);
         bytes.put_u64(self.checkpoint_id);
     }
 
@@ -358,10 +403,14 @@ impl Serde for Barrier {
         assert_eq!(flag, SER_DE_BARRIER, "Invalid `Barrier` flag");
 
         let partition_num = bytes.get_u16();
+        let task_number = bytes.get_u16();
+        let num_tasks = bytes.get_u16();
         let checkpoint_id = bytes.get_u64();
 
         Barrier {
             partition_num,
+            task_number,
+            num_tasks,
             checkpoint_id,
         }
     }
@@ -487,6 +536,29 @@ impl Element {
             _ => panic!("Element is not Barrier"),
         }
     }
+
+    /// mark the upstream task of the `Record` and `Barrier`, the `Watermark` has been marked
+    /// when it is created
+    pub(crate) fn set_task_number(&mut self, task_number: u16, num_tasks: u16) {
+        match self {
+            Element::Record(record) => record.task_number = task_number,
+            Element::Barrier(barrier) => {
+                barrier.task_number = task_number;
+                barrier.num_tasks = num_tasks;
+            }
+            _ => {}
+        }
+    }
+
+    /// the task number of the upstream task, `None` if the element is not bound to a task
+    pub(crate) fn get_task_number(&self) -> Option<u16> {
+        match self {
+            Element::Record(record) => Some(record.task_number),
+            Element::Watermark(watermark) => Some(watermark.task_number),
+            Element::Barrier(barrier) => Some(barrier.task_number),
+            Element::StreamStatus(_) => None,
+        }
+    }
 }
 
 impl Partition for Element {
@@ -572,6 +644,8 @@ mod tests {
     use crate::api::element::types;
     use crate::api::element::{Element, Record, Serde, StreamStatus, Watermark};
     use std::borrow::BorrowMut;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
 
     // #[test]
     // pub fn record_test() {
@@ -669,4 +743,35 @@ mod tests {
         let de_watermark = element_watermark_de.as_stream_status();
         assert_eq!(stream_status.end, de_watermark.end);
     }
+
+    #[test]
+    pub fn serde_element_barrier_test() {
+        let mut element_barrier = Element::new_barrier(1000);
+        element_barrier.set_task_number(1, 3);
+
+        let mut data = element_barrier.to_bytes();
+        let element_barrier_de = Element::deserialize(data.borrow_mut());
+
+        let de_barrier = element_barrier_de.as_barrier();
+        assert_eq!(de_barrier.checkpoint_id, 1000);
+        assert_eq!(de_barrier.task_number, 1);
+        assert_eq!(de_barrier.num_tasks, 3);
+    }
+    #[test]
+    pub fn record_hash_test() {
+        let hash = |record: &Record| {
+            let mut hasher = DefaultHasher::new();
+            record.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut record0 = Record::new();
+        record0.get_writer(&[types::U32]).set_u32(10).unwrap();
+        let mut record1 = record0.clone();
+        record1.task_number = 2;
+        record1.timestamp = 100;
+
+        assert_eq!(record0, record1);
+        assert_eq!(hash(&record0), hash(&record1));
+    }
 }
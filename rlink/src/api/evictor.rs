@@ -0,0 +1,116 @@
+use crate::api::element::Record;
+use crate::api::function::Function;
+use crate::api::window::WindowWrap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// An `Evictor` can remove records from the buffered records of a window before the
+/// `ProcessWindowFunction` is evaluated. the evicted records are removed from the window state.
+pub trait Evictor
+where
+    Self: Function + Debug,
+{
+    /// the `records` are in the arrival order
+    fn evict(&self, records: &mut Vec<Record>, window: &WindowWrap);
+}
+
+/// An `Evictor` that keeps up to a certain amount of the latest records.
+#[derive(Debug)]
+pub struct CountEvictor {
+    max_count: usize,
+}
+
+impl CountEvictor {
+    pub fn of(max_count: usize) -> Self {
+        CountEvictor { max_count }
+    }
+}
+
+impl Evictor for CountEvictor {
+    fn evict(&self, records: &mut Vec<Record>, _window: &WindowWrap) {
+        if records.len() > self.max_count {
+            let evicted_count = records.len() - self.max_count;
+            records.drain(0..evicted_count);
+        }
+    }
+}
+
+impl Function for CountEvictor {
+    fn get_name(&self) -> &str {
+        "CountEvictor"
+    }
+}
+
+/// An `Evictor` that keeps the records for a certain amount of time. the records with the
+/// timestamp less than the max timestamp of the records minus the `window_size` are evicted.
+#[derive(Debug)]
+pub struct TimeEvictor {
+    window_size: u64,
+}
+
+impl TimeEvictor {
+    pub fn of(window_size: Duration) -> Self {
+        TimeEvictor {
+            window_size: window_size.as_millis() as u64,
+        }
+    }
+}
+
+impl Evictor for TimeEvictor {
+    fn evict(&self, records: &mut Vec<Record>, _window: &WindowWrap) {
+        let max_timestamp = match records.iter().map(|record| record.timestamp).max() {
+            Some(max_timestamp) => max_timestamp,
+            None => return,
+        };
+
+        if max_timestamp > self.window_size {
+            let evict_cutoff = max_timestamp - self.window_size;
+            records.retain(|record| record.timestamp >= evict_cutoff);
+        }
+    }
+}
+
+impl Function for TimeEvictor {
+    fn get_name(&self) -> &str {
+        "TimeEvictor"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::element::Record;
+    use crate::api::evictor::{CountEvictor, Evictor, TimeEvictor};
+    use crate::api::window::{TimeWindow, WindowWrap};
+    use std::time::Duration;
+
+    fn build_records(timestamps: Vec<u64>) -> Vec<Record> {
+        timestamps
+            .into_iter()
+            .map(|timestamp| {
+                let mut record = Record::new();
+                record.timestamp = timestamp;
+                record
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn count_evictor_test() {
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 60000));
+        let mut records = build_records(vec![1000, 2000, 3000, 4000]);
+
+        CountEvictor::of(3).evict(&mut records, &window);
+        let timestamps: Vec<u64> = records.iter().map(|record| record.timestamp).collect();
+        assert_eq!(timestamps, vec![2000, 3000, 4000]);
+    }
+
+    #[test]
+    pub fn time_evictor_test() {
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 60000));
+        let mut records = build_records(vec![1000, 12000, 3000, 8000]);
+
+        TimeEvictor::of(Duration::from_secs(5)).evict(&mut records, &window);
+        let timestamps: Vec<u64> = records.iter().map(|record| record.timestamp).collect();
+        assert_eq!(timestamps, vec![12000, 8000]);
+    }
+}
@@ -1,11 +1,15 @@
 use crate::api::checkpoint::{CheckpointHandle, FunctionSnapshotContext};
 use crate::api::element::Record;
+use crate::api::keyed_process::KeyedProcessContext;
 use crate::api::properties::Properties;
+use crate::api::window::TimeWindow;
 use std::fmt::Debug;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Context {
     pub job_id: String,
+    /// the name of the job, it's stable across the restarts, unlike the `job_id`
+    pub job_name: String,
     pub job_properties: Properties,
     pub task_id: String,
     /// task sequence number. each chain，task sequence number start at 0.
@@ -63,5 +67,204 @@ where
     fn open(&mut self, context: &Context);
     ///
     fn reduce(&self, value: Option<&mut Record>, record: &mut Record) -> Record;
+    /// merge two reduced values into one, it is required by the merging windows(eg: session window).
+    /// the default implementation assumes that the reduced value has the same schema as the record.
+    fn merge(&self, value: Option<&mut Record>, other: &mut Record) -> Record {
+        self.reduce(value, other)
+    }
+    /// materialize the output value from the reduced value when the window is emitted.
+    /// the reduced value is emitted as it is by default.
+    fn get_result(&self, value: Record) -> Record {
+        value
+    }
+    fn close(&mut self);
+}
+
+/// an incremental aggregation with an accumulator, the accumulator can have a different
+/// schema from the input record and the output value, such as the (sum, count) of an average.
+pub trait AggregateFunction
+where
+    Self: Function,
+{
+    fn open(&mut self, context: &Context);
+    /// create a new accumulator, it is the start of the aggregation of a key in a window
+    fn create_accumulator(&self) -> Record;
+    /// add the record to the accumulator and return the new accumulator
+    fn add(&self, accumulator: &mut Record, record: &mut Record) -> Record;
+    /// merge two accumulators into one, it is required by the merging windows(eg: session window).
+    fn merge(&self, accumulator: &mut Record, other: &mut Record) -> Record;
+    /// materialize the output value from the accumulator when the window is emitted
+    fn get_result(&self, accumulator: Record) -> Record;
     fn close(&mut self);
 }
+
+/// run the `AggregateFunction` as a `ReduceFunction`, the accumulator is kept as the reduced value
+pub(crate) struct AggregateReduceFunction {
+    aggregate: Box<dyn AggregateFunction>,
+}
+
+impl AggregateReduceFunction {
+    pub fn new(aggregate: Box<dyn AggregateFunction>) -> Self {
+        AggregateReduceFunction { aggregate }
+    }
+}
+
+impl ReduceFunction for AggregateReduceFunction {
+    fn open(&mut self, context: &Context) {
+        self.aggregate.open(context)
+    }
+
+    fn reduce(&self, value: Option<&mut Record>, record: &mut Record) -> Record {
+        match value {
+            Some(accumulator) => self.aggregate.add(accumulator, record),
+            None => {
+                let mut accumulator = self.aggregate.create_accumulator();
+                self.aggregate.add(&mut accumulator, record)
+            }
+        }
+    }
+
+    fn merge(&self, value: Option<&mut Record>, other: &mut Record) -> Record {
+        match value {
+            Some(accumulator) => self.aggregate.merge(accumulator, other),
+            None => other.clone(),
+        }
+    }
+
+    fn get_result(&self, value: Record) -> Record {
+        self.aggregate.get_result(value)
+    }
+
+    fn close(&mut self) {
+        self.aggregate.close()
+    }
+}
+
+impl Function for AggregateReduceFunction {
+    fn get_name(&self) -> &str {
+        self.aggregate.get_name()
+    }
+}
+
+/// a window function that evaluates a window of a key with all the buffered records of it,
+/// such as median, top-N or dedup which can not be computed incrementally.
+pub trait ProcessWindowFunction
+where
+    Self: Function,
+{
+    fn open(&mut self, context: &Context);
+    /// evaluates the `window` of the `key`, each of the returned rows is emitted with the key,
+    /// so a window of a key can emit 0..n rows, such as the top-N rows.
+    fn process(
+        &self,
+        key: &Record,
+        window: &TimeWindow,
+        records: &mut dyn Iterator<Item = &Record>,
+    ) -> Vec<Record>;
+    fn close(&mut self);
+}
+
+/// a low-level function of the keyed stream without window, which can keep the per key state
+/// and register the timers by the `KeyedProcessContext`, such as the timeout detection.
+pub trait KeyedProcessFunction
+where
+    Self: Function,
+{
+    fn open(&mut self, context: &Context);
+    /// process a record of the current key, the returned records are emitted to downstream
+    /// with the timestamp of the record.
+    fn process_element(
+        &mut self,
+        record: &mut Record,
+        ctx: &mut KeyedProcessContext,
+    ) -> Vec<Record>;
+    /// called when a timer of the current key fires, the `timestamp` is the registered time.
+    /// the returned records are emitted to downstream with the timestamp of the timer.
+    fn on_timer(&mut self, timestamp: u64, ctx: &mut KeyedProcessContext) -> Vec<Record>;
+    fn close(&mut self);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::element::{types, Record};
+    use crate::api::function::{
+        AggregateFunction, AggregateReduceFunction, Context, Function, ReduceFunction,
+    };
+
+    /// the accumulator is (sum, count), the result is the average
+    pub struct AvgAggregateFunction {}
+
+    impl AvgAggregateFunction {
+        fn accumulator(sum: i64, count: i64) -> Record {
+            let mut accumulator = Record::new();
+            let mut writer = accumulator.get_writer(&[types::I64, types::I64]);
+            writer.set_i64(sum).unwrap();
+            writer.set_i64(count).unwrap();
+            accumulator
+        }
+
+        fn read(accumulator: &mut Record) -> (i64, i64) {
+            let mut reader = accumulator.get_reader(&[types::I64, types::I64]);
+            (reader.get_i64(0).unwrap(), reader.get_i64(1).unwrap())
+        }
+    }
+
+    impl AggregateFunction for AvgAggregateFunction {
+        fn open(&mut self, _context: &Context) {}
+
+        fn create_accumulator(&self) -> Record {
+            AvgAggregateFunction::accumulator(0, 0)
+        }
+
+        fn add(&self, accumulator: &mut Record, record: &mut Record) -> Record {
+            let (sum, count) = AvgAggregateFunction::read(accumulator);
+            let value = record.get_reader(&[types::I64]).get_i64(0).unwrap();
+            AvgAggregateFunction::accumulator(sum + value, count + 1)
+        }
+
+        fn merge(&self, accumulator: &mut Record, other: &mut Record) -> Record {
+            let (sum, count) = AvgAggregateFunction::read(accumulator);
+            let (other_sum, other_count) = AvgAggregateFunction::read(other);
+            AvgAggregateFunction::accumulator(sum + other_sum, count + other_count)
+        }
+
+        fn get_result(&self, mut accumulator: Record) -> Record {
+            let (sum, count) = AvgAggregateFunction::read(&mut accumulator);
+
+            let mut value = Record::new();
+            value
+                .get_writer(&[types::I64])
+                .set_i64(sum / count)
+                .unwrap();
+            value
+        }
+
+        fn close(&mut self) {}
+    }
+
+    impl Function for AvgAggregateFunction {
+        fn get_name(&self) -> &str {
+            "AvgAggregateFunction"
+        }
+    }
+
+    fn build_record(value: i64) -> Record {
+        let mut record = Record::new();
+        record.get_writer(&[types::I64]).set_i64(value).unwrap();
+        record
+    }
+
+    #[test]
+    pub fn aggregate_reduce_function_test() {
+        let reduce_func = AggregateReduceFunction::new(Box::new(AvgAggregateFunction {}));
+
+        let mut accumulator = reduce_func.reduce(None, &mut build_record(2));
+        let mut accumulator = reduce_func.reduce(Some(&mut accumulator), &mut build_record(4));
+
+        let mut other = reduce_func.reduce(None, &mut build_record(9));
+        let accumulator = reduce_func.merge(Some(&mut accumulator), &mut other);
+
+        let mut value = reduce_func.get_result(accumulator);
+        assert_eq!(value.get_reader(&[types::I64]).get_i64(0).unwrap(), 5);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::api::element::{Record, Serde};
+use crate::storage::keyed_state::mem_keyed_state::{
+    MemoryKeyedState, MemoryMapState, MemoryValueState,
+};
+use crate::storage::keyed_state::mem_list_state::MemoryListState;
+use crate::storage::keyed_state::ListState as _;
+use crate::utils;
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::{BTreeMap, HashSet};
+
+/// the value state of the current key
+#[derive(Debug)]
+pub struct ValueState<'a> {
+    key: &'a Record,
+    state: &'a mut MemoryValueState,
+}
+
+impl<'a> ValueState<'a> {
+    pub fn value(&self) -> Option<Record> {
+        self.state.get(self.key).cloned()
+    }
+
+    pub fn update(&mut self, value: Record) {
+        self.state.insert(self.key.clone(), value);
+    }
+
+    pub fn clear(&mut self) {
+        self.state.remove(self.key);
+    }
+}
+
+/// the list state of the current key, the records are kept in the order of addition
+#[derive(Debug)]
+pub struct ListState<'a> {
+    key: &'a Record,
+    state: &'a mut MemoryListState,
+}
+
+impl<'a> ListState<'a> {
+    pub fn get(&self) -> Vec<Record> {
+        self.state.get(self.key).cloned().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, value: Record) {
+        self.state.add(self.key.clone(), value);
+    }
+
+    /// replace the existing records by the `values`
+    pub fn update(&mut self, values: Vec<Record>) {
+        self.state.remove(self.key);
+        for value in values {
+            self.state.add(self.key.clone(), value);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.state.remove(self.key);
+    }
+}
+
+/// the map state of the current key
+#[derive(Debug)]
+pub struct MapState<'a> {
+    key: &'a Record,
+    state: &'a mut MemoryMapState,
+}
+
+impl<'a> MapState<'a> {
+    pub fn get(&self, user_key: &Record) -> Option<Record> {
+        self.state.get(self.key, user_key).cloned()
+    }
+
+    pub fn contains(&self, user_key: &Record) -> bool {
+        self.state.get(self.key, user_key).is_some()
+    }
+
+    pub fn put(&mut self, user_key: Record, user_value: Record) {
+        self.state.insert(self.key.clone(), user_key, user_value);
+    }
+
+    pub fn remove(&mut self, user_key: &Record) -> Option<Record> {
+        self.state.remove(self.key, user_key)
+    }
+
+    pub fn entries(&self) -> Vec<(Record, Record)> {
+        self.state.entries(self.key)
+    }
+
+    pub fn clear(&mut self) {
+        self.state.clear(self.key);
+    }
+}
+
+/// the event time and processing time timers of the keys in a task.
+/// a key has at most one timer at the same timestamp.
+#[derive(Debug)]
+pub(crate) struct TimerService {
+    event_time_timers: BTreeMap<u64, HashSet<Record>>,
+    processing_time_timers: BTreeMap<u64, HashSet<Record>>,
+}
+
+impl TimerService {
+    pub fn new() -> Self {
+        TimerService {
+            event_time_timers: BTreeMap::new(),
+            processing_time_timers: BTreeMap::new(),
+        }
+    }
+
+    pub fn register_event_time_timer(&mut self, key: &Record, timestamp: u64) {
+        TimerService::register(&mut self.event_time_timers, key, timestamp);
+    }
+
+    pub fn register_processing_time_timer(&mut self, key: &Record, timestamp: u64) {
+        TimerService::register(&mut self.processing_time_timers, key, timestamp);
+    }
+
+    pub fn delete_event_time_timer(&mut self, key: &Record, timestamp: u64) {
+        TimerService::delete(&mut self.event_time_timers, key, timestamp);
+    }
+
+    pub fn delete_processing_time_timer(&mut self, key: &Record, timestamp: u64) {
+        TimerService::delete(&mut self.processing_time_timers, key, timestamp);
+    }
+
+    /// remove the event time timers which are not after the `watermark`,
+    /// returns the `(timestamp, key)` of them in the timestamp order
+    pub fn poll_event_time_timers(&mut self, watermark: u64) -> Vec<(u64, Record)> {
+        TimerService::poll(&mut self.event_time_timers, watermark)
+    }
+
+    /// remove the processing time timers which are not after the `processing_time`,
+    /// returns the `(timestamp, key)` of them in the timestamp order
+    pub fn poll_processing_time_timers(&mut self, processing_time: u64) -> Vec<(u64, Record)> {
+        TimerService::poll(&mut self.processing_time_timers, processing_time)
+    }
+
+    /// write the event time and processing time timers to the `bytes`
+    pub fn serialize(&self, bytes: &mut BytesMut) {
+        TimerService::serialize_timers(&self.event_time_timers, bytes);
+        TimerService::serialize_timers(&self.processing_time_timers, bytes);
+    }
+
+    /// read the timers written by `serialize`
+    pub fn deserialize(bytes: &mut BytesMut) -> Self {
+        TimerService {
+            event_time_timers: TimerService::deserialize_timers(bytes),
+            processing_time_timers: TimerService::deserialize_timers(bytes),
+        }
+    }
+
+    fn serialize_timers(timers: &BTreeMap<u64, HashSet<Record>>, bytes: &mut BytesMut) {
+        bytes.put_u32(timers.len() as u32);
+        for (timestamp, keys) in timers {
+            bytes.put_u64(*timestamp);
+            bytes.put_u32(keys.len() as u32);
+            for key in keys {
+                key.serialize(bytes);
+            }
+        }
+    }
+
+    fn deserialize_timers(bytes: &mut BytesMut) -> BTreeMap<u64, HashSet<Record>> {
+        let mut timers = BTreeMap::new();
+        let len = bytes.get_u32();
+        for _ in 0..len {
+            let timestamp = bytes.get_u64();
+            let key_len = bytes.get_u32() as usize;
+            let mut keys = HashSet::with_capacity(key_len);
+            for _ in 0..key_len {
+                keys.insert(Record::deserialize(bytes));
+            }
+            timers.insert(timestamp, keys);
+        }
+
+        timers
+    }
+
+    fn register(timers: &mut BTreeMap<u64, HashSet<Record>>, key: &Record, timestamp: u64) {
+        timers.entry(timestamp).or_default().insert(key.clone());
+    }
+
+    fn delete(timers: &mut BTreeMap<u64, HashSet<Record>>, key: &Record, timestamp: u64) {
+        if let Some(keys) = timers.get_mut(&timestamp) {
+            keys.remove(key);
+            if keys.is_empty() {
+                timers.remove(&timestamp);
+            }
+        }
+    }
+
+    fn poll(timers: &mut BTreeMap<u64, HashSet<Record>>, timestamp: u64) -> Vec<(u64, Record)> {
+        let expired_timers = if timestamp == u64::MAX {
+            std::mem::take(timers)
+        } else {
+            let remaining_timers = timers.split_off(&(timestamp + 1));
+            std::mem::replace(timers, remaining_timers)
+        };
+
+        expired_timers
+            .into_iter()
+            .flat_map(|(timestamp, keys)| keys.into_iter().map(move |key| (timestamp, key)))
+            .collect()
+    }
+}
+
+/// the context of the `KeyedProcessFunction` for a record or a timer of the current key,
+/// provides the keyed states and the timers of the key.
+#[derive(Debug)]
+pub struct KeyedProcessContext<'a> {
+    key: &'a Record,
+    timestamp: u64,
+    current_watermark: u64,
+    keyed_state: &'a mut MemoryKeyedState,
+    timer_service: &'a mut TimerService,
+}
+
+impl<'a> KeyedProcessContext<'a> {
+    pub(crate) fn new(
+        key: &'a Record,
+        timestamp: u64,
+        current_watermark: u64,
+        keyed_state: &'a mut MemoryKeyedState,
+        timer_service: &'a mut TimerService,
+    ) -> Self {
+        KeyedProcessContext {
+            key,
+            timestamp,
+            current_watermark,
+            keyed_state,
+            timer_service,
+        }
+    }
+
+    pub fn get_current_key(&self) -> &Record {
+        self.key
+    }
+
+    /// the timestamp of the processing record, or the timestamp of the firing timer
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// the aligned watermark of all upstream tasks
+    pub fn current_watermark(&self) -> u64 {
+        self.current_watermark
+    }
+
+    pub fn current_processing_time(&self) -> u64 {
+        utils::date_time::current_timestamp_millis()
+    }
+
+    pub fn value_state(&mut self, name: &str) -> ValueState<'_> {
+        ValueState {
+            key: self.key,
+            state: self.keyed_state.value_state(name),
+        }
+    }
+
+    pub fn list_state(&mut self, name: &str) -> ListState<'_> {
+        ListState {
+            key: self.key,
+            state: self.keyed_state.list_state(name),
+        }
+    }
+
+    pub fn map_state(&mut self, name: &str) -> MapState<'_> {
+        MapState {
+            key: self.key,
+            state: self.keyed_state.map_state(name),
+        }
+    }
+
+    /// the timer fires when the watermark passes the `timestamp`
+    pub fn register_event_time_timer(&mut self, timestamp: u64) {
+        self.timer_service
+            .register_event_time_timer(self.key, timestamp);
+    }
+
+    /// the timer fires when the processing time passes the `timestamp`,
+    /// the processing time timers are checked every second.
+    pub fn register_processing_time_timer(&mut self, timestamp: u64) {
+        self.timer_service
+            .register_processing_time_timer(self.key, timestamp);
+    }
+
+    pub fn delete_event_time_timer(&mut self, timestamp: u64) {
+        self.timer_service
+            .delete_event_time_timer(self.key, timestamp);
+    }
+
+    pub fn delete_processing_time_timer(&mut self, timestamp: u64) {
+        self.timer_service
+            .delete_processing_time_timer(self.key, timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::element::{types, Record};
+    use crate::api::keyed_process::{KeyedProcessContext, TimerService};
+    use crate::storage::keyed_state::mem_keyed_state::MemoryKeyedState;
+    use bytes::BytesMut;
+
+    fn build_record(value: i64) -> Record {
+        let mut record = Record::new();
+        record.get_writer(&[types::I64]).set_i64(value).unwrap();
+        record
+    }
+
+    fn read(record: &mut Record) -> i64 {
+        record.get_reader(&[types::I64]).get_i64(0).unwrap()
+    }
+
+    #[test]
+    pub fn keyed_state_test() {
+        let mut keyed_state = MemoryKeyedState::new();
+        let mut timer_service = TimerService::new();
+
+        let key1 = build_record(1);
+        {
+            let mut ctx =
+                KeyedProcessContext::new(&key1, 0, 0, &mut keyed_state, &mut timer_service);
+            ctx.value_state("count").update(build_record(10));
+            ctx.list_state("history").add(build_record(11));
+            ctx.list_state("history").add(build_record(12));
+            ctx.map_state("mapping")
+                .put(build_record(13), build_record(14));
+        }
+
+        let key2 = build_record(2);
+        {
+            let mut ctx =
+                KeyedProcessContext::new(&key2, 0, 0, &mut keyed_state, &mut timer_service);
+            assert!(ctx.value_state("count").value().is_none());
+            assert!(ctx.list_state("history").get().is_empty());
+            assert!(ctx.map_state("mapping").entries().is_empty());
+        }
+
+        let mut ctx = KeyedProcessContext::new(&key1, 0, 0, &mut keyed_state, &mut timer_service);
+        assert_eq!(read(&mut ctx.value_state("count").value().unwrap()), 10);
+        let history: Vec<i64> = ctx
+            .list_state("history")
+            .get()
+            .iter_mut()
+            .map(read)
+            .collect();
+        assert_eq!(history, vec![11, 12]);
+        let mut user_value = ctx.map_state("mapping").get(&build_record(13)).unwrap();
+        assert_eq!(read(&mut user_value), 14);
+
+        ctx.value_state("count").clear();
+        ctx.list_state("history").clear();
+        ctx.map_state("mapping").remove(&build_record(13));
+        assert!(ctx.value_state("count").value().is_none());
+        assert!(ctx.list_state("history").get().is_empty());
+        assert!(!ctx.map_state("mapping").contains(&build_record(13)));
+    }
+
+    #[test]
+    pub fn timer_service_test() {
+        let mut timer_service = TimerService::new();
+        let key1 = build_record(1);
+        let key2 = build_record(2);
+
+        timer_service.register_event_time_timer(&key1, 3000);
+        timer_service.register_event_time_timer(&key1, 3000);
+        timer_service.register_event_time_timer(&key2, 1000);
+        timer_service.register_event_time_timer(&key2, 5000);
+        timer_service.delete_event_time_timer(&key2, 5000);
+
+        assert!(timer_service.poll_event_time_timers(999).is_empty());
+        let timers: Vec<u64> = timer_service
+            .poll_event_time_timers(3000)
+            .into_iter()
+            .map(|(timestamp, _key)| timestamp)
+            .collect();
+        assert_eq!(timers, vec![1000, 3000]);
+        assert!(timer_service.poll_event_time_timers(u64::MAX).is_empty());
+
+        timer_service.register_processing_time_timer(&key1, 2000);
+        assert!(timer_service.poll_event_time_timers(u64::MAX).is_empty());
+        assert_eq!(timer_service.poll_processing_time_timers(2000).len(), 1);
+    }
+
+    #[test]
+    pub fn timer_service_serde_test() {
+        let mut timer_service = TimerService::new();
+        let key1 = build_record(1);
+        let key2 = build_record(2);
+        timer_service.register_event_time_timer(&key1, 3000);
+        timer_service.register_event_time_timer(&key2, 3000);
+        timer_service.register_processing_time_timer(&key2, 4000);
+
+        let mut bytes = BytesMut::new();
+        timer_service.serialize(&mut bytes);
+        let mut restored_timer_service = TimerService::deserialize(&mut bytes);
+        assert!(bytes.is_empty());
+
+        assert_eq!(restored_timer_service.poll_event_time_timers(3000).len(), 2);
+        let timers = restored_timer_service.poll_processing_time_timers(4000);
+        assert_eq!(timers, vec![(4000, key2)]);
+    }
+}
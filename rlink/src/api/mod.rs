@@ -5,12 +5,15 @@ pub mod cluster;
 pub mod data_stream;
 pub mod element;
 pub mod env;
+pub mod evictor;
 pub mod function;
 pub mod input;
+pub mod keyed_process;
 pub mod metadata;
 pub mod operator;
 pub mod output;
 pub mod properties;
 pub mod split;
+pub mod trigger;
 pub mod watermark;
 pub mod window;
@@ -1,10 +1,11 @@
 use crate::api::function::{
-    FilterFunction, Function, KeySelectorFunction, MapFunction, ReduceFunction,
+    FilterFunction, Function, KeySelectorFunction, KeyedProcessFunction, MapFunction,
+    ProcessWindowFunction, ReduceFunction,
 };
 use crate::api::input::InputFormat;
 use crate::api::output::OutputFormat;
 use crate::api::watermark::WatermarkAssigner;
-use crate::api::window::WindowAssigner;
+use crate::api::window::{WindowAssigner, WindowOptions};
 use std::fmt::Debug;
 
 pub const DEFAULT_PARALLELISM: u32 = 0;
@@ -102,8 +103,10 @@ pub enum StreamOperatorWrap {
     StreamFilter(StreamOperator<dyn FilterFunction>),
     StreamKeyBy(StreamOperator<dyn KeySelectorFunction>),
     StreamReduce(StreamOperator<dyn ReduceFunction>),
+    StreamProcessWindow(StreamOperator<dyn ProcessWindowFunction>),
+    StreamKeyedProcess(StreamOperator<dyn KeyedProcessFunction>),
     StreamWatermarkAssigner(StreamOperator<dyn WatermarkAssigner>),
-    StreamWindowAssigner(StreamOperator<dyn WindowAssigner>),
+    StreamWindowAssigner(StreamOperator<dyn WindowAssigner>, WindowOptions),
     StreamSink(StreamOperator<dyn OutputFormat>),
 }
 
@@ -163,6 +166,38 @@ impl StreamOperatorWrap {
         StreamOperatorWrap::StreamReduce(operator)
     }
 
+    pub fn new_process_window(
+        id: u32,
+        parent_id: u32,
+        parallelism: u32,
+        process_fn: Box<dyn ProcessWindowFunction>,
+    ) -> Self {
+        let operator = StreamOperator::new(
+            id,
+            parent_id,
+            parallelism,
+            FunctionCreator::User,
+            process_fn,
+        );
+        StreamOperatorWrap::StreamProcessWindow(operator)
+    }
+
+    pub fn new_keyed_process(
+        id: u32,
+        parent_id: u32,
+        parallelism: u32,
+        process_fn: Box<dyn KeyedProcessFunction>,
+    ) -> Self {
+        let operator = StreamOperator::new(
+            id,
+            parent_id,
+            parallelism,
+            FunctionCreator::User,
+            process_fn,
+        );
+        StreamOperatorWrap::StreamKeyedProcess(operator)
+    }
+
     pub fn new_watermark_assigner(
         id: u32,
         parent_id: u32,
@@ -183,6 +218,7 @@ impl StreamOperatorWrap {
         parent_id: u32,
         window_assigner: Box<dyn WindowAssigner>,
     ) -> Self {
+        let window_options = WindowOptions::new(window_assigner.as_ref());
         let operator = StreamOperator::new(
             id,
             parent_id,
@@ -190,7 +226,7 @@ impl StreamOperatorWrap {
             FunctionCreator::User,
             window_assigner,
         );
-        StreamOperatorWrap::StreamWindowAssigner(operator)
+        StreamOperatorWrap::StreamWindowAssigner(operator, window_options)
     }
 
     pub fn new_sink(
@@ -211,7 +247,7 @@ impl StreamOperatorWrap {
     }
 
     pub fn is_window(&self) -> bool {
-        if let StreamOperatorWrap::StreamWindowAssigner(_stream_window) = self {
+        if let StreamOperatorWrap::StreamWindowAssigner(_stream_window, _window_options) = self {
             return true;
         }
         false
@@ -231,6 +267,25 @@ impl StreamOperatorWrap {
         false
     }
 
+    pub fn is_process_window(&self) -> bool {
+        if let StreamOperatorWrap::StreamProcessWindow(_stream_process) = self {
+            return true;
+        }
+        false
+    }
+
+    pub fn is_keyed_process(&self) -> bool {
+        if let StreamOperatorWrap::StreamKeyedProcess(_stream_keyed_process) = self {
+            return true;
+        }
+        false
+    }
+
+    /// the operator evaluates the windows, eg: `Reduce` or `ProcessWindow`
+    pub fn is_window_function(&self) -> bool {
+        self.is_reduce() || self.is_process_window()
+    }
+
     pub fn is_sink(&self) -> bool {
         if let StreamOperatorWrap::StreamSink(_stream_sink) = self {
             return true;
@@ -261,8 +316,10 @@ impl TStreamOperator for StreamOperatorWrap {
             StreamOperatorWrap::StreamFilter(op) => op.get_operator_name(),
             StreamOperatorWrap::StreamKeyBy(op) => op.get_operator_name(),
             StreamOperatorWrap::StreamReduce(op) => op.get_operator_name(),
+            StreamOperatorWrap::StreamProcessWindow(op) => op.get_operator_name(),
+            StreamOperatorWrap::StreamKeyedProcess(op) => op.get_operator_name(),
             StreamOperatorWrap::StreamWatermarkAssigner(op) => op.get_operator_name(),
-            StreamOperatorWrap::StreamWindowAssigner(op) => op.get_operator_name(),
+            StreamOperatorWrap::StreamWindowAssigner(op, _) => op.get_operator_name(),
             StreamOperatorWrap::StreamSink(op) => op.get_operator_name(),
         }
     }
@@ -274,8 +331,10 @@ impl TStreamOperator for StreamOperatorWrap {
             StreamOperatorWrap::StreamFilter(op) => op.get_operator_id(),
             StreamOperatorWrap::StreamKeyBy(op) => op.get_operator_id(),
             StreamOperatorWrap::StreamReduce(op) => op.get_operator_id(),
+            StreamOperatorWrap::StreamProcessWindow(op) => op.get_operator_id(),
+            StreamOperatorWrap::StreamKeyedProcess(op) => op.get_operator_id(),
             StreamOperatorWrap::StreamWatermarkAssigner(op) => op.get_operator_id(),
-            StreamOperatorWrap::StreamWindowAssigner(op) => op.get_operator_id(),
+            StreamOperatorWrap::StreamWindowAssigner(op, _) => op.get_operator_id(),
             StreamOperatorWrap::StreamSink(op) => op.get_operator_id(),
         }
     }
@@ -287,8 +346,10 @@ impl TStreamOperator for StreamOperatorWrap {
             StreamOperatorWrap::StreamFilter(op) => op.get_parent_operator_id(),
             StreamOperatorWrap::StreamKeyBy(op) => op.get_parent_operator_id(),
             StreamOperatorWrap::StreamReduce(op) => op.get_parent_operator_id(),
+            StreamOperatorWrap::StreamProcessWindow(op) => op.get_parent_operator_id(),
+            StreamOperatorWrap::StreamKeyedProcess(op) => op.get_parent_operator_id(),
             StreamOperatorWrap::StreamWatermarkAssigner(op) => op.get_parent_operator_id(),
-            StreamOperatorWrap::StreamWindowAssigner(op) => op.get_parent_operator_id(),
+            StreamOperatorWrap::StreamWindowAssigner(op, _) => op.get_parent_operator_id(),
             StreamOperatorWrap::StreamSink(op) => op.get_parent_operator_id(),
         }
     }
@@ -300,8 +361,10 @@ impl TStreamOperator for StreamOperatorWrap {
             StreamOperatorWrap::StreamFilter(op) => op.get_parallelism(),
             StreamOperatorWrap::StreamKeyBy(op) => op.get_parallelism(),
             StreamOperatorWrap::StreamReduce(op) => op.get_parallelism(),
+            StreamOperatorWrap::StreamProcessWindow(op) => op.get_parallelism(),
+            StreamOperatorWrap::StreamKeyedProcess(op) => op.get_parallelism(),
             StreamOperatorWrap::StreamWatermarkAssigner(op) => op.get_parallelism(),
-            StreamOperatorWrap::StreamWindowAssigner(op) => op.get_parallelism(),
+            StreamOperatorWrap::StreamWindowAssigner(op, _) => op.get_parallelism(),
             StreamOperatorWrap::StreamSink(op) => op.get_parallelism(),
         }
     }
@@ -313,8 +376,10 @@ impl TStreamOperator for StreamOperatorWrap {
             StreamOperatorWrap::StreamFilter(op) => op.get_fn_creator(),
             StreamOperatorWrap::StreamKeyBy(op) => op.get_fn_creator(),
             StreamOperatorWrap::StreamReduce(op) => op.get_fn_creator(),
+            StreamOperatorWrap::StreamProcessWindow(op) => op.get_fn_creator(),
+            StreamOperatorWrap::StreamKeyedProcess(op) => op.get_fn_creator(),
             StreamOperatorWrap::StreamWatermarkAssigner(op) => op.get_fn_creator(),
-            StreamOperatorWrap::StreamWindowAssigner(op) => op.get_fn_creator(),
+            StreamOperatorWrap::StreamWindowAssigner(op, _) => op.get_fn_creator(),
             StreamOperatorWrap::StreamSink(op) => op.get_fn_creator(),
         }
     }
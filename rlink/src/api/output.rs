@@ -1,3 +1,4 @@
+use crate::api::checkpoint::CheckpointedFunction;
 use crate::api::element::{Element, Record};
 use crate::api::function::{Context, Function};
 
@@ -21,9 +22,43 @@ where
 
     fn close(&mut self);
 
-    // todo unsupported. `TwoPhaseCommitSinkFunction`
-    fn begin_transaction(&mut self) {}
-    fn prepare_commit(&mut self) {}
-    fn commit(&mut self) {}
-    fn abort(&mut self) {}
+    /// flush the buffered records when the barrier reached, the barrier is not passed
+    /// until it returns, so the records before the checkpoint are never lost.
+    /// the checkpoint of the task is failed if an error is returned
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn get_checkpoint(&mut self) -> Option<Box<&mut dyn CheckpointedFunction>> {
+        None
+    }
+
+    /// the two-phase commit protocol is enabled if a transaction is returned.
+    ///
+    /// begin a transaction after the barrier of `checkpoint_id`, the records are written to it
+    /// until the next barrier. the returned handle identifies the transaction after restore
+    fn begin_transaction(&mut self, _checkpoint_id: u64) -> Option<String> {
+        None
+    }
+
+    /// flush and pre-commit the `transaction` when the barrier reached,
+    /// it's committed or aborted later, no more records are written to it
+    fn prepare_commit(&mut self, _transaction: &str) {}
+
+    /// commit the pre-committed `transaction` after the checkpoint is completed by the job
+    fn commit(&mut self, _transaction: &str) {}
+
+    /// abort the `transaction`, the records written to it are discarded
+    fn abort(&mut self, _transaction: &str) {}
+
+    /// commit the `transaction` pre-committed before the restart.
+    /// it may have been committed, so it must be idempotent
+    fn recover_and_commit(&mut self, transaction: &str) {
+        self.commit(transaction)
+    }
+
+    /// abort the `transaction` which is not pre-committed before the restart
+    fn recover_and_abort(&mut self, transaction: &str) {
+        self.abort(transaction)
+    }
 }
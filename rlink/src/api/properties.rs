@@ -1,4 +1,5 @@
 use crate::api::backend::{CheckpointBackend, KeyedStateBackend, OperatorStateBackend};
+use crate::api::checkpoint::CheckpointMode;
 use crate::api::metadata::MetadataStorageMode;
 use std::collections::HashMap;
 use std::num::ParseIntError;
@@ -23,6 +24,9 @@ pub trait SystemProperties {
     fn set_checkpoint(&mut self, mode: CheckpointBackend);
     fn get_checkpoint(&self) -> Result<CheckpointBackend, PropertiesError>;
 
+    fn set_checkpoint_mode(&mut self, mode: CheckpointMode);
+    fn get_checkpoint_mode(&self) -> Result<CheckpointMode, PropertiesError>;
+
     fn get_cluster_mode(&self) -> ClusterMode;
 }
 
@@ -159,6 +163,18 @@ impl SystemProperties for Properties {
         }
     }
 
+    fn set_checkpoint_mode(&mut self, mode: CheckpointMode) {
+        let value = serde_json::to_string(&mode).unwrap();
+        self.set_string("SYSTEM_CHECKPOINT_MODE".to_string(), value);
+    }
+
+    fn get_checkpoint_mode(&self) -> Result<CheckpointMode, PropertiesError> {
+        match self.get_string("SYSTEM_CHECKPOINT_MODE") {
+            Ok(value) => serde_json::from_str(value.as_str()).map_err(|e| PropertiesError::from(e)),
+            Err(e) => Err(e),
+        }
+    }
+
     fn get_cluster_mode(&self) -> ClusterMode {
         match self.get_string(SYSTEM_CLUSTER_MODE) {
             Ok(value) => ClusterMode::from(value),
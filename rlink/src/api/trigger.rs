@@ -0,0 +1,402 @@
+use crate::api::element::{Record, Serde};
+use crate::api::function::Function;
+use crate::api::window::{Window, WindowWrap};
+use crate::storage::keyed_state::snapshot::{deserialize_window, serialize_window};
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Result type for trigger methods. This determines what happens with the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerResult {
+    /// No action is taken on the window.
+    Continue,
+    /// The window is evaluated and results are emitted. The window is not purged.
+    Fire,
+    /// All elements in the window are cleared and the window is discarded,
+    /// without evaluating the window function or emitting any elements.
+    Purge,
+    /// The window is evaluated and results are emitted. The window is then purged.
+    FireAndPurge,
+}
+
+/// A `Trigger` determines when a window is evaluated to emit the results before
+/// the end of the window, such as the early firing.
+///
+/// Notice: the window is always fired when the watermark(or the processing time) passes the end
+/// of the window, and purged after the `allowed_lateness`.
+/// the trigger is consulted for each key of the window, so the state of the trigger
+/// should be kept per (key, window).
+pub trait Trigger
+where
+    Self: Function + Debug,
+{
+    /// Called for every record that is added to the window of the key.
+    fn on_element(&mut self, key: &Record, record: &Record, window: &WindowWrap) -> TriggerResult;
+
+    /// Called when the aligned watermark advances,
+    /// for each key of the windows that have not been ended.
+    fn on_event_time(&mut self, time: u64, key: &Record, window: &WindowWrap) -> TriggerResult;
+
+    /// Called when the processing time timer ticks,
+    /// for each key of the windows that have not been ended.
+    fn on_processing_time(&mut self, time: u64, key: &Record, window: &WindowWrap)
+        -> TriggerResult;
+
+    /// Clears any state that the trigger might still hold for the window of the key.
+    /// this is called when the key is purged from the window.
+    fn clear(&mut self, key: &Record, window: &WindowWrap);
+
+    /// Writes the state of the trigger to the snapshot of the window state,
+    /// a stateful trigger must write all its state, and read it back in `restore_state`.
+    fn snapshot_state(&self, _bytes: &mut BytesMut) {}
+
+    /// Replaces the state of the trigger by the state written by `snapshot_state`.
+    fn restore_state(&mut self, _bytes: &mut BytesMut) {}
+}
+
+/// write the state of each key and window
+fn serialize_key_windows(state: &HashMap<(Record, WindowWrap), u64>, bytes: &mut BytesMut) {
+    bytes.put_u32(state.len() as u32);
+    for ((key, window), value) in state {
+        key.serialize(bytes);
+        serialize_window(window, bytes);
+        bytes.put_u64(*value);
+    }
+}
+
+fn deserialize_key_windows(bytes: &mut BytesMut) -> HashMap<(Record, WindowWrap), u64> {
+    let len = bytes.get_u32() as usize;
+    let mut state = HashMap::with_capacity(len);
+    for _ in 0..len {
+        let key = Record::deserialize(bytes);
+        let window = deserialize_window(bytes);
+        state.insert((key, window), bytes.get_u64());
+    }
+
+    state
+}
+
+/// A `Trigger` that fires once the count of records of a key in a window reaches the given count.
+#[derive(Debug)]
+pub struct CountTrigger {
+    max_count: u64,
+    counters: HashMap<(Record, WindowWrap), u64>,
+}
+
+impl CountTrigger {
+    pub fn of(max_count: u64) -> Self {
+        if max_count == 0 {
+            panic!("CountTrigger parameters must satisfy 0 < max_count")
+        }
+
+        CountTrigger {
+            max_count,
+            counters: HashMap::new(),
+        }
+    }
+}
+
+impl Trigger for CountTrigger {
+    fn on_element(&mut self, key: &Record, _record: &Record, window: &WindowWrap) -> TriggerResult {
+        let counter = self
+            .counters
+            .entry((key.clone(), window.clone()))
+            .or_insert(0);
+        *counter += 1;
+
+        if *counter >= self.max_count {
+            *counter = 0;
+            TriggerResult::Fire
+        } else {
+            TriggerResult::Continue
+        }
+    }
+
+    fn on_event_time(&mut self, _time: u64, _key: &Record, _window: &WindowWrap) -> TriggerResult {
+        TriggerResult::Continue
+    }
+
+    fn on_processing_time(
+        &mut self,
+        _time: u64,
+        _key: &Record,
+        _window: &WindowWrap,
+    ) -> TriggerResult {
+        TriggerResult::Continue
+    }
+
+    fn clear(&mut self, key: &Record, window: &WindowWrap) {
+        self.counters.remove(&(key.clone(), window.clone()));
+    }
+
+    fn snapshot_state(&self, bytes: &mut BytesMut) {
+        serialize_key_windows(&self.counters, bytes);
+    }
+
+    fn restore_state(&mut self, bytes: &mut BytesMut) {
+        self.counters = deserialize_key_windows(bytes);
+    }
+}
+
+impl Function for CountTrigger {
+    fn get_name(&self) -> &str {
+        "CountTrigger"
+    }
+}
+
+/// A `Trigger` that continuously fires based on a given time interval of event time.
+#[derive(Debug)]
+pub struct ContinuousEventTimeTrigger {
+    interval: u64,
+    /// the next fire timestamp of each key and window
+    fire_timestamps: HashMap<(Record, WindowWrap), u64>,
+}
+
+impl ContinuousEventTimeTrigger {
+    pub fn of(interval: Duration) -> Self {
+        let interval = interval.as_millis() as u64;
+        if interval == 0 {
+            panic!("ContinuousEventTimeTrigger parameters must satisfy 0 < interval")
+        }
+
+        ContinuousEventTimeTrigger {
+            interval,
+            fire_timestamps: HashMap::new(),
+        }
+    }
+
+    fn next_fire_timestamp(&self, timestamp: u64) -> u64 {
+        timestamp - (timestamp % self.interval) + self.interval
+    }
+}
+
+impl Trigger for ContinuousEventTimeTrigger {
+    fn on_element(&mut self, key: &Record, record: &Record, window: &WindowWrap) -> TriggerResult {
+        let state_key = (key.clone(), window.clone());
+        if !self.fire_timestamps.contains_key(&state_key) {
+            let timestamp = std::cmp::max(record.timestamp, window.min_timestamp());
+            let fire_timestamp = self.next_fire_timestamp(timestamp);
+            self.fire_timestamps.insert(state_key, fire_timestamp);
+        }
+
+        TriggerResult::Continue
+    }
+
+    fn on_event_time(&mut self, time: u64, key: &Record, window: &WindowWrap) -> TriggerResult {
+        let next_fire_timestamp = self.next_fire_timestamp(time);
+        match self.fire_timestamps.get_mut(&(key.clone(), window.clone())) {
+            Some(fire_timestamp) => {
+                if *fire_timestamp <= time {
+                    *fire_timestamp = next_fire_timestamp;
+                    TriggerResult::Fire
+                } else {
+                    TriggerResult::Continue
+                }
+            }
+            None => TriggerResult::Continue,
+        }
+    }
+
+    fn on_processing_time(
+        &mut self,
+        _time: u64,
+        _key: &Record,
+        _window: &WindowWrap,
+    ) -> TriggerResult {
+        TriggerResult::Continue
+    }
+
+    fn clear(&mut self, key: &Record, window: &WindowWrap) {
+        self.fire_timestamps.remove(&(key.clone(), window.clone()));
+    }
+
+    fn snapshot_state(&self, bytes: &mut BytesMut) {
+        serialize_key_windows(&self.fire_timestamps, bytes);
+    }
+
+    fn restore_state(&mut self, bytes: &mut BytesMut) {
+        self.fire_timestamps = deserialize_key_windows(bytes);
+    }
+}
+
+impl Function for ContinuousEventTimeTrigger {
+    fn get_name(&self) -> &str {
+        "ContinuousEventTimeTrigger"
+    }
+}
+
+/// A `Trigger` that turns the `TriggerResult::Fire` of the nested trigger into
+/// `TriggerResult::FireAndPurge`, so the window is discarded after each fire.
+#[derive(Debug)]
+pub struct PurgingTrigger {
+    nested_trigger: Box<dyn Trigger>,
+}
+
+impl PurgingTrigger {
+    pub fn of<T>(nested_trigger: T) -> Self
+    where
+        T: Trigger + 'static,
+    {
+        PurgingTrigger {
+            nested_trigger: Box::new(nested_trigger),
+        }
+    }
+
+    fn purging(result: TriggerResult) -> TriggerResult {
+        match result {
+            TriggerResult::Fire => TriggerResult::FireAndPurge,
+            _ => result,
+        }
+    }
+}
+
+impl Trigger for PurgingTrigger {
+    fn on_element(&mut self, key: &Record, record: &Record, window: &WindowWrap) -> TriggerResult {
+        PurgingTrigger::purging(self.nested_trigger.on_element(key, record, window))
+    }
+
+    fn on_event_time(&mut self, time: u64, key: &Record, window: &WindowWrap) -> TriggerResult {
+        PurgingTrigger::purging(self.nested_trigger.on_event_time(time, key, window))
+    }
+
+    fn on_processing_time(
+        &mut self,
+        time: u64,
+        key: &Record,
+        window: &WindowWrap,
+    ) -> TriggerResult {
+        PurgingTrigger::purging(self.nested_trigger.on_processing_time(time, key, window))
+    }
+
+    fn clear(&mut self, key: &Record, window: &WindowWrap) {
+        self.nested_trigger.clear(key, window);
+    }
+
+    fn snapshot_state(&self, bytes: &mut BytesMut) {
+        self.nested_trigger.snapshot_state(bytes);
+    }
+
+    fn restore_state(&mut self, bytes: &mut BytesMut) {
+        self.nested_trigger.restore_state(bytes);
+    }
+}
+
+impl Function for PurgingTrigger {
+    fn get_name(&self) -> &str {
+        "PurgingTrigger"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::element::{types, Record};
+    use crate::api::trigger::{
+        ContinuousEventTimeTrigger, CountTrigger, PurgingTrigger, Trigger, TriggerResult,
+    };
+    use crate::api::window::{TimeWindow, WindowWrap};
+    use bytes::BytesMut;
+    use std::time::Duration;
+
+    fn build_key(key: i64) -> Record {
+        let mut key_record = Record::new();
+        key_record.get_writer(&[types::I64]).set_i64(key).unwrap();
+        key_record
+    }
+
+    #[test]
+    pub fn count_trigger_test() {
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 60000));
+        let record = Record::new();
+        let key1 = build_key(1);
+        let key2 = build_key(2);
+
+        let mut trigger = PurgingTrigger::of(CountTrigger::of(2));
+        assert_eq!(
+            trigger.on_element(&key1, &record, &window),
+            TriggerResult::Continue
+        );
+        // the records are counted per key
+        assert_eq!(
+            trigger.on_element(&key2, &record, &window),
+            TriggerResult::Continue
+        );
+        assert_eq!(
+            trigger.on_element(&key1, &record, &window),
+            TriggerResult::FireAndPurge
+        );
+        assert_eq!(
+            trigger.on_element(&key1, &record, &window),
+            TriggerResult::Continue
+        );
+        assert_eq!(
+            trigger.on_element(&key2, &record, &window),
+            TriggerResult::FireAndPurge
+        );
+    }
+
+    #[test]
+    pub fn continuous_event_time_trigger_test() {
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 60000));
+        let mut record = Record::new();
+        record.timestamp = 1500;
+
+        let key = build_key(1);
+
+        let mut trigger = ContinuousEventTimeTrigger::of(Duration::from_secs(10));
+        assert_eq!(
+            trigger.on_event_time(12000, &key, &window),
+            TriggerResult::Continue
+        );
+
+        trigger.on_element(&key, &record, &window);
+        assert_eq!(
+            trigger.on_event_time(9000, &key, &window),
+            TriggerResult::Continue
+        );
+        assert_eq!(
+            trigger.on_event_time(12000, &key, &window),
+            TriggerResult::Fire
+        );
+        assert_eq!(
+            trigger.on_event_time(15000, &key, &window),
+            TriggerResult::Continue
+        );
+        assert_eq!(
+            trigger.on_event_time(20000, &key, &window),
+            TriggerResult::Fire
+        );
+
+        trigger.clear(&key, &window);
+        assert_eq!(
+            trigger.on_event_time(30000, &key, &window),
+            TriggerResult::Continue
+        );
+    }
+
+    #[test]
+    pub fn trigger_snapshot_test() {
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 60000));
+        let record = Record::new();
+        let key = build_key(1);
+
+        let mut trigger = PurgingTrigger::of(CountTrigger::of(2));
+        assert_eq!(
+            trigger.on_element(&key, &record, &window),
+            TriggerResult::Continue
+        );
+
+        let mut bytes = BytesMut::new();
+        trigger.snapshot_state(&mut bytes);
+        let mut restored_trigger = PurgingTrigger::of(CountTrigger::of(2));
+        restored_trigger.restore_state(&mut bytes);
+        assert!(bytes.is_empty());
+
+        // the count before the snapshot is kept
+        assert_eq!(
+            restored_trigger.on_element(&key, &record, &window),
+            TriggerResult::FireAndPurge
+        );
+    }
+}
@@ -1,4 +1,7 @@
+use crate::api::evictor::Evictor;
 use crate::api::function::Function;
+use crate::api::output::OutputFormat;
+use crate::api::trigger::Trigger;
 use crate::utils;
 use std::cmp::{max, min};
 use std::fmt::Debug;
@@ -62,6 +65,26 @@ pub enum WindowWrap {
     TimeWindow(TimeWindow),
 }
 
+impl WindowWrap {
+    /// Returns `true` if this window intersects the given window.
+    pub fn intersects(&self, other: &WindowWrap) -> bool {
+        match (self, other) {
+            (WindowWrap::TimeWindow(window), WindowWrap::TimeWindow(other)) => {
+                window.intersects(other.clone())
+            }
+        }
+    }
+
+    /// Returns the minimal window covers both this window and the given window.
+    pub fn cover(&self, other: &WindowWrap) -> WindowWrap {
+        match (self, other) {
+            (WindowWrap::TimeWindow(window), WindowWrap::TimeWindow(other)) => {
+                WindowWrap::TimeWindow(window.cover(other.clone()))
+            }
+        }
+    }
+}
+
 impl Window for WindowWrap {
     fn max_timestamp(&self) -> u64 {
         match self {
@@ -90,6 +113,71 @@ where
     Self: Function + Debug,
 {
     fn assign_windows(&self, timestamp: u64, context: WindowAssignerContext) -> Vec<WindowWrap>;
+
+    /// Returns `true` if the assigned windows of a key can be merged with each other,
+    /// eg: the session windows
+    fn is_merging(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the windows are fired by the `Watermark`,
+    /// otherwise the windows are fired by the processing time of the worker
+    fn is_event_time(&self) -> bool {
+        true
+    }
+}
+
+/// The options of the windowed stream, they are applied by the windowed reduce operator
+pub struct WindowOptions {
+    /// the windows of a merging `WindowAssigner` is merged before fire
+    pub(crate) merging: bool,
+    /// the windows are fired by the `Watermark` or the processing time
+    pub(crate) event_time: bool,
+    /// the windows state is kept after the window fire, until the watermark
+    /// passes the end of the window plus the `allowed_lateness`
+    pub(crate) allowed_lateness: u64,
+    /// the output of the records which arrive after the window is purged
+    pub(crate) late_data_output: Option<Box<dyn OutputFormat>>,
+    /// the `Trigger` to fire or purge the windows before the end of the window
+    pub(crate) trigger: Option<Box<dyn Trigger>>,
+    /// the `Evictor`s to remove the buffered records before the `ProcessWindowFunction`
+    pub(crate) evictors: Vec<Box<dyn Evictor>>,
+}
+
+impl WindowOptions {
+    pub fn new(window_assigner: &dyn WindowAssigner) -> Self {
+        WindowOptions {
+            merging: window_assigner.is_merging(),
+            event_time: window_assigner.is_event_time(),
+            allowed_lateness: 0,
+            late_data_output: None,
+            trigger: None,
+            evictors: Vec::new(),
+        }
+    }
+}
+
+impl Debug for WindowOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WindowOptions")
+            .field("merging", &self.merging)
+            .field("event_time", &self.event_time)
+            .field("allowed_lateness", &self.allowed_lateness)
+            .field(
+                "late_data_output",
+                &self.late_data_output.as_ref().map(|x| x.get_name()),
+            )
+            .field("trigger", &self.trigger.as_ref().map(|x| x.get_name()))
+            .field(
+                "evictors",
+                &self
+                    .evictors
+                    .iter()
+                    .map(|x| x.get_name())
+                    .collect::<Vec<&str>>(),
+            )
+            .finish()
+    }
 }
 
 #[derive(Debug)]
@@ -105,7 +193,7 @@ impl SlidingEventTimeWindows {
         let slide = slide.as_millis() as u64;
         let offset = offset.map(|x| x.as_millis() as i64).unwrap_or(0);
 
-        if offset.abs() as u64 >= slide || size <= 0 {
+        if offset.abs() as u64 >= slide || size == 0 {
             panic!(
                 "SlidingEventTimeWindows parameters must satisfy offset.abs() < slide and size > 0"
             )
@@ -146,3 +234,196 @@ impl Function for SlidingEventTimeWindows {
         "SlidingEventTimeWindows"
     }
 }
+
+#[derive(Debug)]
+pub struct TumblingEventTimeWindows {
+    size: u64,
+    offset: i64,
+}
+
+impl TumblingEventTimeWindows {
+    pub fn new(size: Duration, offset: Option<Duration>) -> Self {
+        let size = size.as_millis() as u64;
+        let offset = offset.map(|x| x.as_millis() as i64).unwrap_or(0);
+
+        if offset.abs() as u64 >= size || size == 0 {
+            panic!(
+                "TumblingEventTimeWindows parameters must satisfy offset.abs() < size and size > 0"
+            )
+        }
+        TumblingEventTimeWindows { size, offset }
+    }
+}
+
+impl WindowAssigner for TumblingEventTimeWindows {
+    fn assign_windows(&self, timestamp: u64, _context: WindowAssignerContext) -> Vec<WindowWrap> {
+        let start = TimeWindow::get_window_start_with_offset(timestamp, self.offset, self.size);
+        vec![WindowWrap::TimeWindow(TimeWindow::new(
+            start,
+            start + self.size,
+        ))]
+    }
+}
+
+impl Function for TumblingEventTimeWindows {
+    fn get_name(&self) -> &str {
+        "TumblingEventTimeWindows"
+    }
+}
+
+/// Assign windows by the current processing time of the operator,
+/// the timestamp carried by the element is ignored.
+#[derive(Debug)]
+pub struct TumblingProcessingTimeWindows {
+    size: u64,
+    offset: i64,
+}
+
+impl TumblingProcessingTimeWindows {
+    pub fn new(size: Duration, offset: Option<Duration>) -> Self {
+        let size = size.as_millis() as u64;
+        let offset = offset.map(|x| x.as_millis() as i64).unwrap_or(0);
+
+        if offset.abs() as u64 >= size || size == 0 {
+            panic!("TumblingProcessingTimeWindows parameters must satisfy offset.abs() < size and size > 0")
+        }
+        TumblingProcessingTimeWindows { size, offset }
+    }
+}
+
+impl WindowAssigner for TumblingProcessingTimeWindows {
+    fn assign_windows(&self, _timestamp: u64, context: WindowAssignerContext) -> Vec<WindowWrap> {
+        let timestamp = context.get_current_processing_time();
+        let start = TimeWindow::get_window_start_with_offset(timestamp, self.offset, self.size);
+        vec![WindowWrap::TimeWindow(TimeWindow::new(
+            start,
+            start + self.size,
+        ))]
+    }
+
+    fn is_event_time(&self) -> bool {
+        false
+    }
+}
+
+impl Function for TumblingProcessingTimeWindows {
+    fn get_name(&self) -> &str {
+        "TumblingProcessingTimeWindows"
+    }
+}
+
+/// Assign the element to a session window `[timestamp, timestamp + gap)`,
+/// the overlapping sessions of the same key are merged by the `WindowState`.
+#[derive(Debug)]
+pub struct EventTimeSessionWindows {
+    session_timeout: u64,
+}
+
+impl EventTimeSessionWindows {
+    pub fn with_gap(size: Duration) -> Self {
+        let session_timeout = size.as_millis() as u64;
+        if session_timeout == 0 {
+            panic!("EventTimeSessionWindows parameters must satisfy 0 < size")
+        }
+
+        EventTimeSessionWindows { session_timeout }
+    }
+}
+
+impl WindowAssigner for EventTimeSessionWindows {
+    fn assign_windows(&self, timestamp: u64, _context: WindowAssignerContext) -> Vec<WindowWrap> {
+        vec![WindowWrap::TimeWindow(TimeWindow::new(
+            timestamp,
+            timestamp + self.session_timeout,
+        ))]
+    }
+
+    fn is_merging(&self) -> bool {
+        true
+    }
+}
+
+impl Function for EventTimeSessionWindows {
+    fn get_name(&self) -> &str {
+        "EventTimeSessionWindows"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::window::{
+        EventTimeSessionWindows, SlidingEventTimeWindows, TimeWindow, TumblingEventTimeWindows,
+        TumblingProcessingTimeWindows, Window, WindowAssigner, WindowAssignerContext, WindowWrap,
+    };
+    use std::time::Duration;
+
+    #[test]
+    pub fn tumbling_event_time_windows_test() {
+        let assigner = TumblingEventTimeWindows::new(Duration::from_secs(60), None);
+
+        // 2020-07-01 15:00:30:000
+        let timestamp = 1593586830000u64;
+        let windows = assigner.assign_windows(timestamp, WindowAssignerContext {});
+        assert_eq!(windows.len(), 1);
+        assert_eq!(
+            windows[0],
+            WindowWrap::TimeWindow(TimeWindow::new(1593586800000, 1593586860000))
+        );
+
+        let assigner =
+            TumblingEventTimeWindows::new(Duration::from_secs(60), Some(Duration::from_secs(10)));
+        let windows = assigner.assign_windows(timestamp, WindowAssignerContext {});
+        assert_eq!(
+            windows[0],
+            WindowWrap::TimeWindow(TimeWindow::new(1593586810000, 1593586870000))
+        );
+
+        // same as a sliding window with size == slide
+        let sliding = SlidingEventTimeWindows::new(
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            Some(Duration::from_secs(10)),
+        );
+        assert_eq!(
+            sliding.assign_windows(timestamp, WindowAssignerContext {}),
+            windows
+        );
+    }
+
+    #[test]
+    pub fn tumbling_processing_time_windows_test() {
+        let assigner = TumblingProcessingTimeWindows::new(Duration::from_secs(60), None);
+
+        let context = WindowAssignerContext {};
+        let now = context.get_current_processing_time();
+        let windows = assigner.assign_windows(0, context);
+        assert!(!assigner.is_event_time());
+        assert_eq!(windows.len(), 1);
+        assert!(windows[0].min_timestamp() <= now);
+        assert!(windows[0].max_timestamp() > now);
+        assert_eq!(
+            windows[0].max_timestamp() - windows[0].min_timestamp(),
+            60000
+        );
+    }
+
+    #[test]
+    pub fn event_time_session_windows_test() {
+        let assigner = EventTimeSessionWindows::with_gap(Duration::from_secs(30));
+        assert!(assigner.is_merging());
+
+        let timestamp = 1593586830000u64;
+        let window1 = assigner.assign_windows(timestamp, WindowAssignerContext {})[0].clone();
+        let window2 =
+            assigner.assign_windows(timestamp + 20000, WindowAssignerContext {})[0].clone();
+        let window3 =
+            assigner.assign_windows(timestamp + 60000, WindowAssignerContext {})[0].clone();
+
+        assert!(window1.intersects(&window2));
+        assert!(!window1.intersects(&window3));
+        assert_eq!(
+            window1.cover(&window2),
+            WindowWrap::TimeWindow(TimeWindow::new(timestamp, timestamp + 50000))
+        );
+    }
+}
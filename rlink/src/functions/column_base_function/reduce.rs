@@ -79,6 +79,13 @@ pub trait Aggregation: Debug {
         value_index: usize,
         record_reader: &mut BufferReader,
     );
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    );
 }
 
 #[derive(Debug)]
@@ -118,6 +125,25 @@ impl Aggregation for SumI64 {
             }
         }
     }
+
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    ) {
+        let other_value = other_reader.get_i64(value_index).unwrap();
+        match value_reader {
+            Some(value_reader) => {
+                let stat_value = value_reader.get_i64(value_index).unwrap();
+                writer.set_i64(stat_value + other_value).unwrap();
+            }
+            None => {
+                writer.set_i64(other_value).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -156,6 +182,25 @@ impl Aggregation for SumF64 {
             }
         }
     }
+
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    ) {
+        let other_value = other_reader.get_f64(value_index).unwrap();
+        match value_reader {
+            Some(value_reader) => {
+                let stat_value = value_reader.get_f64(value_index).unwrap();
+                writer.set_f64(stat_value + other_value).unwrap();
+            }
+            None => {
+                writer.set_f64(other_value).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -196,6 +241,27 @@ impl Aggregation for MaxI64 {
             }
         }
     }
+
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    ) {
+        let other_value = other_reader.get_i64(value_index).unwrap();
+        match value_reader {
+            Some(value_reader) => {
+                let stat_value = value_reader.get_i64(value_index).unwrap();
+                writer
+                    .set_i64(std::cmp::max(stat_value, other_value))
+                    .unwrap();
+            }
+            None => {
+                writer.set_i64(other_value).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -240,6 +306,25 @@ impl Aggregation for MaxF64 {
             }
         }
     }
+
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    ) {
+        let other_value = other_reader.get_f64(value_index).unwrap();
+        match value_reader {
+            Some(value_reader) => {
+                let stat_value = value_reader.get_f64(value_index).unwrap();
+                writer.set_f64(stat_value.max(other_value)).unwrap();
+            }
+            None => {
+                writer.set_f64(other_value).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -280,6 +365,27 @@ impl Aggregation for MinI64 {
             }
         }
     }
+
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    ) {
+        let other_value = other_reader.get_i64(value_index).unwrap();
+        match value_reader {
+            Some(value_reader) => {
+                let stat_value = value_reader.get_i64(value_index).unwrap();
+                writer
+                    .set_i64(std::cmp::min(stat_value, other_value))
+                    .unwrap();
+            }
+            None => {
+                writer.set_i64(other_value).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -324,6 +430,25 @@ impl Aggregation for MinF64 {
             }
         }
     }
+
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    ) {
+        let other_value = other_reader.get_f64(value_index).unwrap();
+        match value_reader {
+            Some(value_reader) => {
+                let stat_value = value_reader.get_f64(value_index).unwrap();
+                writer.set_f64(stat_value.min(other_value)).unwrap();
+            }
+            None => {
+                writer.set_f64(other_value).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -373,6 +498,29 @@ impl Aggregation for PctU64 {
             }
         }
     }
+
+    fn merge(
+        &self,
+        writer: &mut BufferWriter,
+        value_reader: Option<&mut BufferReader>,
+        value_index: usize,
+        other_reader: &mut BufferReader,
+    ) {
+        let other_value = other_reader.get_bytes_mut(value_index).unwrap();
+        match value_reader {
+            Some(value_reader) => {
+                let stat_value = value_reader.get_bytes_mut(value_index).unwrap();
+
+                let mut percentile = Percentile::new(self.scale, stat_value);
+                percentile.merge(&Percentile::new(self.scale, other_value));
+
+                writer.set_bytes(stat_value).unwrap();
+            }
+            None => {
+                writer.set_bytes(other_value).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -452,6 +600,39 @@ impl ReduceFunction for ColumnBaseReduceFunction {
         record_rt
     }
 
+    fn merge(&self, value: Option<&mut Record>, other: &mut Record) -> Record {
+        let mut record_rt = Record::with_capacity(self.val_len);
+        let mut writer = record_rt.get_writer(self.val_data_types.as_slice());
+
+        let mut other_reader = other.get_reader(self.val_data_types.as_slice());
+
+        match value {
+            Some(state_value) => {
+                let mut stat_reader = state_value.get_reader(self.val_data_types.as_slice());
+
+                for index in 0..self.agg_operators.len() {
+                    self.agg_operators[index].merge(
+                        writer.borrow_mut(),
+                        Some(stat_reader.borrow_mut()),
+                        index,
+                        other_reader.borrow_mut(),
+                    )
+                }
+            }
+            None => {
+                for index in 0..self.agg_operators.len() {
+                    self.agg_operators[index].merge(
+                        writer.borrow_mut(),
+                        None,
+                        index,
+                        other_reader.borrow_mut(),
+                    )
+                }
+            }
+        }
+        record_rt
+    }
+
     fn close(&mut self) {}
 }
 
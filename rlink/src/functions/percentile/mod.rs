@@ -181,8 +181,14 @@ impl<'a> Percentile<'a> {
     }
 
     pub fn merge(&mut self, percentile: &Percentile) {
-        for i in 0..self.count_container.len() {
-            self.count_container[i] += percentile.count_container[i];
+        // the counters are u64 in the container, adding them byte by byte loses the carry
+        // (and overflows in debug builds). it's used to merge the states of the session windows
+        let mut index = 0;
+        while index < self.count_container.len() {
+            let n = self.read(index) + percentile.read(index);
+            self.write(index, n);
+
+            index += 8;
         }
     }
 }
@@ -274,4 +280,28 @@ mod tests {
         let pos = percentile.get_result(95);
         println!("percentile value: {}", pos);
     }
+
+    #[test]
+    pub fn percentile_merge_test() {
+        let scale: &'static [f64] = &[1f64, 2f64, 3f64, 4f64];
+
+        let mut container1 = vec![0u8; get_percentile_capacity(scale)];
+        let mut container2 = vec![0u8; get_percentile_capacity(scale)];
+
+        let mut percentile1 = Percentile::new(scale, container1.as_mut_slice());
+        for i in 0..300 {
+            percentile1.accumulate((i % 2 + 1) as f64);
+        }
+
+        let mut percentile2 = Percentile::new(scale, container2.as_mut_slice());
+        for _ in 0..300 {
+            percentile2.accumulate(4f64);
+        }
+
+        percentile1.merge(&percentile2);
+
+        assert_eq!(percentile1.get_counter(), 600);
+        assert_eq!(percentile1.get_result(50), 4f64);
+        assert_eq!(percentile1.get_result(0), 1f64);
+    }
 }
@@ -52,8 +52,10 @@ fn build_source_plan(operators: &Vec<StreamOperatorWrap>) -> Vec<OperatorChain>
     let next_operator = next_operator.unwrap();
 
     let dependency_chain_id = source_operator_chain.chain_id;
-    let next_operator_chains = if next_operator.is_window() || next_operator.is_reduce() {
+    let next_operator_chains = if next_operator.is_window() || next_operator.is_window_function() {
         build_reduce_plan(&operators, step_index, dependency_chain_id)
+    } else if next_operator.is_keyed_process() {
+        build_keyed_process_plan(operators, step_index, dependency_chain_id)
     } else if next_operator.is_map() || next_operator.is_filter() {
         build_map_filter_plan(operators, step_index, dependency_chain_id, false)
     } else if next_operator.is_sink() {
@@ -88,7 +90,7 @@ fn build_source_plan0(operators: &Vec<StreamOperatorWrap>) -> OperatorChain {
             break;
         }
 
-        if operator.is_window() || operator.is_reduce() {
+        if operator.is_window() || operator.is_window_function() || operator.is_keyed_process() {
             break;
         }
 
@@ -126,7 +128,7 @@ fn build_reduce_plan(
     let first_operator = operators.get(start_index).unwrap();
     if first_operator.is_window() {
         build_window_reduce_plan(operators, start_index, dependency_chain_id)
-    } else if first_operator.is_reduce() {
+    } else if first_operator.is_window_function() {
         panic!("the Operator `Reduce` without `Window` are not supported");
     } else {
         panic!("the Operator must start with `Window` or `Reduce` operator");
@@ -170,7 +172,7 @@ fn build_window_reduce_plan0(
     let reduce_operator = operators
         .iter()
         .skip(start_index - 1)
-        .find(|op| op.is_reduce())
+        .find(|op| op.is_window_function())
         .expect("No `Reduce` Operator under `Window` mode");
 
     let parallelism = reduce_operator.get_parallelism();
@@ -207,7 +209,7 @@ fn build_window_reduce_plan0(
         nodes.push(graph_node);
 
         // chain can only contain Operators between `Window` and `Reduce` in `Window` mode
-        if operator.is_reduce() {
+        if operator.is_window_function() {
             break;
         }
     }
@@ -225,6 +227,100 @@ fn build_window_reduce_plan0(
     }
 }
 
+fn build_keyed_process_plan(
+    operators: &Vec<StreamOperatorWrap>,
+    start_index: usize,
+    dependency_chain_id: u32,
+) -> Vec<OperatorChain> {
+    let keyed_process_operator_chain =
+        build_keyed_process_plan0(operators, start_index, dependency_chain_id);
+
+    if check_is_end_chain(&keyed_process_operator_chain, operators) {
+        return vec![keyed_process_operator_chain];
+    }
+
+    let step_index = get_step_index(&keyed_process_operator_chain);
+
+    let next_operator = operators.get(step_index).unwrap();
+    let dependency_chain_id = keyed_process_operator_chain.chain_id;
+    let next_operator_chains = if next_operator.is_window() || next_operator.is_window_function() {
+        build_reduce_plan(&operators, step_index, dependency_chain_id)
+    } else if next_operator.is_keyed_process() {
+        build_keyed_process_plan(operators, step_index, dependency_chain_id)
+    } else if next_operator.is_map() || next_operator.is_filter() {
+        build_map_filter_plan(operators, step_index, dependency_chain_id, false)
+    } else if next_operator.is_sink() {
+        build_sink_plan(operators, step_index, dependency_chain_id, false)
+    } else {
+        panic!("Not Supported Operator")
+    };
+
+    let mut operator_chains = vec![keyed_process_operator_chain];
+    operator_chains.extend(next_operator_chains);
+    operator_chains
+}
+
+fn build_keyed_process_plan0(
+    operators: &Vec<StreamOperatorWrap>,
+    start_index: usize,
+    dependency_chain_id: u32,
+) -> OperatorChain {
+    let first_operator = operators.get(start_index).unwrap();
+    if !first_operator.is_keyed_process() {
+        panic!("the Operator must start with `KeyedProcess` operator");
+    }
+
+    // the records are partitioned by the `KeyBy` of the dependency chain
+    if !operators.get(start_index - 1).unwrap().is_key_by() {
+        panic!("the Operator `KeyedProcess` must follow the `KeyBy` operator");
+    }
+
+    let parallelism = first_operator.get_parallelism();
+    if parallelism == DEFAULT_PARALLELISM {
+        panic!("Operator `KeyedProcess` must be set the `parallelism`");
+    }
+
+    let mut nodes = Vec::new();
+    for index in start_index..operators.len() {
+        let operator = operators.get(index).unwrap();
+
+        if index > start_index {
+            if operator.get_parallelism() != parallelism
+                && operator.get_parallelism() != DEFAULT_PARALLELISM
+            {
+                break;
+            }
+
+            if operator.is_window() || operator.is_window_function() || operator.is_keyed_process()
+            {
+                break;
+            }
+        }
+
+        let graph_node = GraphNode {
+            name: operator.get_operator_name().to_string(),
+            node_id: operator.get_operator_id(),
+            parent_node_id: operator.get_parent_operator_id(),
+            parallelism: operator.get_parallelism(),
+            operator_index: index as u32,
+        };
+
+        nodes.push(graph_node);
+    }
+
+    OperatorChain {
+        chain_id: dependency_chain_id + 1,
+        dependency_chain_id,
+        follower_chain_id: 0,
+        dependency_edge: ChainEdge::CrossTask,
+        follower_edge: ChainEdge::InSameTask,
+        parallelism,
+        dependency_parallelism: 0,
+        follower_parallelism: 0,
+        nodes,
+    }
+}
+
 fn build_map_filter_plan(
     operators: &Vec<StreamOperatorWrap>,
     start_index: usize,
@@ -246,8 +342,10 @@ fn build_map_filter_plan(
 
     let next_operator = operators.get(step_index).unwrap();
     let dependency_chain_id = map_filter_operator_chain.chain_id;
-    let next_operator_chains = if next_operator.is_window() || next_operator.is_reduce() {
+    let next_operator_chains = if next_operator.is_window() || next_operator.is_window_function() {
         build_reduce_plan(&operators, step_index, dependency_chain_id)
+    } else if next_operator.is_keyed_process() {
+        build_keyed_process_plan(operators, step_index, dependency_chain_id)
     } else if next_operator.is_sink() {
         build_sink_plan(
             operators,
@@ -293,7 +391,7 @@ fn build_map_filter_plan0(
             break;
         }
 
-        if operator.is_window() || operator.is_reduce() {
+        if operator.is_window() || operator.is_window_function() || operator.is_keyed_process() {
             break;
         }
 
@@ -2,6 +2,7 @@ use crate::api::input::InputFormat;
 use crate::api::operator::{FunctionCreator, StreamOperatorWrap, TStreamOperator};
 use crate::api::output::OutputFormat;
 use crate::graph::{ChainEdge, GraphNode, JobGraph, OperatorChain};
+use crate::runtime::worker::checkpoint::TaskCheckpoint;
 use crate::runtime::worker::io::mem_channel_input::MemChannelInputFormat;
 use crate::runtime::worker::io::mem_channel_output::MemChannelOutputFormat;
 use crate::runtime::worker::io::net_channel_input::NetChannelInputFormat;
@@ -9,7 +10,11 @@ use crate::runtime::worker::io::net_channel_output::NetChannelOutputFormat;
 use crate::storage::metadata::MetadataLoader;
 use std::collections::HashMap;
 
-pub fn revise_logic_graph(mut job_graph: JobGraph, metadata_loader: MetadataLoader) -> JobGraph {
+pub fn revise_logic_graph(
+    mut job_graph: JobGraph,
+    metadata_loader: MetadataLoader,
+    task_checkpoint: TaskCheckpoint,
+) -> JobGraph {
     let job_chain = job_graph.chain_map.clone();
     let mut revise_job_chain = job_chain.clone();
 
@@ -32,6 +37,7 @@ pub fn revise_logic_graph(mut job_graph: JobGraph, metadata_loader: MetadataLoad
                 chain_id,
                 chain.dependency_chain_id,
                 metadata_loader.clone(),
+                task_checkpoint.clone(),
             );
             let name = stream_source.get_operator_name().to_string();
             job_graph.operators.push(stream_source);
@@ -104,6 +110,7 @@ fn create_source(
     chain_id: u32,
     dependency_chain_id: u32,
     metadata_loader: MetadataLoader,
+    task_checkpoint: TaskCheckpoint,
 ) -> StreamOperatorWrap {
     match dependency_edge {
         ChainEdge::InSameTask => create_mem_source(
@@ -120,6 +127,7 @@ fn create_source(
             chain_id,
             dependency_chain_id,
             metadata_loader,
+            task_checkpoint,
         ),
     }
 }
@@ -131,8 +139,14 @@ fn create_net_source(
     chain_id: u32,
     dependency_chain_id: u32,
     metadata_loader: MetadataLoader,
+    task_checkpoint: TaskCheckpoint,
 ) -> StreamOperatorWrap {
-    let input_func = NetChannelInputFormat::new(chain_id, dependency_chain_id, metadata_loader);
+    let input_func = NetChannelInputFormat::new(
+        chain_id,
+        dependency_chain_id,
+        metadata_loader,
+        task_checkpoint,
+    );
     let source_func: Box<dyn InputFormat> = Box::new(input_func);
 
     let stream_source = StreamOperatorWrap::new_source(
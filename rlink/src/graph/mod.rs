@@ -4,6 +4,7 @@ use crate::api::split::InputSplit;
 use crate::graph::execution_graph::build_logic_plan_group;
 use crate::graph::job_graph::build_job_graph;
 use crate::graph::job_graph_revise::revise_logic_graph;
+use crate::runtime::worker::checkpoint::TaskCheckpoint;
 use crate::storage::metadata::MetadataLoader;
 use std::collections::HashMap;
 
@@ -97,11 +98,12 @@ pub struct PhysicGraph {
 pub(crate) fn build_logic_plan<T: StreamGraph>(
     data_stream: T,
     metadata_loader: MetadataLoader,
+    task_checkpoint: TaskCheckpoint,
 ) -> JobGraph {
     let operators = data_stream.into_operators();
 
     let logic_plan = build_job_graph(operators);
-    let revise_plan = revise_logic_graph(logic_plan, metadata_loader);
+    let revise_plan = revise_logic_graph(logic_plan, metadata_loader, task_checkpoint);
     build_logic_plan_group(revise_plan)
 }
 
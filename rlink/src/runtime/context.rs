@@ -15,6 +15,7 @@ use crate::utils;
 ///     `task_manager_id`: ignore
 ///     `num_task_managers`: ignore task manager size
 ///     `cluster_config`: ignore
+///     `savepoint`: optional, resume the job from the named savepoint
 /// `Local` and `Worker` process args:
 ///     `bind_ip`: ignore, default with "0.0.0.0"
 ///     `task_manager_id`: task manager process id, generated by `Coordinator`
@@ -31,6 +32,7 @@ use crate::utils;
 ///         `job_id`: job id, generated by `JobManager`
 ///         `task_manager_id`: ignore
 ///         `cluster_config`: cluster config path, generated by `TaskManager`
+///         `savepoint`: optional, resume the job from the named savepoint
 ///     `Worker` process args:
 ///         `cluster_mode`: must be `Standalone`
 ///         `manager_type`: must be `Worker`
@@ -56,6 +58,9 @@ pub(crate) struct Context {
     pub metric_addr: String,
     /// effective only in `Worker` mode
     pub coordinator_address: String,
+    /// effective only in `Coordinator` mode, resume the job from the named savepoint
+    /// rather than the latest checkpoint
+    pub savepoint: Option<String>,
 
     /// on yarn arg
     pub worker_process_path: String,
@@ -75,6 +80,7 @@ impl Context {
         cluster_config: ClusterConfig,
         metric_addr: String,
         coordinator_address: String,
+        savepoint: Option<String>,
         worker_process_path: String,
         memory_mb: usize,
         v_cores: usize,
@@ -90,6 +96,7 @@ impl Context {
             cluster_config,
             metric_addr,
             coordinator_address,
+            savepoint,
             worker_process_path,
             memory_mb,
             v_cores,
@@ -191,6 +198,11 @@ impl Context {
                 .expect("`coordinator_address` argument is not found"),
         };
 
+        let savepoint = match manager_type {
+            ManagerType::Coordinator => utils::parse_arg("savepoint"),
+            _ => None,
+        };
+
         Context::new(
             job_name.to_string(),
             job_id,
@@ -202,6 +214,7 @@ impl Context {
             cluster_config,
             metric_addr,
             coordinator_address,
+            savepoint,
             worker_process_path,
             memory_mb,
             v_cores,
@@ -1,13 +1,25 @@
-use crate::api::checkpoint::Checkpoint;
+use crate::api::checkpoint::{is_savepoint, new_savepoint_id, Checkpoint, SAVEPOINT_FLAG};
+use crate::api::operator::{FunctionCreator, TStreamOperator};
 use crate::api::properties::SystemProperties;
-use crate::graph::JobGraph;
+use crate::graph::{ChainEdge, JobGraph, OperatorChain};
 use crate::runtime::context::Context;
 use crate::runtime::{ChainId, JobDescriptor};
 use crate::storage::checkpoint::{CheckpointStorage, CheckpointStorageWrap};
+use crate::utils::date_time::current_timestamp_millis;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
+/// the savepoint is taken to the workers by the heartbeat in the timeout after triggered
+const SAVEPOINT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// a savepoint triggered by the user, the `checkpoint_id` is reserved by `new_savepoint_id`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Savepoint {
+    pub name: String,
+    pub checkpoint_id: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct ChainCheckpoint {
     job_name: String,
@@ -23,6 +35,13 @@ pub(crate) struct ChainCheckpoint {
     /// Map<task_num, Checkpoint>
     current_cks: HashMap<u16, Checkpoint>,
     latest_finish_cks: Vec<Checkpoint>,
+    /// the latest checkpoint acknowledged by all tasks and saved to the storage
+    completed_ck_id: u64,
+
+    /// the savepoint in progress
+    savepoint: Option<Savepoint>,
+    /// Map<task_num, Checkpoint> of the savepoint in progress
+    savepoint_cks: HashMap<u16, Checkpoint>,
 }
 
 impl ChainCheckpoint {
@@ -42,10 +61,29 @@ impl ChainCheckpoint {
             current_ck_id: 0,
             current_cks: HashMap::with_capacity(parallelism as usize),
             latest_finish_cks: Vec::with_capacity(parallelism as usize),
+            completed_ck_id: 0,
+            savepoint: None,
+            savepoint_cks: HashMap::new(),
         }
     }
 
+    pub fn begin_savepoint(&mut self, savepoint: Savepoint) {
+        if let Some(previous) = self.savepoint.as_ref() {
+            warn!(
+                "chain_id={} savepoint {} is not completed, replaced by {}",
+                self.chain_id, previous.name, savepoint.name
+            );
+        }
+
+        self.savepoint = Some(savepoint);
+        self.savepoint_cks.clear();
+    }
+
     pub fn add(&mut self, ck: Checkpoint) -> anyhow::Result<()> {
+        if is_savepoint(ck.checkpoint_id) {
+            return self.add_savepoint(ck);
+        }
+
         if ck.checkpoint_id == self.current_ck_id {
             if self.is_align() {
                 Err(anyhow::Error::msg(format!(
@@ -86,6 +124,41 @@ impl ChainCheckpoint {
         }
     }
 
+    fn add_savepoint(&mut self, ck: Checkpoint) -> anyhow::Result<()> {
+        let savepoint = match self.savepoint.as_ref() {
+            Some(savepoint) if savepoint.checkpoint_id == ck.checkpoint_id => savepoint.clone(),
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "the savepoint is not in progress. {:?}",
+                    &ck
+                )));
+            }
+        };
+
+        self.savepoint_cks.insert(ck.task_num, ck);
+        if self.savepoint_cks.len() < self.parallelism as usize {
+            return Ok(());
+        }
+
+        let finish_cks: Vec<Checkpoint> = self.savepoint_cks.drain().map(|x| x.1).collect();
+        self.savepoint = None;
+
+        match self.storage.as_mut() {
+            Some(storage) => storage.save_savepoint(
+                self.job_name.as_str(),
+                self.job_id.as_str(),
+                savepoint.name.as_str(),
+                self.chain_id,
+                savepoint.checkpoint_id,
+                finish_cks,
+            ),
+            None => Err(anyhow::Error::msg(format!(
+                "the checkpoint storage is not found, savepoint {} is dropped",
+                savepoint.name
+            ))),
+        }
+    }
+
     fn is_align(&self) -> bool {
         self.current_cks.len() == self.parallelism as usize
     }
@@ -98,12 +171,14 @@ impl ChainCheckpoint {
             self.latest_finish_cks
                 .extend_from_slice(finish_cks.as_slice());
 
-            // todo save checkpoint
-            self.storage_checkpoint(finish_cks);
+            if self.storage_checkpoint(finish_cks) {
+                self.completed_ck_id = self.current_ck_id;
+            }
         }
     }
 
-    fn storage_checkpoint(&mut self, cks: Vec<Checkpoint>) {
+    /// return `false` if the checkpoint is failed to save
+    fn storage_checkpoint(&mut self, cks: Vec<Checkpoint>) -> bool {
         match self.storage.as_mut() {
             Some(storage) => {
                 let rt = storage.save(
@@ -115,11 +190,14 @@ impl ChainCheckpoint {
                     Duration::from_secs(60 * 30).as_millis() as u64,
                 );
                 match rt {
-                    Ok(_) => {}
-                    Err(e) => error!("checkpoint storage error. {}", e),
+                    Ok(_) => true,
+                    Err(e) => {
+                        error!("checkpoint storage error. {}", e);
+                        false
+                    }
                 }
             }
-            None => {}
+            None => true,
         }
     }
 
@@ -129,6 +207,15 @@ impl ChainCheckpoint {
             None => Ok(vec![]),
         }
     }
+
+    pub fn load_savepoint(&mut self, savepoint_name: &str) -> anyhow::Result<Vec<Checkpoint>> {
+        match self.storage.as_mut() {
+            Some(storage) => {
+                storage.load_savepoint(self.job_name.as_str(), savepoint_name, self.chain_id)
+            }
+            None => Ok(vec![]),
+        }
+    }
 }
 
 impl Clone for ChainCheckpoint {
@@ -142,6 +229,9 @@ impl Clone for ChainCheckpoint {
             current_ck_id: self.current_ck_id,
             current_cks: self.current_cks.clone(),
             latest_finish_cks: self.latest_finish_cks.clone(),
+            completed_ck_id: self.completed_ck_id,
+            savepoint: self.savepoint.clone(),
+            savepoint_cks: self.savepoint_cks.clone(),
         }
     }
 }
@@ -152,6 +242,11 @@ pub(crate) type ChainCheckpointSafe = Arc<RwLock<ChainCheckpoint>>;
 pub(crate) struct CheckpointManager {
     job_name: String,
     chain_cks: dashmap::DashMap<ChainId, ChainCheckpointSafe>,
+    /// the chains with the stateful operators, each task of them reports the checkpoints.
+    /// the other chains are known to be stateless, they never report
+    stateful_chain_ids: Vec<ChainId>,
+    /// the latest triggered savepoint, it is taken to the workers by the heartbeat
+    latest_savepoint: Arc<RwLock<Option<Savepoint>>>,
 }
 
 impl CheckpointManager {
@@ -164,7 +259,12 @@ impl CheckpointManager {
             .unwrap_or(None);
 
         let chain_cks = dashmap::DashMap::new();
+        let mut stateful_chain_ids = Vec::new();
         for (chain_id, operator_chain) in &job_graph.chain_map {
+            if CheckpointManager::is_stateful_chain(job_graph, operator_chain) {
+                stateful_chain_ids.push(*chain_id);
+            }
+
             let job_name = context.job_name.clone();
             let job_id = context.job_id.clone();
             let chain_id = *chain_id;
@@ -180,7 +280,35 @@ impl CheckpointManager {
         CheckpointManager {
             job_name: context.job_name.clone(),
             chain_cks,
+            stateful_chain_ids,
+            latest_savepoint: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// the chain reports the checkpoints if there is any operator of the user source,
+    /// the window function, the keyed process or the user sink in it. the chain in the same
+    /// task of the window chain reports the finished windows by the source of it
+    fn is_stateful_chain(job_graph: &JobGraph, operator_chain: &OperatorChain) -> bool {
+        if let ChainEdge::InSameTask = operator_chain.dependency_edge {
+            return true;
         }
+
+        operator_chain.nodes.iter().any(|node| {
+            job_graph
+                .operators
+                .iter()
+                .find(|operator| operator.get_operator_id() == node.node_id)
+                .map(|operator| match operator.get_fn_creator() {
+                    FunctionCreator::User => {
+                        operator.is_source()
+                            || operator.is_window_function()
+                            || operator.is_keyed_process()
+                            || operator.is_sink()
+                    }
+                    FunctionCreator::System => false,
+                })
+                .unwrap_or(false)
+        })
     }
 
     pub fn add(&self, ck: Checkpoint) -> anyhow::Result<()> {
@@ -207,6 +335,82 @@ impl CheckpointManager {
         Ok(chain_checkpoints)
     }
 
+    pub fn load_savepoint(
+        &mut self,
+        savepoint_name: &str,
+    ) -> anyhow::Result<HashMap<ChainId, Vec<Checkpoint>>> {
+        let mut chain_checkpoints = HashMap::new();
+        for entry in &self.chain_cks {
+            let mut chain_ck = entry.value().write().unwrap();
+            let checkpoints = chain_ck.load_savepoint(savepoint_name)?;
+            chain_checkpoints.insert(*entry.key(), checkpoints);
+        }
+
+        Ok(chain_checkpoints)
+    }
+
+    /// reserve a `checkpoint_id` for the savepoint, the barrier of it is injected by the
+    /// sources after the workers receive it from the heartbeat
+    pub fn trigger_savepoint(&self, savepoint_name: &str) -> anyhow::Result<Savepoint> {
+        let valid_name = !savepoint_name.is_empty()
+            && savepoint_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return Err(anyhow::Error::msg(format!(
+                "invalid savepoint name `{}`, only [a-zA-Z0-9_-] is allowed",
+                savepoint_name
+            )));
+        }
+
+        let savepoint = Savepoint {
+            name: savepoint_name.to_string(),
+            checkpoint_id: new_savepoint_id(),
+        };
+        for entry in &self.chain_cks {
+            let mut chain_ck = entry.value().write().unwrap();
+            chain_ck.begin_savepoint(savepoint.clone());
+        }
+
+        let mut latest_savepoint = self.latest_savepoint.write().unwrap();
+        *latest_savepoint = Some(savepoint.clone());
+
+        info!("trigger savepoint {:?}", savepoint);
+        Ok(savepoint)
+    }
+
+    /// the latest savepoint triggered in the `SAVEPOINT_TIMEOUT`
+    pub fn get_pending_savepoint(&self) -> Option<Savepoint> {
+        let latest_savepoint = self.latest_savepoint.read().unwrap();
+        latest_savepoint
+            .as_ref()
+            .filter(|savepoint| {
+                let trigger_timestamp = savepoint.checkpoint_id & !SAVEPOINT_FLAG;
+                current_timestamp_millis()
+                    < trigger_timestamp + SAVEPOINT_TIMEOUT.as_millis() as u64
+            })
+            .map(|savepoint| savepoint.clone())
+    }
+
+    /// the latest checkpoint completed by all stateful chains, it is taken to the workers by
+    /// the heartbeat. no checkpoint is completed until each stateful chain has completed one
+    pub fn get_completed_checkpoint_id(&self) -> Option<u64> {
+        let mut completed_ck_id: Option<u64> = None;
+        for chain_id in &self.stateful_chain_ids {
+            let ck_id = match self.chain_cks.get(chain_id) {
+                Some(entry) => entry.value().read().unwrap().completed_ck_id,
+                None => 0,
+            };
+            if ck_id == 0 {
+                return None;
+            }
+
+            completed_ck_id = Some(completed_ck_id.map_or(ck_id, |x| x.min(ck_id)));
+        }
+
+        completed_ck_id
+    }
+
     pub fn get(&self) -> HashMap<ChainId, ChainCheckpoint> {
         let mut map = HashMap::new();
         for entry in &self.chain_cks {
@@ -228,6 +432,112 @@ impl Clone for CheckpointManager {
         CheckpointManager {
             job_name: self.job_name.clone(),
             chain_cks,
+            stateful_chain_ids: self.stateful_chain_ids.clone(),
+            latest_savepoint: self.latest_savepoint.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::api::checkpoint::{
+        is_savepoint, new_savepoint_id, Checkpoint, CheckpointHandle, SAVEPOINT_FLAG,
+    };
+    use crate::runtime::coordinator::checkpoint_manager::{
+        ChainCheckpoint, CheckpointManager, Savepoint,
+    };
+    use crate::storage::checkpoint::memory_checkpoint_storage::MemoryCheckpointStorage;
+    use crate::storage::checkpoint::CheckpointStorageWrap;
+    use std::sync::{Arc, RwLock};
+
+    fn build_checkpoint(task_num: u16, checkpoint_id: u64) -> Checkpoint {
+        Checkpoint {
+            chain_id: 1,
+            task_num,
+            checkpoint_id,
+            handle: CheckpointHandle {
+                handle: format!("{}", task_num),
+            },
+        }
+    }
+
+    #[test]
+    pub fn savepoint_test() {
+        let storage =
+            CheckpointStorageWrap::MemoryCheckpointStorage(MemoryCheckpointStorage::new());
+        let mut chain_ck =
+            ChainCheckpoint::new("job".to_string(), "id".to_string(), 1, 2, Some(storage));
+
+        let savepoint_id = new_savepoint_id();
+        assert!(is_savepoint(savepoint_id));
+        assert!(!is_savepoint(savepoint_id & !SAVEPOINT_FLAG));
+
+        // the savepoint is not triggered
+        assert!(chain_ck.add(build_checkpoint(0, savepoint_id)).is_err());
+
+        chain_ck.begin_savepoint(Savepoint {
+            name: "v1".to_string(),
+            checkpoint_id: savepoint_id,
+        });
+        chain_ck.add(build_checkpoint(0, 1000)).unwrap();
+        chain_ck.add(build_checkpoint(0, savepoint_id)).unwrap();
+        assert!(chain_ck.load_savepoint("v1").unwrap().is_empty());
+
+        // the periodic checkpoints are not affected by the savepoint
+        chain_ck.add(build_checkpoint(1, 1000)).unwrap();
+        assert_eq!(chain_ck.latest_finish_cks.len(), 2);
+
+        chain_ck.add(build_checkpoint(1, savepoint_id)).unwrap();
+        assert_eq!(chain_ck.load_savepoint("v1").unwrap().len(), 2);
+        assert!(chain_ck.savepoint.is_none());
+    }
+
+    #[test]
+    pub fn completed_checkpoint_test() {
+        let mut chain_ck = ChainCheckpoint::new("job".to_string(), "id".to_string(), 1, 2, None);
+
+        chain_ck.add(build_checkpoint(0, 1000)).unwrap();
+        chain_ck.add(build_checkpoint(1, 1000)).unwrap();
+        assert_eq!(chain_ck.completed_ck_id, 1000);
+
+        // the checkpoint is completed after all tasks acknowledged
+        chain_ck.add(build_checkpoint(0, 2000)).unwrap();
+        assert_eq!(chain_ck.completed_ck_id, 1000);
+        chain_ck.add(build_checkpoint(1, 2000)).unwrap();
+        assert_eq!(chain_ck.completed_ck_id, 2000);
+    }
+
+    #[test]
+    pub fn completed_checkpoint_of_job_test() {
+        let chain_cks = dashmap::DashMap::new();
+        for chain_id in 1..4 {
+            let chain_ck =
+                ChainCheckpoint::new("job".to_string(), "id".to_string(), chain_id, 1, None);
+            chain_cks.insert(chain_id, Arc::new(RwLock::new(chain_ck)));
+        }
+        let ck_manager = CheckpointManager {
+            job_name: "job".to_string(),
+            chain_cks,
+            stateful_chain_ids: vec![1, 2],
+            latest_savepoint: Arc::new(RwLock::new(None)),
+        };
+        let checkpoint = |chain_id, checkpoint_id| {
+            let mut ck = build_checkpoint(0, checkpoint_id);
+            ck.chain_id = chain_id;
+            ck
+        };
+
+        // the chain 2 has not reported yet
+        ck_manager.add(checkpoint(1, 1000)).unwrap();
+        assert_eq!(ck_manager.get_completed_checkpoint_id(), None);
+
+        // the stateless chain 3 is never waited
+        ck_manager.add(checkpoint(2, 1000)).unwrap();
+        assert_eq!(ck_manager.get_completed_checkpoint_id(), Some(1000));
+
+        ck_manager.add(checkpoint(1, 2000)).unwrap();
+        assert_eq!(ck_manager.get_completed_checkpoint_id(), Some(1000));
+        ck_manager.add(checkpoint(2, 2000)).unwrap();
+        assert_eq!(ck_manager.get_completed_checkpoint_id(), Some(2000));
+    }
+}
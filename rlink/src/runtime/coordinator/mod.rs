@@ -12,6 +12,7 @@ use crate::runtime::context::Context;
 use crate::runtime::coordinator::checkpoint_manager::CheckpointManager;
 use crate::runtime::coordinator::server::web_launch;
 use crate::runtime::coordinator::task_distribution::build_job_descriptor;
+use crate::runtime::worker::checkpoint::TaskCheckpoint;
 use crate::runtime::{JobDescriptor, TaskManagerStatus};
 use crate::storage::metadata::MetadataLoader;
 use crate::storage::metadata::{
@@ -75,7 +76,11 @@ where
             .build_stream(&job_properties, &self.stream_env);
         info!("DataStream: {:?}", data_stream);
 
-        let logic_plan = build_logic_plan(data_stream, MetadataLoader::place_holder());
+        let logic_plan = build_logic_plan(
+            data_stream,
+            MetadataLoader::place_holder(),
+            TaskCheckpoint::new(),
+        );
         info!(
             "Logic Plan: {}",
             serde_json::to_string_pretty(&logic_plan).unwrap()
@@ -174,7 +179,19 @@ where
         job_descriptor: &mut JobDescriptor,
     ) -> CheckpointManager {
         let mut ck_manager = CheckpointManager::new(job_graph, &self.context, &job_descriptor);
-        let chain_checkpoints = ck_manager.load().expect("load checkpoints error");
+        let chain_checkpoints = match self.context.savepoint.as_ref() {
+            Some(savepoint_name) => {
+                info!("resume from savepoint `{}`", savepoint_name);
+                let chain_checkpoints = ck_manager
+                    .load_savepoint(savepoint_name.as_str())
+                    .expect("load savepoint error");
+                if chain_checkpoints.values().all(|cks| cks.is_empty()) {
+                    panic!("savepoint `{}` not found", savepoint_name);
+                }
+                chain_checkpoints
+            }
+            None => ck_manager.load().expect("load checkpoints error"),
+        };
         if chain_checkpoints.len() == 0 {
             return ck_manager;
         }
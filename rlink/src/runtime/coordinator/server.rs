@@ -99,6 +99,7 @@ async fn serve(
                 .service(web::resource("/metadata").route(web::get().to(get_metadata)))
                 .service(web::resource("/checkpoint").route(web::post().to(register_checkpoint)))
                 .service(web::resource("/checkpoints").route(web::get().to(get_checkpoint)))
+                .service(web::resource("/savepoint").route(web::post().to(trigger_savepoint)))
         })
         .disable_signals()
         .workers(8)
@@ -148,9 +149,18 @@ pub(crate) struct HeartbeatModel {
     pub status: String,
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub(crate) struct HeartbeatResponseModel {
+    /// the `checkpoint_id` of the pending savepoint
+    pub savepoint_id: Option<u64>,
+    /// the `checkpoint_id` of the latest checkpoint completed by all chains
+    pub completed_checkpoint_id: Option<u64>,
+}
+
 pub(crate) async fn heartbeat(
     heartbeat_model: web::Json<HeartbeatModel>,
     context: Data<WebContext>,
+    ck_manager: Data<CheckpointManager>,
 ) -> Result<HttpResponse, Error> {
     let metadata_storage = MetadataStorageWrap::new(&context.metadata_mode);
 
@@ -167,7 +177,18 @@ pub(crate) async fn heartbeat(
         )
         .unwrap();
 
-    let response = StdResponse::new(ResponseCode::OK, Some(true));
+    let savepoint_id = ck_manager
+        .get_ref()
+        .get_pending_savepoint()
+        .map(|savepoint| savepoint.checkpoint_id);
+    let completed_checkpoint_id = ck_manager.get_ref().get_completed_checkpoint_id();
+    let response = StdResponse::new(
+        ResponseCode::OK,
+        Some(HeartbeatResponseModel {
+            savepoint_id,
+            completed_checkpoint_id,
+        }),
+    );
     Ok(HttpResponse::Ok().json(response))
 }
 
@@ -204,6 +225,29 @@ pub(crate) async fn register_checkpoint(
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub(crate) struct SavepointModel {
+    pub name: String,
+}
+
+pub(crate) async fn trigger_savepoint(
+    savepoint_model: web::Json<SavepointModel>,
+    ck_manager: Data<CheckpointManager>,
+) -> Result<HttpResponse, Error> {
+    let response = match ck_manager
+        .get_ref()
+        .trigger_savepoint(savepoint_model.name.as_str())
+    {
+        Ok(savepoint) => StdResponse::new(ResponseCode::OK, Some(savepoint)),
+        Err(e) => {
+            error!("trigger savepoint error. {}", e);
+            StdResponse::new(ResponseCode::ERR(e.to_string()), None)
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 pub(crate) async fn get_checkpoint(
     ck_manager: Data<CheckpointManager>,
 ) -> Result<HttpResponse, Error> {
@@ -1,6 +1,8 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::api::checkpoint::Checkpoint;
+use crate::api::checkpoint::{Checkpoint, CheckpointHandle, SAVEPOINT_FLAG};
 use crate::api::cluster::StdResponse;
 use crate::channel::{bounded, Receiver, Sender, TryRecvError, TrySendError};
 use crate::utils::http_client::post;
@@ -22,6 +24,178 @@ lazy_static! {
     static ref CK_CHANNEL: CheckpointChannel = CheckpointChannel::new();
 }
 
+/// the `checkpoint_id` of the latest savepoint received from the coordinator
+static SAVEPOINT_ID: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn update_savepoint_id(savepoint_id: u64) {
+    let previous = SAVEPOINT_ID.fetch_max(savepoint_id, Ordering::SeqCst);
+    if previous < savepoint_id {
+        info!("receive savepoint({}) from coordinator", savepoint_id);
+    }
+}
+
+pub(crate) fn get_savepoint_id() -> u64 {
+    SAVEPOINT_ID.load(Ordering::SeqCst)
+}
+
+/// the `checkpoint_id` of the latest checkpoint completed by all chains of the job
+static COMPLETED_CHECKPOINT_ID: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn update_completed_checkpoint_id(checkpoint_id: u64) {
+    let previous = COMPLETED_CHECKPOINT_ID.fetch_max(checkpoint_id, Ordering::SeqCst);
+    if previous < checkpoint_id {
+        debug!(
+            "receive completed checkpoint({}) from coordinator",
+            checkpoint_id
+        );
+    }
+}
+
+pub(crate) fn get_completed_checkpoint_id() -> u64 {
+    COMPLETED_CHECKPOINT_ID.load(Ordering::SeqCst)
+}
+
+/// the handle of a stateful operator in the checkpoint of a task
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct OperatorHandle {
+    pub operator_id: u32,
+    pub handle: String,
+}
+
+impl OperatorHandle {
+    /// the handle of the task is made up of the handles of the operators
+    pub fn encode(handles: &[OperatorHandle]) -> CheckpointHandle {
+        CheckpointHandle {
+            handle: serde_json::to_string(handles).unwrap(),
+        }
+    }
+
+    /// the handle of the operator in the handle of the task,
+    /// `None` if the operator has not been snapshot in the checkpoint
+    pub fn decode(handle: &CheckpointHandle, operator_id: u32) -> Option<CheckpointHandle> {
+        match serde_json::from_str::<Vec<OperatorHandle>>(handle.handle.as_str()) {
+            Ok(handles) => handles
+                .into_iter()
+                .find(|operator_handle| operator_handle.operator_id == operator_id)
+                .map(|operator_handle| CheckpointHandle {
+                    handle: operator_handle.handle,
+                }),
+            Err(e) => {
+                error!("illegal checkpoint handle({}). {}", handle.handle, e);
+                None
+            }
+        }
+    }
+}
+
+/// the handles of the stateful operators of a task. the operators add the handles when the
+/// barrier passes through them, and the source of the chain reports the handles as the
+/// checkpoint of the task after the barrier has passed through the whole chain.
+/// so the checkpoint is never acknowledged before all operators of the task have snapshot
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TaskCheckpoint {
+    handles: Arc<Mutex<Vec<(u64, OperatorHandle)>>>,
+    /// the checkpoints failed by an operator, they are never reported
+    failed: Arc<Mutex<Vec<u64>>>,
+}
+
+impl TaskCheckpoint {
+    pub fn new() -> Self {
+        TaskCheckpoint::default()
+    }
+
+    pub fn add(&self, operator_id: u32, checkpoint_id: u64, handle: CheckpointHandle) {
+        let operator_handle = OperatorHandle {
+            operator_id,
+            handle: handle.handle,
+        };
+        let mut handles = self.handles.lock().unwrap();
+        handles.push((checkpoint_id, operator_handle));
+    }
+
+    /// fail the checkpoint, the task doesn't acknowledge it, so it's never completed
+    pub fn fail(&self, checkpoint_id: u64) {
+        let mut failed = self.failed.lock().unwrap();
+        failed.push(checkpoint_id);
+    }
+
+    /// take the handles of the checkpoint, `None` if it's failed by any operator.
+    /// the handles left by the other checkpoints are dropped
+    pub fn take(&self, checkpoint_id: u64) -> Option<Vec<OperatorHandle>> {
+        let is_failed = {
+            let mut failed = self.failed.lock().unwrap();
+            let is_failed = failed.contains(&checkpoint_id);
+            failed.clear();
+            is_failed
+        };
+
+        let mut handles = self.handles.lock().unwrap();
+        let handles: Vec<OperatorHandle> = handles
+            .drain(..)
+            .filter_map(|(id, operator_handle)| {
+                if id == checkpoint_id {
+                    Some(operator_handle)
+                } else {
+                    warn!(
+                        "drop the handle of checkpoint({}) when reporting checkpoint({}). {:?}",
+                        id, checkpoint_id, operator_handle
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        if is_failed {
+            None
+        } else {
+            Some(handles)
+        }
+    }
+}
+
+/// count the barriers of a checkpoint reached from the upstream tasks. the savepoint flag is
+/// ignored, the checkpoints are ordered by the timestamp, so a periodic checkpoint after
+/// a savepoint is never taken as the late one
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BarrierCounter {
+    checkpoint_id: u64,
+    reached_barriers: usize,
+}
+
+impl BarrierCounter {
+    pub fn new() -> Self {
+        BarrierCounter::default()
+    }
+
+    /// returns `true` when the barriers of all `num_barriers` upstream tasks have reached,
+    /// the counter is reset for the next checkpoint
+    pub fn reach(&mut self, checkpoint_id: u64, num_barriers: usize) -> bool {
+        if self.checkpoint_id != checkpoint_id {
+            if self.checkpoint_id != 0
+                && checkpoint_id & !SAVEPOINT_FLAG < self.checkpoint_id & !SAVEPOINT_FLAG
+            {
+                error!(
+                    "Unusual state of Checkpoint. Barrier's `checkpoint_id`({}) is less than `current_checkpoint_id`({})",
+                    checkpoint_id, self.checkpoint_id
+                );
+                return false;
+            }
+
+            self.checkpoint_id = checkpoint_id;
+            self.reached_barriers = 0;
+        }
+
+        self.reached_barriers += 1;
+        if self.reached_barriers < num_barriers {
+            return false;
+        }
+
+        self.checkpoint_id = 0;
+        self.reached_barriers = 0;
+        true
+    }
+}
+
 pub(crate) fn report_checkpoint(ck: Checkpoint) -> Option<Checkpoint> {
     let ck_channel = &*CK_CHANNEL;
 
@@ -80,3 +254,66 @@ pub(crate) async fn report_checkpoint0(coordinator_address: &str, ck: Checkpoint
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::api::checkpoint::{CheckpointHandle, SAVEPOINT_FLAG};
+    use crate::runtime::worker::checkpoint::{BarrierCounter, OperatorHandle, TaskCheckpoint};
+
+    #[test]
+    pub fn barrier_counter_test() {
+        let mut counter = BarrierCounter::new();
+        assert!(!counter.reach(1000, 2));
+        assert!(counter.reach(1000, 2));
+
+        // the periodic checkpoint after a savepoint is not dropped
+        let savepoint_id = SAVEPOINT_FLAG | 1500;
+        assert!(!counter.reach(savepoint_id, 2));
+        assert!(counter.reach(savepoint_id, 2));
+        assert!(!counter.reach(2000, 2));
+        assert!(counter.reach(2000, 2));
+
+        // the barrier of a later checkpoint gives up the incomplete one
+        assert!(!counter.reach(3000, 2));
+        assert!(!counter.reach(4000, 2));
+        // the late barrier is dropped
+        assert!(!counter.reach(3000, 2));
+        assert!(counter.reach(4000, 2));
+
+        assert!(counter.reach(5000, 1));
+    }
+
+    #[test]
+    pub fn task_checkpoint_test() {
+        let handle = |handle: &str| CheckpointHandle {
+            handle: handle.to_string(),
+        };
+
+        let task_checkpoint = TaskCheckpoint::new();
+        task_checkpoint.add(3, 1000, handle("stale"));
+        task_checkpoint.add(3, 2000, handle("window"));
+        task_checkpoint.add(5, 2000, handle("{\"pending\":[]}"));
+
+        let handles = task_checkpoint.take(2000).unwrap();
+        assert_eq!(handles.len(), 2);
+        assert!(task_checkpoint.take(1000).unwrap().is_empty());
+
+        // the failed checkpoint is never reported, the next one is not affected
+        task_checkpoint.add(3, 3000, handle("window"));
+        task_checkpoint.fail(3000);
+        assert!(task_checkpoint.take(3000).is_none());
+        assert!(task_checkpoint.take(4000).unwrap().is_empty());
+
+        let task_handle = OperatorHandle::encode(handles.as_slice());
+        assert_eq!(
+            OperatorHandle::decode(&task_handle, 3).unwrap().handle,
+            "window"
+        );
+        assert_eq!(
+            OperatorHandle::decode(&task_handle, 5).unwrap().handle,
+            "{\"pending\":[]}"
+        );
+        assert!(OperatorHandle::decode(&task_handle, 7).is_none());
+        assert!(OperatorHandle::decode(&handle("1000"), 3).is_none());
+    }
+}
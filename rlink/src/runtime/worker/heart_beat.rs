@@ -1,5 +1,6 @@
 use crate::api::cluster::StdResponse;
-use crate::runtime::coordinator::server::HeartbeatModel;
+use crate::runtime::coordinator::server::{HeartbeatModel, HeartbeatResponseModel};
+use crate::runtime::worker::checkpoint::{update_completed_checkpoint_id, update_savepoint_id};
 use crate::utils::http_client::post;
 use crate::utils::{date_time, get_runtime, panic};
 use std::time::Duration;
@@ -45,7 +46,7 @@ pub(crate) async fn status_heartbeat_async(
     let body = serde_json::to_string(&model).unwrap();
 
     let begin_time = date_time::current_timestamp_millis();
-    let resp = post::<StdResponse<bool>>(url, body).await;
+    let resp = post::<StdResponse<HeartbeatResponseModel>>(url, body).await;
     let end_time = date_time::current_timestamp_millis();
     let elapsed = end_time - begin_time;
 
@@ -54,6 +55,15 @@ pub(crate) async fn status_heartbeat_async(
             if elapsed > 1000 {
                 warn!("heartbeat success. {:?}, elapsed: {}ms > 1s", resp, elapsed);
             }
+
+            if let Some(data) = resp.data {
+                if let Some(savepoint_id) = data.savepoint_id {
+                    update_savepoint_id(savepoint_id);
+                }
+                if let Some(completed_checkpoint_id) = data.completed_checkpoint_id {
+                    update_completed_checkpoint_id(completed_checkpoint_id);
+                }
+            }
         }
         Err(e) => {
             error!("heartbeat error. {}, elapsed: {}ms", e, elapsed);
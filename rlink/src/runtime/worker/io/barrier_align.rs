@@ -0,0 +1,225 @@
+use crate::api::checkpoint::{CheckpointMode, SAVEPOINT_FLAG};
+use crate::api::element::Element;
+use crate::runtime::worker::checkpoint::TaskCheckpoint;
+use std::collections::{HashSet, VecDeque};
+
+/// the maximum of the held back elements, the alignment is given up and the checkpoint is failed
+/// if exceeded, to keep the memory bounded when an upstream task is far behind
+const MAX_BLOCKED_ELEMENTS: usize = 500000;
+
+/// Align the barriers of the upstream tasks which are merged into one channel.
+///
+/// In the `Aligned` mode, once the barrier of a checkpoint reached from an upstream task,
+/// the elements of that task are held back until the barriers of all upstream tasks reached,
+/// so the downstream snapshot never includes the elements after the barrier.
+/// the barriers themselves are always emitted, the downstream counts them to trigger the snapshot.
+#[derive(Debug)]
+pub(crate) struct BarrierAligner {
+    mode: CheckpointMode,
+    task_checkpoint: TaskCheckpoint,
+
+    checkpoint_id: u64,
+    /// the alignment of the current checkpoint is given up, the checkpoint can't be completed
+    given_up: bool,
+    blocked_tasks: HashSet<u16>,
+    /// the elements of the blocked tasks in the arrival order
+    blocked_elements: VecDeque<Element>,
+    /// the elements released by the latest alignment, they are aligned again
+    /// because there may be the barrier of the next checkpoint in them
+    released_elements: VecDeque<Element>,
+}
+
+impl BarrierAligner {
+    pub fn new(mode: CheckpointMode, task_checkpoint: TaskCheckpoint) -> Self {
+        BarrierAligner {
+            mode,
+            task_checkpoint,
+            checkpoint_id: 0,
+            given_up: false,
+            blocked_tasks: HashSet::new(),
+            blocked_elements: VecDeque::new(),
+            released_elements: VecDeque::new(),
+        }
+    }
+
+    /// take the next element released by the latest alignment,
+    /// the released elements are taken before the elements of the channel
+    pub fn next_released(&mut self) -> Option<Element> {
+        self.released_elements.pop_front()
+    }
+
+    /// return the element if it can be emitted now,
+    /// otherwise it is held back until the barriers are aligned
+    pub fn align(&mut self, element: Element) -> Option<Element> {
+        if self.mode == CheckpointMode::Unaligned {
+            return Some(element);
+        }
+
+        let task_number = match element.get_task_number() {
+            Some(task_number) => task_number,
+            None => return Some(element),
+        };
+
+        if self.blocked_tasks.contains(&task_number) {
+            self.blocked_elements.push_back(element);
+            if self.blocked_elements.len() > MAX_BLOCKED_ELEMENTS {
+                error!(
+                    "too many elements are held back by checkpoint({}), give up the alignment",
+                    self.checkpoint_id
+                );
+                // the elements after the barrier are emitted before the snapshot,
+                // so the checkpoint is no longer exactly-once
+                self.given_up = true;
+                self.task_checkpoint.fail(self.checkpoint_id);
+                self.release();
+            }
+            return None;
+        }
+
+        if let Element::Barrier(barrier) = &element {
+            if barrier.checkpoint_id != self.checkpoint_id {
+                // the savepoint flag is ignored, the checkpoints are ordered by the timestamp
+                let timestamp = barrier.checkpoint_id & !SAVEPOINT_FLAG;
+                if timestamp < self.checkpoint_id & !SAVEPOINT_FLAG {
+                    // the late barrier of a checkpoint which has been given up
+                    return Some(element);
+                }
+
+                if !self.blocked_tasks.is_empty() {
+                    warn!(
+                        "checkpoint({}) is not aligned before checkpoint({}) reached",
+                        self.checkpoint_id, barrier.checkpoint_id
+                    );
+                    self.release();
+                }
+                self.checkpoint_id = barrier.checkpoint_id;
+                self.given_up = false;
+            }
+
+            if self.given_up {
+                // the source takes the checkpoint after each barrier, it's failed again
+                // until the barriers of all upstream tasks are passed through
+                self.task_checkpoint.fail(self.checkpoint_id);
+                return Some(element);
+            }
+
+            self.blocked_tasks.insert(task_number);
+            if self.blocked_tasks.len() >= barrier.num_tasks as usize {
+                debug!("checkpoint({}) aligned", self.checkpoint_id);
+                self.release();
+            }
+        }
+
+        Some(element)
+    }
+
+    fn release(&mut self) {
+        self.blocked_tasks.clear();
+
+        // the held back elements arrived before the released elements of the same task
+        while let Some(element) = self.blocked_elements.pop_back() {
+            self.released_elements.push_front(element);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::checkpoint::CheckpointMode;
+    use crate::api::element::{Element, Record};
+    use crate::runtime::worker::checkpoint::TaskCheckpoint;
+    use crate::runtime::worker::io::barrier_align::{BarrierAligner, MAX_BLOCKED_ELEMENTS};
+
+    fn record(task_number: u16, timestamp: u64) -> Element {
+        let mut record = Record::new();
+        record.task_number = task_number;
+        record.timestamp = timestamp;
+        Element::Record(record)
+    }
+
+    fn barrier(task_number: u16, checkpoint_id: u64) -> Element {
+        let mut barrier = Element::new_barrier(checkpoint_id);
+        barrier.set_task_number(task_number, 2);
+        barrier
+    }
+
+    fn drain(aligner: &mut BarrierAligner, elements: Vec<Element>) -> Vec<Element> {
+        let mut emitted = Vec::new();
+        for element in elements {
+            if let Some(element) = aligner.align(element) {
+                emitted.push(element);
+            }
+            while let Some(element) = aligner.next_released() {
+                if let Some(element) = aligner.align(element) {
+                    emitted.push(element);
+                }
+            }
+        }
+        emitted
+    }
+
+    #[test]
+    pub fn barrier_align_test() {
+        let elements = vec![
+            record(0, 1),
+            barrier(0, 100),
+            record(0, 2),
+            record(1, 3),
+            barrier(0, 200),
+            record(0, 4),
+            barrier(1, 100),
+            record(1, 5),
+        ];
+
+        let mut aligner = BarrierAligner::new(CheckpointMode::Aligned, TaskCheckpoint::new());
+        let emitted = drain(&mut aligner, elements.clone());
+
+        let ids: Vec<u64> = emitted
+            .iter()
+            .map(|element| match element {
+                Element::Record(record) => record.timestamp,
+                Element::Barrier(barrier) => barrier.checkpoint_id,
+                _ => 0,
+            })
+            .collect();
+        // the records after the barrier(100) of the task 0 are held back until task 1 aligned
+        assert_eq!(ids, vec![1, 100, 3, 100, 2, 200, 5]);
+
+        // the record 4 is held back by the checkpoint 200
+        assert_eq!(aligner.blocked_elements.len(), 1);
+
+        let mut aligner = BarrierAligner::new(CheckpointMode::Unaligned, TaskCheckpoint::new());
+        let emitted = drain(&mut aligner, elements);
+        assert_eq!(emitted.len(), 8);
+    }
+
+    #[test]
+    pub fn barrier_align_overflow_test() {
+        let task_checkpoint = TaskCheckpoint::new();
+        let mut aligner = BarrierAligner::new(CheckpointMode::Aligned, task_checkpoint.clone());
+
+        let mut elements = vec![barrier(0, 100)];
+        for timestamp in 0..MAX_BLOCKED_ELEMENTS + 1 {
+            elements.push(record(0, timestamp as u64));
+        }
+        elements.push(barrier(1, 100));
+        elements.push(record(1, 1));
+
+        let emitted = drain(&mut aligner, elements);
+        // all elements are released, and the late barrier doesn't block the task 1
+        assert_eq!(emitted.len(), MAX_BLOCKED_ELEMENTS + 4);
+        assert!(aligner.blocked_tasks.is_empty());
+        assert!(aligner.blocked_elements.is_empty());
+
+        // the checkpoint given up is failed
+        assert!(task_checkpoint.take(100).is_none());
+
+        // the next checkpoint is aligned again
+        let emitted = drain(
+            &mut aligner,
+            vec![barrier(0, 200), record(0, 2), barrier(1, 200)],
+        );
+        assert_eq!(emitted.len(), 3);
+        assert!(task_checkpoint.take(200).is_some());
+    }
+}
@@ -1,11 +1,9 @@
-use crate::api::backend::KeyedStateBackend;
 use crate::api::checkpoint::{CheckpointHandle, CheckpointedFunction, FunctionSnapshotContext};
-use crate::api::element::{Barrier, Element, Record};
+use crate::api::element::{Barrier, Element, Record, WindowFiring};
 use crate::api::function::{Context, Function};
 use crate::api::input::{InputFormat, InputSplitSource};
-use crate::api::properties::{Properties, SystemProperties};
+use crate::api::properties::Properties;
 use crate::api::split::{InputSplit, InputSplitAssigner};
-use crate::api::window::Window;
 use crate::channel::{
     mb, named_bounded, ElementReceiver, ElementSender, RecvTimeoutError, TryRecvError,
 };
@@ -15,7 +13,6 @@ use crate::storage::keyed_state::{ReducingState, ReducingStateWrap, StateKey};
 use crate::utils;
 use crate::utils::date_time::timestamp_str;
 use metrics::gauge;
-use std::str::FromStr;
 use std::time::Duration;
 
 pub(crate) const WINDOWS_FINISH_CHECKPOINT_ID: u64 = 0;
@@ -30,7 +27,6 @@ pub(crate) struct MemChannelInputFormat {
     receiver: Option<ElementReceiver>,
 
     job_id: String,
-    state_mode: Option<KeyedStateBackend>,
     /// `Watermark` window to state iter thread
     window_to_state_sender: Option<ElementSender>,
     /// window's state iter to `InputFormat` thread
@@ -38,6 +34,8 @@ pub(crate) struct MemChannelInputFormat {
 
     window_output_begin_ts: u64,
     window_start_flag: bool,
+    /// the firing of the windows which are being taken from the state
+    current_firing: Option<WindowFiring>,
 
     checkpoint: Option<InputCheckpointed>,
 
@@ -52,21 +50,18 @@ impl MemChannelInputFormat {
             task_number: 0,
             receiver: None,
             job_id: "".to_string(),
-            state_mode: None,
             window_to_state_sender: None,
             stat_to_input_receiver: None,
             window_output_begin_ts: 0,
             window_start_flag: false,
+            current_firing: None,
             checkpoint: None,
             elapsed_guava: None,
         }
     }
 
-    fn state_handle(&mut self, finish_window: u64) {
-        info!(
-            "apply checkpoint `finish_window` timestamp: {}",
-            finish_window
-        );
+    fn state_handle(&mut self, finished_firing: Option<WindowFiring>) {
+        info!("apply checkpoint `finished_firing`: {:?}", finished_firing);
 
         let state_receiver = {
             let tags = vec![
@@ -94,7 +89,6 @@ impl MemChannelInputFormat {
 
         let dependency_chain_id = self.dependency_chain_id;
         let task_number = self.task_number;
-        let state_mode = self.state_mode.as_ref().unwrap().clone();
         utils::spawn("memory-channel", move || loop {
             match state_receiver.recv_timeout(Duration::from_secs(120)) {
                 Ok(element) => {
@@ -104,25 +98,29 @@ impl MemChannelInputFormat {
                         timestamp_str(watermark.timestamp)
                     );
 
+                    // the same window may be fired again by the trigger or the late records,
+                    // only the firings taken before the checkpoint are skipped
+                    let processed = match (watermark.window_firing, finished_firing) {
+                        (Some(firing), Some(finished_firing)) => firing <= finished_firing,
+                        _ => false,
+                    };
+
                     let drop_windows = watermark.drop_windows.as_ref().unwrap();
                     for window in drop_windows {
                         debug!("begin iter window({:?})", window);
 
-                        if window.max_timestamp() <= finish_window {
+                        let state_key =
+                            StateKey::new(window.clone(), dependency_chain_id, task_number);
+                        if processed {
                             info!(
-                                "checkpoint hand={}, window have been processed, skipped",
-                                finish_window
+                                "checkpoint handle={:?}, window({:?}) of firing({:?}) have been processed, skipped",
+                                finished_firing, window, watermark.window_firing
                             );
+                            MemChannelInputFormat::drop_stat(state_key);
                             continue;
                         }
 
-                        let state_key =
-                            StateKey::new(window.clone(), dependency_chain_id, task_number);
-                        MemChannelInputFormat::iter_stat(
-                            state_key,
-                            state_mode.clone(),
-                            &state_sender,
-                        );
+                        MemChannelInputFormat::iter_stat(state_key, &state_sender);
                         debug!("finish window({:?}) iter", window);
                     }
 
@@ -144,40 +142,17 @@ impl MemChannelInputFormat {
         });
     }
 
-    fn iter_stat(state_key: StateKey, state_mode: KeyedStateBackend, state_sender: &ElementSender) {
-        let reducing_state = ReducingStateWrap::new(&state_key, state_mode);
+    /// iterate the emitted state of the window, the state is destroyed after the iteration
+    fn iter_stat(state_key: StateKey, state_sender: &ElementSender) {
+        let reducing_state = ReducingStateWrap::from(&state_key);
 
         match reducing_state {
             Some(reducing_state) => {
-                {
-                    let mut state_iter = reducing_state.iter();
-                    loop {
-                        match state_iter.next() {
-                            Some((mut key, val)) => {
-                                key.extend(val).expect("key value merge error");
-                                key.trigger_window = Some(state_key.window.clone());
-                                state_sender
-                                    .try_send_loop(Element::Record(key), Duration::from_secs(1));
-                            }
-                            None => {
-                                // send Barrier as the window's finish flag.
-                                // use window maximum time as barrier checkpoint_id.
-                                let checkpoint_id = state_key.window.max_timestamp();
-                                let mut window_finish_barrier = Barrier::new(checkpoint_id);
-                                window_finish_barrier.partition_num = state_key.task_number;
-
-                                state_sender
-                                    .send_timeout(
-                                        Element::Barrier(window_finish_barrier),
-                                        Duration::from_secs(1),
-                                    )
-                                    .unwrap();
-                                break;
-                            }
-                        }
-                    }
-
-                    drop(state_iter);
+                // the iterator is dropped before the state is destroyed
+                for (mut key, val) in reducing_state.iter() {
+                    key.extend(val).expect("key value merge error");
+                    key.trigger_window = Some(state_key.window.clone());
+                    state_sender.try_send_loop(Element::Record(key), Duration::from_secs(1));
                 }
 
                 reducing_state.destroy();
@@ -187,6 +162,13 @@ impl MemChannelInputFormat {
             }
         }
     }
+
+    /// destroy the emitted state of the window without the iteration
+    fn drop_stat(state_key: StateKey) {
+        if let Some(reducing_state) = ReducingStateWrap::from(&state_key) {
+            reducing_state.destroy();
+        }
+    }
 }
 
 impl InputSplitSource for MemChannelInputFormat {
@@ -227,11 +209,6 @@ impl InputFormat for MemChannelInputFormat {
         );
         self.checkpoint = Some(input_checkpoint);
 
-        let state_mode = context
-            .job_properties
-            .get_keyed_state_backend()
-            .unwrap_or(KeyedStateBackend::Memory);
-        self.state_mode = Some(state_mode);
         self.job_id = context.job_id.clone();
 
         self.receiver = Some(create_mem_channel(self.dependency_chain_id, context.task_number).1);
@@ -269,12 +246,17 @@ impl InputFormat for MemChannelInputFormat {
                 Ok(element) => {
                     if element.is_watermark() {
                         debug!("begin send Watermark to sink");
+                        self.current_firing = element.as_watermark().window_firing;
                         self.window_to_state_sender
                             .as_ref()
                             .unwrap()
                             .try_send_loop(element, Duration::from_secs(1));
                         self.window_output_begin_ts = utils::date_time::current_timestamp_millis();
                         self.window_start_flag = true;
+                    } else if element.is_barrier() {
+                        // the windows dropped before the barrier have been taken to downstream,
+                        // the checkpoint is fired by the `SourceRunnable` with the barrier
+                        return Some(element);
                     } else if element.is_record() {
                         panic!("Unsupported element type");
                     }
@@ -287,12 +269,22 @@ impl InputFormat for MemChannelInputFormat {
         }
 
         // try receive from state iter thread
-        match self.stat_to_input_receiver.as_ref().unwrap().try_recv() {
-            Ok(element) => {
-                if element.is_barrier() {
+        loop {
+            match self.stat_to_input_receiver.as_ref().unwrap().try_recv() {
+                Ok(element) => {
+                    if !element.is_barrier() {
+                        return Some(element);
+                    }
+
                     let checkpoint_id = element.as_barrier().checkpoint_id;
                     if checkpoint_id == WINDOWS_FINISH_CHECKPOINT_ID {
                         // batch window's finish Barrier flag
+                        // the finished firing only updates the handle, it's snapshot by
+                        // the checkpoint of the barrier forwarded by the window
+                        if let Some(firing) = self.current_firing.take() {
+                            self.checkpoint.as_mut().unwrap().set_handle(firing);
+                            debug!("update checkpoint handle={:?}", firing);
+                        }
 
                         let window_output_end_ts = utils::date_time::current_timestamp_millis();
                         let elapsed = window_output_end_ts - self.window_output_begin_ts;
@@ -309,28 +301,16 @@ impl InputFormat for MemChannelInputFormat {
                         self.window_start_flag = false;
 
                         // ignore batch window's finish Barrier flag
-                        None
-                    } else {
-                        // the `Watermark` is per window's finish Barrier flag.
-                        // update handle and emit `Watermark`, will trigger checkpoint operation in
-                        // the `SourceRunnable`. see
-                        // `self.checkpoint(row.as_barrier().checkpoint_id);` in SourceRunnable::run
-                        let finish_window = checkpoint_id;
-                        self.checkpoint.as_mut().unwrap().set_handle(finish_window);
-                        debug!("update checkpoint handle={}", finish_window);
-
-                        Some(element)
+                        return None;
                     }
-                } else {
-                    Some(element)
                 }
-            }
-            Err(TryRecvError::Empty) => None,
-            Err(TryRecvError::Disconnected) => {
-                panic!(
-                    "{} `stat_to_input_receiver` `Disconnected`",
-                    self.get_name()
-                );
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    panic!(
+                        "{} `stat_to_input_receiver` `Disconnected`",
+                        self.get_name()
+                    );
+                }
             }
         }
     }
@@ -347,15 +327,15 @@ impl InputFormat for MemChannelInputFormat {
 
 /// MemoryInputFormat Checkpoint
 ///
-/// the `handle` is maximum timestamp for drop window,
-/// always the `checkpoint_id` == `handle` in `CheckpointHandle`
+/// the `handle` is the latest `WindowFiring` which has been taken downstream,
+/// the windows of the same or the earlier firing are skipped after the restore
 #[derive(Debug)]
 pub struct InputCheckpointed {
     job_id: String,
     chain_id: u32,
     task_number: u16,
 
-    finish_window: u64,
+    finished_firing: Option<WindowFiring>,
 }
 
 impl InputCheckpointed {
@@ -364,16 +344,16 @@ impl InputCheckpointed {
             job_id,
             chain_id,
             task_number,
-            finish_window: 0,
+            finished_firing: None,
         }
     }
 
-    pub fn set_handle(&mut self, finish_window: u64) {
-        self.finish_window = finish_window;
+    pub(crate) fn set_handle(&mut self, finished_firing: WindowFiring) {
+        self.finished_firing = Some(finished_firing);
     }
 
-    pub fn handle(&self) -> u64 {
-        self.finish_window
+    pub(crate) fn handle(&self) -> Option<WindowFiring> {
+        self.finished_firing
     }
 }
 
@@ -385,14 +365,55 @@ impl CheckpointedFunction for InputCheckpointed {
     ) {
         if context.checkpoint_id > 0 && handle.is_some() {
             let data = handle.as_ref().unwrap();
-            self.finish_window = u64::from_str(data.handle.as_str())
-                .expect("`InputCheckpointed` handle must be u64");
+            self.finished_firing = match serde_json::from_str(data.handle.as_str()) {
+                Ok(finished_firing) => finished_firing,
+                Err(e) => {
+                    warn!(
+                        "illegal `InputCheckpointed` handle({}), no firing is skipped. {}",
+                        data.handle, e
+                    );
+                    None
+                }
+            };
         }
     }
 
     fn snapshot_state(&mut self, _context: &FunctionSnapshotContext) -> CheckpointHandle {
         CheckpointHandle {
-            handle: self.finish_window.to_string(),
+            handle: serde_json::to_string(&self.finished_firing).unwrap(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::api::checkpoint::{CheckpointHandle, CheckpointedFunction, FunctionSnapshotContext};
+    use crate::api::element::WindowFiring;
+    use crate::runtime::worker::io::mem_channel_input::InputCheckpointed;
+
+    #[test]
+    pub fn input_checkpointed_test() {
+        let context = FunctionSnapshotContext::new(1, 0, 2000);
+
+        let mut checkpointed = InputCheckpointed::new("job".to_string(), 1, 0);
+        checkpointed.set_handle(WindowFiring::new(1000, 3));
+        let handle = Some(checkpointed.snapshot_state(&context));
+
+        let mut restored = InputCheckpointed::new("job".to_string(), 1, 0);
+        restored.initialize_state(&context, &handle);
+        assert_eq!(restored.handle(), Some(WindowFiring::new(1000, 3)));
+
+        // the firings are ordered by the checkpoint first, the same window fired
+        // again after the checkpoint is not skipped
+        assert!(WindowFiring::new(1000, 4) > WindowFiring::new(1000, 3));
+        assert!(WindowFiring::new(2000, 1) > WindowFiring::new(1000, 3));
+
+        // the legacy handle of the window timestamp skips nothing
+        let legacy_handle = Some(CheckpointHandle {
+            handle: "1599999999999".to_string(),
+        });
+        let mut restored = InputCheckpointed::new("job".to_string(), 1, 0);
+        restored.initialize_state(&context, &legacy_handle);
+        assert_eq!(restored.handle(), None);
+    }
+}
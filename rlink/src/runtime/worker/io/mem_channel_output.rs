@@ -30,8 +30,10 @@ impl OutputFormat for MemChannelOutputFormat {
     fn write_record(&mut self, _record: Record) {}
 
     fn write_element(&mut self, element: Element) {
-        if !element.is_watermark() {
-            error!("Only `Watermark` can be handle");
+        // the `Barrier` is forwarded by the window, and it's taken to the downstream chain
+        // after the windows dropped before it
+        if !element.is_watermark() && !element.is_barrier() {
+            error!("Only `Watermark` and `Barrier` can be handle");
             return;
         }
 
@@ -3,6 +3,7 @@ use crate::metrics::Tag;
 use std::collections::HashMap;
 use std::sync::Mutex;
 
+pub(crate) mod barrier_align;
 pub(crate) mod mem_channel_input;
 pub(crate) mod mem_channel_output;
 pub(crate) mod net_channel_input;
@@ -1,11 +1,14 @@
+use crate::api::checkpoint::CheckpointMode;
 use crate::api::element::{Element, Record};
 use crate::api::function::{Context, Function};
 use crate::api::input::{InputFormat, InputSplitSource};
-use crate::api::properties::Properties;
+use crate::api::properties::{Properties, SystemProperties};
 use crate::api::split::{InputSplit, InputSplitAssigner};
 use crate::channel::{mb, named_bounded, ElementReceiver, TryRecvError};
 use crate::metrics::Tag;
 use crate::net::worker_client_pool::WorkerClientPool;
+use crate::runtime::worker::checkpoint::TaskCheckpoint;
+use crate::runtime::worker::io::barrier_align::BarrierAligner;
 use crate::runtime::worker::io::NET_IN_CHANNEL_SIZE;
 use crate::storage::metadata::MetadataLoader;
 use crate::utils;
@@ -19,10 +22,18 @@ pub(crate) struct NetChannelInputFormat {
     metadata_loader: MetadataLoader,
     receiver: Option<ElementReceiver>,
     worker_client_pool: Option<WorkerClientPool>,
+    task_checkpoint: TaskCheckpoint,
+    /// the elements of all upstream tasks are merged into the `receiver`
+    barrier_aligner: Option<BarrierAligner>,
 }
 
 impl NetChannelInputFormat {
-    pub fn new(chain_id: u32, dependency_chain_id: u32, metadata_loader: MetadataLoader) -> Self {
+    pub fn new(
+        chain_id: u32,
+        dependency_chain_id: u32,
+        metadata_loader: MetadataLoader,
+        task_checkpoint: TaskCheckpoint,
+    ) -> Self {
         NetChannelInputFormat {
             // task_descriptor,
             chain_id,
@@ -30,6 +41,8 @@ impl NetChannelInputFormat {
             metadata_loader,
             receiver: None,
             worker_client_pool: None,
+            task_checkpoint,
+            barrier_aligner: None,
         }
     }
 }
@@ -79,13 +92,23 @@ impl InputFormat for NetChannelInputFormat {
             worker_client_pool_clone.build();
         });
 
+        let checkpoint_mode = context
+            .job_properties
+            .get_checkpoint_mode()
+            .unwrap_or(CheckpointMode::Aligned);
+
         self.receiver = Some(rx);
         self.worker_client_pool = Some(worker_client_pool);
+        self.barrier_aligner = Some(BarrierAligner::new(
+            checkpoint_mode,
+            self.task_checkpoint.clone(),
+        ));
 
         info!(
-            "PollInputFormat({}) open. partition_num: {}",
+            "PollInputFormat({}) open. partition_num: {}, checkpoint_mode: {:?}",
             self.get_name(),
-            input_split.get_split_number()
+            input_split.get_split_number(),
+            checkpoint_mode,
         );
     }
 
@@ -98,8 +121,19 @@ impl InputFormat for NetChannelInputFormat {
     }
 
     fn next_element(&mut self) -> Option<Element> {
-        match self.receiver.as_ref().unwrap().try_recv() {
-            Ok(element) => {
+        loop {
+            let element = match self.barrier_aligner.as_mut().unwrap().next_released() {
+                Some(element) => element,
+                None => match self.receiver.as_ref().unwrap().try_recv() {
+                    Ok(element) => element,
+                    Err(TryRecvError::Empty) => return None,
+                    Err(TryRecvError::Disconnected) => {
+                        panic!("{} receiver `Disconnected`", self.get_name());
+                    }
+                },
+            };
+
+            if let Some(element) = self.barrier_aligner.as_mut().unwrap().align(element) {
                 // todo delete debug log
                 if element.is_watermark() {
                     debug!(
@@ -110,11 +144,7 @@ impl InputFormat for NetChannelInputFormat {
                         )
                     );
                 }
-                Some(element)
-            }
-            Err(TryRecvError::Empty) => None,
-            Err(TryRecvError::Disconnected) => {
-                panic!("{} receiver `Disconnected`", self.get_name());
+                return Some(element);
             }
         }
     }
@@ -7,6 +7,8 @@ use std::time::Duration;
 
 #[derive(Debug)]
 pub(crate) struct NetChannelOutputFormat {
+    task_number: u16,
+    num_tasks: u16,
     next_chain_parallelism: u32,
     senders: Vec<ElementSender>,
 }
@@ -19,6 +21,8 @@ impl NetChannelOutputFormat {
         let senders = c.iter().map(|(tx, _rx)| tx.clone()).collect();
 
         NetChannelOutputFormat {
+            task_number: 0,
+            num_tasks: 0,
             next_chain_parallelism,
             senders,
         }
@@ -27,6 +31,9 @@ impl NetChannelOutputFormat {
 
 impl OutputFormat for NetChannelOutputFormat {
     fn open(&mut self, context: &Context) {
+        self.task_number = context.task_number;
+        self.num_tasks = context.num_tasks;
+
         info!(
             "OutputFormat ({}) open, task_number={}, num_tasks={}",
             self.get_name(),
@@ -37,11 +44,15 @@ impl OutputFormat for NetChannelOutputFormat {
 
     fn write_record(&mut self, _record: Record) {}
 
-    fn write_element(&mut self, element: Element) {
+    fn write_element(&mut self, mut element: Element) {
         // if element.is_watermark() {
         //     info!("channel send watermark");
         // }
 
+        // the elements of all tasks are merged into one channel by the downstream,
+        // the task number is used to align the barriers
+        element.set_task_number(self.task_number, self.num_tasks);
+
         let sender = self.senders.get(element.get_partition() as usize).unwrap();
         sender.try_send_loop(element, Duration::from_secs(1));
 
@@ -4,9 +4,11 @@ use crate::api::function::KeySelectorFunction;
 use crate::api::operator::{StreamOperator, StreamOperatorWrap};
 use crate::graph::{build_logic_plan, JobGraph, OperatorChain};
 use crate::runtime::context::Context;
+use crate::runtime::worker::checkpoint::TaskCheckpoint;
 use crate::runtime::worker::runnable::{
-    FilterRunnable, KeyByRunnable, MapRunnable, ReduceRunnable, Runnable, RunnableContext,
-    SinkRunnable, SourceRunnable, WatermarkAssignerRunnable, WindowAssignerRunnable,
+    FilterRunnable, KeyByRunnable, KeyedProcessRunnable, MapRunnable, ProcessWindowRunnable,
+    ReduceRunnable, Runnable, RunnableContext, SinkRunnable, SourceRunnable,
+    WatermarkAssignerRunnable, WindowAssignerRunnable,
 };
 use crate::runtime::{JobDescriptor, TaskDescriptor, TaskManagerStatus};
 use crate::storage::metadata::MetadataLoader;
@@ -108,7 +110,13 @@ where
         );
         debug!("DataStream: {:?}", data_stream);
 
-        let logic_plan = build_logic_plan(data_stream, self.metadata_loader.clone());
+        // the system source of the chain fails the checkpoint if the barriers are not aligned
+        let task_checkpoint = TaskCheckpoint::new();
+        let logic_plan = build_logic_plan(
+            data_stream,
+            self.metadata_loader.clone(),
+            task_checkpoint.clone(),
+        );
         debug!("Logic Plan: {:?}", logic_plan);
 
         let mut operator_invoke_chain = self.build_invoke_chain(logic_plan);
@@ -119,6 +127,7 @@ where
             job_descriptor: self.job_descriptor.clone(),
             task_descriptor: self.task_descriptor.clone(),
             window_timer: self.window_timer.clone(),
+            task_checkpoint,
         };
 
         info!("open Operator Chain");
@@ -141,6 +150,7 @@ where
 
         let chain_nodes = operator_chain.nodes.clone();
 
+        let mut window_options = None;
         let mut invoke_operators = Vec::new();
         for index in 0..chain_nodes.len() {
             let operator_id = chain_nodes[index].node_id;
@@ -173,7 +183,33 @@ where
                 StreamOperatorWrap::StreamReduce(stream_operator) => {
                     let stream_key_by = self
                         .get_dependency_key_by(&mut logic_plan, operator_chain.dependency_chain_id);
-                    let op = ReduceRunnable::new(stream_key_by, stream_operator, None);
+                    let window_options = window_options
+                        .take()
+                        .expect("`WindowOptions` not found, the reduce must follow a window");
+                    let op =
+                        ReduceRunnable::new(stream_key_by, stream_operator, window_options, None);
+                    let op: Box<dyn Runnable> = Box::new(op);
+                    op
+                }
+                StreamOperatorWrap::StreamProcessWindow(stream_operator) => {
+                    let stream_key_by = self
+                        .get_dependency_key_by(&mut logic_plan, operator_chain.dependency_chain_id);
+                    let window_options = window_options
+                        .take()
+                        .expect("`WindowOptions` not found, the process must follow a window");
+                    let op = ProcessWindowRunnable::new(
+                        stream_key_by,
+                        stream_operator,
+                        window_options,
+                        None,
+                    );
+                    let op: Box<dyn Runnable> = Box::new(op);
+                    op
+                }
+                StreamOperatorWrap::StreamKeyedProcess(stream_operator) => {
+                    let stream_key_by = self
+                        .get_dependency_key_by(&mut logic_plan, operator_chain.dependency_chain_id);
+                    let op = KeyedProcessRunnable::new(stream_key_by, stream_operator, None);
                     let op: Box<dyn Runnable> = Box::new(op);
                     op
                 }
@@ -182,7 +218,8 @@ where
                     let op: Box<dyn Runnable> = Box::new(op);
                     op
                 }
-                StreamOperatorWrap::StreamWindowAssigner(stream_operator) => {
+                StreamOperatorWrap::StreamWindowAssigner(stream_operator, options) => {
+                    window_options = Some(options);
                     let op = WindowAssignerRunnable::new(stream_operator, None);
                     let op: Box<dyn Runnable> = Box::new(op);
                     op
@@ -0,0 +1,337 @@
+use crate::api::checkpoint::CheckpointHandle;
+use crate::api::element::{Element, Record, StreamStatus, Watermark};
+use crate::api::function::{KeySelectorFunction, KeyedProcessFunction};
+use crate::api::keyed_process::{KeyedProcessContext, TimerService};
+use crate::api::operator::{StreamOperator, TStreamOperator};
+use crate::metrics::{register_counter, Tag};
+use crate::runtime::worker::checkpoint::{BarrierCounter, TaskCheckpoint};
+use crate::runtime::worker::runnable::{Runnable, RunnableContext};
+use crate::runtime::ChainId;
+use crate::storage::keyed_state::mem_keyed_state::MemoryKeyedState;
+use crate::storage::keyed_state::snapshot::{check_version, SnapshotStorage};
+use crate::utils;
+use crate::utils::timer::TimerChannel;
+use bytes::{BufMut, BytesMut};
+use std::borrow::BorrowMut;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// process the records of each key by the `KeyedProcessFunction` with the keyed states,
+/// and fire the timers of the keys by the aligned watermark or the processing time.
+#[derive(Debug)]
+pub(crate) struct KeyedProcessRunnable {
+    chain_id: ChainId,
+    task_number: u16,
+    num_tasks: u16,
+    dependency_parallelism: u32,
+
+    stream_key_by: Option<StreamOperator<dyn KeySelectorFunction>>,
+    stream_keyed_process: StreamOperator<dyn KeyedProcessFunction>,
+    next_runnable: Option<Box<dyn Runnable>>,
+
+    keyed_state: MemoryKeyedState,
+    timer_service: TimerService,
+    processing_time_timer: Option<TimerChannel>,
+    snapshot_storage: Option<SnapshotStorage>,
+    task_checkpoint: Option<TaskCheckpoint>,
+
+    /// the latest watermark timestamp of each upstream task
+    upstream_watermarks: HashMap<u16, u64>,
+    current_watermark: u64,
+
+    barrier_counter: BarrierCounter,
+
+    counter: Arc<AtomicU64>,
+}
+
+impl KeyedProcessRunnable {
+    pub fn new(
+        stream_key_by: Option<StreamOperator<dyn KeySelectorFunction>>,
+        stream_keyed_process: StreamOperator<dyn KeyedProcessFunction>,
+        next_runnable: Option<Box<dyn Runnable>>,
+    ) -> Self {
+        KeyedProcessRunnable {
+            chain_id: 0,
+            task_number: 0,
+            num_tasks: 0,
+            dependency_parallelism: 0,
+            stream_key_by,
+            stream_keyed_process,
+            next_runnable,
+            keyed_state: MemoryKeyedState::new(),
+            timer_service: TimerService::new(),
+            processing_time_timer: None,
+            snapshot_storage: None,
+            task_checkpoint: None,
+            upstream_watermarks: HashMap::new(),
+            current_watermark: 0,
+            barrier_counter: BarrierCounter::new(),
+            counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Runnable for KeyedProcessRunnable {
+    fn open(&mut self, context: &RunnableContext) {
+        self.next_runnable.as_mut().unwrap().open(context);
+
+        let fun_context = context.to_fun_context();
+        self.stream_keyed_process.operator_fn.open(&fun_context);
+        if let Some(stream_key_by) = self.stream_key_by.as_mut() {
+            stream_key_by.operator_fn.open(&fun_context);
+        }
+
+        self.chain_id = context.task_descriptor.chain_id;
+        self.task_number = context.task_descriptor.task_number;
+        self.num_tasks = context.task_descriptor.num_tasks;
+        self.dependency_parallelism = context.task_descriptor.dependency_parallelism;
+
+        self.snapshot_storage = Some(SnapshotStorage::new(
+            &context.job_descriptor.job_manager.job_properties,
+            context.job_descriptor.job_manager.job_id.as_str(),
+            self.chain_id,
+            self.task_number,
+        ));
+        self.task_checkpoint = Some(context.task_checkpoint.clone());
+        if context.task_descriptor.checkpoint_id > 0 {
+            let operator_id = self.stream_keyed_process.get_operator_id();
+            if let Some(handle) = context.get_checkpoint_handle(operator_id) {
+                info!(
+                    "restore keyed state from checkpoint({}), handle={}",
+                    context.task_descriptor.checkpoint_id, handle.handle
+                );
+                self.restore(&handle);
+            }
+        }
+
+        let processing_time_timer = context
+            .window_timer
+            .register("Keyed Process Timer", Duration::from_secs(1))
+            .expect("register keyed process timer error");
+        self.processing_time_timer = Some(processing_time_timer);
+
+        info!(
+            "KeyedProcessRunnable Opened. task_number={}, num_tasks={}",
+            self.task_number, self.num_tasks
+        );
+
+        let tags = vec![
+            Tag(
+                "chain_id".to_string(),
+                context.task_descriptor.chain_id.to_string(),
+            ),
+            Tag(
+                "partition_num".to_string(),
+                context.task_descriptor.task_number.to_string(),
+            ),
+        ];
+        let metric_name = format!(
+            "KeyedProcess_{}",
+            self.stream_keyed_process.operator_fn.as_ref().get_name()
+        );
+        register_counter(metric_name.as_str(), tags, self.counter.clone());
+    }
+
+    fn run(&mut self, element: Element) {
+        self.check_processing_time_timers();
+
+        match element {
+            Element::Record(mut record) => {
+                let key = match &self.stream_key_by {
+                    Some(stream_key_by) => stream_key_by.operator_fn.get_key(record.borrow_mut()),
+                    None => Record::with_capacity(0),
+                };
+
+                let timestamp = record.timestamp;
+                let mut ctx = KeyedProcessContext::new(
+                    &key,
+                    timestamp,
+                    self.current_watermark,
+                    &mut self.keyed_state,
+                    &mut self.timer_service,
+                );
+                let records = self
+                    .stream_keyed_process
+                    .operator_fn
+                    .process_element(&mut record, &mut ctx);
+
+                self.counter.fetch_add(1, Ordering::Relaxed);
+                self.emit_records(records, timestamp);
+            }
+            Element::Watermark(watermark) => {
+                let upstream_timestamp = self
+                    .upstream_watermarks
+                    .entry(watermark.task_number)
+                    .or_insert(0);
+                if watermark.timestamp > *upstream_timestamp {
+                    *upstream_timestamp = watermark.timestamp;
+                }
+
+                // the watermark is aligned after all upstream tasks have reported
+                if self.upstream_watermarks.len() < self.dependency_parallelism as usize {
+                    return;
+                }
+
+                let align_timestamp = *self.upstream_watermarks.values().min().unwrap();
+                if align_timestamp <= self.current_watermark {
+                    return;
+                }
+
+                self.current_watermark = align_timestamp;
+                self.fire_event_time_timers();
+
+                let stream_status = StreamStatus::new(watermark.status_timestamp, false);
+                let watermark = Watermark::new(
+                    self.task_number,
+                    self.num_tasks,
+                    align_timestamp,
+                    &stream_status,
+                );
+                self.next_runnable
+                    .as_mut()
+                    .unwrap()
+                    .run(Element::from(watermark));
+            }
+            Element::Barrier(barrier) => {
+                let num_barriers = self.dependency_parallelism as usize;
+                if self
+                    .barrier_counter
+                    .reach(barrier.checkpoint_id, num_barriers)
+                {
+                    self.checkpoint(barrier.checkpoint_id);
+
+                    self.next_runnable
+                        .as_mut()
+                        .unwrap()
+                        .run(Element::from(barrier));
+                }
+            }
+            Element::StreamStatus(stream_status) => {
+                self.next_runnable
+                    .as_mut()
+                    .unwrap()
+                    .run(Element::from(stream_status));
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(stream_key_by) = self.stream_key_by.as_mut() {
+            stream_key_by.operator_fn.close();
+        }
+        self.stream_keyed_process.operator_fn.close();
+        self.next_runnable.as_mut().unwrap().close();
+    }
+
+    fn set_next_runnable(&mut self, next_runnable: Option<Box<dyn Runnable>>) {
+        self.next_runnable = next_runnable;
+    }
+
+    /// snapshot the keyed states and the timers after the barriers of all upstream tasks
+    /// have reached
+    fn checkpoint(&mut self, checkpoint_id: u64) {
+        let mut bytes = BytesMut::with_capacity(4096);
+        bytes.put_u8(SNAPSHOT_VERSION);
+        self.keyed_state.serialize(&mut bytes);
+        self.timer_service.serialize(&mut bytes);
+
+        let snapshot_storage = self.snapshot_storage.as_ref().unwrap();
+        match snapshot_storage.write(checkpoint_id, bytes.as_ref()) {
+            Some(handle) => {
+                let operator_id = self.stream_keyed_process.get_operator_id();
+                self.task_checkpoint
+                    .as_ref()
+                    .unwrap()
+                    .add(operator_id, checkpoint_id, handle);
+            }
+            None => {
+                // the offsets of the source must not move on without the keyed state
+                error!(
+                    "the keyed state of checkpoint({}) is not snapshot, the checkpoint is failed",
+                    checkpoint_id
+                );
+                self.task_checkpoint.as_ref().unwrap().fail(checkpoint_id);
+            }
+        }
+    }
+}
+
+impl KeyedProcessRunnable {
+    /// restore the keyed states and the timers, the task starts with the empty state
+    /// if the snapshot can't be restored
+    fn restore(&mut self, handle: &CheckpointHandle) {
+        let snapshot_storage = self.snapshot_storage.as_ref().unwrap();
+        let rt = snapshot_storage
+            .read(handle)
+            .and_then(|mut bytes| check_version(&mut bytes, SNAPSHOT_VERSION).map(|_| bytes));
+        match rt {
+            Ok(mut bytes) => {
+                self.keyed_state = MemoryKeyedState::deserialize(&mut bytes);
+                self.timer_service = TimerService::deserialize(&mut bytes);
+            }
+            Err(e) => error!(
+                "restore keyed state from {} error, start with the empty state. {}",
+                handle.handle, e
+            ),
+        }
+    }
+
+    fn fire_event_time_timers(&mut self) {
+        let timers = self
+            .timer_service
+            .poll_event_time_timers(self.current_watermark);
+        self.fire_timers(timers);
+    }
+
+    fn check_processing_time_timers(&mut self) {
+        let triggered = self
+            .processing_time_timer
+            .as_ref()
+            .map(|timer| timer.try_recv().is_ok())
+            .unwrap_or(false);
+        if !triggered {
+            return;
+        }
+
+        let current_timestamp = utils::date_time::current_timestamp_millis();
+        let timers = self
+            .timer_service
+            .poll_processing_time_timers(current_timestamp);
+        self.fire_timers(timers);
+    }
+
+    /// call the `on_timer` of the timers in the timestamp order,
+    /// the timers registered during the firing are fired at the next check.
+    fn fire_timers(&mut self, timers: Vec<(u64, Record)>) {
+        for (timestamp, key) in timers {
+            let mut ctx = KeyedProcessContext::new(
+                &key,
+                timestamp,
+                self.current_watermark,
+                &mut self.keyed_state,
+                &mut self.timer_service,
+            );
+            let records = self
+                .stream_keyed_process
+                .operator_fn
+                .on_timer(timestamp, &mut ctx);
+
+            self.emit_records(records, timestamp);
+        }
+    }
+
+    fn emit_records(&mut self, records: Vec<Record>, timestamp: u64) {
+        for mut record in records {
+            record.timestamp = timestamp;
+            self.next_runnable
+                .as_mut()
+                .unwrap()
+                .run(Element::Record(record));
+        }
+    }
+}
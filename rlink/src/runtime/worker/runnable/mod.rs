@@ -3,21 +3,27 @@ use std::fmt::Debug;
 
 pub mod filter_runnable;
 pub mod key_by_runnable;
+pub mod keyed_process_runnable;
 pub mod map_runnable;
+pub mod process_window_runnable;
 pub mod reduce_runnable;
 pub mod sink_runnable;
 pub mod source_runnable;
 pub mod watermark_assigner_runnable;
 pub mod window_assigner_runnable;
+pub mod window_operator;
 
+use crate::runtime::worker::checkpoint::{OperatorHandle, TaskCheckpoint};
 use crate::runtime::worker::FunctionContext;
 use crate::runtime::{JobDescriptor, TaskDescriptor};
 use crate::utils::timer::WindowTimer;
 
-use crate::api::checkpoint::FunctionSnapshotContext;
+use crate::api::checkpoint::{CheckpointHandle, FunctionSnapshotContext};
 pub(crate) use filter_runnable::FilterRunnable;
 pub(crate) use key_by_runnable::KeyByRunnable;
+pub(crate) use keyed_process_runnable::KeyedProcessRunnable;
 pub(crate) use map_runnable::MapRunnable;
+pub(crate) use process_window_runnable::ProcessWindowRunnable;
 pub(crate) use reduce_runnable::ReduceRunnable;
 pub(crate) use sink_runnable::SinkRunnable;
 pub(crate) use source_runnable::SourceRunnable;
@@ -32,12 +38,15 @@ pub(crate) struct RunnableContext {
     pub(crate) job_descriptor: JobDescriptor,
     pub(crate) task_descriptor: TaskDescriptor,
     pub(crate) window_timer: WindowTimer,
+    /// the handles of the stateful operators in the task, see `TaskCheckpoint`
+    pub(crate) task_checkpoint: TaskCheckpoint,
 }
 
 impl RunnableContext {
     pub(crate) fn to_fun_context(&self) -> FunctionContext {
         FunctionContext {
             job_id: self.job_descriptor.job_manager.job_id.clone(),
+            job_name: self.job_descriptor.job_manager.job_name.clone(),
             job_properties: self.job_descriptor.job_manager.job_properties.clone(),
             task_id: self.task_descriptor.task_id.clone(),
             task_number: self.task_descriptor.task_number,
@@ -49,6 +58,14 @@ impl RunnableContext {
         }
     }
 
+    /// the handle of the operator in the checkpoint which the task is recovered from
+    pub(crate) fn get_checkpoint_handle(&self, operator_id: u32) -> Option<CheckpointHandle> {
+        self.task_descriptor
+            .checkpoint_handle
+            .as_ref()
+            .and_then(|handle| OperatorHandle::decode(handle, operator_id))
+    }
+
     pub(crate) fn get_checkpoint_context(&self, checkpoint_id: u64) -> FunctionSnapshotContext {
         FunctionSnapshotContext::new(
             self.task_descriptor.chain_id,
@@ -0,0 +1,189 @@
+use crate::api::backend::KeyedStateBackend;
+use crate::api::element::{Barrier, Element, Record};
+use crate::api::evictor::Evictor;
+use crate::api::function::{KeySelectorFunction, ProcessWindowFunction};
+use crate::api::operator::{StreamOperator, TStreamOperator};
+use crate::api::properties::SystemProperties;
+use crate::api::window::{WindowOptions, WindowWrap};
+use crate::runtime::worker::runnable::window_operator::{WindowEvaluator, WindowOperator};
+use crate::runtime::worker::runnable::{Runnable, RunnableContext};
+use crate::storage::keyed_state::{ListWindowState, ListWindowStateWrap};
+
+/// buffer all the records of each window, and evaluate the window by the
+/// `ProcessWindowFunction` when it is fired
+#[derive(Debug)]
+pub(crate) struct ProcessWindowRunnable {
+    stream_process: StreamOperator<dyn ProcessWindowFunction>,
+    next_runnable: Option<Box<dyn Runnable>>,
+
+    window_operator: WindowOperator,
+    evictors: Vec<Box<dyn Evictor>>,
+
+    state: Option<ListWindowStateWrap>,
+}
+
+impl ProcessWindowRunnable {
+    pub fn new(
+        stream_key_by: Option<StreamOperator<dyn KeySelectorFunction>>,
+        stream_process: StreamOperator<dyn ProcessWindowFunction>,
+        mut window_options: WindowOptions,
+        next_runnable: Option<Box<dyn Runnable>>,
+    ) -> Self {
+        let operator_id = stream_process.get_operator_id();
+        let evictors = std::mem::take(&mut window_options.evictors);
+        ProcessWindowRunnable {
+            stream_process,
+            next_runnable,
+            window_operator: WindowOperator::new(operator_id, stream_key_by, window_options),
+            evictors,
+            state: None,
+        }
+    }
+}
+
+impl Runnable for ProcessWindowRunnable {
+    fn open(&mut self, context: &RunnableContext) {
+        self.next_runnable.as_mut().unwrap().open(context);
+
+        let fun_context = context.to_fun_context();
+        self.stream_process.operator_fn.open(&fun_context);
+
+        let fn_name = self.stream_process.operator_fn.as_ref().get_name();
+        self.window_operator.open(context, "ProcessWindow", fn_name);
+
+        info!(
+            "ProcessWindowRunnable Opened. task_number={}, num_tasks={}",
+            context.task_descriptor.task_number, context.task_descriptor.num_tasks
+        );
+
+        let state_mode = context
+            .job_descriptor
+            .job_manager
+            .job_properties
+            .get_keyed_state_backend()
+            .unwrap_or(KeyedStateBackend::Memory);
+
+        let new_state = || {
+            ListWindowStateWrap::new(
+                context.task_descriptor.chain_id,
+                context.task_descriptor.task_number,
+                state_mode.clone(),
+            )
+        };
+        let mut state = new_state();
+        if !self
+            .window_operator
+            .restore(context, |bytes| state.restore(bytes))
+        {
+            state = new_state();
+        }
+        self.state = Some(state);
+    }
+
+    fn run(&mut self, element: Element) {
+        let mut windows = ProcessWindows {
+            state: self.state.as_mut().unwrap(),
+            process_func: self.stream_process.operator_fn.as_ref(),
+            evictors: self.evictors.as_slice(),
+        };
+        let next_runnable = self.next_runnable.as_mut().unwrap().as_mut();
+
+        if let Some(checkpoint_id) = self
+            .window_operator
+            .run(element, &mut windows, next_runnable)
+        {
+            self.checkpoint(checkpoint_id);
+
+            // the barrier is forwarded after the snapshot, so the operators in the downstream
+            // of the window, such as the transactional sinks, take part in the checkpoint
+            let barrier = Element::new_barrier(checkpoint_id);
+            self.next_runnable.as_mut().unwrap().run(barrier);
+        }
+    }
+
+    fn close(&mut self) {
+        self.window_operator.close();
+        self.stream_process.operator_fn.close();
+        self.next_runnable.as_mut().unwrap().close();
+    }
+
+    fn set_next_runnable(&mut self, next_runnable: Option<Box<dyn Runnable>>) {
+        self.next_runnable = next_runnable;
+    }
+
+    /// snapshot the buffered records after the barriers of all upstream tasks have reached
+    fn checkpoint(&mut self, checkpoint_id: u64) {
+        let state = self.state.as_mut().unwrap();
+        self.window_operator.checkpoint(checkpoint_id, |bytes| {
+            state.snapshot(Barrier::new(checkpoint_id), bytes)
+        });
+    }
+}
+
+/// the records are buffered in the windows, and evaluated by the `ProcessWindowFunction`
+/// after evicted by the `Evictor`s when the windows are fired
+struct ProcessWindows<'a> {
+    state: &'a mut ListWindowStateWrap,
+    process_func: &'a dyn ProcessWindowFunction,
+    evictors: &'a [Box<dyn Evictor>],
+}
+
+impl<'a> ProcessWindows<'a> {
+    /// evict the buffered records and evaluate the window of the key
+    fn process(
+        process_func: &dyn ProcessWindowFunction,
+        evictors: &[Box<dyn Evictor>],
+        key: &Record,
+        window: &WindowWrap,
+        records: &mut Vec<Record>,
+    ) -> Vec<Record> {
+        for evictor in evictors {
+            evictor.evict(records, window);
+        }
+
+        match window {
+            WindowWrap::TimeWindow(time_window) => {
+                process_func.process(key, time_window, &mut records.iter())
+            }
+        }
+    }
+}
+
+impl<'a> WindowEvaluator for ProcessWindows<'a> {
+    fn add(&mut self, key: Record, record: Record) {
+        self.state.add(key, record);
+    }
+
+    fn windows(&self) -> Vec<WindowWrap> {
+        self.state.windows()
+    }
+
+    fn keys(&self, window: &WindowWrap) -> Vec<Record> {
+        self.state.keys(window)
+    }
+
+    fn fire_window(&mut self, window: &WindowWrap) -> bool {
+        let (process_func, evictors) = (self.process_func, self.evictors);
+        self.state.fire_window(window, |key, window, records| {
+            ProcessWindows::process(process_func, evictors, key, window, records)
+        })
+    }
+
+    fn drop_window(&mut self, window: &WindowWrap) -> bool {
+        let (process_func, evictors) = (self.process_func, self.evictors);
+        self.state.drop_window(window, |key, window, records| {
+            ProcessWindows::process(process_func, evictors, key, window, records)
+        })
+    }
+
+    fn fire_keys(&mut self, window: &WindowWrap, keys: &[Record]) -> bool {
+        let (process_func, evictors) = (self.process_func, self.evictors);
+        self.state.fire_keys(window, keys, |key, window, records| {
+            ProcessWindows::process(process_func, evictors, key, window, records)
+        })
+    }
+
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]) {
+        self.state.purge_keys(window, keys)
+    }
+}
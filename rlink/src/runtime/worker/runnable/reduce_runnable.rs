@@ -1,87 +1,54 @@
 use crate::api::backend::KeyedStateBackend;
 use crate::api::element::{Barrier, Element, Record, Watermark};
 use crate::api::function::{KeySelectorFunction, ReduceFunction};
-use crate::api::operator::StreamOperator;
+use crate::api::operator::{StreamOperator, TStreamOperator};
 use crate::api::properties::SystemProperties;
-use crate::api::window::{Window, WindowWrap};
-use crate::metrics::{register_counter, Tag};
+use crate::api::window::{Window, WindowOptions, WindowWrap};
+use crate::runtime::worker::runnable::window_operator::{WindowEvaluator, WindowOperator};
 use crate::runtime::worker::runnable::{Runnable, RunnableContext};
 use crate::storage::keyed_state::{WindowState, WindowStateWrap};
-use crate::utils::date_time::timestamp_str;
-use std::borrow::BorrowMut;
 use std::collections::HashMap;
-use std::sync::atomic::AtomicU64;
-use std::sync::atomic::Ordering;
-use std::sync::Arc;
 
 #[derive(Debug)]
 pub(crate) struct ReduceRunnable {
-    task_id: Option<String>,
-    task_number: u16,
-    dependency_parallelism: u32,
-
-    stream_key_by: Option<StreamOperator<dyn KeySelectorFunction>>,
     stream_reduce: StreamOperator<dyn ReduceFunction>,
     next_runnable: Option<Box<dyn Runnable>>,
 
-    state: Option<WindowStateWrap>, // HashMap<Vec<u8>, Record>, // HashMap<TimeWindow, HashMap<Record, Record>>,
-
-    current_checkpoint_id: u64,
-    reached_barriers: Vec<Barrier>,
-
-    max_watermark_status_timestamp: u64,
-    watermark_align: Option<WatermarkAlign>,
-    // the Record can be operate after this window(include this window's time)
-    limited_watermark_window: Option<WindowWrap>,
+    window_operator: WindowOperator,
 
-    counter: Arc<AtomicU64>,
-    expire_counter: Arc<AtomicU64>,
+    state: Option<WindowStateWrap>, // HashMap<Vec<u8>, Record>, // HashMap<TimeWindow, HashMap<Record, Record>>,
 }
 
 impl ReduceRunnable {
     pub fn new(
         stream_key_by: Option<StreamOperator<dyn KeySelectorFunction>>,
         stream_reduce: StreamOperator<dyn ReduceFunction>,
+        window_options: WindowOptions,
         next_runnable: Option<Box<dyn Runnable>>,
     ) -> Self {
+        let operator_id = stream_reduce.get_operator_id();
         ReduceRunnable {
-            task_id: None,
-            task_number: 0,
-            dependency_parallelism: 0,
-            stream_key_by,
             stream_reduce,
             next_runnable,
+            window_operator: WindowOperator::new(operator_id, stream_key_by, window_options),
             state: None,
-            current_checkpoint_id: 0,
-            reached_barriers: Vec::new(),
-            max_watermark_status_timestamp: 0,
-            watermark_align: None,
-            limited_watermark_window: None,
-            counter: Arc::new(AtomicU64::new(0)),
-            expire_counter: Arc::new(AtomicU64::new(0)),
         }
     }
 }
 
 impl Runnable for ReduceRunnable {
     fn open(&mut self, context: &RunnableContext) {
-        self.task_id = Some(context.task_descriptor.task_id.clone());
         self.next_runnable.as_mut().unwrap().open(context);
 
         let fun_context = context.to_fun_context();
         self.stream_reduce.operator_fn.open(&fun_context);
-        self.stream_key_by
-            .as_mut()
-            .map(|s| s.operator_fn.open(&fun_context));
 
-        self.task_number = context.task_descriptor.task_number;
-        self.dependency_parallelism = context.task_descriptor.dependency_parallelism;
-
-        self.watermark_align = Some(WatermarkAlign::new());
+        let fn_name = self.stream_reduce.operator_fn.as_ref().get_name();
+        self.window_operator.open(context, "Reduce", fn_name);
 
         info!(
             "ReduceRunnable Opened. task_number={}, num_tasks={}",
-            self.task_number, context.task_descriptor.num_tasks
+            context.task_descriptor.task_number, context.task_descriptor.num_tasks
         );
 
         let state_mode = context
@@ -91,175 +58,116 @@ impl Runnable for ReduceRunnable {
             .get_keyed_state_backend()
             .unwrap_or(KeyedStateBackend::Memory);
 
-        self.state = Some(WindowStateWrap::new(
-            context.job_descriptor.job_manager.job_id.clone(),
-            context.task_descriptor.chain_id,
-            context.task_descriptor.task_number,
-            state_mode,
-        ));
-
-        let tags = vec![
-            Tag(
-                "chain_id".to_string(),
-                context.task_descriptor.chain_id.to_string(),
-            ),
-            Tag(
-                "partition_num".to_string(),
-                context.task_descriptor.task_number.to_string(),
-            ),
-        ];
-        let fn_name = self.stream_reduce.operator_fn.as_ref().get_name();
-
-        let metric_name = format!("Reduce_{}", fn_name);
-        register_counter(metric_name.as_str(), tags.clone(), self.counter.clone());
-
-        let metric_name = format!("Reduce_Expire_{}", fn_name);
-        register_counter(metric_name.as_str(), tags, self.expire_counter.clone());
+        let new_state = || {
+            WindowStateWrap::new(
+                context.job_descriptor.job_manager.job_id.clone(),
+                context.task_descriptor.chain_id,
+                context.task_descriptor.task_number,
+                state_mode.clone(),
+            )
+        };
+        let mut state = new_state();
+        if !self
+            .window_operator
+            .restore(context, |bytes| state.restore(bytes))
+        {
+            state = new_state();
+        }
+        self.state = Some(state);
     }
 
     fn run(&mut self, element: Element) {
-        let state = self.state.as_mut().unwrap();
-        match element {
-            Element::Record(mut record) => {
-                // Record expiration check
-                let acceptable = self
-                    .limited_watermark_window
-                    .as_ref()
-                    .map(|limit_window| {
-                        record
-                            .get_max_location_windows()
-                            .map(|window| window.min_timestamp() >= limit_window.min_timestamp())
-                            .unwrap_or(true)
-                    })
-                    .unwrap_or(true);
-                if !acceptable {
-                    let n = self.expire_counter.fetch_add(1, Ordering::Relaxed);
-                    if n & 1048575 == 1 {
-                        error!(
-                            "expire data. record window={:?}, limit window={:?}",
-                            record.get_min_location_windows().unwrap(),
-                            self.limited_watermark_window.as_ref().unwrap()
-                        );
-                    }
-                    return;
-                }
-
-                let key = match &self.stream_key_by {
-                    Some(stream_key_by) => stream_key_by.operator_fn.get_key(record.borrow_mut()),
-                    None => Record::with_capacity(0),
-                };
+        let mut windows = ReduceWindows {
+            state: self.state.as_mut().unwrap(),
+            reduce_func: self.stream_reduce.operator_fn.as_ref(),
+            merging: self.window_operator.merging(),
+        };
+        let next_runnable = self.next_runnable.as_mut().unwrap().as_mut();
 
-                let reduce_func = &self.stream_reduce.operator_fn;
-                state.merge(key, record, |val1, val2| reduce_func.reduce(val1, val2));
+        if let Some(checkpoint_id) = self
+            .window_operator
+            .run(element, &mut windows, next_runnable)
+        {
+            self.checkpoint(checkpoint_id);
 
-                self.counter.fetch_add(1, Ordering::Relaxed);
-            }
-            Element::Watermark(watermark) => {
-                let watermark_status_timestamp = watermark.status_timestamp;
+            // the barrier is forwarded after the snapshot, so the operators in the downstream
+            // of the window, such as the transactional sinks, take part in the checkpoint
+            let barrier = Element::new_barrier(checkpoint_id);
+            self.next_runnable.as_mut().unwrap().run(barrier);
+        }
+    }
 
-                let watermark_align = self.watermark_align.as_mut().unwrap();
-                watermark_align.insert(watermark);
+    fn close(&mut self) {
+        self.window_operator.close();
+        self.stream_reduce.operator_fn.close();
+        self.next_runnable.as_mut().unwrap().close();
+    }
 
-                if watermark_status_timestamp < self.max_watermark_status_timestamp {
-                    return;
-                }
+    fn set_next_runnable(&mut self, next_runnable: Option<Box<dyn Runnable>>) {
+        self.next_runnable = next_runnable;
+    }
 
-                self.max_watermark_status_timestamp = watermark_status_timestamp;
-                let align_watermarks = watermark_align.align();
-
-                if align_watermarks.len() > 0 {
-                    let mut align_watermark = align_watermarks[align_watermarks.len() - 1].clone();
-                    let minimum_watermark_window =
-                        align_watermark.get_min_location_windows().unwrap();
-                    self.limited_watermark_window = Some(minimum_watermark_window.clone());
-
-                    // info!("minimum_watermark_window: {:?}", minimum_watermark_window);
-
-                    let mut drop_windows = Vec::new();
-
-                    // info!("begin drop window");
-                    for window in state.windows() {
-                        // info!(
-                        //     "check window [{}/{}]",
-                        //     timestamp_str(window.min_timestamp()),
-                        //     timestamp_str(window.max_timestamp()),
-                        // );
-                        if window.max_timestamp() <= minimum_watermark_window.min_timestamp() {
-                            drop_windows.push(window.clone());
-                            state.drop_window(&window);
-
-                            // info!(
-                            //     "drop window [{}/{}]",
-                            //     timestamp_str(window.min_timestamp()),
-                            //     timestamp_str(window.max_timestamp()),
-                            // );
-                        }
-                    }
+    /// snapshot the window state after the barriers of all upstream tasks have reached
+    fn checkpoint(&mut self, checkpoint_id: u64) {
+        let state = self.state.as_mut().unwrap();
+        self.window_operator.checkpoint(checkpoint_id, |bytes| {
+            state.snapshot(Barrier::new(checkpoint_id), bytes)
+        });
+    }
+}
 
-                    drop_windows.sort_by_key(|w| w.max_timestamp());
+/// the windows are reduced incrementally, the values are materialized by
+/// `ReduceFunction::get_result` when the windows are fired
+struct ReduceWindows<'a> {
+    state: &'a mut WindowStateWrap,
+    reduce_func: &'a dyn ReduceFunction,
+    merging: bool,
+}
 
-                    if drop_windows.len() > 0 {
-                        debug!(
-                            "check window for drop, trigger watermark={}, drop window size={}",
-                            timestamp_str(minimum_watermark_window.max_timestamp()),
-                            drop_windows.len()
-                        );
+impl<'a> WindowEvaluator for ReduceWindows<'a> {
+    fn add(&mut self, key: Record, record: Record) {
+        let reduce_func = self.reduce_func;
+        if self.merging {
+            self.state.merge_windows(
+                key,
+                record,
+                |val1, val2| reduce_func.reduce(val1, val2),
+                |val1, val2| reduce_func.merge(val1, val2),
+            );
+        } else {
+            self.state
+                .merge(key, record, |val1, val2| reduce_func.reduce(val1, val2));
+        }
+    }
 
-                        align_watermark.drop_windows = Some(drop_windows);
-                        self.next_runnable
-                            .as_mut()
-                            .unwrap()
-                            .run(Element::from(align_watermark));
-                    }
-                }
-            }
-            Element::Barrier(barrier) => {
-                if self.current_checkpoint_id == 0 {
-                    self.current_checkpoint_id = barrier.checkpoint_id;
-                }
+    fn windows(&self) -> Vec<WindowWrap> {
+        self.state.windows()
+    }
 
-                if self.current_checkpoint_id == barrier.checkpoint_id {
-                    self.reached_barriers.push(barrier);
-                    if self.reached_barriers.len() == self.dependency_parallelism as usize {
-                        self.checkpoint(self.current_checkpoint_id);
-                    }
+    fn keys(&self, window: &WindowWrap) -> Vec<Record> {
+        self.state.keys(window)
+    }
 
-                    self.current_checkpoint_id = 0;
-                    self.reached_barriers.clear();
-                } else {
-                    if self.current_checkpoint_id > barrier.checkpoint_id {
-                        error!(
-                            "Unusual state of Checkpoint. Barrier's `checkpoint_id` is less than `current_checkpoint_id`"
-                        )
-                    } else {
-                        error!(
-                            "Found a new checkpoint({}) if the current checkpoint({}) is not completed",
-                            barrier.checkpoint_id,
-                            self.current_checkpoint_id,
-                        );
-
-                        self.current_checkpoint_id = barrier.checkpoint_id;
-                        self.reached_barriers.clear();
-                        self.reached_barriers.push(barrier.clone());
-                    }
-                }
-            }
-            _ => {}
-        }
+    fn fire_window(&mut self, window: &WindowWrap) -> bool {
+        let reduce_func = self.reduce_func;
+        self.state
+            .fire_window(window, |value| reduce_func.get_result(value))
     }
 
-    fn close(&mut self) {
-        self.stream_key_by.as_mut().map(|s| s.operator_fn.close());
-        self.stream_reduce.operator_fn.close();
-        self.next_runnable.as_mut().unwrap().close();
+    fn drop_window(&mut self, window: &WindowWrap) -> bool {
+        let reduce_func = self.reduce_func;
+        self.state
+            .drop_window(window, |value| reduce_func.get_result(value))
     }
 
-    fn set_next_runnable(&mut self, next_runnable: Option<Box<dyn Runnable>>) {
-        self.next_runnable = next_runnable;
+    fn fire_keys(&mut self, window: &WindowWrap, keys: &[Record]) -> bool {
+        let reduce_func = self.reduce_func;
+        self.state
+            .fire_keys(window, keys, |value| reduce_func.get_result(value))
     }
 
-    fn checkpoint(&mut self, _checkpoint_id: u64) {
-        // foreach self.reached_barriers
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]) {
+        self.state.purge_keys(window, keys)
     }
 }
 
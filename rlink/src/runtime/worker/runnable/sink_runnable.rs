@@ -1,8 +1,13 @@
+use crate::api::checkpoint::SAVEPOINT_FLAG;
 use crate::api::element::Element;
 use crate::api::operator::{FunctionCreator, StreamOperator, TStreamOperator};
 use crate::api::output::OutputFormat;
 use crate::metrics::{register_counter, Tag};
+use crate::runtime::worker::checkpoint::{
+    get_completed_checkpoint_id, BarrierCounter, TaskCheckpoint,
+};
 use crate::runtime::worker::runnable::{Runnable, RunnableContext};
+use crate::storage::transaction::{Transaction, TransactionSnapshot};
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -13,6 +18,15 @@ pub(crate) struct SinkRunnable {
     num_tasks: u16,
 
     stream_sink: StreamOperator<dyn OutputFormat>,
+    /// the `checkpoint_id` of the latest completed checkpoint notified to the sink
+    completed_checkpoint_id: u64,
+
+    task_checkpoint: Option<TaskCheckpoint>,
+    barrier_counter: BarrierCounter,
+
+    /// the sink takes part in the two-phase commit by the transactions
+    transactional: bool,
+    transactions: TransactionSnapshot,
 
     counter: Arc<AtomicU64>,
 }
@@ -23,6 +37,11 @@ impl SinkRunnable {
             task_number: 0,
             num_tasks: 0,
             stream_sink,
+            completed_checkpoint_id: 0,
+            task_checkpoint: None,
+            barrier_counter: BarrierCounter::new(),
+            transactional: false,
+            transactions: TransactionSnapshot::default(),
             counter: Arc::new(AtomicU64::new(0)),
         }
     }
@@ -41,6 +60,11 @@ impl Runnable for SinkRunnable {
         let fun_context = context.to_fun_context();
         self.stream_sink.operator_fn.open(&fun_context);
 
+        if let FunctionCreator::User = self.stream_sink.get_fn_creator() {
+            self.task_checkpoint = Some(context.task_checkpoint.clone());
+            self.open_transactions(context);
+        }
+
         let tags = vec![
             Tag(
                 "chain_id".to_string(),
@@ -73,7 +97,17 @@ impl Runnable for SinkRunnable {
                             .write_element(Element::from(barrier));
                     }
                     FunctionCreator::User => {
-                        self.checkpoint(barrier.checkpoint_id);
+                        self.check_completed_checkpoint();
+
+                        // the barriers of the upstream tasks are counted by the `num_tasks`
+                        // of the barrier, the barrier in the same task is counted as one
+                        let num_barriers = barrier.num_tasks.max(1) as usize;
+                        if self
+                            .barrier_counter
+                            .reach(barrier.checkpoint_id, num_barriers)
+                        {
+                            self.checkpoint(barrier.checkpoint_id);
+                        }
                     }
                 }
             }
@@ -99,7 +133,9 @@ impl Runnable for SinkRunnable {
                             .write_element(Element::from(stream_status));
                     }
                     FunctionCreator::User => {
-                        // nothing to do
+                        // the `StreamStatus` is the tick of the source, it's emitted even if
+                        // there is no data, so the transactions of an idle sink are committed
+                        self.check_completed_checkpoint();
                     }
                 }
             }
@@ -107,6 +143,13 @@ impl Runnable for SinkRunnable {
     }
 
     fn close(&mut self) {
+        // the pending transactions are kept, they are committed after restore
+        if let Some(transaction) = self.transactions.current.take() {
+            self.stream_sink
+                .operator_fn
+                .abort(transaction.handle.as_str());
+        }
+
         self.stream_sink.operator_fn.close();
     }
 
@@ -114,7 +157,123 @@ impl Runnable for SinkRunnable {
         unimplemented!()
     }
 
-    fn checkpoint(&mut self, _checkpoint_id: u64) {
-        self.stream_sink.operator_fn.prepare_commit();
+    /// flush the records and pre-commit the current transaction and begin a new one
+    /// after the barriers of all upstream tasks have reached. the transactions are kept
+    /// in the handle of the sink, which is reported by the source of the chain
+    fn checkpoint(&mut self, checkpoint_id: u64) {
+        // the current transaction is kept open, its records are committed by a later checkpoint
+        if let Err(e) = self.stream_sink.operator_fn.flush() {
+            error!(
+                "flush error, checkpoint({}) is failed. {}",
+                checkpoint_id, e
+            );
+            self.task_checkpoint.as_ref().unwrap().fail(checkpoint_id);
+            return;
+        }
+
+        if self.transactional {
+            let output_format = &mut self.stream_sink.operator_fn;
+            if let Some(mut transaction) = self.transactions.current.take() {
+                output_format.prepare_commit(transaction.handle.as_str());
+                transaction.prepare_checkpoint_id = checkpoint_id;
+                self.transactions.pending.push(transaction);
+            }
+
+            self.transactions.current =
+                output_format
+                    .begin_transaction(checkpoint_id)
+                    .map(|handle| Transaction {
+                        begin_checkpoint_id: checkpoint_id,
+                        prepare_checkpoint_id: 0,
+                        handle,
+                    });
+        }
+
+        // the non-transactional sink is acknowledged with the empty snapshot
+        let operator_id = self.stream_sink.get_operator_id();
+        self.task_checkpoint.as_ref().unwrap().add(
+            operator_id,
+            checkpoint_id,
+            self.transactions.to_handle(),
+        );
+    }
+}
+
+impl SinkRunnable {
+    /// recover the transactions of the restored checkpoint and begin the first transaction.
+    /// the transactions pre-committed before the checkpoint are committed,
+    /// and the transaction in progress is aborted
+    fn open_transactions(&mut self, context: &RunnableContext) {
+        let checkpoint_id = context.task_descriptor.checkpoint_id;
+        let operator_id = self.stream_sink.get_operator_id();
+
+        let output_format = &mut self.stream_sink.operator_fn;
+        if checkpoint_id > 0 {
+            let snapshot = context
+                .get_checkpoint_handle(operator_id)
+                .and_then(|handle| match TransactionSnapshot::from_handle(&handle) {
+                    Ok(snapshot) => Some(snapshot),
+                    Err(e) => {
+                        error!(
+                            "illegal transactions of checkpoint({}), handle={}. {}",
+                            checkpoint_id, handle.handle, e
+                        );
+                        None
+                    }
+                });
+            if let Some(snapshot) = snapshot {
+                for transaction in snapshot.pending {
+                    info!("recover and commit transaction {:?}", transaction);
+                    output_format.recover_and_commit(transaction.handle.as_str());
+                }
+                if let Some(transaction) = snapshot.current {
+                    info!("recover and abort transaction {:?}", transaction);
+                    output_format.recover_and_abort(transaction.handle.as_str());
+                }
+            }
+        }
+
+        if let Some(handle) = output_format.begin_transaction(checkpoint_id) {
+            self.transactions.current = Some(Transaction {
+                begin_checkpoint_id: checkpoint_id,
+                prepare_checkpoint_id: 0,
+                handle,
+            });
+            self.transactional = true;
+        }
+    }
+
+    /// notify the sink if a later checkpoint has been completed by the job
+    fn check_completed_checkpoint(&mut self) {
+        let completed_checkpoint_id = get_completed_checkpoint_id();
+        if completed_checkpoint_id > self.completed_checkpoint_id {
+            self.completed_checkpoint_id = completed_checkpoint_id;
+            self.notify_checkpoint_complete(completed_checkpoint_id);
+        }
+    }
+
+    fn notify_checkpoint_complete(&mut self, checkpoint_id: u64) {
+        debug!("notify checkpoint({}) complete", checkpoint_id);
+        if let Some(checkpoint) = self.stream_sink.operator_fn.get_checkpoint() {
+            checkpoint.notify_checkpoint_complete(checkpoint_id);
+        }
+
+        // the savepoint flag is ignored, the checkpoints are ordered by the timestamp
+        let completed_timestamp = checkpoint_id & !SAVEPOINT_FLAG;
+        let (committable, pending) =
+            self.transactions
+                .pending
+                .drain(..)
+                .partition(|transaction: &Transaction| {
+                    transaction.prepare_checkpoint_id & !SAVEPOINT_FLAG <= completed_timestamp
+                });
+        self.transactions.pending = pending;
+
+        for transaction in committable {
+            debug!("commit transaction {:?}", transaction);
+            self.stream_sink
+                .operator_fn
+                .commit(transaction.handle.as_str());
+        }
     }
 }
@@ -5,7 +5,9 @@ use crate::api::operator::{FunctionCreator, StreamOperator, TStreamOperator};
 use crate::api::properties::SystemProperties;
 use crate::api::split::InputSplit;
 use crate::metrics::{register_counter, Tag};
-use crate::runtime::worker::checkpoint::report_checkpoint;
+use crate::runtime::worker::checkpoint::{
+    get_completed_checkpoint_id, get_savepoint_id, report_checkpoint, OperatorHandle,
+};
 use crate::runtime::worker::runnable::{Runnable, RunnableContext};
 use crate::utils::timer::TimerChannel;
 use std::sync::atomic::AtomicU64;
@@ -26,6 +28,10 @@ pub(crate) struct SourceRunnable {
 
     stream_status_timer: Option<TimerChannel>,
     checkpoint_timer: Option<TimerChannel>,
+    /// the `checkpoint_id` of the latest savepoint injected by the source
+    savepoint_id: u64,
+    /// the `checkpoint_id` of the latest completed checkpoint notified to the source
+    completed_checkpoint_id: u64,
 
     counter: Arc<AtomicU64>,
 }
@@ -48,6 +54,8 @@ impl SourceRunnable {
 
             stream_status_timer: None,
             checkpoint_timer: None,
+            savepoint_id: 0,
+            completed_checkpoint_id: 0,
 
             counter: Arc::new(AtomicU64::new(0)),
         }
@@ -67,10 +75,25 @@ impl Runnable for SourceRunnable {
 
         let input_split = context.task_descriptor.input_split.clone();
 
-        let fun_context = context.to_fun_context();
+        // the handle of the task is made up of the handles of the stateful operators in the chain
+        let mut fun_context = context.to_fun_context();
+        fun_context.checkpoint_handle =
+            context.get_checkpoint_handle(self.stream_source.get_operator_id());
         let source_func = self.stream_source.operator_fn.as_mut();
         source_func.open(input_split, &fun_context);
 
+        // the `StreamStatus` of the system source is a tick to downstream operators,
+        // such as the processing time windows that need to be fired even without any data
+        let stream_status_interval = match self.stream_source.get_fn_creator() {
+            FunctionCreator::User => Duration::from_secs(10),
+            FunctionCreator::System => Duration::from_secs(3),
+        };
+        let stream_status_timer = context
+            .window_timer
+            .register("StreamStatus Event Timer", stream_status_interval)
+            .expect("register StreamStatus timer error");
+        self.stream_status_timer = Some(stream_status_timer);
+
         if let FunctionCreator::User = self.stream_source.get_fn_creator() {
             let checkpoint_period = context
                 .job_descriptor
@@ -79,12 +102,6 @@ impl Runnable for SourceRunnable {
                 .get_checkpoint_internal()
                 .unwrap_or(Duration::from_secs(30));
 
-            let stream_status_timer = context
-                .window_timer
-                .register("StreamStatus Event Timer", Duration::from_secs(10))
-                .expect("register StreamStatus timer error");
-            self.stream_status_timer = Some(stream_status_timer);
-
             let checkpoint_timer = context
                 .window_timer
                 .register("Checkpoint Event Timer", checkpoint_period)
@@ -134,12 +151,16 @@ impl Runnable for SourceRunnable {
                                     "Source `next_element` get Watermark({})",
                                     row.as_watermark().timestamp
                                 );
-                            } else if row.is_barrier() {
-                                // if `InputFormat` return the Barrier, fire checkpoint immediately
-                                self.checkpoint(row.as_barrier().checkpoint_id);
                             }
 
-                            self.next_runnable.as_mut().unwrap().run(row);
+                            if row.is_barrier() {
+                                // if `InputFormat` return the Barrier, fire checkpoint immediately
+                                let checkpoint_id = row.as_barrier().checkpoint_id;
+                                self.next_runnable.as_mut().unwrap().run(row);
+                                self.checkpoint(checkpoint_id);
+                            } else {
+                                self.next_runnable.as_mut().unwrap().run(row);
+                            }
                             counter += 1;
                         }
                         None => break,
@@ -150,21 +171,38 @@ impl Runnable for SourceRunnable {
                 }
             };
 
-            if let FunctionCreator::User = fn_creator {
-                if let Ok(window_time) = self.stream_status_timer.as_ref().unwrap().try_recv() {
-                    debug!("Trigger StreamStatus");
-                    let stream_status = Element::new_stream_status(window_time, end);
-                    self.next_runnable.as_mut().unwrap().run(stream_status);
-                };
+            if let Ok(window_time) = self.stream_status_timer.as_ref().unwrap().try_recv() {
+                debug!("Trigger StreamStatus");
+                let stream_status = Element::new_stream_status(window_time, end);
+                self.next_runnable.as_mut().unwrap().run(stream_status);
+            };
 
+            if let FunctionCreator::User = fn_creator {
                 if let Ok(window_time) = self.checkpoint_timer.as_ref().unwrap().try_recv() {
                     debug!("Trigger Checkpoint");
                     let barrier = Element::new_barrier(window_time);
 
+                    self.next_runnable.as_mut().unwrap().run(barrier);
+
                     self.checkpoint(window_time);
+                };
+
+                let savepoint_id = get_savepoint_id();
+                if savepoint_id > self.savepoint_id {
+                    info!("Trigger Savepoint({})", savepoint_id);
+                    self.savepoint_id = savepoint_id;
+                    let barrier = Element::new_barrier(savepoint_id);
 
                     self.next_runnable.as_mut().unwrap().run(barrier);
-                };
+
+                    self.checkpoint(savepoint_id);
+                }
+
+                let completed_checkpoint_id = get_completed_checkpoint_id();
+                if completed_checkpoint_id > self.completed_checkpoint_id {
+                    self.completed_checkpoint_id = completed_checkpoint_id;
+                    self.notify_checkpoint_complete(completed_checkpoint_id);
+                }
             }
 
             if counter == 0 {
@@ -200,28 +238,62 @@ impl Runnable for SourceRunnable {
         self.next_runnable = next_runnable;
     }
 
+    /// the barrier has passed through the whole chain before the checkpoint of the source,
+    /// so the downstream operators of the chain have added their handles to the `TaskCheckpoint`
+    /// and the checkpoint of the task is reported with the handles of all of them
     fn checkpoint(&mut self, checkpoint_id: u64) {
-        let context = {
+        let (snapshot_context, task_checkpoint) = {
             let context = self.context.as_ref().unwrap();
-            context.get_checkpoint_context(checkpoint_id)
+            (
+                context.get_checkpoint_context(checkpoint_id),
+                context.task_checkpoint.clone(),
+            )
         };
 
         let fn_name = self.stream_source.operator_fn.get_name();
         debug!("begin checkpoint : {}", fn_name);
 
-        match self.stream_source.operator_fn.get_checkpoint() {
-            Some(checkpoint) => {
-                let ck_handle = checkpoint.snapshot_state(&context);
-                let ck = Checkpoint {
-                    chain_id: self.chain_id,
-                    task_num: self.task_number,
-                    checkpoint_id,
-                    handle: ck_handle,
-                };
+        let operator_id = self.stream_source.get_operator_id();
+        if let Some(checkpoint) = self.stream_source.operator_fn.get_checkpoint() {
+            let ck_handle = checkpoint.snapshot_state(&snapshot_context);
+            task_checkpoint.add(operator_id, checkpoint_id, ck_handle);
+        }
 
-                report_checkpoint(ck);
+        let handles = match task_checkpoint.take(checkpoint_id) {
+            Some(handles) => handles,
+            None => {
+                warn!(
+                    "checkpoint({}) is failed by the operators of the chain, not reported",
+                    checkpoint_id
+                );
+                return;
+            }
+        };
+        // the user source is always acknowledged, even if the chain is stateless,
+        // the coordinator waits for the chain of it to complete the checkpoint
+        if handles.is_empty() {
+            if let FunctionCreator::System = self.stream_source.get_fn_creator() {
+                return;
             }
-            None => {}
+        }
+
+        let ck = Checkpoint {
+            chain_id: self.chain_id,
+            task_num: self.task_number,
+            checkpoint_id,
+            handle: OperatorHandle::encode(handles.as_slice()),
+        };
+        if let Some(ck) = report_checkpoint(ck) {
+            error!("report checkpoint error, the channel is full. {:?}", ck);
+        }
+    }
+}
+
+impl SourceRunnable {
+    fn notify_checkpoint_complete(&mut self, checkpoint_id: u64) {
+        debug!("notify checkpoint({}) complete", checkpoint_id);
+        if let Some(checkpoint) = self.stream_source.operator_fn.get_checkpoint() {
+            checkpoint.notify_checkpoint_complete(checkpoint_id);
         }
     }
 }
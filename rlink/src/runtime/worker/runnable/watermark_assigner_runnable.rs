@@ -119,6 +119,8 @@ impl Runnable for WatermarkAssignerRunnable {
                     None => {}
                 }
             }
+        } else if element.is_barrier() {
+            self.next_runnable.as_mut().unwrap().run(element);
         }
     }
 
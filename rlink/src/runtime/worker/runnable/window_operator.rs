@@ -0,0 +1,664 @@
+use crate::api::checkpoint::SAVEPOINT_FLAG;
+use crate::api::element::{Barrier, Element, Record, StreamStatus, Watermark, WindowFiring};
+use crate::api::function::KeySelectorFunction;
+use crate::api::operator::StreamOperator;
+use crate::api::trigger::{Trigger, TriggerResult};
+use crate::api::window::{TimeWindow, Window, WindowOptions, WindowWrap};
+use crate::metrics::{register_counter, Tag};
+use crate::runtime::worker::checkpoint::TaskCheckpoint;
+use crate::runtime::worker::runnable::reduce_runnable::WatermarkAlign;
+use crate::runtime::worker::runnable::{Runnable, RunnableContext};
+use crate::runtime::ChainId;
+use crate::storage::keyed_state::snapshot::{
+    check_version, deserialize_window, serialize_window, SnapshotStorage,
+};
+use crate::utils;
+use crate::utils::date_time::timestamp_str;
+use crate::utils::timer::TimerChannel;
+use bytes::{Buf, BufMut, BytesMut};
+use std::borrow::BorrowMut;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// the window state of a windowed operator, the `WindowOperator` drives the windows of it
+/// and the evaluator materializes the fired windows, by reducing or by processing the
+/// buffered records
+pub(crate) trait WindowEvaluator {
+    /// add the record of the key to each location window of the record
+    fn add(&mut self, key: Record, record: Record);
+
+    fn windows(&self) -> Vec<WindowWrap>;
+
+    fn keys(&self, window: &WindowWrap) -> Vec<Record>;
+
+    /// see `WindowState::fire_window`
+    fn fire_window(&mut self, window: &WindowWrap) -> bool;
+
+    /// see `WindowState::drop_window`
+    fn drop_window(&mut self, window: &WindowWrap) -> bool;
+
+    /// see `WindowState::fire_keys`
+    fn fire_keys(&mut self, window: &WindowWrap, keys: &[Record]) -> bool;
+
+    /// see `WindowState::purge_keys`
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]);
+}
+
+/// the window driving logic shared by the windowed operators: the expiration of the records,
+/// the triggers, the watermark alignment, the processing time timer and the barrier alignment
+#[derive(Debug)]
+pub(crate) struct WindowOperator {
+    operator_id: u32,
+    chain_id: ChainId,
+    task_number: u16,
+    num_tasks: u16,
+    dependency_parallelism: u32,
+
+    stream_key_by: Option<StreamOperator<dyn KeySelectorFunction>>,
+
+    window_options: WindowOptions,
+    processing_time_timer: Option<TimerChannel>,
+
+    snapshot_storage: Option<SnapshotStorage>,
+    task_checkpoint: Option<TaskCheckpoint>,
+    current_checkpoint_id: u64,
+    reached_barriers: Vec<Barrier>,
+    /// the emitted windows are tracked by the `WindowFiring` downstream,
+    /// the sequence is restarted after each checkpoint
+    firing_checkpoint_id: u64,
+    firing_sequence: u64,
+
+    max_watermark_status_timestamp: u64,
+    /// the timestamp of the latest aligned watermark(or the processing time) the windows fired by,
+    /// the windows fired by the records are taken downstream with it
+    current_watermark_timestamp: u64,
+    watermark_align: Option<WatermarkAlign>,
+    // the Record can be operate after this window(include this window's time)
+    limited_watermark_window: Option<WindowWrap>,
+
+    counter: Arc<AtomicU64>,
+    expire_counter: Arc<AtomicU64>,
+}
+
+impl WindowOperator {
+    pub fn new(
+        operator_id: u32,
+        stream_key_by: Option<StreamOperator<dyn KeySelectorFunction>>,
+        window_options: WindowOptions,
+    ) -> Self {
+        WindowOperator {
+            operator_id,
+            chain_id: 0,
+            task_number: 0,
+            num_tasks: 0,
+            dependency_parallelism: 0,
+            stream_key_by,
+            window_options,
+            processing_time_timer: None,
+            snapshot_storage: None,
+            task_checkpoint: None,
+            current_checkpoint_id: 0,
+            reached_barriers: Vec::new(),
+            firing_checkpoint_id: 0,
+            firing_sequence: 0,
+            max_watermark_status_timestamp: 0,
+            current_watermark_timestamp: 0,
+            watermark_align: None,
+            limited_watermark_window: None,
+            counter: Arc::new(AtomicU64::new(0)),
+            expire_counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// the metrics are registered as `{metric_prefix}_{fn_name}` and
+    /// `{metric_prefix}_Expire_{fn_name}`
+    pub fn open(&mut self, context: &RunnableContext, metric_prefix: &str, fn_name: &str) {
+        let fun_context = context.to_fun_context();
+        if let Some(stream_key_by) = self.stream_key_by.as_mut() {
+            stream_key_by.operator_fn.open(&fun_context);
+        }
+
+        self.chain_id = context.task_descriptor.chain_id;
+        self.task_number = context.task_descriptor.task_number;
+        self.num_tasks = context.task_descriptor.num_tasks;
+        self.dependency_parallelism = context.task_descriptor.dependency_parallelism;
+        // the firings after the restore are always later than the firings before the checkpoint
+        self.firing_checkpoint_id = context.task_descriptor.checkpoint_id & !SAVEPOINT_FLAG;
+
+        self.watermark_align = Some(WatermarkAlign::new());
+        self.snapshot_storage = Some(SnapshotStorage::new(
+            &context.job_descriptor.job_manager.job_properties,
+            context.job_descriptor.job_manager.job_id.as_str(),
+            self.chain_id,
+            self.task_number,
+        ));
+        self.task_checkpoint = Some(context.task_checkpoint.clone());
+
+        if let Some(late_data_output) = self.window_options.late_data_output.as_mut() {
+            late_data_output.open(&fun_context);
+        }
+
+        if !self.window_options.event_time {
+            // the timer is scheduled by the global window timer, so the windows are checked
+            // at each tick of the global window timer
+            let processing_time_timer = context
+                .window_timer
+                .register("Processing Time Window Timer", Duration::from_secs(1))
+                .expect("register processing time window timer error");
+            self.processing_time_timer = Some(processing_time_timer);
+        }
+
+        let tags = vec![
+            Tag("chain_id".to_string(), self.chain_id.to_string()),
+            Tag("partition_num".to_string(), self.task_number.to_string()),
+        ];
+
+        let metric_name = format!("{}_{}", metric_prefix, fn_name);
+        register_counter(metric_name.as_str(), tags.clone(), self.counter.clone());
+
+        let metric_name = format!("{}_Expire_{}", metric_prefix, fn_name);
+        register_counter(metric_name.as_str(), tags, self.expire_counter.clone());
+    }
+
+    pub fn close(&mut self) {
+        if let Some(stream_key_by) = self.stream_key_by.as_mut() {
+            stream_key_by.operator_fn.close();
+        }
+        if let Some(late_data_output) = self.window_options.late_data_output.as_mut() {
+            late_data_output.close();
+        }
+    }
+
+    pub fn merging(&self) -> bool {
+        self.window_options.merging
+    }
+
+    /// restore the window state by `restore_state` and the state of the operator
+    /// from the checkpoint which the task is recovered from.
+    /// returns `false` if the snapshot can't be restored, then the task should start with
+    /// the empty window state
+    pub fn restore<F>(&mut self, context: &RunnableContext, restore_state: F) -> bool
+    where
+        F: FnOnce(&mut BytesMut) -> std::io::Result<()>,
+    {
+        if context.task_descriptor.checkpoint_id == 0 {
+            return true;
+        }
+        // the empty handle is acknowledged by the window state without snapshot
+        let handle = match context.get_checkpoint_handle(self.operator_id) {
+            Some(handle) if !handle.handle.is_empty() => handle,
+            _ => return true,
+        };
+
+        info!(
+            "restore window state from checkpoint({}), handle={}",
+            context.task_descriptor.checkpoint_id, handle.handle
+        );
+
+        let snapshot_storage = self.snapshot_storage.as_ref().unwrap();
+        let rt = snapshot_storage.read(&handle).and_then(|mut bytes| {
+            check_version(&mut bytes, SNAPSHOT_VERSION)?;
+            restore_state(&mut bytes)?;
+            Ok(bytes)
+        });
+        match rt {
+            Ok(mut bytes) => {
+                self.deserialize(&mut bytes);
+                true
+            }
+            Err(e) => {
+                error!(
+                    "restore window state from {} error, start with the empty state. {}",
+                    handle.handle, e
+                );
+                false
+            }
+        }
+    }
+
+    /// snapshot the window state by `snapshot_state` with the state of the operator,
+    /// such as the state of the trigger, and add the handle to the checkpoint of the task.
+    /// the window state which can't be snapshot is acknowledged with an empty handle,
+    /// so the checkpoints of the other chains are still completed
+    pub fn checkpoint<F>(&self, checkpoint_id: u64, snapshot_state: F)
+    where
+        F: FnOnce(&mut BytesMut) -> bool,
+    {
+        let mut bytes = BytesMut::with_capacity(4096);
+        bytes.put_u8(SNAPSHOT_VERSION);
+        let handle = if snapshot_state(&mut bytes) {
+            self.serialize(&mut bytes);
+
+            let snapshot_storage = self.snapshot_storage.as_ref().unwrap();
+            match snapshot_storage.write(checkpoint_id, bytes.as_ref()) {
+                Some(handle) => handle,
+                None => {
+                    error!(
+                        "the window state of checkpoint({}) is not snapshot, the checkpoint is failed",
+                        checkpoint_id
+                    );
+                    self.task_checkpoint.as_ref().unwrap().fail(checkpoint_id);
+                    return;
+                }
+            }
+        } else {
+            // the offsets of the source must not move on without the window state
+            error!(
+                "the window state of checkpoint({}) can't be restored, the checkpoint is failed",
+                checkpoint_id
+            );
+            self.task_checkpoint.as_ref().unwrap().fail(checkpoint_id);
+            return;
+        };
+
+        self.task_checkpoint
+            .as_ref()
+            .unwrap()
+            .add(self.operator_id, checkpoint_id, handle);
+    }
+
+    /// the watermark timer and the state of the trigger
+    fn serialize(&self, bytes: &mut BytesMut) {
+        bytes.put_u64(self.max_watermark_status_timestamp);
+        match self.limited_watermark_window.as_ref() {
+            Some(window) => {
+                bytes.put_u8(1);
+                serialize_window(window, bytes);
+            }
+            None => bytes.put_u8(0),
+        }
+
+        if let Some(trigger) = self.window_options.trigger.as_ref() {
+            trigger.snapshot_state(bytes);
+        }
+    }
+
+    fn deserialize(&mut self, bytes: &mut BytesMut) {
+        self.max_watermark_status_timestamp = bytes.get_u64();
+        self.limited_watermark_window = if bytes.get_u8() == 1 {
+            Some(deserialize_window(bytes))
+        } else {
+            None
+        };
+
+        if let Some(trigger) = self.window_options.trigger.as_mut() {
+            trigger.restore_state(bytes);
+        }
+    }
+
+    /// drive the windows of the `evaluator` by the `element`, the fired windows are taken
+    /// to the `next_runnable` by the `Watermark`.
+    /// returns the checkpoint id if the barriers of all upstream tasks have reached,
+    /// the window state should be snapshot by the caller
+    pub fn run<W: WindowEvaluator>(
+        &mut self,
+        element: Element,
+        evaluator: &mut W,
+        next_runnable: &mut dyn Runnable,
+    ) -> Option<u64> {
+        if !self.window_options.event_time && !element.is_stream_status() {
+            self.check_processing_time_windows(evaluator, next_runnable);
+        }
+
+        match element {
+            Element::Record(record) => {
+                self.on_record(record, evaluator, next_runnable);
+                None
+            }
+            Element::Watermark(watermark) => {
+                self.on_watermark(watermark, evaluator, next_runnable);
+                None
+            }
+            Element::Barrier(barrier) => self.on_barrier(barrier),
+            Element::StreamStatus(_) => {
+                // the `StreamStatus` is the tick of the source, it's emitted even if there is
+                // no data, so the processing time windows of an idle stream are fired here
+                if !self.window_options.event_time {
+                    self.fire_processing_time_windows(evaluator, next_runnable);
+                }
+                None
+            }
+        }
+    }
+
+    fn on_record<W: WindowEvaluator>(
+        &mut self,
+        mut record: Record,
+        evaluator: &mut W,
+        next_runnable: &mut dyn Runnable,
+    ) {
+        // Record expiration check
+        let merging_window = self.window_options.merging;
+        let event_time = self.window_options.event_time;
+        let allowed_lateness = self.window_options.allowed_lateness;
+        let acceptable = self
+            .limited_watermark_window
+            .as_ref()
+            .map(|limit_window| {
+                record
+                    .get_max_location_windows()
+                    .map(|window| {
+                        if merging_window || !event_time || allowed_lateness > 0 {
+                            // the record is acceptable until the window is purged
+                            window.max_timestamp() + allowed_lateness > limit_window.min_timestamp()
+                        } else {
+                            window.min_timestamp() >= limit_window.min_timestamp()
+                        }
+                    })
+                    .unwrap_or(true)
+            })
+            .unwrap_or(true);
+        if !acceptable {
+            let n = self.expire_counter.fetch_add(1, Ordering::Relaxed);
+            if let Some(late_data_output) = self.window_options.late_data_output.as_mut() {
+                late_data_output.write_record(record);
+                return;
+            }
+
+            if n & 1048575 == 1 {
+                error!(
+                    "expire data. record window={:?}, limit window={:?}",
+                    record.get_min_location_windows().unwrap(),
+                    self.limited_watermark_window.as_ref().unwrap()
+                );
+            }
+            return;
+        }
+
+        let key = match &self.stream_key_by {
+            Some(stream_key_by) => stream_key_by.operator_fn.get_key(record.borrow_mut()),
+            None => Record::with_capacity(0),
+        };
+
+        // the trigger is consulted before the record is added into the windows,
+        // the windows of a merging `WindowAssigner` are only triggered by the time
+        let trigger_results: Vec<(WindowWrap, TriggerResult)> =
+            match self.window_options.trigger.as_mut() {
+                Some(trigger) if !merging_window => record
+                    .get_location_windows()
+                    .iter()
+                    .map(|window| {
+                        let trigger_result = trigger.on_element(&key, &record, window);
+                        (window.clone(), trigger_result)
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+        let trigger_key = if !trigger_results.is_empty() {
+            Some(key.clone())
+        } else {
+            None
+        };
+        evaluator.add(key, record);
+        self.counter.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(trigger_key) = trigger_key {
+            let trigger = self.window_options.trigger.as_mut().unwrap();
+            let mut drop_windows = Vec::new();
+            for (window, trigger_result) in trigger_results {
+                if WindowOperator::apply_trigger_result(
+                    evaluator,
+                    trigger.as_mut(),
+                    &window,
+                    vec![(trigger_key.clone(), trigger_result)],
+                ) {
+                    drop_windows.push(window);
+                }
+            }
+
+            // the event time is not advanced by the record
+            let timestamp = self.current_watermark_timestamp;
+            let watermark = self.new_watermark(timestamp);
+            self.emit_windows(next_runnable, timestamp, drop_windows, watermark);
+        }
+    }
+
+    fn on_watermark<W: WindowEvaluator>(
+        &mut self,
+        watermark: Watermark,
+        evaluator: &mut W,
+        next_runnable: &mut dyn Runnable,
+    ) {
+        if !self.window_options.event_time {
+            // the windows are fired by the processing time timer
+            return;
+        }
+
+        let watermark_status_timestamp = watermark.status_timestamp;
+
+        let watermark_align = self.watermark_align.as_mut().unwrap();
+        watermark_align.insert(watermark);
+
+        if watermark_status_timestamp < self.max_watermark_status_timestamp {
+            return;
+        }
+
+        self.max_watermark_status_timestamp = watermark_status_timestamp;
+        let align_watermarks = watermark_align.align();
+
+        if let Some(align_watermark) = align_watermarks.last() {
+            let align_watermark = align_watermark.clone();
+            let minimum_watermark_window =
+                align_watermark.get_min_location_windows().unwrap().clone();
+            self.limited_watermark_window = Some(minimum_watermark_window.clone());
+            self.current_watermark_timestamp = align_watermark.timestamp;
+
+            self.fire_windows(
+                evaluator,
+                next_runnable,
+                minimum_watermark_window.min_timestamp(),
+                align_watermark.timestamp,
+                align_watermark,
+            );
+        }
+    }
+
+    /// returns the checkpoint id when the barriers of all upstream tasks have reached
+    fn on_barrier(&mut self, barrier: Barrier) -> Option<u64> {
+        // the barriers have been aligned by the input of the chain, in the `Aligned`
+        // mode no record after the barrier is reached before the snapshot
+        if self.current_checkpoint_id == 0 {
+            self.current_checkpoint_id = barrier.checkpoint_id;
+        }
+
+        if self.current_checkpoint_id == barrier.checkpoint_id {
+            self.reached_barriers.push(barrier);
+            if self.reached_barriers.len() == self.dependency_parallelism as usize {
+                let checkpoint_id = self.current_checkpoint_id;
+
+                self.current_checkpoint_id = 0;
+                self.reached_barriers.clear();
+
+                self.firing_checkpoint_id = checkpoint_id & !SAVEPOINT_FLAG;
+                self.firing_sequence = 0;
+
+                return Some(checkpoint_id);
+            }
+        } else {
+            if self.current_checkpoint_id > barrier.checkpoint_id {
+                error!(
+                    "Unusual state of Checkpoint. Barrier's `checkpoint_id` is less than `current_checkpoint_id`"
+                )
+            } else {
+                error!(
+                    "Found a new checkpoint({}) if the current checkpoint({}) is not completed",
+                    barrier.checkpoint_id, self.current_checkpoint_id,
+                );
+
+                self.current_checkpoint_id = barrier.checkpoint_id;
+                self.reached_barriers.clear();
+                self.reached_barriers.push(barrier);
+            }
+        }
+
+        None
+    }
+
+    /// fire the windows which end before the `fire_timestamp`, the windows are dropped from the
+    /// state unless it is in the `allowed_lateness`. the trigger is consulted with the
+    /// `trigger_timestamp` for the windows which have not been ended,
+    /// and take the fired windows downstream by the `Watermark`
+    fn fire_windows<W: WindowEvaluator>(
+        &mut self,
+        evaluator: &mut W,
+        next_runnable: &mut dyn Runnable,
+        fire_timestamp: u64,
+        trigger_timestamp: u64,
+        watermark: Watermark,
+    ) {
+        let allowed_lateness = self.window_options.allowed_lateness;
+        let event_time = self.window_options.event_time;
+
+        let mut drop_windows = Vec::new();
+        for window in evaluator.windows() {
+            let emitted = if window.max_timestamp() + allowed_lateness <= fire_timestamp {
+                if let Some(trigger) = self.window_options.trigger.as_mut() {
+                    for key in evaluator.keys(&window) {
+                        trigger.clear(&key, &window);
+                    }
+                }
+                evaluator.drop_window(&window)
+            } else if window.max_timestamp() <= fire_timestamp {
+                evaluator.fire_window(&window)
+            } else if let Some(trigger) = self.window_options.trigger.as_mut() {
+                let trigger_results = evaluator
+                    .keys(&window)
+                    .into_iter()
+                    .map(|key| {
+                        let trigger_result = if event_time {
+                            trigger.on_event_time(trigger_timestamp, &key, &window)
+                        } else {
+                            trigger.on_processing_time(trigger_timestamp, &key, &window)
+                        };
+                        (key, trigger_result)
+                    })
+                    .collect();
+
+                WindowOperator::apply_trigger_result(
+                    evaluator,
+                    trigger.as_mut(),
+                    &window,
+                    trigger_results,
+                )
+            } else {
+                false
+            };
+
+            if emitted {
+                drop_windows.push(window);
+            }
+        }
+
+        self.emit_windows(next_runnable, fire_timestamp, drop_windows, watermark);
+    }
+
+    /// apply the `TriggerResult` of each key to the window state,
+    /// the fired keys of the window are emitted together.
+    /// returns `true` if there is any state emitted
+    fn apply_trigger_result<W: WindowEvaluator>(
+        evaluator: &mut W,
+        trigger: &mut dyn Trigger,
+        window: &WindowWrap,
+        trigger_results: Vec<(Record, TriggerResult)>,
+    ) -> bool {
+        let mut fire_keys = Vec::new();
+        let mut purge_keys = Vec::new();
+        for (key, trigger_result) in trigger_results {
+            match trigger_result {
+                TriggerResult::Continue => {}
+                TriggerResult::Fire => fire_keys.push(key),
+                TriggerResult::Purge => purge_keys.push(key),
+                TriggerResult::FireAndPurge => {
+                    fire_keys.push(key.clone());
+                    purge_keys.push(key);
+                }
+            }
+        }
+
+        let emitted = !fire_keys.is_empty() && evaluator.fire_keys(window, fire_keys.as_slice());
+
+        if !purge_keys.is_empty() {
+            for key in &purge_keys {
+                trigger.clear(key, window);
+            }
+            evaluator.purge_keys(window, purge_keys.as_slice());
+        }
+
+        emitted
+    }
+
+    /// take the emitted windows downstream by the `Watermark` as a new `WindowFiring`
+    fn emit_windows(
+        &mut self,
+        next_runnable: &mut dyn Runnable,
+        fire_timestamp: u64,
+        mut drop_windows: Vec<WindowWrap>,
+        mut watermark: Watermark,
+    ) {
+        drop_windows.sort_by_key(|w| w.max_timestamp());
+
+        if !drop_windows.is_empty() {
+            debug!(
+                "check window for drop, trigger timestamp={}, drop window size={}",
+                timestamp_str(fire_timestamp),
+                drop_windows.len()
+            );
+
+            self.firing_sequence += 1;
+            watermark.drop_windows = Some(drop_windows);
+            watermark.window_firing = Some(WindowFiring::new(
+                self.firing_checkpoint_id,
+                self.firing_sequence,
+            ));
+            next_runnable.run(Element::from(watermark));
+        }
+    }
+
+    fn new_watermark(&self, timestamp: u64) -> Watermark {
+        let stream_status = StreamStatus::new(timestamp, false);
+        Watermark::new(self.task_number, self.num_tasks, timestamp, &stream_status)
+    }
+
+    fn check_processing_time_windows<W: WindowEvaluator>(
+        &mut self,
+        evaluator: &mut W,
+        next_runnable: &mut dyn Runnable,
+    ) {
+        let triggered = self
+            .processing_time_timer
+            .as_ref()
+            .map(|timer| timer.try_recv().is_ok())
+            .unwrap_or(false);
+        if triggered {
+            self.fire_processing_time_windows(evaluator, next_runnable);
+        }
+    }
+
+    /// fire the windows end before the current processing time
+    fn fire_processing_time_windows<W: WindowEvaluator>(
+        &mut self,
+        evaluator: &mut W,
+        next_runnable: &mut dyn Runnable,
+    ) {
+        let current_timestamp = utils::date_time::current_timestamp_millis();
+
+        // the windows end before `current_timestamp` are fired,
+        // so the records of those windows are expired since now
+        let limit_window = TimeWindow::new(current_timestamp, current_timestamp);
+        self.limited_watermark_window = Some(WindowWrap::TimeWindow(limit_window));
+        self.current_watermark_timestamp = current_timestamp;
+
+        let watermark = self.new_watermark(current_timestamp);
+        self.fire_windows(
+            evaluator,
+            next_runnable,
+            current_timestamp,
+            current_timestamp,
+            watermark,
+        );
+    }
+}
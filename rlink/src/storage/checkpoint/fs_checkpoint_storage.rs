@@ -0,0 +1,284 @@
+use std::path::{Path, PathBuf};
+
+use crate::api::checkpoint::Checkpoint;
+use crate::runtime::{ChainId, CheckpointId};
+use crate::storage::checkpoint::CheckpointStorage;
+
+const CHECKPOINT_EXTENSION: &str = "json";
+
+/// the completed checkpoint of a chain, persisted as a file
+#[derive(Debug, Serialize, Deserialize)]
+struct ChainCheckpointFile {
+    job_name: String,
+    job_id: String,
+    chain_id: ChainId,
+    checkpoint_id: CheckpointId,
+    finish_cks: Vec<Checkpoint>,
+}
+
+/// keep each completed checkpoint in `{path}/{job_name}/{chain_id}/{checkpoint_id}.json`,
+/// and each savepoint in `{path}/{job_name}/savepoints/{savepoint_name}/{chain_id}.json`.
+/// the file is written to a temporary file and renamed, so a partial file is never loaded.
+#[derive(Debug)]
+pub struct FileSystemCheckpointStorage {
+    path: PathBuf,
+}
+
+impl FileSystemCheckpointStorage {
+    pub fn new(path: &str) -> Self {
+        FileSystemCheckpointStorage {
+            path: PathBuf::from(path),
+        }
+    }
+
+    fn get_chain_dir(&self, job_name: &str, chain_id: ChainId) -> PathBuf {
+        self.path.join(job_name).join(chain_id.to_string())
+    }
+
+    /// the checkpoint ids of the files in the `chain_dir`, in ascending order
+    fn list_checkpoint_ids(chain_dir: &Path) -> anyhow::Result<Vec<CheckpointId>> {
+        let mut checkpoint_ids = Vec::new();
+        for entry in std::fs::read_dir(chain_dir)? {
+            let path = entry?.path();
+            let is_checkpoint = path
+                .extension()
+                .map(|ext| ext == CHECKPOINT_EXTENSION)
+                .unwrap_or(false);
+            if !is_checkpoint {
+                continue;
+            }
+
+            let checkpoint_id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<CheckpointId>().ok());
+            if let Some(checkpoint_id) = checkpoint_id {
+                checkpoint_ids.push(checkpoint_id);
+            }
+        }
+
+        checkpoint_ids.sort();
+        Ok(checkpoint_ids)
+    }
+
+    fn get_checkpoint_path(chain_dir: &Path, checkpoint_id: CheckpointId) -> PathBuf {
+        chain_dir.join(format!("{}.{}", checkpoint_id, CHECKPOINT_EXTENSION))
+    }
+
+    fn get_savepoint_path(
+        &self,
+        job_name: &str,
+        savepoint_name: &str,
+        chain_id: ChainId,
+    ) -> PathBuf {
+        self.path
+            .join(job_name)
+            .join("savepoints")
+            .join(savepoint_name)
+            .join(format!("{}.{}", chain_id, CHECKPOINT_EXTENSION))
+    }
+
+    fn write_atomic(path: &Path, chain_checkpoint: &ChainCheckpointFile) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let data = serde_json::to_string(chain_checkpoint)?;
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, data)?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    fn read(path: &Path) -> anyhow::Result<ChainCheckpointFile> {
+        let data = std::fs::read_to_string(path)?;
+        let chain_checkpoint = serde_json::from_str(data.as_str())?;
+        Ok(chain_checkpoint)
+    }
+}
+
+impl CheckpointStorage for FileSystemCheckpointStorage {
+    fn save(
+        &mut self,
+        job_name: &str,
+        job_id: &str,
+        chain_id: u32,
+        checkpoint_id: u64,
+        finish_cks: Vec<Checkpoint>,
+        ttl: u64,
+    ) -> anyhow::Result<()> {
+        let chain_dir = self.get_chain_dir(job_name, chain_id);
+        let chain_checkpoint = ChainCheckpointFile {
+            job_name: job_name.to_string(),
+            job_id: job_id.to_string(),
+            chain_id,
+            checkpoint_id,
+            finish_cks,
+        };
+
+        let path = FileSystemCheckpointStorage::get_checkpoint_path(&chain_dir, checkpoint_id);
+        FileSystemCheckpointStorage::write_atomic(&path, &chain_checkpoint)?;
+
+        info!(
+            "checkpoint save success, chain_id={}, checkpoint_id={}, path={:?}",
+            chain_id, checkpoint_id, path
+        );
+
+        if checkpoint_id < ttl {
+            return Ok(());
+        }
+
+        // the latest checkpoint is always kept, it is saved just now
+        let checkpoint_id_ttl = checkpoint_id - ttl;
+        let checkpoint_ids = FileSystemCheckpointStorage::list_checkpoint_ids(&chain_dir)?;
+        for ck_id in checkpoint_ids {
+            if ck_id >= checkpoint_id_ttl {
+                break;
+            }
+
+            let path = FileSystemCheckpointStorage::get_checkpoint_path(&chain_dir, ck_id);
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!("remove expired checkpoint({:?}) error. {}", path, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, job_name: &str, chain_id: u32) -> anyhow::Result<Vec<Checkpoint>> {
+        let chain_dir = self.get_chain_dir(job_name, chain_id);
+        if !chain_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let checkpoint_ids = FileSystemCheckpointStorage::list_checkpoint_ids(&chain_dir)?;
+        let checkpoint_id = match checkpoint_ids.last() {
+            Some(checkpoint_id) => *checkpoint_id,
+            None => return Ok(vec![]),
+        };
+
+        let path = FileSystemCheckpointStorage::get_checkpoint_path(&chain_dir, checkpoint_id);
+        let chain_checkpoint = FileSystemCheckpointStorage::read(&path)?;
+
+        info!(
+            "checkpoint load success, chain_id={}, checkpoint_id={}",
+            chain_id, checkpoint_id
+        );
+        Ok(chain_checkpoint.finish_cks)
+    }
+
+    fn save_savepoint(
+        &mut self,
+        job_name: &str,
+        job_id: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+        checkpoint_id: u64,
+        finish_cks: Vec<Checkpoint>,
+    ) -> anyhow::Result<()> {
+        let chain_checkpoint = ChainCheckpointFile {
+            job_name: job_name.to_string(),
+            job_id: job_id.to_string(),
+            chain_id,
+            checkpoint_id,
+            finish_cks,
+        };
+
+        let path = self.get_savepoint_path(job_name, savepoint_name, chain_id);
+        FileSystemCheckpointStorage::write_atomic(&path, &chain_checkpoint)?;
+
+        info!(
+            "savepoint save success, name={}, chain_id={}, path={:?}",
+            savepoint_name, chain_id, path
+        );
+        Ok(())
+    }
+
+    fn load_savepoint(
+        &mut self,
+        job_name: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+    ) -> anyhow::Result<Vec<Checkpoint>> {
+        let path = self.get_savepoint_path(job_name, savepoint_name, chain_id);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        let chain_checkpoint = FileSystemCheckpointStorage::read(&path)?;
+
+        info!(
+            "savepoint load success, name={}, chain_id={}",
+            savepoint_name, chain_id
+        );
+        Ok(chain_checkpoint.finish_cks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::checkpoint::{Checkpoint, CheckpointHandle};
+    use crate::storage::checkpoint::fs_checkpoint_storage::FileSystemCheckpointStorage;
+    use crate::storage::checkpoint::CheckpointStorage;
+
+    fn build_checkpoints(checkpoint_id: u64) -> Vec<Checkpoint> {
+        (0..2)
+            .map(|task_num| Checkpoint {
+                chain_id: 5u32,
+                task_num,
+                checkpoint_id,
+                handle: CheckpointHandle {
+                    handle: format!("{}_{}", checkpoint_id, task_num),
+                },
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn fs_storage_test() {
+        let path = std::env::temp_dir().join(format!("rlink-test-{}", uuid::Uuid::new_v4()));
+        let mut fs_storage = FileSystemCheckpointStorage::new(path.to_str().unwrap());
+        assert!(fs_storage.load("abc", 5u32).unwrap().is_empty());
+
+        for checkpoint_id in vec![1000, 2000, 3000] {
+            fs_storage
+                .save(
+                    "abc",
+                    "def",
+                    5u32,
+                    checkpoint_id,
+                    build_checkpoints(checkpoint_id),
+                    1500,
+                )
+                .unwrap();
+        }
+
+        let mut cks = fs_storage.load("abc", 5u32).unwrap();
+        cks.sort_by_key(|ck| ck.task_num);
+        assert_eq!(cks.len(), 2);
+        assert_eq!(cks[0].checkpoint_id, 3000);
+        assert_eq!(cks[1].handle.handle, "3000_1");
+
+        // the checkpoint 1000 is expired
+        let chain_dir = fs_storage.get_chain_dir("abc", 5u32);
+        let checkpoint_ids = FileSystemCheckpointStorage::list_checkpoint_ids(&chain_dir).unwrap();
+        assert_eq!(checkpoint_ids, vec![2000, 3000]);
+
+        // the savepoint is kept apart from the checkpoints
+        fs_storage
+            .save_savepoint("abc", "def", "v1", 5u32, 1000, build_checkpoints(1000))
+            .unwrap();
+        assert_eq!(
+            fs_storage.load_savepoint("abc", "v1", 5u32).unwrap().len(),
+            2
+        );
+        assert!(fs_storage
+            .load_savepoint("abc", "v2", 5u32)
+            .unwrap()
+            .is_empty());
+        assert_eq!(fs_storage.load("abc", 5u32).unwrap()[0].checkpoint_id, 3000);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
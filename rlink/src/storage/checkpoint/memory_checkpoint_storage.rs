@@ -1,18 +1,21 @@
 use std::collections::HashMap;
 
 use crate::api::checkpoint::Checkpoint;
-use crate::runtime::CheckpointId;
+use crate::runtime::{ChainId, CheckpointId};
 use crate::storage::checkpoint::CheckpointStorage;
 
 #[derive(Debug)]
 pub struct MemoryCheckpointStorage {
     history_cks: HashMap<CheckpointId, Vec<Checkpoint>>,
+    /// Map<(savepoint_name, chain_id), Vec<Checkpoint>>
+    savepoints: HashMap<(String, ChainId), Vec<Checkpoint>>,
 }
 
 impl MemoryCheckpointStorage {
     pub fn new() -> Self {
         MemoryCheckpointStorage {
             history_cks: HashMap::new(),
+            savepoints: HashMap::new(),
         }
     }
 }
@@ -64,4 +67,32 @@ impl CheckpointStorage for MemoryCheckpointStorage {
     fn load(&mut self, _job_name: &str, _chain_id: u32) -> anyhow::Result<Vec<Checkpoint>> {
         Ok(vec![])
     }
+
+    fn save_savepoint(
+        &mut self,
+        _job_name: &str,
+        _job_id: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+        _checkpoint_id: u64,
+        finish_cks: Vec<Checkpoint>,
+    ) -> anyhow::Result<()> {
+        self.savepoints
+            .insert((savepoint_name.to_string(), chain_id), finish_cks);
+        Ok(())
+    }
+
+    fn load_savepoint(
+        &mut self,
+        _job_name: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+    ) -> anyhow::Result<Vec<Checkpoint>> {
+        let cks = self
+            .savepoints
+            .get(&(savepoint_name.to_string(), chain_id))
+            .map(|cks| cks.clone())
+            .unwrap_or_default();
+        Ok(cks)
+    }
 }
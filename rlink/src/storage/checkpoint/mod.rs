@@ -1,9 +1,11 @@
 use crate::api::backend::CheckpointBackend;
 use crate::api::checkpoint::Checkpoint;
 use crate::runtime::ChainId;
+use crate::storage::checkpoint::fs_checkpoint_storage::FileSystemCheckpointStorage;
 use crate::storage::checkpoint::memory_checkpoint_storage::MemoryCheckpointStorage;
 use crate::storage::checkpoint::mysql_checkpoint_storage::MySqlCheckpointStorage;
 
+pub mod fs_checkpoint_storage;
 pub mod memory_checkpoint_storage;
 pub mod mysql_checkpoint_storage;
 
@@ -18,12 +20,31 @@ pub trait CheckpointStorage {
         ttl: u64,
     ) -> anyhow::Result<()>;
     fn load(&mut self, job_name: &str, chain_id: ChainId) -> anyhow::Result<Vec<Checkpoint>>;
+
+    /// save the completed savepoint of the chain under the `savepoint_name`, without ttl.
+    /// the existing savepoint of the same name is replaced
+    fn save_savepoint(
+        &mut self,
+        job_name: &str,
+        job_id: &str,
+        savepoint_name: &str,
+        chain_id: ChainId,
+        checkpoint_id: u64,
+        finish_cks: Vec<Checkpoint>,
+    ) -> anyhow::Result<()>;
+    fn load_savepoint(
+        &mut self,
+        job_name: &str,
+        savepoint_name: &str,
+        chain_id: ChainId,
+    ) -> anyhow::Result<Vec<Checkpoint>>;
 }
 
 #[derive(Debug)]
 pub enum CheckpointStorageWrap {
     MemoryCheckpointStorage(MemoryCheckpointStorage),
     MySqlCheckpointStorage(MySqlCheckpointStorage),
+    FileSystemCheckpointStorage(FileSystemCheckpointStorage),
 }
 
 impl CheckpointStorageWrap {
@@ -35,6 +56,11 @@ impl CheckpointStorageWrap {
             CheckpointBackend::MySql { endpoint } => CheckpointStorageWrap::MySqlCheckpointStorage(
                 MySqlCheckpointStorage::new(endpoint.as_str()),
             ),
+            CheckpointBackend::FileSystem { path } => {
+                CheckpointStorageWrap::FileSystemCheckpointStorage(
+                    FileSystemCheckpointStorage::new(path.as_str()),
+                )
+            }
         }
     }
 }
@@ -56,6 +82,9 @@ impl CheckpointStorage for CheckpointStorageWrap {
             CheckpointStorageWrap::MySqlCheckpointStorage(storage) => {
                 storage.save(job_name, job_id, chain_id, checkpoint_id, finish_cks, ttl)
             }
+            CheckpointStorageWrap::FileSystemCheckpointStorage(storage) => {
+                storage.save(job_name, job_id, chain_id, checkpoint_id, finish_cks, ttl)
+            }
         }
     }
 
@@ -67,6 +96,65 @@ impl CheckpointStorage for CheckpointStorageWrap {
             CheckpointStorageWrap::MySqlCheckpointStorage(storage) => {
                 storage.load(job_name, chain_id)
             }
+            CheckpointStorageWrap::FileSystemCheckpointStorage(storage) => {
+                storage.load(job_name, chain_id)
+            }
+        }
+    }
+
+    fn save_savepoint(
+        &mut self,
+        job_name: &str,
+        job_id: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+        checkpoint_id: u64,
+        finish_cks: Vec<Checkpoint>,
+    ) -> anyhow::Result<()> {
+        match self {
+            CheckpointStorageWrap::MemoryCheckpointStorage(storage) => storage.save_savepoint(
+                job_name,
+                job_id,
+                savepoint_name,
+                chain_id,
+                checkpoint_id,
+                finish_cks,
+            ),
+            CheckpointStorageWrap::MySqlCheckpointStorage(storage) => storage.save_savepoint(
+                job_name,
+                job_id,
+                savepoint_name,
+                chain_id,
+                checkpoint_id,
+                finish_cks,
+            ),
+            CheckpointStorageWrap::FileSystemCheckpointStorage(storage) => storage.save_savepoint(
+                job_name,
+                job_id,
+                savepoint_name,
+                chain_id,
+                checkpoint_id,
+                finish_cks,
+            ),
+        }
+    }
+
+    fn load_savepoint(
+        &mut self,
+        job_name: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+    ) -> anyhow::Result<Vec<Checkpoint>> {
+        match self {
+            CheckpointStorageWrap::MemoryCheckpointStorage(storage) => {
+                storage.load_savepoint(job_name, savepoint_name, chain_id)
+            }
+            CheckpointStorageWrap::MySqlCheckpointStorage(storage) => {
+                storage.load_savepoint(job_name, savepoint_name, chain_id)
+            }
+            CheckpointStorageWrap::FileSystemCheckpointStorage(storage) => {
+                storage.load_savepoint(job_name, savepoint_name, chain_id)
+            }
         }
     }
 }
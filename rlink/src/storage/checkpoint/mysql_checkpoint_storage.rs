@@ -5,6 +5,43 @@ use crate::api::checkpoint::{Checkpoint, CheckpointHandle};
 use crate::storage::checkpoint::CheckpointStorage;
 use crate::utils::date_time::{current_timestamp, fmt_date_time};
 
+/// keep the checkpoints and savepoints in the MySQL tables:
+///
+/// ```sql
+/// CREATE TABLE `rlink_cks` (
+///   `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+///   `job_name` varchar(255) NOT NULL,
+///   `application_id` varchar(255) NOT NULL,
+///   `chain_id` int(10) unsigned NOT NULL,
+///   `checkpoint_id` bigint(20) unsigned NOT NULL,
+///   `task_num` smallint(5) unsigned NOT NULL,
+///   `handle` text NOT NULL,
+///   `create_time` datetime NOT NULL,
+///   PRIMARY KEY (`id`),
+///   KEY `idx_job_chain_ck` (`job_name`, `chain_id`, `checkpoint_id`)
+/// ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+///
+/// CREATE TABLE `rlink_savepoints` (
+///   `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+///   `job_name` varchar(255) NOT NULL,
+///   `savepoint_name` varchar(255) NOT NULL,
+///   `application_id` varchar(255) NOT NULL,
+///   `chain_id` int(10) unsigned NOT NULL,
+///   `checkpoint_id` bigint(20) unsigned NOT NULL,
+///   `task_num` smallint(5) unsigned NOT NULL,
+///   `handle` text NOT NULL,
+///   `create_time` datetime NOT NULL,
+///   PRIMARY KEY (`id`),
+///   KEY `idx_job_savepoint_chain` (`job_name`, `savepoint_name`, `chain_id`)
+/// ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+/// ```
+///
+/// the `rlink_savepoints` table is required since the savepoints are supported,
+/// it should be created on the existing deployments before upgrading.
+///
+/// the handles of the keyed states are the paths of the snapshot files, a savepoint
+/// is only portable across hosts if the snapshots are written to a shared file system,
+/// see `OperatorStateBackend::DFS` and `CheckpointBackend::FileSystem`
 #[derive(Debug)]
 pub struct MySqlCheckpointStorage {
     url: String,
@@ -111,6 +148,98 @@ where cks.job_name = :job_name
         info!("checkpoint load success");
         Ok(selected_payments)
     }
+
+    fn save_savepoint(
+        &mut self,
+        job_name: &str,
+        application_id: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+        checkpoint_id: u64,
+        finish_cks: Vec<Checkpoint>,
+    ) -> anyhow::Result<()> {
+        let pool = Pool::new(self.url.as_str())?;
+
+        let mut conn = pool.get_conn()?;
+        let mut tx = conn.start_transaction(TxOpts::default())?;
+        tx.exec_drop(
+            r"
+delete
+from rlink_savepoints
+where job_name = :job_name
+  and savepoint_name = :savepoint_name
+  and chain_id = :chain_id",
+            params! {
+                "job_name" => job_name,
+                "savepoint_name" => savepoint_name,
+                "chain_id" => chain_id,
+            },
+        )?;
+        tx.exec_batch(
+            r"
+insert into rlink_savepoints 
+  (job_name, savepoint_name, application_id, chain_id, checkpoint_id, task_num, handle, create_time)
+values 
+  (:job_name, :savepoint_name, :application_id, :chain_id, :checkpoint_id, :task_num, :handle, :create_time)",
+            finish_cks.iter().map(|p| {
+                params! {
+                    "job_name" => job_name,
+                    "savepoint_name" => savepoint_name,
+                    "application_id" => application_id,
+                    "chain_id" => chain_id,
+                    "checkpoint_id" => checkpoint_id,
+                    "task_num" => p.task_num,
+                    "handle" => &p.handle.handle,
+                    "create_time" => fmt_date_time(current_timestamp(), "%Y-%m-%d %T"),
+                }
+            }),
+        )?;
+        tx.commit()?;
+
+        info!(
+            "savepoint save success, name={}, chain_id={}, checkpoint_id={}",
+            savepoint_name, chain_id, checkpoint_id
+        );
+        Ok(())
+    }
+
+    fn load_savepoint(
+        &mut self,
+        job_name: &str,
+        savepoint_name: &str,
+        chain_id: u32,
+    ) -> anyhow::Result<Vec<Checkpoint>> {
+        let pool = Pool::new(self.url.as_str())?;
+
+        let mut conn = pool.get_conn()?;
+
+        let stmt = conn.prep(
+            r"
+SELECT chain_id, checkpoint_id, task_num, handle
+from rlink_savepoints
+where job_name = :job_name
+  and savepoint_name = :savepoint_name
+  and chain_id = :chain_id",
+        )?;
+
+        let cks = conn.exec_map(
+            &stmt,
+            params! {
+                "job_name" => job_name,
+                "savepoint_name" => savepoint_name,
+                "chain_id" => chain_id
+            },
+            |(chain_id, checkpoint_id, task_num, handle)| Checkpoint {
+                chain_id,
+                task_num,
+                checkpoint_id,
+                handle: CheckpointHandle { handle },
+            },
+        )?;
+
+        info!("savepoint load success, name={}", savepoint_name);
+        Ok(cks)
+    }
 }
 
 #[cfg(test)]
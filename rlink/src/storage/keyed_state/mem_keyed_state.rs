@@ -0,0 +1,290 @@
+use crate::api::element::{Record, Serde};
+use crate::storage::keyed_state::mem_list_state::MemoryListState;
+use crate::storage::keyed_state::ListState;
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::HashMap;
+
+/// the single value of each key, See flink `ValueState`
+#[derive(Clone, Debug)]
+pub struct MemoryValueState {
+    kv: HashMap<Record, Record>,
+}
+
+impl MemoryValueState {
+    pub fn new() -> Self {
+        MemoryValueState { kv: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &Record) -> Option<&Record> {
+        self.kv.get(key)
+    }
+
+    pub fn insert(&mut self, key: Record, val: Record) {
+        self.kv.insert(key, val);
+    }
+
+    pub fn remove(&mut self, key: &Record) -> Option<Record> {
+        self.kv.remove(key)
+    }
+
+    fn serialize(&self, bytes: &mut BytesMut) {
+        bytes.put_u32(self.kv.len() as u32);
+        for (key, val) in &self.kv {
+            key.serialize(bytes);
+            val.serialize(bytes);
+        }
+    }
+
+    fn deserialize(bytes: &mut BytesMut) -> Self {
+        let len = bytes.get_u32() as usize;
+        let mut kv = HashMap::with_capacity(len);
+        for _ in 0..len {
+            let key = Record::deserialize(bytes);
+            let val = Record::deserialize(bytes);
+            kv.insert(key, val);
+        }
+
+        MemoryValueState { kv }
+    }
+}
+
+/// the user key-value mapping of each key, See flink `MapState`
+#[derive(Clone, Debug)]
+pub struct MemoryMapState {
+    kv: HashMap<Record, HashMap<Record, Record>>,
+}
+
+impl MemoryMapState {
+    pub fn new() -> Self {
+        MemoryMapState { kv: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &Record, user_key: &Record) -> Option<&Record> {
+        self.kv.get(key).and_then(|map| map.get(user_key))
+    }
+
+    pub fn insert(&mut self, key: Record, user_key: Record, user_value: Record) {
+        self.kv
+            .entry(key)
+            .or_insert_with(|| HashMap::new())
+            .insert(user_key, user_value);
+    }
+
+    pub fn remove(&mut self, key: &Record, user_key: &Record) -> Option<Record> {
+        let map = self.kv.get_mut(key)?;
+        let user_value = map.remove(user_key);
+        if map.is_empty() {
+            self.kv.remove(key);
+        }
+
+        user_value
+    }
+
+    pub fn entries(&self, key: &Record) -> Vec<(Record, Record)> {
+        match self.kv.get(key) {
+            Some(map) => map
+                .iter()
+                .map(|(user_key, user_value)| (user_key.clone(), user_value.clone()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self, key: &Record) {
+        self.kv.remove(key);
+    }
+
+    fn serialize(&self, bytes: &mut BytesMut) {
+        bytes.put_u32(self.kv.len() as u32);
+        for (key, map) in &self.kv {
+            key.serialize(bytes);
+            bytes.put_u32(map.len() as u32);
+            for (user_key, user_value) in map {
+                user_key.serialize(bytes);
+                user_value.serialize(bytes);
+            }
+        }
+    }
+
+    fn deserialize(bytes: &mut BytesMut) -> Self {
+        let len = bytes.get_u32() as usize;
+        let mut kv = HashMap::with_capacity(len);
+        for _ in 0..len {
+            let key = Record::deserialize(bytes);
+            let map_len = bytes.get_u32() as usize;
+            let mut map = HashMap::with_capacity(map_len);
+            for _ in 0..map_len {
+                let user_key = Record::deserialize(bytes);
+                let user_value = Record::deserialize(bytes);
+                map.insert(user_key, user_value);
+            }
+            kv.insert(key, map);
+        }
+
+        MemoryMapState { kv }
+    }
+}
+
+/// the named keyed states of a task, registered by the `KeyedProcessFunction` on the first access
+#[derive(Clone, Debug)]
+pub struct MemoryKeyedState {
+    value_states: HashMap<String, MemoryValueState>,
+    list_states: HashMap<String, MemoryListState>,
+    map_states: HashMap<String, MemoryMapState>,
+}
+
+impl MemoryKeyedState {
+    pub fn new() -> Self {
+        MemoryKeyedState {
+            value_states: HashMap::new(),
+            list_states: HashMap::new(),
+            map_states: HashMap::new(),
+        }
+    }
+
+    pub fn value_state(&mut self, name: &str) -> &mut MemoryValueState {
+        self.value_states
+            .entry(name.to_string())
+            .or_insert_with(|| MemoryValueState::new())
+    }
+
+    pub fn list_state(&mut self, name: &str) -> &mut MemoryListState {
+        self.list_states
+            .entry(name.to_string())
+            .or_insert_with(|| MemoryListState::with_capacity(0))
+    }
+
+    pub fn map_state(&mut self, name: &str) -> &mut MemoryMapState {
+        self.map_states
+            .entry(name.to_string())
+            .or_insert_with(|| MemoryMapState::new())
+    }
+
+    /// write all the named states to the `bytes`
+    pub fn serialize(&self, bytes: &mut BytesMut) {
+        bytes.put_u32(self.value_states.len() as u32);
+        for (name, state) in &self.value_states {
+            serialize_name(name, bytes);
+            state.serialize(bytes);
+        }
+
+        bytes.put_u32(self.list_states.len() as u32);
+        for (name, state) in &self.list_states {
+            serialize_name(name, bytes);
+            let keys = state.keys();
+            bytes.put_u32(keys.len() as u32);
+            for key in keys {
+                let values = state.get(&key).unwrap();
+                key.serialize(bytes);
+                bytes.put_u32(values.len() as u32);
+                for value in values {
+                    value.serialize(bytes);
+                }
+            }
+        }
+
+        bytes.put_u32(self.map_states.len() as u32);
+        for (name, state) in &self.map_states {
+            serialize_name(name, bytes);
+            state.serialize(bytes);
+        }
+    }
+
+    /// read the named states written by `serialize`
+    pub fn deserialize(bytes: &mut BytesMut) -> Self {
+        let mut keyed_state = MemoryKeyedState::new();
+
+        let len = bytes.get_u32();
+        for _ in 0..len {
+            let name = deserialize_name(bytes);
+            let state = MemoryValueState::deserialize(bytes);
+            keyed_state.value_states.insert(name, state);
+        }
+
+        let len = bytes.get_u32();
+        for _ in 0..len {
+            let name = deserialize_name(bytes);
+            let key_len = bytes.get_u32() as usize;
+            let mut state = MemoryListState::with_capacity(key_len);
+            for _ in 0..key_len {
+                let key = Record::deserialize(bytes);
+                let value_len = bytes.get_u32();
+                for _ in 0..value_len {
+                    state.add(key.clone(), Record::deserialize(bytes));
+                }
+            }
+            keyed_state.list_states.insert(name, state);
+        }
+
+        let len = bytes.get_u32();
+        for _ in 0..len {
+            let name = deserialize_name(bytes);
+            let state = MemoryMapState::deserialize(bytes);
+            keyed_state.map_states.insert(name, state);
+        }
+
+        keyed_state
+    }
+}
+
+fn serialize_name(name: &str, bytes: &mut BytesMut) {
+    bytes.put_u32(name.len() as u32);
+    bytes.put_slice(name.as_bytes());
+}
+
+fn deserialize_name(bytes: &mut BytesMut) -> String {
+    let len = bytes.get_u32() as usize;
+    let name = bytes.split_to(len);
+    String::from_utf8(name.to_vec()).expect("Invalid keyed state name")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::element::{types, Record};
+    use crate::storage::keyed_state::mem_keyed_state::MemoryKeyedState;
+    use crate::storage::keyed_state::ListState;
+    use bytes::BytesMut;
+
+    fn build_record(value: i64) -> Record {
+        let mut record = Record::new();
+        record.get_writer(&[types::I64]).set_i64(value).unwrap();
+        record
+    }
+
+    #[test]
+    pub fn keyed_state_serde_test() {
+        let key = build_record(1);
+
+        let mut keyed_state = MemoryKeyedState::new();
+        keyed_state
+            .value_state("count")
+            .insert(key.clone(), build_record(3));
+        keyed_state
+            .list_state("events")
+            .add(key.clone(), build_record(4));
+        keyed_state
+            .list_state("events")
+            .add(key.clone(), build_record(5));
+        keyed_state
+            .map_state("last")
+            .insert(key.clone(), build_record(6), build_record(7));
+
+        let mut bytes = BytesMut::new();
+        keyed_state.serialize(&mut bytes);
+        let mut restored_state = MemoryKeyedState::deserialize(&mut bytes);
+        assert!(bytes.is_empty());
+
+        assert_eq!(
+            restored_state.value_state("count").get(&key),
+            Some(&build_record(3))
+        );
+        assert_eq!(
+            restored_state.list_state("events").get(&key),
+            Some(&vec![build_record(4), build_record(5)])
+        );
+        assert_eq!(
+            restored_state.map_state("last").get(&key, &build_record(6)),
+            Some(&build_record(7))
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::api::element::Record;
+use crate::storage::keyed_state::{ListState, StateIterator, StateKey};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct MemoryListState {
+    kv: HashMap<Record, Vec<Record>>,
+}
+
+impl MemoryListState {
+    pub fn new(state_key: &StateKey, suggest_capacity: usize) -> Self {
+        debug!(
+            "create memory list state {:?}, suggest capacity {}",
+            state_key, suggest_capacity
+        );
+        MemoryListState {
+            kv: HashMap::with_capacity(suggest_capacity),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        MemoryListState {
+            kv: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn get(&self, key: &Record) -> Option<&Vec<Record>> {
+        self.kv.get(key)
+    }
+
+    /// iterate each value of the keys as a (key, value) pair
+    pub fn iter(&self) -> StateIterator {
+        let iter = self
+            .kv
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |val| (key.clone(), val.clone())));
+        StateIterator::List(Box::new(iter))
+    }
+}
+
+impl ListState for MemoryListState {
+    fn add(&mut self, key: Record, val: Record) {
+        self.kv.entry(key).or_insert_with(|| Vec::new()).push(val);
+    }
+
+    fn get_mut(&mut self, key: &Record) -> Option<&mut Vec<Record>> {
+        self.kv.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &Record) -> Option<Vec<Record>> {
+        self.kv.remove(key)
+    }
+
+    fn keys(&self) -> Vec<Record> {
+        self.kv.keys().map(|key| key.clone()).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.kv.len()
+    }
+}
@@ -0,0 +1,414 @@
+use crate::api::element::{Barrier, Record, Serde};
+use crate::api::window::WindowWrap;
+use crate::runtime::ChainId;
+use crate::storage::keyed_state::mem_list_state::MemoryListState;
+use crate::storage::keyed_state::mem_storage::{append_drop_window, StorageKey};
+use crate::storage::keyed_state::snapshot::{check_version, deserialize_window, serialize_window};
+use crate::storage::keyed_state::{ListState, ListWindowState, ReducingStateWrap, StateKey};
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::{HashMap, HashSet};
+
+const SNAPSHOT_VERSION: u8 = 1;
+
+#[derive(Clone, Debug)]
+pub struct MemoryListWindowState {
+    chain_id: ChainId,
+    task_number: u16,
+
+    windows: HashMap<WindowWrap, MemoryListState>,
+    suggest_state_capacity: usize,
+
+    /// the windows have been fired but kept for the late records,
+    /// and the keys updated by the late records since the latest fire
+    fired_windows: HashMap<WindowWrap, HashSet<Record>>,
+}
+
+impl MemoryListWindowState {
+    pub fn new(chain_id: ChainId, task_number: u16) -> Self {
+        MemoryListWindowState {
+            chain_id,
+            task_number,
+            windows: HashMap::new(),
+            suggest_state_capacity: 512,
+            fired_windows: HashMap::new(),
+        }
+    }
+
+    /// serialize the buffered records and the late keys of the fired windows
+    fn serialize(&self, bytes: &mut BytesMut) {
+        bytes.put_u8(SNAPSHOT_VERSION);
+
+        bytes.put_u32(self.windows.len() as u32);
+        for (window, state) in &self.windows {
+            serialize_window(window, bytes);
+
+            match self.fired_windows.get(window) {
+                Some(late_keys) => {
+                    bytes.put_u8(1);
+                    bytes.put_u32(late_keys.len() as u32);
+                    for key in late_keys {
+                        key.serialize(bytes);
+                    }
+                }
+                None => bytes.put_u8(0),
+            }
+
+            let keys = state.keys();
+            bytes.put_u32(keys.len() as u32);
+            for key in keys {
+                let records = state.get(&key).unwrap();
+                key.serialize(bytes);
+                bytes.put_u32(records.len() as u32);
+                for record in records {
+                    record.serialize(bytes);
+                }
+            }
+        }
+    }
+
+    fn deserialize(&mut self, bytes: &mut BytesMut) -> std::io::Result<()> {
+        check_version(bytes, SNAPSHOT_VERSION)?;
+
+        self.windows.clear();
+        self.fired_windows.clear();
+
+        let window_len = bytes.get_u32();
+        for _ in 0..window_len {
+            let window = deserialize_window(bytes);
+
+            if bytes.get_u8() == 1 {
+                let late_key_len = bytes.get_u32();
+                let mut late_keys = HashSet::with_capacity(late_key_len as usize);
+                for _ in 0..late_key_len {
+                    late_keys.insert(Record::deserialize(bytes));
+                }
+                self.fired_windows.insert(window.clone(), late_keys);
+            }
+
+            let key_len = bytes.get_u32() as usize;
+            let state_key = StateKey::new(window.clone(), self.chain_id, self.task_number);
+            let mut state = MemoryListState::new(&state_key, key_len);
+            for _ in 0..key_len {
+                let key = Record::deserialize(bytes);
+                let record_len = bytes.get_u32();
+                for _ in 0..record_len {
+                    state.add(key.clone(), Record::deserialize(bytes));
+                }
+            }
+            self.windows.insert(window, state);
+        }
+
+        Ok(())
+    }
+
+    /// evaluate the `keys` of the window and emit the results to downstream
+    fn emit_window<F>(
+        chain_id: ChainId,
+        task_number: u16,
+        window: &WindowWrap,
+        state: &mut MemoryListState,
+        keys: Vec<Record>,
+        process_fun: F,
+    ) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>,
+    {
+        let state_key = StateKey::new(window.clone(), chain_id, task_number);
+        let mut emit_state = MemoryListState::new(&state_key, keys.len());
+        for key in keys {
+            if let Some(records) = state.get_mut(&key) {
+                for val in process_fun(&key, window, records) {
+                    emit_state.add(key.clone(), val);
+                }
+            }
+        }
+
+        if emit_state.len() == 0 {
+            return false;
+        }
+
+        let storage_key = StorageKey::new(chain_id, task_number);
+        append_drop_window(
+            storage_key,
+            window.clone(),
+            ReducingStateWrap::MemoryListState(emit_state),
+        );
+
+        true
+    }
+}
+
+impl ListWindowState for MemoryListWindowState {
+    fn windows(&self) -> Vec<WindowWrap> {
+        self.windows.keys().map(|window| window.clone()).collect()
+    }
+
+    fn add(&mut self, key: Record, record: Record) {
+        let windows = record.get_location_windows().clone();
+        for window in windows {
+            if let Some(late_keys) = self.fired_windows.get_mut(&window) {
+                late_keys.insert(key.clone());
+            }
+
+            let state_key = StateKey::new(window.clone(), self.chain_id, self.task_number);
+            let suggest_state_capacity = self.suggest_state_capacity;
+            self.windows
+                .entry(window)
+                .or_insert_with(|| MemoryListState::new(&state_key, suggest_state_capacity))
+                .add(key.clone(), record.clone());
+        }
+    }
+
+    fn fire_window<F>(&mut self, window: &WindowWrap, process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>,
+    {
+        let state = match self.windows.get_mut(window) {
+            Some(state) => state,
+            None => return false,
+        };
+
+        let keys = match self.fired_windows.get_mut(window) {
+            Some(late_keys) => late_keys.drain().collect(),
+            None => {
+                self.fired_windows.insert(window.clone(), HashSet::new());
+                state.keys()
+            }
+        };
+
+        MemoryListWindowState::emit_window(
+            self.chain_id,
+            self.task_number,
+            window,
+            state,
+            keys,
+            process_fun,
+        )
+    }
+
+    fn drop_window<F>(&mut self, window: &WindowWrap, process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>,
+    {
+        match self.windows.remove(window) {
+            Some(mut state) => {
+                let len = state.len() as f32;
+                self.suggest_state_capacity = (len * 1.1f32) as usize;
+
+                // only the keys updated by the late records have not been emitted
+                let keys = match self.fired_windows.remove(window) {
+                    Some(late_keys) => late_keys.into_iter().collect(),
+                    None => state.keys(),
+                };
+
+                MemoryListWindowState::emit_window(
+                    self.chain_id,
+                    self.task_number,
+                    window,
+                    &mut state,
+                    keys,
+                    process_fun,
+                )
+            }
+            None => false,
+        }
+    }
+
+    fn keys(&self, window: &WindowWrap) -> Vec<Record> {
+        match self.windows.get(window) {
+            Some(state) => state.keys(),
+            None => Vec::new(),
+        }
+    }
+
+    fn fire_keys<F>(&mut self, window: &WindowWrap, keys: &[Record], process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>,
+    {
+        let state = match self.windows.get_mut(window) {
+            Some(state) => state,
+            None => return false,
+        };
+
+        // the keys are up to date in the downstream, they are not late keys anymore
+        if let Some(late_keys) = self.fired_windows.get_mut(window) {
+            for key in keys {
+                late_keys.remove(key);
+            }
+        }
+
+        MemoryListWindowState::emit_window(
+            self.chain_id,
+            self.task_number,
+            window,
+            state,
+            keys.to_vec(),
+            process_fun,
+        )
+    }
+
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]) {
+        let empty = match self.windows.get_mut(window) {
+            Some(state) => {
+                for key in keys {
+                    state.remove(key);
+                }
+                state.len() == 0
+            }
+            None => return,
+        };
+
+        if let Some(late_keys) = self.fired_windows.get_mut(window) {
+            for key in keys {
+                late_keys.remove(key);
+            }
+        }
+
+        if empty {
+            self.windows.remove(window);
+            self.fired_windows.remove(window);
+        }
+    }
+
+    fn snapshot(&mut self, barrier: Barrier, bytes: &mut BytesMut) -> bool {
+        self.serialize(bytes);
+        info!(
+            "snapshot {} list windows, checkpoint_id={}",
+            self.windows.len(),
+            barrier.checkpoint_id
+        );
+
+        true
+    }
+
+    fn restore(&mut self, bytes: &mut BytesMut) -> std::io::Result<()> {
+        self.deserialize(bytes)?;
+        info!("restore {} list windows", self.windows.len());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::element::{types, Barrier, Record};
+    use crate::api::window::{TimeWindow, WindowWrap};
+    use crate::storage::keyed_state::mem_list_window_state::MemoryListWindowState;
+    use crate::storage::keyed_state::mem_storage::remove_drop_window;
+    use crate::storage::keyed_state::{ListWindowState, ReducingState};
+    use bytes::BytesMut;
+
+    fn build_record(key: i64, value: i64) -> (Record, Record) {
+        let mut key_record = Record::new();
+        key_record.get_writer(&[types::I64]).set_i64(key).unwrap();
+
+        let mut record = Record::new();
+        record.get_writer(&[types::I64]).set_i64(value).unwrap();
+        record.set_location_windows(vec![WindowWrap::TimeWindow(TimeWindow::new(0, 10))]);
+
+        (key_record, record)
+    }
+
+    fn get_value(record: &mut Record) -> i64 {
+        record.get_reader(&[types::I64]).get_i64(0).unwrap()
+    }
+
+    fn max(_key: &Record, _window: &WindowWrap, records: &mut Vec<Record>) -> Vec<Record> {
+        let n = records.iter_mut().map(get_value).max().unwrap_or(0);
+
+        let mut value = Record::new();
+        value.get_writer(&[types::I64]).set_i64(n).unwrap();
+        vec![value]
+    }
+
+    /// emit the values greater than 4, one row per value
+    fn filter(_key: &Record, _window: &WindowWrap, records: &mut Vec<Record>) -> Vec<Record> {
+        records
+            .iter_mut()
+            .filter_map(|record| {
+                if get_value(record) > 4 {
+                    Some(record.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn list_window_state_test() {
+        let mut state = MemoryListWindowState::new(4, 0);
+        for (key, value) in vec![(1, 3), (1, 7), (2, 5), (1, 2)] {
+            let (key, record) = build_record(key, value);
+            state.add(key, record);
+        }
+
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 10));
+        assert!(state.fire_window(&window, max));
+
+        let mut fire_state = remove_drop_window(4, 0, window.clone()).unwrap();
+        assert_eq!(fire_state.len(), 2);
+        let (key, _record) = build_record(1, 0);
+        let value = fire_state.get_mut(&key).unwrap();
+        assert_eq!(get_value(value), 7);
+
+        // only the key updated by the late record is emitted
+        let (key, record) = build_record(2, 9);
+        state.add(key, record);
+        assert!(state.drop_window(&window, max));
+        assert_eq!(remove_drop_window(4, 0, window.clone()).unwrap().len(), 1);
+        assert!(state.windows().is_empty());
+    }
+
+    #[test]
+    pub fn multiple_rows_test() {
+        let mut state = MemoryListWindowState::new(5, 0);
+        for (key, value) in vec![(1, 3), (1, 7), (1, 5), (2, 1)] {
+            let (key, record) = build_record(key, value);
+            state.add(key, record);
+        }
+
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 10));
+        assert!(state.drop_window(&window, filter));
+
+        // 2 rows of the key 1 and no row of the key 2
+        let drop_state = remove_drop_window(5, 0, window.clone()).unwrap();
+        let mut rows: Vec<i64> = drop_state
+            .iter()
+            .map(|(_key, mut val)| get_value(&mut val))
+            .collect();
+        rows.sort();
+        assert_eq!(rows, vec![5, 7]);
+    }
+
+    #[test]
+    pub fn snapshot_restore_test() {
+        let mut state = MemoryListWindowState::new(6, 0);
+        for (key, value) in vec![(1, 3), (1, 7), (2, 5)] {
+            let (key, record) = build_record(key, value);
+            state.add(key, record);
+        }
+
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 10));
+        assert!(state.fire_window(&window, max));
+        remove_drop_window(6, 0, window.clone()).unwrap();
+
+        let mut bytes = BytesMut::new();
+        assert!(state.snapshot(Barrier::new(1), &mut bytes));
+        let mut restored_state = MemoryListWindowState::new(6, 0);
+        restored_state.restore(&mut bytes).unwrap();
+        assert!(bytes.is_empty());
+
+        assert_eq!(restored_state.windows(), vec![window.clone()]);
+        // the fired window is not emitted again until it is updated by a late record
+        assert!(!restored_state.fire_window(&window, max));
+
+        let (key, record) = build_record(1, 9);
+        restored_state.add(key, record);
+        assert!(restored_state.drop_window(&window, max));
+        let mut drop_state = remove_drop_window(6, 0, window.clone()).unwrap();
+        assert_eq!(drop_state.len(), 1);
+        let (key, _record) = build_record(1, 0);
+        assert_eq!(get_value(drop_state.get_mut(&key).unwrap()), 9);
+    }
+}
@@ -1,5 +1,4 @@
 use crate::api::element::Record;
-use crate::storage::keyed_state::mem_storage::remove_drop_window;
 use crate::storage::keyed_state::{ReducingState, StateIterator, StateKey};
 use std::collections::HashMap;
 
@@ -22,19 +21,22 @@ impl MemoryReducingState {
         }
     }
 
-    pub fn from(state_key: &StateKey) -> Option<MemoryReducingState> {
-        let state = remove_drop_window(
-            state_key.chain_id,
-            state_key.task_number,
-            state_key.window.clone(),
-        );
-        if state.is_some() {
-            debug!("remove state {:?}", state_key);
-        } else {
-            error!("can not found state {:?}", state_key);
-        }
+    pub fn get(&self, key: &Record) -> Option<&Record> {
+        self.kv.get(key)
+    }
 
-        state
+    /// transform all the values by the `f`
+    pub fn map_values<F>(self, f: F) -> Self
+    where
+        F: Fn(Record) -> Record,
+    {
+        MemoryReducingState {
+            kv: self
+                .kv
+                .into_iter()
+                .map(|(key, val)| (key, f(val)))
+                .collect(),
+        }
     }
 }
 
@@ -47,6 +49,10 @@ impl ReducingState for MemoryReducingState {
         self.kv.insert(key, val);
     }
 
+    fn remove(&mut self, key: &Record) -> Option<Record> {
+        self.kv.remove(key)
+    }
+
     fn flush(&mut self) {}
 
     fn snapshot(&mut self) {}
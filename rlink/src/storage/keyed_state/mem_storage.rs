@@ -1,11 +1,15 @@
 use crate::api::window::WindowWrap;
 use crate::runtime::ChainId;
-use crate::storage::keyed_state::mem_reducing_state::MemoryReducingState;
+use crate::storage::keyed_state::ReducingStateWrap;
 use dashmap::DashMap;
+use std::collections::VecDeque;
+
+/// the emitted window states of a task, which are consumed by the `MemChannelInputFormat`.
+/// the state is kept in the memory or in a RocksDB depends on the `KeyedStateBackend`
+type WindowStateQueue = DashMap<WindowWrap, VecDeque<ReducingStateWrap>>;
 
 lazy_static! {
-    static ref DROP_WINDOW_STATE_STORAGE: DashMap<StorageKey, DashMap<WindowWrap, MemoryReducingState>> =
-        DashMap::new();
+    static ref DROP_WINDOW_STATE_STORAGE: DashMap<StorageKey, WindowStateQueue> = DashMap::new();
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -26,28 +30,42 @@ impl StorageKey {
 pub(crate) fn append_drop_window(
     storage_key: StorageKey,
     window: WindowWrap,
-    state: MemoryReducingState,
+    state: ReducingStateWrap,
 ) {
-    let drop_window_states: &DashMap<StorageKey, DashMap<WindowWrap, MemoryReducingState>> =
-        &*DROP_WINDOW_STATE_STORAGE;
+    let drop_window_states: &DashMap<StorageKey, WindowStateQueue> = &*DROP_WINDOW_STATE_STORAGE;
 
     let task_storage = drop_window_states
         .entry(storage_key)
         .or_insert_with(|| DashMap::new());
-    task_storage.value().insert(window, state);
+
+    // the window may be emitted again(by the trigger or the late records)
+    // before the previous one is consumed, each emitted state is consumed in order
+    let mut window_states = task_storage
+        .value()
+        .entry(window)
+        .or_insert_with(|| VecDeque::new());
+    window_states.push_back(state);
 }
 
 pub(crate) fn remove_drop_window(
     chain_id: ChainId,
     task_number: u16,
     window: WindowWrap,
-) -> Option<MemoryReducingState> {
-    let drop_window_states: &DashMap<StorageKey, DashMap<WindowWrap, MemoryReducingState>> =
-        &*DROP_WINDOW_STATE_STORAGE;
+) -> Option<ReducingStateWrap> {
+    let drop_window_states: &DashMap<StorageKey, WindowStateQueue> = &*DROP_WINDOW_STATE_STORAGE;
 
     let key = StorageKey::new(chain_id, task_number);
     match drop_window_states.get(&key) {
-        Some(task_storage) => task_storage.value().remove(&window).map(|(_k, v)| v),
+        Some(task_storage) => {
+            let state = task_storage
+                .value()
+                .get_mut(&window)
+                .and_then(|mut states| states.value_mut().pop_front());
+            task_storage
+                .value()
+                .remove_if(&window, |_window, states| states.is_empty());
+            state
+        }
         None => None,
     }
 }
@@ -1,30 +1,40 @@
-use crate::api::element::{Barrier, Record};
+use crate::api::element::{Barrier, Record, Serde};
 use crate::api::window::WindowWrap;
 use crate::runtime::ChainId;
 use crate::storage::keyed_state::mem_reducing_state::MemoryReducingState;
 use crate::storage::keyed_state::mem_storage::{append_drop_window, StorageKey};
-use crate::storage::keyed_state::{ReducingState, StateKey, WindowState};
+use crate::storage::keyed_state::snapshot::{check_version, deserialize_window, serialize_window};
+use crate::storage::keyed_state::{ReducingState, ReducingStateWrap, StateKey, WindowState};
+use bytes::{Buf, BufMut, BytesMut};
 use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+const SNAPSHOT_VERSION: u8 = 1;
 
 #[derive(Clone, Debug)]
 pub struct MemoryWindowState {
-    job_id: String,
     chain_id: ChainId,
     task_number: u16,
 
     windows: HashMap<WindowWrap, MemoryReducingState>,
     suggest_state_capacity: usize,
+
+    /// the windows of each key, only used by the merging windows
+    key_windows: HashMap<Record, Vec<WindowWrap>>,
+    /// the windows have been fired but kept for the late records,
+    /// and the keys updated by the late records since the latest fire
+    fired_windows: HashMap<WindowWrap, HashSet<Record>>,
 }
 
 impl MemoryWindowState {
-    pub fn new(job_id: String, chain_id: ChainId, task_number: u16) -> Self {
+    pub fn new(chain_id: ChainId, task_number: u16) -> Self {
         MemoryWindowState {
-            job_id,
             chain_id,
             task_number,
             windows: HashMap::new(),
             suggest_state_capacity: 512,
+            key_windows: HashMap::new(),
+            fired_windows: HashMap::new(),
         }
     }
 
@@ -39,6 +49,10 @@ impl MemoryWindowState {
     {
         match self.windows.get_mut(window) {
             Some(state) => {
+                if let Some(late_keys) = self.fired_windows.get_mut(window) {
+                    late_keys.insert(key.clone());
+                }
+
                 let state_record = state.get_mut(&key);
                 let new_val = reduce_fun(state_record, record);
                 state.insert(key, new_val);
@@ -54,6 +68,109 @@ impl MemoryWindowState {
             }
         }
     }
+
+    /// serialize the windows, the late keys of the fired windows and the windows of each key
+    fn serialize(&self, bytes: &mut BytesMut) {
+        bytes.put_u8(SNAPSHOT_VERSION);
+
+        bytes.put_u32(self.windows.len() as u32);
+        for (window, state) in &self.windows {
+            serialize_window(window, bytes);
+
+            match self.fired_windows.get(window) {
+                Some(late_keys) => {
+                    bytes.put_u8(1);
+                    bytes.put_u32(late_keys.len() as u32);
+                    for key in late_keys {
+                        key.serialize(bytes);
+                    }
+                }
+                None => bytes.put_u8(0),
+            }
+
+            bytes.put_u32(state.len() as u32);
+            for (key, val) in state.iter() {
+                key.serialize(bytes);
+                val.serialize(bytes);
+            }
+        }
+
+        bytes.put_u32(self.key_windows.len() as u32);
+        for (key, windows) in &self.key_windows {
+            key.serialize(bytes);
+            bytes.put_u32(windows.len() as u32);
+            for window in windows {
+                serialize_window(window, bytes);
+            }
+        }
+    }
+
+    fn deserialize(&mut self, bytes: &mut BytesMut) -> std::io::Result<()> {
+        check_version(bytes, SNAPSHOT_VERSION)?;
+
+        self.windows.clear();
+        self.fired_windows.clear();
+        self.key_windows.clear();
+
+        let window_len = bytes.get_u32();
+        for _ in 0..window_len {
+            let window = deserialize_window(bytes);
+
+            if bytes.get_u8() == 1 {
+                let late_key_len = bytes.get_u32();
+                let mut late_keys = HashSet::with_capacity(late_key_len as usize);
+                for _ in 0..late_key_len {
+                    late_keys.insert(Record::deserialize(bytes));
+                }
+                self.fired_windows.insert(window.clone(), late_keys);
+            }
+
+            let state_len = bytes.get_u32() as usize;
+            let state_key = StateKey::new(window.clone(), self.chain_id, self.task_number);
+            let mut state = MemoryReducingState::new(&state_key, state_len);
+            for _ in 0..state_len {
+                let key = Record::deserialize(bytes);
+                let val = Record::deserialize(bytes);
+                state.insert(key, val);
+            }
+            self.windows.insert(window, state);
+        }
+
+        let key_len = bytes.get_u32();
+        for _ in 0..key_len {
+            let key = Record::deserialize(bytes);
+            let window_len = bytes.get_u32();
+            let windows = (0..window_len).map(|_| deserialize_window(bytes)).collect();
+            self.key_windows.insert(key, windows);
+        }
+
+        Ok(())
+    }
+
+    /// remove the `window` from the windows of each key in the `state`
+    fn remove_key_windows(&mut self, window: &WindowWrap, state: &MemoryReducingState) {
+        if self.key_windows.len() == 0 {
+            return;
+        }
+
+        for (key, _val) in state.iter() {
+            self.remove_key_window(&key, window);
+        }
+    }
+
+    /// remove the `window` from the windows of the `key`
+    fn remove_key_window(&mut self, key: &Record, window: &WindowWrap) {
+        let empty = match self.key_windows.get_mut(key) {
+            Some(key_windows) => {
+                key_windows.retain(|w| !w.eq(window));
+                key_windows.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            self.key_windows.remove(key);
+        }
+    }
 }
 
 impl WindowState for MemoryWindowState {
@@ -84,24 +201,244 @@ impl WindowState for MemoryWindowState {
         }
     }
 
-    fn drop_window(&mut self, window: &WindowWrap) {
+    fn merge_windows<F, M>(&mut self, key: Record, mut record: Record, reduce_fun: F, merge_fun: M)
+    where
+        F: Fn(Option<&mut Record>, &mut Record) -> Record,
+        M: Fn(Option<&mut Record>, &mut Record) -> Record,
+    {
+        let window = match record.get_min_location_windows() {
+            Some(window) => window.clone(),
+            None => return,
+        };
+
+        let key_windows = self.key_windows.remove(&key).unwrap_or_default();
+
+        let mut merged_window = window.clone();
+        let mut merged_value: Option<Record> = None;
+        let mut remain_windows = Vec::with_capacity(key_windows.len() + 1);
+        for key_window in key_windows {
+            if !key_window.intersects(&window) {
+                remain_windows.push(key_window);
+                continue;
+            }
+
+            merged_window = merged_window.cover(&key_window);
+
+            let value = match self.windows.get_mut(&key_window) {
+                Some(state) => {
+                    let value = state.remove(&key);
+                    if state.len() == 0 {
+                        self.windows.remove(&key_window);
+                        self.fired_windows.remove(&key_window);
+                    }
+                    value
+                }
+                None => None,
+            };
+
+            if let Some(mut value) = value {
+                merged_value = match merged_value {
+                    Some(mut merged_value) => Some(merge_fun(Some(&mut merged_value), &mut value)),
+                    None => Some(value),
+                };
+            }
+        }
+
+        let new_val = reduce_fun(merged_value.as_mut(), record.borrow_mut());
+
+        let state_key = StateKey::new(merged_window.clone(), self.chain_id, self.task_number);
+        let suggest_state_capacity = self.suggest_state_capacity;
+        self.windows
+            .entry(merged_window.clone())
+            .or_insert_with(|| MemoryReducingState::new(&state_key, suggest_state_capacity))
+            .insert(key.clone(), new_val);
+
+        remain_windows.push(merged_window);
+        self.key_windows.insert(key, remain_windows);
+    }
+
+    fn fire_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        let state = match self.windows.get(window) {
+            Some(state) => state,
+            None => return false,
+        };
+
+        let fire_state = match self.fired_windows.get_mut(window) {
+            Some(late_keys) => {
+                if late_keys.is_empty() {
+                    return false;
+                }
+
+                let state_key = StateKey::new(window.clone(), self.chain_id, self.task_number);
+                let mut fire_state = MemoryReducingState::new(&state_key, late_keys.len());
+                for key in late_keys.drain() {
+                    if let Some(val) = state.get(&key).map(|val| val.clone()) {
+                        fire_state.insert(key, result_fun(val));
+                    }
+                }
+                fire_state
+            }
+            None => {
+                self.fired_windows.insert(window.clone(), HashSet::new());
+                state.clone().map_values(result_fun)
+            }
+        };
+
+        let state_key = StorageKey::new(self.chain_id, self.task_number);
+        append_drop_window(
+            state_key,
+            window.clone(),
+            ReducingStateWrap::MemoryReducingState(fire_state),
+        );
+
+        true
+    }
+
+    fn drop_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
         match self.windows.remove(&window) {
-            Some(state) => {
+            Some(mut state) => {
                 let len = state.len() as f32;
                 self.suggest_state_capacity = (len * 1.1f32) as usize;
 
+                self.remove_key_windows(window, &state);
+
+                if let Some(late_keys) = self.fired_windows.remove(window) {
+                    // only the keys updated by the late records have not been emitted
+                    let keys: Vec<Record> = state
+                        .iter()
+                        .map(|(key, _val)| key)
+                        .filter(|key| !late_keys.contains(key))
+                        .collect();
+                    for key in keys {
+                        state.remove(&key);
+                    }
+                }
+
+                if state.len() == 0 {
+                    return false;
+                }
+
                 let state_key = StorageKey::new(self.chain_id, self.task_number);
-                append_drop_window(state_key, window.clone(), state);
+                let state = state.map_values(result_fun);
+                append_drop_window(
+                    state_key,
+                    window.clone(),
+                    ReducingStateWrap::MemoryReducingState(state),
+                );
+
+                true
             }
-            None => {}
+            None => false,
+        }
+    }
+
+    fn keys(&self, window: &WindowWrap) -> Vec<Record> {
+        match self.windows.get(window) {
+            Some(state) => state.iter().map(|(key, _val)| key).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn fire_keys<R>(&mut self, window: &WindowWrap, keys: &[Record], result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        let state = match self.windows.get(window) {
+            Some(state) => state,
+            None => return false,
+        };
+
+        let state_key = StateKey::new(window.clone(), self.chain_id, self.task_number);
+        let mut fire_state = MemoryReducingState::new(&state_key, keys.len());
+        for key in keys {
+            if let Some(val) = state.get(key) {
+                fire_state.insert(key.clone(), result_fun(val.clone()));
+            }
+        }
+
+        // the keys are up to date in the downstream, they are not late keys anymore
+        if let Some(late_keys) = self.fired_windows.get_mut(window) {
+            for key in keys {
+                late_keys.remove(key);
+            }
+        }
+
+        if fire_state.len() == 0 {
+            return false;
+        }
+
+        let state_key = StorageKey::new(self.chain_id, self.task_number);
+        append_drop_window(
+            state_key,
+            window.clone(),
+            ReducingStateWrap::MemoryReducingState(fire_state),
+        );
+
+        true
+    }
+
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]) {
+        let empty = match self.windows.get_mut(window) {
+            Some(state) => {
+                for key in keys {
+                    state.remove(key);
+                }
+                state.len() == 0
+            }
+            None => return,
         };
+
+        if let Some(late_keys) = self.fired_windows.get_mut(window) {
+            for key in keys {
+                late_keys.remove(key);
+            }
+        }
+        if self.key_windows.len() > 0 {
+            for key in keys {
+                self.remove_key_window(key, window);
+            }
+        }
+
+        if empty {
+            self.windows.remove(window);
+            self.fired_windows.remove(window);
+        }
+    }
+
+    fn snapshot(&mut self, barrier: Barrier, bytes: &mut BytesMut) -> bool {
+        self.serialize(bytes);
+        info!(
+            "snapshot {} windows, checkpoint_id={}",
+            self.windows.len(),
+            barrier.checkpoint_id
+        );
+
+        true
     }
 
-    fn snapshot(&mut self, _barrier: Barrier) {}
+    fn restore(&mut self, bytes: &mut BytesMut) -> std::io::Result<()> {
+        self.deserialize(bytes)?;
+        info!("restore {} windows", self.windows.len());
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::api::element::{types, Barrier, Record};
+    use crate::api::window::{TimeWindow, Window, WindowWrap};
+    use crate::storage::keyed_state::mem_storage::remove_drop_window;
+    use crate::storage::keyed_state::mem_window_state::MemoryWindowState;
+    use crate::storage::keyed_state::{ReducingState, WindowState};
+    use bytes::BytesMut;
+
     #[test]
     pub fn dash_map_test() {
         let map = dashmap::DashMap::new();
@@ -110,4 +447,189 @@ mod tests {
         assert_eq!(map.len(), 1);
         assert_eq!(map.get("a").unwrap().value().clone(), 1);
     }
+
+    fn build_record(key: i64, timestamp: u64) -> (Record, Record) {
+        let mut key_record = Record::new();
+        key_record.get_writer(&[types::I64]).set_i64(key).unwrap();
+
+        let mut record = Record::new();
+        record.get_writer(&[types::I64]).set_i64(1).unwrap();
+        record.set_location_windows(vec![WindowWrap::TimeWindow(TimeWindow::new(
+            timestamp,
+            timestamp + 10,
+        ))]);
+
+        (key_record, record)
+    }
+
+    fn count(value: Option<&mut Record>, record: &mut Record) -> Record {
+        let n = record.get_reader(&[types::I64]).get_i64(0).unwrap();
+        let n = n + value
+            .map(|value| value.get_reader(&[types::I64]).get_i64(0).unwrap())
+            .unwrap_or(0);
+
+        let mut value = Record::new();
+        value.get_writer(&[types::I64]).set_i64(n).unwrap();
+        value
+    }
+
+    fn identity(value: Record) -> Record {
+        value
+    }
+
+    #[test]
+    pub fn merge_windows_test() {
+        let mut state = MemoryWindowState::new(1, 0);
+        for (key, timestamp) in vec![(1, 0), (1, 25), (2, 5), (1, 8), (1, 16)] {
+            let (key, record) = build_record(key, timestamp);
+            state.merge_windows(key, record, count, count);
+        }
+
+        let mut windows = state.windows();
+        windows.sort_by_key(|w| w.min_timestamp());
+        assert_eq!(
+            windows,
+            vec![
+                WindowWrap::TimeWindow(TimeWindow::new(0, 35)),
+                WindowWrap::TimeWindow(TimeWindow::new(5, 15)),
+            ]
+        );
+
+        let (key, _record) = build_record(1, 0);
+        let value = state.windows.get_mut(&windows[0]).unwrap().get_mut(&key);
+        assert_eq!(
+            value.unwrap().get_reader(&[types::I64]).get_i64(0).unwrap(),
+            4
+        );
+
+        state.drop_window(&windows[0], identity);
+        assert_eq!(state.key_windows.len(), 1);
+    }
+
+    #[test]
+    pub fn fire_window_test() {
+        let mut state = MemoryWindowState::new(2, 0);
+        for key in vec![1, 2] {
+            let (key, record) = build_record(key, 0);
+            state.merge(key, record, count);
+        }
+
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 10));
+        assert!(state.fire_window(&window, identity));
+        assert_eq!(remove_drop_window(2, 0, window.clone()).unwrap().len(), 2);
+
+        // nothing updated since the latest fire
+        assert!(!state.fire_window(&window, identity));
+
+        let (key, record) = build_record(1, 0);
+        state.merge(key, record, count);
+        assert!(state.fire_window(&window, identity));
+        assert_eq!(remove_drop_window(2, 0, window.clone()).unwrap().len(), 1);
+
+        assert!(!state.drop_window(&window, identity));
+        assert!(state.windows().is_empty());
+    }
+
+    #[test]
+    pub fn purge_window_test() {
+        let mut state = MemoryWindowState::new(3, 0);
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 10));
+
+        let (key, record) = build_record(1, 0);
+        state.merge(key, record, count);
+        assert!(state.drop_window(&window, identity));
+
+        // the window is emitted again before the previous one is consumed
+        for key in vec![1, 2] {
+            let (key, record) = build_record(key, 0);
+            state.merge(key, record, count);
+        }
+        assert!(state.drop_window(&window, identity));
+
+        assert_eq!(remove_drop_window(3, 0, window.clone()).unwrap().len(), 1);
+        assert_eq!(remove_drop_window(3, 0, window.clone()).unwrap().len(), 2);
+        assert!(remove_drop_window(3, 0, window.clone()).is_none());
+
+        let (key, record) = build_record(1, 0);
+        state.merge(key, record, count);
+        let keys = state.keys(&window);
+        state.purge_keys(&window, keys.as_slice());
+        assert!(state.windows().is_empty());
+        assert!(remove_drop_window(3, 0, window.clone()).is_none());
+    }
+
+    #[test]
+    pub fn fire_keys_test() {
+        let mut state = MemoryWindowState::new(5, 0);
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 10));
+        for key in vec![1, 1, 2] {
+            let (key, record) = build_record(key, 0);
+            state.merge(key, record, count);
+        }
+
+        // only the fired key is emitted, and the window is kept
+        let (key1, _record) = build_record(1, 0);
+        assert!(state.fire_keys(&window, &[key1.clone()], identity));
+        let mut fire_state = remove_drop_window(5, 0, window.clone()).unwrap();
+        assert_eq!(fire_state.len(), 1);
+        let value = fire_state.get_mut(&key1).unwrap();
+        assert_eq!(value.get_reader(&[types::I64]).get_i64(0).unwrap(), 2);
+
+        // the purged key is not emitted by the window
+        state.purge_keys(&window, &[key1.clone()]);
+        assert_eq!(state.keys(&window).len(), 1);
+        assert!(state.drop_window(&window, identity));
+        let mut drop_state = remove_drop_window(5, 0, window.clone()).unwrap();
+        assert_eq!(drop_state.len(), 1);
+        assert!(drop_state.get_mut(&key1).is_none());
+    }
+
+    #[test]
+    pub fn snapshot_restore_test() {
+        let mut state = MemoryWindowState::new(4, 0);
+        for (key, timestamp) in vec![(1, 0), (1, 5), (2, 20)] {
+            let (key, record) = build_record(key, timestamp);
+            state.merge_windows(key, record, count, count);
+        }
+
+        let window = WindowWrap::TimeWindow(TimeWindow::new(20, 30));
+        assert!(state.fire_window(&window, identity));
+        assert_eq!(remove_drop_window(4, 0, window.clone()).unwrap().len(), 1);
+
+        let mut bytes = BytesMut::new();
+        assert!(state.snapshot(Barrier::new(1), &mut bytes));
+
+        let mut restored_state = MemoryWindowState::new(4, 0);
+        restored_state.restore(&mut bytes).unwrap();
+        assert!(bytes.is_empty());
+
+        // a snapshot of another version is rejected
+        let mut bytes = BytesMut::from(&[0u8][..]);
+        assert!(MemoryWindowState::new(4, 0).restore(&mut bytes).is_err());
+
+        let mut windows = restored_state.windows();
+        windows.sort_by_key(|w| w.min_timestamp());
+        assert_eq!(
+            windows,
+            vec![
+                WindowWrap::TimeWindow(TimeWindow::new(0, 15)),
+                window.clone()
+            ]
+        );
+        assert_eq!(restored_state.key_windows.len(), 2);
+
+        let (key, _record) = build_record(1, 0);
+        let value = restored_state
+            .windows
+            .get_mut(&windows[0])
+            .unwrap()
+            .get_mut(&key);
+        assert_eq!(
+            value.unwrap().get_reader(&[types::I64]).get_i64(0).unwrap(),
+            2
+        );
+
+        // the fired window is not emitted again until it is updated by a late record
+        assert!(!restored_state.fire_window(&window, identity));
+    }
 }
@@ -1,16 +1,34 @@
 use crate::api::backend::KeyedStateBackend;
 use crate::api::element::{Barrier, Record};
 use crate::api::window::WindowWrap;
+use crate::storage::keyed_state::mem_list_state::MemoryListState;
+use crate::storage::keyed_state::mem_list_window_state::MemoryListWindowState;
 use crate::storage::keyed_state::mem_reducing_state::MemoryReducingState;
+use crate::storage::keyed_state::mem_storage::remove_drop_window;
 use crate::storage::keyed_state::mem_window_state::MemoryWindowState;
+#[cfg(feature = "rocksdb")]
+use crate::storage::keyed_state::rocksdb_reducing_state::{
+    record_from_bytes, RocksDBReducingState,
+};
+#[cfg(feature = "rocksdb")]
+use crate::storage::keyed_state::rocksdb_window_state::RocksDBWindowState;
+use bytes::BytesMut;
+#[cfg(feature = "rocksdb")]
+use rocksdb::DBIterator;
 use std::collections::hash_map::Iter;
 use std::fmt::Debug;
 
+pub mod mem_keyed_state;
+pub mod mem_list_state;
+pub mod mem_list_window_state;
 pub mod mem_reducing_state;
 pub mod mem_storage;
 pub mod mem_window_state;
-// pub mod rocksdb_reducing_state;
-// pub mod rocksdb_window_state;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb_reducing_state;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb_window_state;
+pub mod snapshot;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct StateKey {
@@ -42,7 +60,10 @@ impl StateKey {
 
 pub enum StateIterator<'a> {
     HashMap(Iter<'a, Record, Record>),
-    // RocksDB(DBIterator<'a>),
+    /// each value of the key is iterated as a (key, value) pair
+    List(Box<dyn Iterator<Item = (Record, Record)> + 'a>),
+    #[cfg(feature = "rocksdb")]
+    RocksDB(DBIterator<'a>),
 }
 
 impl<'a> Iterator for StateIterator<'a> {
@@ -53,15 +74,11 @@ impl<'a> Iterator for StateIterator<'a> {
             StateIterator::HashMap(iter) => {
                 iter.next().map(|(key, val)| (key.clone(), val.clone()))
             }
-            // StateIterator::RocksDB(iter) => iter.next().map(|kv| {
-            //     let mut bytes_mut = BytesMut::from(&*(kv.0));
-            //     let key = Record::deserialize(bytes_mut.borrow_mut());
-            // 
-            //     let mut bytes_mut1 = BytesMut::from(&*(kv.1));
-            //     let val = Record::deserialize(bytes_mut1.borrow_mut());
-            // 
-            //     (key, val)
-            // }),
+            StateIterator::List(iter) => iter.next(),
+            #[cfg(feature = "rocksdb")]
+            StateIterator::RocksDB(iter) => iter
+                .next()
+                .map(|(key, val)| (record_from_bytes(&key), record_from_bytes(&val))),
         }
     }
 }
@@ -70,6 +87,7 @@ impl<'a> Iterator for StateIterator<'a> {
 pub trait ReducingState: Debug {
     fn get_mut(&mut self, key: &Record) -> Option<&mut Record>;
     fn insert(&mut self, key: Record, val: Record);
+    fn remove(&mut self, key: &Record) -> Option<Record>;
     fn flush(&mut self);
     fn snapshot(&mut self);
     fn close(self);
@@ -78,23 +96,39 @@ pub trait ReducingState: Debug {
     fn len(&self) -> usize;
 }
 
+/// See flink `ListState`
+pub trait ListState: Debug {
+    fn add(&mut self, key: Record, val: Record);
+    fn get_mut(&mut self, key: &Record) -> Option<&mut Vec<Record>>;
+    fn remove(&mut self, key: &Record) -> Option<Vec<Record>>;
+    fn keys(&self) -> Vec<Record>;
+    fn len(&self) -> usize;
+}
+
 #[derive(Debug)]
 pub enum ReducingStateWrap {
     MemoryReducingState(MemoryReducingState),
-    // RocksDBReducingState(RocksDBReducingState),
+    /// the emitted results of the `ProcessWindowFunction`, each key has 0..n values
+    MemoryListState(MemoryListState),
+    #[cfg(feature = "rocksdb")]
+    RocksDBReducingState(RocksDBReducingState),
 }
 
 impl ReducingStateWrap {
-    pub fn new(state_key: &StateKey, mode: KeyedStateBackend) -> Option<ReducingStateWrap> {
-        match mode {
-            KeyedStateBackend::Memory => MemoryReducingState::from(state_key)
-                .map(|state| ReducingStateWrap::MemoryReducingState(state)),
-            // KeyedStateBackend::RocksDBStateBackend(backend_path) => {
-            //     Some(ReducingStateWrap::RocksDBReducingState(
-            //         RocksDBReducingState::new(state_key, backend_path.as_str(), true),
-            //     ))
-            // }
+    /// take the earliest emitted state of the window, which is emitted by the `WindowState`
+    pub fn from(state_key: &StateKey) -> Option<ReducingStateWrap> {
+        let state = remove_drop_window(
+            state_key.chain_id,
+            state_key.task_number,
+            state_key.window.clone(),
+        );
+        if state.is_some() {
+            debug!("remove state {:?}", state_key);
+        } else {
+            error!("can not found state {:?}", state_key);
         }
+
+        state
     }
 }
 
@@ -102,56 +136,85 @@ impl ReducingState for ReducingStateWrap {
     fn get_mut(&mut self, key: &Record) -> Option<&mut Record> {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.get_mut(key),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.get(key),
+            ReducingStateWrap::MemoryListState(state) => {
+                state.get_mut(key).and_then(|values| values.last_mut())
+            }
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.get_mut(key),
         }
     }
 
     fn insert(&mut self, key: Record, val: Record) {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.insert(key, val),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.insert(key, val),
+            ReducingStateWrap::MemoryListState(state) => state.add(key, val),
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.insert(key, val),
+        }
+    }
+
+    fn remove(&mut self, key: &Record) -> Option<Record> {
+        match self {
+            ReducingStateWrap::MemoryReducingState(state) => state.remove(key),
+            ReducingStateWrap::MemoryListState(state) => {
+                state.remove(key).and_then(|mut values| values.pop())
+            }
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.remove(key),
         }
     }
 
     fn flush(&mut self) {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.flush(),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.flush(),
+            ReducingStateWrap::MemoryListState(_state) => {}
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.flush(),
         }
     }
 
     fn snapshot(&mut self) {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.snapshot(),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.snapshot(),
+            ReducingStateWrap::MemoryListState(_state) => {}
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.snapshot(),
         }
     }
 
     fn close(self) {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.close(),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.close(),
+            ReducingStateWrap::MemoryListState(_state) => {}
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.close(),
         }
     }
 
     fn destroy(self) {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.destroy(),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.destroy(),
+            ReducingStateWrap::MemoryListState(_state) => {}
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.destroy(),
         }
     }
 
     fn iter(&self) -> StateIterator {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.iter(),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.iter(),
+            ReducingStateWrap::MemoryListState(state) => state.iter(),
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.iter(),
         }
     }
 
     fn len(&self) -> usize {
         match self {
             ReducingStateWrap::MemoryReducingState(state) => state.len(),
-            // ReducingStateWrap::RocksDBReducingState(state) => state.capacity(),
+            ReducingStateWrap::MemoryListState(state) => state.len(),
+            #[cfg(feature = "rocksdb")]
+            ReducingStateWrap::RocksDBReducingState(state) => state.len(),
         }
     }
 }
@@ -168,26 +231,71 @@ pub trait WindowState: Debug {
     where
         F: Fn(Option<&mut Record>, &mut Record) -> Record;
 
-    fn drop_window(&mut self, window: &WindowWrap);
+    /// merge the record into the window of the key, and all the windows of the key
+    /// which intersect it are merged into one window by the `merge_fun`.
+    /// used by the merging window assigner, eg: session windows
+    fn merge_windows<F, M>(&mut self, key: Record, record: Record, reduce_fun: F, merge_fun: M)
+    where
+        F: Fn(Option<&mut Record>, &mut Record) -> Record,
+        M: Fn(Option<&mut Record>, &mut Record) -> Record;
+
+    /// emit the window state to downstream and keep it for the late records.
+    /// the whole window is emitted at the first time, after then only the keys updated
+    /// by the late records are emitted.
+    /// the emitted values are materialized by the `result_fun`.
+    /// returns `true` if there is any state emitted
+    fn fire_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record;
+
+    /// emit the window state which has not been emitted to downstream and remove the window.
+    /// the emitted values are materialized by the `result_fun`.
+    /// returns `true` if there is any state emitted
+    fn drop_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record;
 
-    fn snapshot(&mut self, barrier: Barrier);
+    /// the keys of the window
+    fn keys(&self, window: &WindowWrap) -> Vec<Record>;
+
+    /// emit the state of the `keys` in the window to downstream and keep it,
+    /// used by the `Trigger` which fires each key of the window separately.
+    /// returns `true` if there is any state emitted
+    fn fire_keys<R>(&mut self, window: &WindowWrap, keys: &[Record], result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record;
+
+    /// remove the `keys` from the window without emitting any state,
+    /// the window is removed if there is no key left
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]);
+
+    /// write the state at the `barrier` to the `bytes`.
+    /// returns `false` if the state can't be restored from the snapshot
+    fn snapshot(&mut self, barrier: Barrier, bytes: &mut BytesMut) -> bool;
+
+    /// replace the state by the snapshot written by `snapshot`
+    fn restore(&mut self, bytes: &mut BytesMut) -> std::io::Result<()>;
 }
 
 #[derive(Debug)]
 pub enum WindowStateWrap {
     MemoryWindowState(MemoryWindowState),
-    // RocksDBWindowState(RocksDBWindowState),
+    #[cfg(feature = "rocksdb")]
+    RocksDBWindowState(RocksDBWindowState),
 }
 
 impl WindowStateWrap {
+    // the `job_id` is only used by the RocksDB
+    #[allow(unused_variables)]
     pub fn new(job_id: String, chain_id: u32, task_number: u16, mode: KeyedStateBackend) -> Self {
         match mode {
-            KeyedStateBackend::Memory => WindowStateWrap::MemoryWindowState(
-                MemoryWindowState::new(job_id, chain_id, task_number),
+            KeyedStateBackend::Memory => {
+                WindowStateWrap::MemoryWindowState(MemoryWindowState::new(chain_id, task_number))
+            }
+            #[cfg(feature = "rocksdb")]
+            KeyedStateBackend::RocksDB { path } => WindowStateWrap::RocksDBWindowState(
+                RocksDBWindowState::new(job_id, chain_id, task_number, path),
             ),
-            // KeyedStateBackend::RocksDBStateBackend(path) => WindowStateWrap::RocksDBWindowState(
-            //     RocksDBWindowState::new(job_id, chain_id, task_number, path),
-            // ),
         }
     }
 }
@@ -196,7 +304,8 @@ impl WindowState for WindowStateWrap {
     fn windows(&self) -> Vec<WindowWrap> {
         match self {
             WindowStateWrap::MemoryWindowState(state) => state.windows(),
-            // WindowStateWrap::RocksDBWindowState(state) => state.windows(),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.windows(),
         }
     }
 
@@ -205,23 +314,230 @@ impl WindowState for WindowStateWrap {
         F: Fn(Option<&mut Record>, &mut Record) -> Record,
     {
         match self {
-            WindowStateWrap::MemoryWindowState(state) => state.merge(key, record, reduce_fun), // WindowStateWrap::RocksDBWindowState(state) => {
-                                                                                               //     state.merge(windows, key, record, reduce_fun)
-                                                                                               // }
+            WindowStateWrap::MemoryWindowState(state) => state.merge(key, record, reduce_fun),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.merge(key, record, reduce_fun),
+        }
+    }
+
+    fn merge_windows<F, M>(&mut self, key: Record, record: Record, reduce_fun: F, merge_fun: M)
+    where
+        F: Fn(Option<&mut Record>, &mut Record) -> Record,
+        M: Fn(Option<&mut Record>, &mut Record) -> Record,
+    {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => {
+                state.merge_windows(key, record, reduce_fun, merge_fun)
+            }
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => {
+                state.merge_windows(key, record, reduce_fun, merge_fun)
+            }
+        }
+    }
+
+    fn fire_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => state.fire_window(window, result_fun),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.fire_window(window, result_fun),
+        }
+    }
+
+    fn drop_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => state.drop_window(window, result_fun),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.drop_window(window, result_fun),
+        }
+    }
+
+    fn keys(&self, window: &WindowWrap) -> Vec<Record> {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => state.keys(window),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.keys(window),
+        }
+    }
+
+    fn fire_keys<R>(&mut self, window: &WindowWrap, keys: &[Record], result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => state.fire_keys(window, keys, result_fun),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.fire_keys(window, keys, result_fun),
+        }
+    }
+
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]) {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => state.purge_keys(window, keys),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.purge_keys(window, keys),
+        }
+    }
+
+    fn snapshot(&mut self, barrier: Barrier, bytes: &mut BytesMut) -> bool {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => state.snapshot(barrier, bytes),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.snapshot(barrier, bytes),
+        }
+    }
+
+    fn restore(&mut self, bytes: &mut BytesMut) -> std::io::Result<()> {
+        match self {
+            WindowStateWrap::MemoryWindowState(state) => state.restore(bytes),
+            #[cfg(feature = "rocksdb")]
+            WindowStateWrap::RocksDBWindowState(state) => state.restore(bytes),
+        }
+    }
+}
+
+/// the window state which buffers all the records of each key,
+/// the window is evaluated by the `ProcessWindowFunction` when it is emitted.
+pub trait ListWindowState: Debug {
+    fn windows(&self) -> Vec<WindowWrap>;
+
+    /// append the record to each location window of the record
+    fn add(&mut self, key: Record, record: Record);
+
+    /// evaluate the window by the `process_fun` and emit the results to downstream,
+    /// the buffered records are kept for the late records.
+    /// the whole window is emitted at the first time, after then only the keys updated
+    /// by the late records are emitted.
+    /// returns `true` if there is any result emitted
+    fn fire_window<F>(&mut self, window: &WindowWrap, process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>;
+
+    /// evaluate the keys which have not been emitted by the `process_fun`,
+    /// emit the results to downstream and remove the window.
+    /// returns `true` if there is any result emitted
+    fn drop_window<F>(&mut self, window: &WindowWrap, process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>;
+
+    /// the keys of the window
+    fn keys(&self, window: &WindowWrap) -> Vec<Record>;
+
+    /// evaluate the `keys` of the window by the `process_fun` and emit the results
+    /// to downstream, the buffered records are kept.
+    /// returns `true` if there is any result emitted
+    fn fire_keys<F>(&mut self, window: &WindowWrap, keys: &[Record], process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>;
+
+    /// remove the `keys` from the window without emitting any result,
+    /// the window is removed if there is no key left
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]);
+
+    /// write the buffered records at the `barrier` to the `bytes`.
+    /// returns `false` if the state can't be restored from the snapshot
+    fn snapshot(&mut self, barrier: Barrier, bytes: &mut BytesMut) -> bool;
+
+    /// replace the state by the snapshot written by `snapshot`
+    fn restore(&mut self, bytes: &mut BytesMut) -> std::io::Result<()>;
+}
+
+#[derive(Debug)]
+pub enum ListWindowStateWrap {
+    MemoryListWindowState(MemoryListWindowState),
+}
+
+impl ListWindowStateWrap {
+    pub fn new(chain_id: u32, task_number: u16, mode: KeyedStateBackend) -> Self {
+        match mode {
+            KeyedStateBackend::Memory => ListWindowStateWrap::MemoryListWindowState(
+                MemoryListWindowState::new(chain_id, task_number),
+            ),
+            #[cfg(feature = "rocksdb")]
+            KeyedStateBackend::RocksDB { .. } => {
+                warn!("the `ProcessWindowFunction` state is not supported by RocksDB, use Memory");
+                ListWindowStateWrap::MemoryListWindowState(MemoryListWindowState::new(
+                    chain_id,
+                    task_number,
+                ))
+            }
+        }
+    }
+}
+
+impl ListWindowState for ListWindowStateWrap {
+    fn windows(&self) -> Vec<WindowWrap> {
+        match self {
+            ListWindowStateWrap::MemoryListWindowState(state) => state.windows(),
+        }
+    }
+
+    fn add(&mut self, key: Record, record: Record) {
+        match self {
+            ListWindowStateWrap::MemoryListWindowState(state) => state.add(key, record),
+        }
+    }
+
+    fn fire_window<F>(&mut self, window: &WindowWrap, process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>,
+    {
+        match self {
+            ListWindowStateWrap::MemoryListWindowState(state) => {
+                state.fire_window(window, process_fun)
+            }
+        }
+    }
+
+    fn drop_window<F>(&mut self, window: &WindowWrap, process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>,
+    {
+        match self {
+            ListWindowStateWrap::MemoryListWindowState(state) => {
+                state.drop_window(window, process_fun)
+            }
+        }
+    }
+
+    fn keys(&self, window: &WindowWrap) -> Vec<Record> {
+        match self {
+            ListWindowStateWrap::MemoryListWindowState(state) => state.keys(window),
+        }
+    }
+
+    fn fire_keys<F>(&mut self, window: &WindowWrap, keys: &[Record], process_fun: F) -> bool
+    where
+        F: Fn(&Record, &WindowWrap, &mut Vec<Record>) -> Vec<Record>,
+    {
+        match self {
+            ListWindowStateWrap::MemoryListWindowState(state) => {
+                state.fire_keys(window, keys, process_fun)
+            }
+        }
+    }
+
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]) {
+        match self {
+            ListWindowStateWrap::MemoryListWindowState(state) => state.purge_keys(window, keys),
         }
     }
 
-    fn drop_window(&mut self, window: &WindowWrap) {
+    fn snapshot(&mut self, barrier: Barrier, bytes: &mut BytesMut) -> bool {
         match self {
-            WindowStateWrap::MemoryWindowState(state) => state.drop_window(window),
-            // WindowStateWrap::RocksDBWindowState(state) => state.drop_window(window),
+            ListWindowStateWrap::MemoryListWindowState(state) => state.snapshot(barrier, bytes),
         }
     }
 
-    fn snapshot(&mut self, barrier: Barrier) {
+    fn restore(&mut self, bytes: &mut BytesMut) -> std::io::Result<()> {
         match self {
-            WindowStateWrap::MemoryWindowState(state) => state.snapshot(barrier),
-            // WindowStateWrap::RocksDBWindowState(state) => state.snapshot(barrier),
+            ListWindowStateWrap::MemoryListWindowState(state) => state.restore(bytes),
         }
     }
 }
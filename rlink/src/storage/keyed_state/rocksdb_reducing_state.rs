@@ -1,75 +1,73 @@
-use crate::api::element::{Element, Record, Serde};
+use crate::api::element::{Record, Serde};
 use crate::storage::keyed_state::{ReducingState, StateIterator, StateKey};
-use crate::utils;
 use bytes::BytesMut;
-// use metrics::counter;
-use rocksdb::checkpoint::Checkpoint;
 use rocksdb::{BlockBasedOptions, IteratorMode, Options, WriteBatch, WriteOptions, DB};
 use std::borrow::BorrowMut;
-use std::iter::FromIterator;
 use std::path::PathBuf;
 
 #[inline]
-fn get_db_path(state_key: &StateKey) -> PathBuf {
-    utils::get_work_space().join(format!("state/{}.db", state_key.to_string()))
+pub(crate) fn record_to_bytes(record: &Record) -> Vec<u8> {
+    record.to_bytes().to_vec()
 }
 
 #[inline]
-fn get_checkpoint_path(state_key: &StateKey) -> PathBuf {
-    utils::get_work_space().join(format!("state/{}.ckp", state_key.to_string()))
+pub(crate) fn record_from_bytes(bytes: &[u8]) -> Record {
+    let mut bytes_mut = BytesMut::from(bytes);
+    Record::deserialize(bytes_mut.borrow_mut())
 }
 
 #[derive(Debug)]
 pub struct RocksDBReducingState {
     state_key: StateKey,
-    backend_path: String,
+    path: PathBuf,
 
     db: DB,
+    len: usize,
+    /// the value read by `get_mut`, the changes must be written back by `insert`
+    value_buf: Option<Record>,
 }
 
 impl RocksDBReducingState {
-    pub fn new(state_key: &StateKey, backend_path: &str, read_only: bool) -> Self {
-        let path = get_db_path(state_key);
+    pub fn new(state_key: &StateKey, path: PathBuf) -> Self {
+        info!("Try to open RocksDB {:?} in {:?}", state_key, path);
 
-        info!("Try to open RocksDB [readonly={}] in {:?}", read_only, path);
+        let mut block_opts = BlockBasedOptions::default();
+        block_opts.set_block_size(128 * 1024); // 128K
+        block_opts.set_lru_cache(4 * 1024 * 1024); // 4M
 
         let mut db_opts = Options::default();
-        let db = if read_only {
-            DB::open_for_read_only(&db_opts, path.clone(), true).unwrap_or_else(|e| {
-                panic!("open RocksDB({:?}) error.{}", path, e);
-            })
-        } else {
-            let mut block_opts = BlockBasedOptions::default();
-            block_opts.set_block_size(128 * 1024); // 128K
-            block_opts.set_lru_cache(4 * 1024 * 1024); // 16M
-
-            db_opts.create_missing_column_families(true);
-            db_opts.create_if_missing(true);
-            db_opts.set_use_fsync(false);
-            db_opts.set_write_buffer_size(16 * 1024 * 1024);
-            db_opts.set_max_background_compactions(1);
-            db_opts.set_max_background_flushes(1);
-            db_opts.set_block_based_table_factory(&block_opts);
-            DB::open(&db_opts, path.clone()).unwrap_or_else(|e| {
-                panic!("open RocksDB({:?}) error.{}", path, e);
-            })
-        };
+        db_opts.create_missing_column_families(true);
+        db_opts.create_if_missing(true);
+        db_opts.set_use_fsync(false);
+        db_opts.set_write_buffer_size(16 * 1024 * 1024);
+        db_opts.set_max_background_compactions(1);
+        db_opts.set_max_background_flushes(1);
+        db_opts.set_block_based_table_factory(&block_opts);
+        // the RocksDB doesn't create the parent dirs of the db
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap_or_else(|e| {
+                panic!("create RocksDB dir({:?}) error.{}", parent, e);
+            });
+        }
+
+        let db = DB::open(&db_opts, path.clone()).unwrap_or_else(|e| {
+            panic!("open RocksDB({:?}) error.{}", path, e);
+        });
+
+        let len = db.iterator(IteratorMode::Start).count();
 
         RocksDBReducingState {
             state_key: state_key.clone(),
-            backend_path: backend_path.to_string(),
+            path,
             db,
+            len,
+            value_buf: None,
         }
     }
-}
 
-impl ReducingState for RocksDBReducingState {
-    fn get(&self, key: &Record) -> Option<Record> {
-        match self.db.get(key.to_bytes().to_vec()) {
-            Ok(value) => value.map(|v| {
-                let mut bytes_mut = BytesMut::from_iter(v);
-                Element::deserialize(bytes_mut.borrow_mut()).into_record()
-            }),
+    pub fn get(&self, key: &Record) -> Option<Record> {
+        match self.db.get(record_to_bytes(key)) {
+            Ok(value) => value.map(|v| record_from_bytes(v.as_slice())),
             Err(e) => {
                 error!("RocksDB `get` error. {}", e);
                 None
@@ -77,9 +75,31 @@ impl ReducingState for RocksDBReducingState {
         }
     }
 
+    fn contains(&self, key: &[u8]) -> bool {
+        match self.db.get_pinned(key) {
+            Ok(value) => value.is_some(),
+            Err(e) => {
+                error!("RocksDB `get` error. {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl ReducingState for RocksDBReducingState {
+    fn get_mut(&mut self, key: &Record) -> Option<&mut Record> {
+        self.value_buf = self.get(key);
+        self.value_buf.as_mut()
+    }
+
     fn insert(&mut self, key: Record, val: Record) {
+        let key = record_to_bytes(&key);
+        if !self.contains(key.as_slice()) {
+            self.len += 1;
+        }
+
         let mut batch = WriteBatch::default();
-        batch.put(key.to_bytes().to_vec(), val.to_bytes().to_vec());
+        batch.put(key, record_to_bytes(&val));
 
         let mut write_opts = WriteOptions::default();
         write_opts.disable_wal(true);
@@ -88,8 +108,18 @@ impl ReducingState for RocksDBReducingState {
             Ok(_) => {}
             Err(e) => error!("RocksDB `write` error. {}", e),
         }
+    }
+
+    fn remove(&mut self, key: &Record) -> Option<Record> {
+        let val = self.get(key);
+        if val.is_some() {
+            match self.db.delete(record_to_bytes(key)) {
+                Ok(_) => self.len -= 1,
+                Err(e) => error!("RocksDB `delete` error. {}", e),
+            }
+        }
 
-        // counter!(format!("RocksDB.State.{}", self.state_key.to_string()), 1);
+        val
     }
 
     fn flush(&mut self) {
@@ -99,45 +129,25 @@ impl ReducingState for RocksDBReducingState {
         }
     }
 
-    fn snapshot(&mut self) {
-        match Checkpoint::new(&self.db) {
-            Ok(checkpoint) => {
-                let path = get_checkpoint_path(&self.state_key);
-                match checkpoint.create_checkpoint(path) {
-                    Ok(_) => info!(
-                        "RocksDB create checkpoint({}) success.",
-                        self.state_key.to_string()
-                    ),
-                    Err(e) => error!(
-                        "RocksDB create checkpoint({}) error. {}",
-                        self.state_key.to_string(),
-                        e
-                    ),
-                }
-            }
-            Err(e) => {
-                error!(
-                    "RocksDB new checkpoint({}) error. {}",
-                    self.state_key.to_string(),
-                    e
-                );
-            }
-        }
-    }
+    /// the RocksDB state can't be restored, so no RocksDB checkpoint is created,
+    /// the checkpoint of the window is failed by the `RocksDBWindowState`
+    fn snapshot(&mut self) {}
 
     fn close(self) {
-        info!("close RocksDB {:?}", get_db_path(&self.state_key));
+        info!("close RocksDB {:?}", self.path);
         drop(self)
     }
 
     fn destroy(self) {
-        let path = get_db_path(&self.state_key);
+        let path = self.path.clone();
         info!("destroy RocksDB {:?}", path);
 
         self.close();
 
         let opts = Options::default();
-        DB::destroy(&opts, path).expect("destroy RocksDB error");
+        if let Err(e) = DB::destroy(&opts, path.clone()) {
+            error!("destroy RocksDB({:?}) error. {}", path, e);
+        }
     }
 
     fn iter(&self) -> StateIterator {
@@ -145,32 +155,46 @@ impl ReducingState for RocksDBReducingState {
 
         StateIterator::RocksDB(db_iterator)
     }
+
+    fn len(&self) -> usize {
+        self.len
+    }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use crate::api::window::{TimeWindow, WindowWrap};
-//     use crate::storage::keyed_state::rocksdb_reducing_state::RocksDBReducingState;
-//     use crate::storage::keyed_state::{ReducingState, StateKey};
-//
-//     #[test]
-//     pub fn record_test() {
-//         for i in 0..100 {
-//             let key_state = StateKey::new(
-//                 "job_id".to_string(),
-//                 WindowWrap::TimeWindow(TimeWindow::new(i, i + 1)),
-//                 1,
-//                 0,
-//             );
-//             {
-//                 let window_state = RocksDBReducingState::new(&key_state, "", false);
-//                 window_state.close();
-//             }
-//
-//             {
-//                 let window_state = RocksDBReducingState::new(&key_state, "", true);
-//                 window_state.close();
-//             }
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use crate::api::element::{types, Record};
+    use crate::api::window::{TimeWindow, WindowWrap};
+    use crate::storage::keyed_state::rocksdb_reducing_state::RocksDBReducingState;
+    use crate::storage::keyed_state::{ReducingState, StateKey};
+
+    fn build_record(value: i64) -> Record {
+        let mut record = Record::new();
+        record.get_writer(&[types::I64]).set_i64(value).unwrap();
+        record
+    }
+
+    #[test]
+    pub fn rocksdb_reducing_state_test() {
+        let state_key = StateKey::new(WindowWrap::TimeWindow(TimeWindow::new(0, 10)), 1, 0);
+        let path = std::env::temp_dir().join(format!("rlink-test-{}.db", uuid::Uuid::new_v4()));
+
+        let mut state = RocksDBReducingState::new(&state_key, path);
+        state.insert(build_record(1), build_record(10));
+        state.insert(build_record(2), build_record(20));
+        state.insert(build_record(1), build_record(11));
+        assert_eq!(state.len(), 2);
+
+        let value = state.get_mut(&build_record(1)).unwrap();
+        assert_eq!(value.get_reader(&[types::I64]).get_i64(0).unwrap(), 11);
+
+        assert!(state.remove(&build_record(2)).is_some());
+        assert!(state.remove(&build_record(2)).is_none());
+        assert_eq!(state.len(), 1);
+
+        let pairs: Vec<(Record, Record)> = state.iter().collect();
+        assert_eq!(pairs, vec![(build_record(1), build_record(11))]);
+
+        state.destroy();
+    }
+}
@@ -1,29 +1,50 @@
 use crate::api::element::{Barrier, Record};
 use crate::api::window::{Window, WindowWrap};
+use crate::runtime::ChainId;
+use crate::storage::keyed_state::mem_storage::{append_drop_window, StorageKey};
 use crate::storage::keyed_state::rocksdb_reducing_state::RocksDBReducingState;
-use crate::storage::keyed_state::{ReducingState, StateKey, WindowState};
+use crate::storage::keyed_state::{ReducingState, ReducingStateWrap, StateKey, WindowState};
+use bytes::BytesMut;
 use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
+/// the same as the `MemoryWindowState`, but the state of each window is kept in a RocksDB.
+/// each emitted window state is copied into a new RocksDB, which is destroyed
+/// after it is consumed by the downstream.
+///
+/// NOTE: the state is not included in the checkpoints, `snapshot` returns `None` and `restore`
+/// is not supported, so the windows in progress are lost when the task is restarted.
 #[derive(Debug)]
 pub struct RocksDBWindowState {
     job_id: String,
-    chain_id: u32,
+    chain_id: ChainId,
     task_number: u16,
     path: String,
-    reducing_states: HashMap<StateKey, RocksDBReducingState>,
-    max_drop_window_timestamp: u64,
+
+    windows: HashMap<WindowWrap, RocksDBReducingState>,
+
+    /// the windows of each key, only used by the merging windows
+    key_windows: HashMap<Record, Vec<WindowWrap>>,
+    /// the windows have been fired but kept for the late records,
+    /// and the keys updated by the late records since the latest fire
+    fired_windows: HashMap<WindowWrap, HashSet<Record>>,
+
+    /// the sequence of the emitted window states, make the RocksDB path unique
+    emit_sequence: u64,
 }
 
 impl RocksDBWindowState {
-    pub fn new(job_id: String, chain_id: u32, task_number: u16, path: String) -> Self {
+    pub fn new(job_id: String, chain_id: ChainId, task_number: u16, path: String) -> Self {
         RocksDBWindowState {
             job_id,
             chain_id,
             task_number,
             path,
-            reducing_states: HashMap::new(),
-            max_drop_window_timestamp: 0,
+            windows: HashMap::new(),
+            key_windows: HashMap::new(),
+            fired_windows: HashMap::new(),
+            emit_sequence: 0,
         }
     }
 
@@ -31,78 +52,370 @@ impl RocksDBWindowState {
     fn create_state_key(&self, window: WindowWrap) -> StateKey {
         StateKey::new(window, self.chain_id, self.task_number)
     }
+
+    /// {path}/{job_id}/{chain_id}_{task_number}/{window_min}_{window_max}.{name}
+    fn get_db_path(&self, window: &WindowWrap, name: &str) -> PathBuf {
+        PathBuf::from(self.path.as_str())
+            .join(self.job_id.as_str())
+            .join(format!("{}_{}", self.chain_id, self.task_number))
+            .join(format!(
+                "{}_{}.{}",
+                window.min_timestamp(),
+                window.max_timestamp(),
+                name
+            ))
+    }
+
+    fn get_or_create_state(&mut self, window: &WindowWrap) -> &mut RocksDBReducingState {
+        if !self.windows.contains_key(window) {
+            let state_key = self.create_state_key(window.clone());
+            let path = self.get_db_path(window, "db");
+            let state = RocksDBReducingState::new(&state_key, path);
+            self.windows.insert(window.clone(), state);
+        }
+
+        self.windows.get_mut(window).unwrap()
+    }
+
+    fn create_emit_state(&mut self, window: &WindowWrap) -> RocksDBReducingState {
+        self.emit_sequence += 1;
+        let name = format!("emit{}", self.emit_sequence);
+
+        let state_key = self.create_state_key(window.clone());
+        let path = self.get_db_path(window, name.as_str());
+        RocksDBReducingState::new(&state_key, path)
+    }
+
+    /// take the `emit_state` to the downstream, returns `true` if it is not empty
+    fn append_emit_state(&self, window: &WindowWrap, emit_state: RocksDBReducingState) -> bool {
+        if emit_state.len() == 0 {
+            emit_state.destroy();
+            return false;
+        }
+
+        let storage_key = StorageKey::new(self.chain_id, self.task_number);
+        append_drop_window(
+            storage_key,
+            window.clone(),
+            ReducingStateWrap::RocksDBReducingState(emit_state),
+        );
+
+        true
+    }
+
+    /// remove the `window` from the windows of each key in the `state`
+    fn remove_key_windows(&mut self, window: &WindowWrap, state: &RocksDBReducingState) {
+        if self.key_windows.len() == 0 {
+            return;
+        }
+
+        for (key, _val) in state.iter() {
+            self.remove_key_window(&key, window);
+        }
+    }
+
+    /// remove the `window` from the windows of the `key`
+    fn remove_key_window(&mut self, key: &Record, window: &WindowWrap) {
+        let empty = match self.key_windows.get_mut(key) {
+            Some(key_windows) => {
+                key_windows.retain(|w| !w.eq(window));
+                key_windows.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            self.key_windows.remove(key);
+        }
+    }
 }
 
 impl WindowState for RocksDBWindowState {
     fn windows(&self) -> Vec<WindowWrap> {
-        self.reducing_states
-            .keys()
-            .map(|state_key| state_key.window.clone())
-            .collect()
-    }
-
-    fn merge<F>(
-        &mut self,
-        windows: &Vec<WindowWrap>,
-        key: Record,
-        mut record: Record,
-        reduce_fun: F,
-    ) where
-        F: Fn(Option<Record>, &mut Record) -> Record,
+        self.windows.keys().map(|window| window.clone()).collect()
+    }
+
+    fn merge<F>(&mut self, key: Record, mut record: Record, reduce_fun: F)
+    where
+        F: Fn(Option<&mut Record>, &mut Record) -> Record,
     {
-        for window in windows {
-            let state_key = self.create_state_key(window.clone());
-            let reducing_state = match self.reducing_states.get_mut(&state_key) {
-                Some(state) => state,
-                None => {
-                    if window.max_timestamp() <= self.max_drop_window_timestamp {
-                        warn!(
-                            "window not exist, timestamp={} too low, Record timestamp={}",
-                            window.max_timestamp(),
-                            &record.timestamp,
-                        );
-                        continue;
-                    }
+        let windows = record.get_location_windows().clone();
+        for window in &windows {
+            if let Some(late_keys) = self.fired_windows.get_mut(window) {
+                late_keys.insert(key.clone());
+            }
+
+            let state = self.get_or_create_state(window);
+            let new_val = reduce_fun(state.get_mut(&key), record.borrow_mut());
+            state.insert(key.clone(), new_val);
+        }
+    }
+
+    fn merge_windows<F, M>(&mut self, key: Record, mut record: Record, reduce_fun: F, merge_fun: M)
+    where
+        F: Fn(Option<&mut Record>, &mut Record) -> Record,
+        M: Fn(Option<&mut Record>, &mut Record) -> Record,
+    {
+        let window = match record.get_min_location_windows() {
+            Some(window) => window.clone(),
+            None => return,
+        };
+
+        let key_windows = self.key_windows.remove(&key).unwrap_or_default();
+
+        let mut merged_window = window.clone();
+        let mut merged_value: Option<Record> = None;
+        let mut remain_windows = Vec::with_capacity(key_windows.len() + 1);
+        for key_window in key_windows {
+            if !key_window.intersects(&window) {
+                remain_windows.push(key_window);
+                continue;
+            }
 
-                    let state = RocksDBReducingState::new(&state_key, self.path.as_str(), false);
-                    self.reducing_states.insert(state_key.clone(), state);
-                    self.reducing_states.get_mut(&state_key).unwrap()
+            merged_window = merged_window.cover(&key_window);
+
+            let value = match self.windows.get_mut(&key_window) {
+                Some(state) => {
+                    let value = state.remove(&key);
+                    if state.len() == 0 {
+                        if let Some(state) = self.windows.remove(&key_window) {
+                            state.destroy();
+                        }
+                        self.fired_windows.remove(&key_window);
+                    }
+                    value
                 }
+                None => None,
             };
 
-            let rt = reducing_state.get(&key);
-            let new_value = reduce_fun(rt, record.borrow_mut());
-            reducing_state.insert(key.clone(), new_value);
-            // match reducing_state.get(&key) {
-            //     Some(origin_value) => {
-            //         let new_value = reduce_fun(&origin_value, &val);
-            //         reducing_state.insert(key.clone(), new_value);
-            //     }
-            //     None => {
-            //         reducing_state.insert(key.clone(), val.clone());
-            //     }
-            // }
+            if let Some(mut value) = value {
+                merged_value = match merged_value {
+                    Some(mut merged_value) => Some(merge_fun(Some(&mut merged_value), &mut value)),
+                    None => Some(value),
+                };
+            }
         }
+
+        let new_val = reduce_fun(merged_value.as_mut(), record.borrow_mut());
+        self.get_or_create_state(&merged_window)
+            .insert(key.clone(), new_val);
+
+        remain_windows.push(merged_window);
+        self.key_windows.insert(key, remain_windows);
     }
 
-    fn drop_window(&mut self, window: &WindowWrap) {
-        let state_key = self.create_state_key(window.clone());
-        let state = self.reducing_states.remove(&state_key);
-        state
-            .map(|mut state| {
-                if self.max_drop_window_timestamp < window.max_timestamp() {
-                    self.max_drop_window_timestamp = window.max_timestamp();
+    fn fire_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        if !self.windows.contains_key(window) {
+            return false;
+        }
+
+        let late_keys: Option<Vec<Record>> = match self.fired_windows.get_mut(window) {
+            Some(late_keys) => {
+                if late_keys.is_empty() {
+                    return false;
                 }
+                Some(late_keys.drain().collect())
+            }
+            None => {
+                self.fired_windows.insert(window.clone(), HashSet::new());
+                None
+            }
+        };
 
-                state.flush();
-                state.close();
-            })
-            .unwrap();
+        let mut emit_state = self.create_emit_state(window);
+        let state = self.windows.get(window).unwrap();
+        match late_keys {
+            Some(late_keys) => {
+                for key in late_keys {
+                    if let Some(val) = state.get(&key) {
+                        emit_state.insert(key, result_fun(val));
+                    }
+                }
+            }
+            None => {
+                for (key, val) in state.iter() {
+                    emit_state.insert(key, result_fun(val));
+                }
+            }
+        }
+
+        self.append_emit_state(window, emit_state)
     }
 
-    fn snapshot(&mut self, _barrier: Barrier) {
-        for (_, state) in &mut self.reducing_states {
-            state.snapshot()
+    fn drop_window<R>(&mut self, window: &WindowWrap, result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        let state = match self.windows.remove(window) {
+            Some(state) => state,
+            None => return false,
+        };
+
+        self.remove_key_windows(window, &state);
+
+        let mut emit_state = self.create_emit_state(window);
+        match self.fired_windows.remove(window) {
+            Some(late_keys) => {
+                // only the keys updated by the late records have not been emitted
+                for key in late_keys {
+                    if let Some(val) = state.get(&key) {
+                        emit_state.insert(key, result_fun(val));
+                    }
+                }
+            }
+            None => {
+                for (key, val) in state.iter() {
+                    emit_state.insert(key, result_fun(val));
+                }
+            }
+        }
+        state.destroy();
+
+        self.append_emit_state(window, emit_state)
+    }
+
+    fn keys(&self, window: &WindowWrap) -> Vec<Record> {
+        match self.windows.get(window) {
+            Some(state) => state.iter().map(|(key, _val)| key).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn fire_keys<R>(&mut self, window: &WindowWrap, keys: &[Record], result_fun: R) -> bool
+    where
+        R: Fn(Record) -> Record,
+    {
+        if !self.windows.contains_key(window) {
+            return false;
+        }
+
+        let mut emit_state = self.create_emit_state(window);
+        let state = self.windows.get(window).unwrap();
+        for key in keys {
+            if let Some(val) = state.get(key) {
+                emit_state.insert(key.clone(), result_fun(val));
+            }
+        }
+
+        // the keys are up to date in the downstream, they are not late keys anymore
+        if let Some(late_keys) = self.fired_windows.get_mut(window) {
+            for key in keys {
+                late_keys.remove(key);
+            }
+        }
+
+        self.append_emit_state(window, emit_state)
+    }
+
+    fn purge_keys(&mut self, window: &WindowWrap, keys: &[Record]) {
+        let empty = match self.windows.get_mut(window) {
+            Some(state) => {
+                for key in keys {
+                    state.remove(key);
+                }
+                state.len() == 0
+            }
+            None => return,
+        };
+
+        if let Some(late_keys) = self.fired_windows.get_mut(window) {
+            for key in keys {
+                late_keys.remove(key);
+            }
         }
+        if self.key_windows.len() > 0 {
+            for key in keys {
+                self.remove_key_window(key, window);
+            }
+        }
+
+        if empty {
+            if let Some(state) = self.windows.remove(window) {
+                state.destroy();
+            }
+            self.fired_windows.remove(window);
+        }
+    }
+
+    /// the RocksDB state can't be restored from the snapshot, the checkpoint is failed
+    fn snapshot(&mut self, _barrier: Barrier, _bytes: &mut BytesMut) -> bool {
+        false
+    }
+
+    fn restore(&mut self, _bytes: &mut BytesMut) -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "restore RocksDB window state is not supported",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::element::{types, Record};
+    use crate::api::window::{TimeWindow, WindowWrap};
+    use crate::storage::keyed_state::mem_storage::remove_drop_window;
+    use crate::storage::keyed_state::rocksdb_window_state::RocksDBWindowState;
+    use crate::storage::keyed_state::{ReducingState, WindowState};
+
+    fn build_record(key: i64) -> (Record, Record) {
+        let mut key_record = Record::new();
+        key_record.get_writer(&[types::I64]).set_i64(key).unwrap();
+
+        let mut record = Record::new();
+        record.get_writer(&[types::I64]).set_i64(1).unwrap();
+        record.set_location_windows(vec![WindowWrap::TimeWindow(TimeWindow::new(0, 10))]);
+
+        (key_record, record)
+    }
+
+    fn count(value: Option<&mut Record>, record: &mut Record) -> Record {
+        let n = record.get_reader(&[types::I64]).get_i64(0).unwrap();
+        let n = n + value
+            .map(|value| value.get_reader(&[types::I64]).get_i64(0).unwrap())
+            .unwrap_or(0);
+
+        let mut value = Record::new();
+        value.get_writer(&[types::I64]).set_i64(n).unwrap();
+        value
+    }
+
+    fn identity(value: Record) -> Record {
+        value
+    }
+
+    #[test]
+    pub fn rocksdb_window_state_test() {
+        let path = std::env::temp_dir().join("rlink-test");
+        let job_id = uuid::Uuid::new_v4().to_string();
+        let mut state =
+            RocksDBWindowState::new(job_id.clone(), 6, 0, path.to_str().unwrap().to_string());
+        for key in vec![1, 1, 2] {
+            let (key, record) = build_record(key);
+            state.merge(key, record, count);
+        }
+
+        let window = WindowWrap::TimeWindow(TimeWindow::new(0, 10));
+        assert!(state.fire_window(&window, identity));
+        let mut fire_state = remove_drop_window(6, 0, window.clone()).unwrap();
+        assert_eq!(fire_state.len(), 2);
+        let (key, _record) = build_record(1);
+        let value = fire_state.get_mut(&key).unwrap();
+        assert_eq!(value.get_reader(&[types::I64]).get_i64(0).unwrap(), 2);
+        fire_state.destroy();
+
+        // only the key updated by the late record is emitted
+        let (key, record) = build_record(2);
+        state.merge(key, record, count);
+        assert!(state.drop_window(&window, identity));
+        let drop_state = remove_drop_window(6, 0, window.clone()).unwrap();
+        assert_eq!(drop_state.len(), 1);
+        drop_state.destroy();
+        assert!(state.windows().is_empty());
+
+        drop(state);
+        std::fs::remove_dir_all(path.join(job_id)).unwrap();
     }
 }
@@ -0,0 +1,189 @@
+use crate::api::backend::{CheckpointBackend, OperatorStateBackend};
+use crate::api::checkpoint::{is_savepoint, CheckpointHandle};
+use crate::api::properties::{Properties, SystemProperties};
+use crate::api::window::{TimeWindow, Window, WindowWrap};
+use crate::runtime::ChainId;
+use crate::utils;
+use bytes::{Buf, BufMut, BytesMut};
+use std::path::PathBuf;
+
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+/// the snapshots of the previous checkpoints are retained, in case the latest checkpoint
+/// is not completed by all tasks of the chain. the snapshots of the savepoints are never removed
+const RETAINED_SNAPSHOTS: usize = 3;
+
+/// the snapshot files of the state of a task,
+/// each snapshot is written to `{checkpoint_id}.snapshot` in the `dir`
+#[derive(Clone, Debug)]
+pub struct SnapshotStorage {
+    dir: PathBuf,
+}
+
+impl SnapshotStorage {
+    /// {base_dir}/snapshot/{job_id}/{chain_id}_{task_number}
+    ///
+    /// the `base_dir` is the path of the `OperatorStateBackend::DFS`, or the path of the
+    /// `CheckpointBackend::FileSystem`, which should be a shared file system,
+    /// so the task can be restored on another host.
+    /// if neither is configured, the snapshots are written to the local work space
+    pub fn new(
+        job_properties: &Properties,
+        job_id: &str,
+        chain_id: ChainId,
+        task_number: u16,
+    ) -> Self {
+        let dir = SnapshotStorage::base_dir(job_properties)
+            .join("snapshot")
+            .join(job_id)
+            .join(format!("{}_{}", chain_id, task_number));
+        SnapshotStorage { dir }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_dir(dir: PathBuf) -> Self {
+        SnapshotStorage { dir }
+    }
+
+    fn base_dir(job_properties: &Properties) -> PathBuf {
+        if let Ok(OperatorStateBackend::DFS { path }) = job_properties.get_operator_state_backend()
+        {
+            return PathBuf::from(path);
+        }
+
+        if let Ok(CheckpointBackend::FileSystem { path }) = job_properties.get_checkpoint() {
+            return PathBuf::from(path);
+        }
+
+        warn!("no shared file system is configured, the state snapshots are written to the local work space and can't be restored on another host");
+        utils::get_work_space()
+    }
+
+    /// write the snapshot of the checkpoint, the path of the snapshot is the handle
+    pub fn write(&self, checkpoint_id: u64, bytes: &[u8]) -> Option<CheckpointHandle> {
+        if let Err(e) = std::fs::create_dir_all(&self.dir) {
+            error!("create snapshot dir({:?}) error. {}", self.dir, e);
+            return None;
+        }
+
+        let path = self
+            .dir
+            .join(format!("{}.{}", checkpoint_id, SNAPSHOT_EXTENSION));
+        let tmp_path = path.with_extension("tmp");
+
+        // write to a temporary file at first, a broken snapshot is never referenced by a handle
+        let rt = std::fs::write(&tmp_path, bytes).and_then(|_| std::fs::rename(&tmp_path, &path));
+        if let Err(e) = rt {
+            error!("write snapshot({:?}) error. {}", path, e);
+            return None;
+        }
+
+        self.clear_expired_snapshots();
+
+        Some(CheckpointHandle {
+            handle: path.to_str().unwrap().to_string(),
+        })
+    }
+
+    /// read the snapshot of the `handle`
+    pub fn read(&self, handle: &CheckpointHandle) -> std::io::Result<BytesMut> {
+        let data = std::fs::read(handle.handle.as_str())?;
+        Ok(BytesMut::from(data.as_slice()))
+    }
+
+    /// remove the snapshots except the latest `RETAINED_SNAPSHOTS`
+    fn clear_expired_snapshots(&self) {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("read snapshot dir({:?}) error. {}", self.dir, e);
+                return;
+            }
+        };
+
+        let mut checkpoint_ids: Vec<u64> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .map(|ext| ext == SNAPSHOT_EXTENSION)
+                    .unwrap_or(false)
+            })
+            .filter_map(|path| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u64>().ok())
+            })
+            .filter(|checkpoint_id| !is_savepoint(*checkpoint_id))
+            .collect();
+        if checkpoint_ids.len() <= RETAINED_SNAPSHOTS {
+            return;
+        }
+
+        checkpoint_ids.sort();
+        let expired_len = checkpoint_ids.len() - RETAINED_SNAPSHOTS;
+        for checkpoint_id in &checkpoint_ids[..expired_len] {
+            let path = self
+                .dir
+                .join(format!("{}.{}", checkpoint_id, SNAPSHOT_EXTENSION));
+            if let Err(e) = std::fs::remove_file(&path) {
+                error!("remove expired snapshot({:?}) error. {}", path, e);
+            }
+        }
+    }
+}
+
+pub(crate) fn serialize_window(window: &WindowWrap, bytes: &mut BytesMut) {
+    match window {
+        WindowWrap::TimeWindow(time_window) => {
+            bytes.put_u64(time_window.min_timestamp());
+            bytes.put_u64(time_window.max_timestamp());
+        }
+    }
+}
+
+pub(crate) fn deserialize_window(bytes: &mut BytesMut) -> WindowWrap {
+    let start = bytes.get_u64();
+    let end = bytes.get_u64();
+    WindowWrap::TimeWindow(TimeWindow::new(start, end))
+}
+
+/// check the version at the head of the snapshot
+pub(crate) fn check_version(bytes: &mut BytesMut, version: u8) -> std::io::Result<()> {
+    if bytes.is_empty() || bytes.get_u8() != version {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Invalid snapshot version",
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::checkpoint::CheckpointHandle;
+    use crate::storage::keyed_state::snapshot::SnapshotStorage;
+
+    #[test]
+    pub fn snapshot_storage_test() {
+        let dir = std::env::temp_dir().join(format!("rlink-test-{}", uuid::Uuid::new_v4()));
+        let storage = SnapshotStorage::with_dir(dir.clone());
+
+        let mut handle = None;
+        for checkpoint_id in 1..6 {
+            handle = storage.write(checkpoint_id, &[checkpoint_id as u8]);
+        }
+        // only the latest snapshots are retained
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+
+        let bytes = storage.read(&handle.unwrap()).unwrap();
+        assert_eq!(bytes.as_ref(), &[5u8]);
+
+        let missing_handle = CheckpointHandle {
+            handle: dir.join("0.snapshot").to_str().unwrap().to_string(),
+        };
+        assert!(storage.read(&missing_handle).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
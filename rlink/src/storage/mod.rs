@@ -2,3 +2,4 @@ pub mod checkpoint;
 pub mod keyed_state;
 pub mod metadata;
 pub mod operator_state;
+pub mod transaction;
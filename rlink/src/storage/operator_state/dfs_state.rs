@@ -1,10 +1,17 @@
 use crate::api::backend::{OperatorState, StateValue};
+use crate::api::checkpoint::is_savepoint;
 use crate::runtime::CheckpointId;
 use crate::storage::operator_state::dfs_state_manager::DFSListStateManager;
-use crate::storage::operator_state::{OperatorStateManager, StateName};
+use crate::storage::operator_state::StateName;
 use std::collections::HashMap;
 
+/// the latest checkpoints of a task are retained, the older ones are deleted after snapshot.
+/// the savepoints are never deleted
+const RETAINED_CHECKPOINTS: usize = 3;
+
 impl StateName {
+    /// parse the file name `job_{job_name}.ckpt_{checkpoint_id}.cid_{chain_id}.tn_{task_number}`,
+    /// with the `.progress` suffix if the file is writing
     pub fn parse(file_name: &str) -> Result<Self, std::io::Error> {
         let (file_name, progress) = if file_name.ends_with(".progress") {
             (&file_name[0..file_name.len() - 9], true)
@@ -12,7 +19,9 @@ impl StateName {
             (file_name, false)
         };
 
-        let pairs: Vec<&str> = file_name.split(".").collect();
+        // the `job_name` may contain `.`, the fields are split from the end
+        let mut pairs: Vec<&str> = file_name.rsplitn(4, '.').collect();
+        pairs.reverse();
         if pairs.len() != 4 {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -20,13 +29,13 @@ impl StateName {
             ));
         }
 
-        let job_id = StateName::parse_string(pairs[0], "job")?;
+        let job_name = StateName::parse_string(pairs[0], "job")?;
         let checkpoint_id = StateName::parse_num::<u64>(pairs[1], "ckpt")?;
         let chain_id = StateName::parse_num::<u32>(pairs[2], "cid")?;
         let task_number = StateName::parse_num::<u16>(pairs[3], "tn")?;
 
         Ok(StateName {
-            job_id,
+            job_name,
             checkpoint_id,
             chain_id,
             task_number,
@@ -35,22 +44,23 @@ impl StateName {
     }
 
     fn parse_string(block: &str, field: &str) -> Result<String, std::io::Error> {
-        let job_id_pair: Vec<&str> = block.split("_").collect();
-        if job_id_pair.len() != 2 && job_id_pair[0].ne(field) {
+        // the `job_name` may contain `_`
+        let job_name_pair: Vec<&str> = block.splitn(2, "_").collect();
+        if job_name_pair.len() != 2 || job_name_pair[0].ne(field) {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                "invalid file name, `job` not found",
+                format!("invalid file name, `{}` not found", field),
             ));
         }
-        Ok(job_id_pair[1].to_string())
+        Ok(job_name_pair[1].to_string())
     }
 
     fn parse_num<T: std::str::FromStr>(block: &str, field: &str) -> Result<T, std::io::Error> {
         let checkpoint_id_pair: Vec<&str> = block.split("_").collect();
-        if checkpoint_id_pair.len() != 2 && checkpoint_id_pair[0].ne(field) {
+        if checkpoint_id_pair.len() != 2 || checkpoint_id_pair[0].ne(field) {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                "invalid file name, `ckpt` not found",
+                format!("invalid file name, `{}` not found", field),
             ));
         }
         match T::from_str(checkpoint_id_pair[1]) {
@@ -65,14 +75,16 @@ impl StateName {
     pub fn to_file_name(&self) -> String {
         format!(
             "job_{}.ckpt_{}.cid_{}.tn_{}",
-            self.job_id, self.checkpoint_id, self.chain_id, self.task_number
+            self.job_name, self.checkpoint_id, self.chain_id, self.task_number
         )
     }
 }
 
 #[derive(Debug)]
 pub struct DFSListState {
-    pub(crate) job_id: String,
+    /// the `job_id` is regenerated by each submission, the states of the previous run
+    /// are found by the `job_name`
+    pub(crate) job_name: String,
     pub(crate) task_number: u16,
 
     pub(crate) manager: DFSListStateManager,
@@ -81,9 +93,9 @@ pub struct DFSListState {
 }
 
 impl DFSListState {
-    pub(crate) fn new(job_id: String, task_number: u16, manager: DFSListStateManager) -> Self {
+    pub(crate) fn new(job_name: String, task_number: u16, manager: DFSListStateManager) -> Self {
         DFSListState {
-            job_id,
+            job_name,
             task_number,
             manager,
             checkpoint_id: 0,
@@ -92,6 +104,31 @@ impl DFSListState {
     }
 }
 
+impl DFSListState {
+    /// delete the checkpoints of the task except the latest `RETAINED_CHECKPOINTS`
+    fn clear_expired_checkpoints(&mut self) -> std::io::Result<()> {
+        let mut state_names: Vec<StateName> = self
+            .manager
+            .load()?
+            .into_iter()
+            .filter(|state_name| state_name.job_name.eq(&self.job_name))
+            .filter(|state_name| state_name.task_number == self.task_number)
+            .filter(|state_name| !is_savepoint(state_name.checkpoint_id))
+            .collect();
+        if state_names.len() <= RETAINED_CHECKPOINTS {
+            return Ok(());
+        }
+
+        state_names.sort_by_key(|state_name| state_name.checkpoint_id);
+        let expired_len = state_names.len() - RETAINED_CHECKPOINTS;
+        for state_name in &state_names[..expired_len] {
+            self.manager.delete(state_name)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl OperatorState for DFSListState {
     fn update(&mut self, checkpoint_id: u64, values: Vec<String>) {
         self.checkpoint_id = checkpoint_id;
@@ -101,12 +138,14 @@ impl OperatorState for DFSListState {
 
     fn snapshot(&mut self) -> std::io::Result<()> {
         let ck_file = StateName::new(
-            self.job_id.clone(),
+            self.job_name.clone(),
             self.manager.chain_id,
             self.task_number,
             self.checkpoint_id,
         );
-        self.manager.save(&ck_file, &self.values)
+        self.manager.save(&ck_file, &self.values)?;
+
+        self.clear_expired_checkpoints()
     }
 
     fn load_latest(
@@ -118,6 +157,10 @@ impl OperatorState for DFSListState {
         match checkpoint_states {
             Some(state_names) => {
                 let mut state_values = HashMap::new();
+                // the chain dir may be shared by the other jobs
+                let state_names = state_names
+                    .iter()
+                    .filter(|state_name| state_name.job_name.eq(&self.job_name));
                 for state_name in state_names {
                     let values = self.manager.load_checkpoint_value(state_name)?;
                     state_values.insert(state_name.task_number, StateValue::new(values));
@@ -129,3 +172,74 @@ impl OperatorState for DFSListState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::api::backend::{OperatorState, OperatorStateBackend};
+    use crate::storage::operator_state::{
+        OperatorStateManager, OperatorStateManagerWrap, StateName,
+    };
+
+    #[test]
+    pub fn state_name_test() {
+        let state_name = StateName::new("rlink_kafka.example".to_string(), 3, 4, 5);
+        let file_name = state_name.to_file_name();
+        assert_eq!(file_name, "job_rlink_kafka.example.ckpt_5.cid_3.tn_4");
+
+        let state_name = StateName::parse(format!("{}.progress", file_name).as_str()).unwrap();
+        assert_eq!(state_name.job_name, "rlink_kafka.example");
+        assert_eq!(state_name.checkpoint_id, 5);
+        assert_eq!(state_name.chain_id, 3);
+        assert_eq!(state_name.task_number, 4);
+        assert!(state_name.progress);
+
+        assert!(StateName::parse("job_a.ckpt_5.chain_3.tn_4").is_err());
+        assert!(StateName::parse("ckpt_5.cid_3.tn_4").is_err());
+    }
+
+    #[test]
+    pub fn dfs_state_test() {
+        let path = std::env::temp_dir().join(format!("rlink-test-{}", uuid::Uuid::new_v4()));
+        let backend = OperatorStateBackend::DFS {
+            path: path.to_str().unwrap().to_string(),
+        };
+        let manager = OperatorStateManagerWrap::new(1, backend);
+
+        let mut states: Vec<Box<dyn OperatorState>> = (0..2)
+            .map(|task_number| manager.create_state("job".to_string(), task_number))
+            .collect();
+        for checkpoint_id in 1..6 {
+            for (task_number, state) in states.iter_mut().enumerate() {
+                state.update(
+                    checkpoint_id,
+                    vec![format!("{}_{}", checkpoint_id, task_number)],
+                );
+                state.snapshot().unwrap();
+            }
+        }
+
+        let state_values = states[0].load_latest(5).unwrap();
+        assert_eq!(state_values.len(), 2);
+        assert_eq!(
+            state_values.get(&1).unwrap().values,
+            vec!["5_1".to_string()]
+        );
+
+        // the expired checkpoints are deleted
+        assert!(states[0].load_latest(2).unwrap().is_empty());
+        assert_eq!(states[1].load_latest(3).unwrap().len(), 2);
+
+        // the states of the other jobs are isolated
+        let mut other_job = manager.create_state("other_job".to_string(), 0);
+        other_job.update(5, vec!["other".to_string()]);
+        other_job.snapshot().unwrap();
+        assert_eq!(states[0].load_latest(5).unwrap().len(), 2);
+        assert_eq!(other_job.load_latest(5).unwrap().len(), 1);
+
+        // the states are found by the job restarted with the same name
+        let restarted = manager.create_state("job".to_string(), 0);
+        assert_eq!(restarted.load_latest(5).unwrap().len(), 2);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
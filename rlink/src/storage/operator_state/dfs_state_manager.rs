@@ -5,9 +5,10 @@ use crate::storage::operator_state::{
 };
 use crate::utils::fs::{read_file, write_file};
 use std::collections::HashMap;
-use std::fs::DirBuilder;
 use std::path::PathBuf;
 
+/// the operator states of a chain are kept in `{path}/chain_{chain_id}/{task_number}/`,
+/// each checkpoint of a task is a file named by the `StateName`
 #[derive(Clone, Debug)]
 pub struct DFSListStateManager {
     pub(crate) chain_id: ChainId,
@@ -19,24 +20,10 @@ impl DFSListStateManager {
         let path = path.join(format!("chain_{}", chain_id));
         DFSListStateManager { chain_id, path }
     }
-}
 
-impl OperatorStateManager for DFSListStateManager {
-    fn prepare(&mut self, parallelism: u32) -> std::io::Result<()> {
-        info!(
-            "Recursive make dir {} for state chain_{}",
-            self.path.to_str().unwrap(),
-            self.chain_id
-        );
-        for task_number in 0..parallelism {
-            let path = self.path.join(format!("{}", task_number));
-            DirBuilder::new().recursive(true).create(path)?;
-        }
-
-        Ok(())
-    }
-
-    fn save(&mut self, state_name: &StateName, values: &Vec<String>) -> std::io::Result<()> {
+    /// write the `values` to a `.progress` file and rename it when finished,
+    /// so a partial file is never loaded
+    pub fn save(&mut self, state_name: &StateName, values: &Vec<String>) -> std::io::Result<()> {
         let ck_file = state_name.to_file_name();
         let ck_file_progress = format!("{}.progress", ck_file);
         let path = self.path.join(format!("{}", state_name.task_number));
@@ -45,26 +32,34 @@ impl OperatorStateManager for DFSListStateManager {
         std::fs::rename(path.join(ck_file_progress), path.join(ck_file))
     }
 
-    fn load(&self) -> std::io::Result<Vec<StateName>> {
-        let chain_dirs = std::fs::read_dir(self.path.clone())?;
+    /// the completed `StateName`s of all tasks
+    pub fn load(&self) -> std::io::Result<Vec<StateName>> {
         let mut checkpoint_files = Vec::new();
+        if !self.path.exists() {
+            return Ok(checkpoint_files);
+        }
 
-        // walk chain dir
-        for chain_dir in chain_dirs {
-            if let Ok(chain_dir_entry) = chain_dir {
-                if !chain_dir_entry.metadata().unwrap().is_dir() {
+        // walk task dir
+        let task_dirs = std::fs::read_dir(self.path.clone())?;
+        for task_dir in task_dirs {
+            if let Ok(task_dir_entry) = task_dir {
+                if !task_dir_entry.metadata()?.is_dir() {
                     continue;
                 }
 
-                // walk task dir
-                let task_dirs = std::fs::read_dir(chain_dir_entry.path())?;
-                for task_dir in task_dirs {
-                    if let Ok(task_dir_entry) = task_dir {
+                // walk checkpoint files
+                let checkpoint_entries = std::fs::read_dir(task_dir_entry.path())?;
+                for checkpoint_entry in checkpoint_entries {
+                    if let Ok(checkpoint_entry) = checkpoint_entry {
                         // parse file name to `StateName`
-                        let file_name = task_dir_entry.file_name();
+                        let file_name = checkpoint_entry.file_name();
                         let file_name = file_name.to_str().unwrap();
                         match StateName::parse(file_name) {
-                            Ok(file_name) => checkpoint_files.push(file_name),
+                            Ok(state_name) => {
+                                if !state_name.progress {
+                                    checkpoint_files.push(state_name);
+                                }
+                            }
                             Err(e) => error!("found illegal file({}). {}", file_name, e),
                         }
                     }
@@ -75,7 +70,7 @@ impl OperatorStateManager for DFSListStateManager {
         Ok(checkpoint_files)
     }
 
-    fn load_as_map(&self) -> std::io::Result<HashMap<CheckpointId, Vec<StateName>>> {
+    pub fn load_as_map(&self) -> std::io::Result<HashMap<CheckpointId, Vec<StateName>>> {
         let state_names = self.load()?;
         let mut checkpoint_map = HashMap::new();
         for state_name in state_names {
@@ -88,7 +83,7 @@ impl OperatorStateManager for DFSListStateManager {
         Ok(checkpoint_map)
     }
 
-    fn delete(&mut self, state_name: &StateName) -> std::io::Result<()> {
+    pub fn delete(&mut self, state_name: &StateName) -> std::io::Result<()> {
         let path = self
             .path
             .join(format!("{}", state_name.task_number))
@@ -96,26 +91,31 @@ impl OperatorStateManager for DFSListStateManager {
         std::fs::remove_file(path)
     }
 
-    fn as_reader(&self) -> std::io::Result<OperatorStateReader> {
+    pub fn as_reader(&self) -> std::io::Result<OperatorStateReader> {
         let c = self.load_as_map()?;
         Ok(OperatorStateReader::new(c))
     }
 
-    fn create_state(&self, job_id: String, task_number: u16) -> Box<dyn OperatorState> {
-        let state = DFSListState::new(job_id, task_number, self.clone());
-        let state: Box<dyn OperatorState> = Box::new(state);
-        state
-    }
-
-    fn load_checkpoint_value(&self, state_name: &StateName) -> std::io::Result<Vec<String>> {
+    pub fn load_checkpoint_value(&self, state_name: &StateName) -> std::io::Result<Vec<String>> {
         let ck_file = state_name.to_file_name();
         let path = self
             .path
             .join(format!("{}", state_name.task_number))
             .join(ck_file);
         let content = read_file(path)?;
-        let values: Vec<&str> = content.split("\n").collect();
-        let values: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+        let values: Vec<String> = content
+            .split("\n")
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_string())
+            .collect();
         Ok(values)
     }
 }
+
+impl OperatorStateManager for DFSListStateManager {
+    fn create_state(&self, job_name: String, task_number: u16) -> Box<dyn OperatorState> {
+        let state = DFSListState::new(job_name, task_number, self.clone());
+        let state: Box<dyn OperatorState> = Box::new(state);
+        state
+    }
+}
@@ -25,7 +25,7 @@ impl OperatorState for EmptyOperatorState {
 pub struct EmptyOperatorStateManager {}
 
 impl OperatorStateManager for EmptyOperatorStateManager {
-    fn create_state(&self, _job_id: String, _task_number: u16) -> Box<dyn OperatorState> {
+    fn create_state(&self, _job_name: String, _task_number: u16) -> Box<dyn OperatorState> {
         let state = EmptyOperatorState {};
         let state: Box<dyn OperatorState> = Box::new(state);
         state
@@ -1,56 +1,92 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::path::PathBuf;
 
 use crate::api::backend::{OperatorState, OperatorStateBackend};
-use crate::runtime::ChainId;
+use crate::runtime::{ChainId, CheckpointId};
+use crate::storage::operator_state::dfs_state_manager::DFSListStateManager;
 use crate::storage::operator_state::empty_state::EmptyOperatorStateManager;
+use crate::utils;
 
+pub mod dfs_state;
+pub mod dfs_state_manager;
 pub mod empty_state;
 
-// #[derive(Clone, Debug)]
-// pub struct StateName {
-//     pub(crate) job_id: String,
-//     pub(crate) chain_id: ChainId,
-//     pub(crate) task_number: u16,
-//     pub(crate) checkpoint_id: CheckpointId,
-//     pub(crate) progress: bool,
-// }
-//
-// impl StateName {
-//     pub fn new(job_id: String, chain_id: u32, task_number: u16, checkpoint_id: u64) -> Self {
-//         StateName {
-//             job_id,
-//             chain_id,
-//             task_number,
-//             checkpoint_id,
-//             progress: false,
-//         }
-//     }
-// }
+#[derive(Clone, Debug)]
+pub struct StateName {
+    /// the states are kept by the `job_name`, so they're restored after the job is restarted
+    pub(crate) job_name: String,
+    pub(crate) chain_id: ChainId,
+    pub(crate) task_number: u16,
+    pub(crate) checkpoint_id: CheckpointId,
+    pub(crate) progress: bool,
+}
+
+impl StateName {
+    pub fn new(job_name: String, chain_id: u32, task_number: u16, checkpoint_id: u64) -> Self {
+        StateName {
+            job_name,
+            chain_id,
+            task_number,
+            checkpoint_id,
+            progress: false,
+        }
+    }
+}
+
+/// the completed `StateName`s of each checkpoint
+#[derive(Debug)]
+pub struct OperatorStateReader {
+    checkpoint_states: HashMap<CheckpointId, Vec<StateName>>,
+}
+
+impl OperatorStateReader {
+    pub fn new(checkpoint_states: HashMap<CheckpointId, Vec<StateName>>) -> Self {
+        OperatorStateReader { checkpoint_states }
+    }
+
+    pub fn get_checkpoint_states(&self) -> &HashMap<CheckpointId, Vec<StateName>> {
+        &self.checkpoint_states
+    }
+}
 
 pub(crate) trait OperatorStateManager: Clone + Debug {
-    fn create_state(&self, job_id: String, task_number: u16) -> Box<dyn OperatorState>;
+    fn create_state(&self, job_name: String, task_number: u16) -> Box<dyn OperatorState>;
 }
 
 #[derive(Clone, Debug)]
 pub enum OperatorStateManagerWrap {
     EmptyOperatorStateManager(EmptyOperatorStateManager),
+    DFSListStateManager(DFSListStateManager),
 }
 
 impl OperatorStateManagerWrap {
-    pub(crate) fn new(_chain_id: ChainId, state: OperatorStateBackend) -> Self {
+    pub(crate) fn new(chain_id: ChainId, state: OperatorStateBackend) -> Self {
         match state {
             OperatorStateBackend::None => {
                 OperatorStateManagerWrap::EmptyOperatorStateManager(EmptyOperatorStateManager {})
             }
+            OperatorStateBackend::DFSStateBackendWorkDir => {
+                let path = utils::get_work_space().join("operator_state");
+                OperatorStateManagerWrap::DFSListStateManager(DFSListStateManager::new(
+                    chain_id, path,
+                ))
+            }
+            OperatorStateBackend::DFS { path } => OperatorStateManagerWrap::DFSListStateManager(
+                DFSListStateManager::new(chain_id, PathBuf::from(path)),
+            ),
         }
     }
 }
 
 impl OperatorStateManager for OperatorStateManagerWrap {
-    fn create_state(&self, job_id: String, task_number: u16) -> Box<dyn OperatorState> {
+    fn create_state(&self, job_name: String, task_number: u16) -> Box<dyn OperatorState> {
         match self {
             OperatorStateManagerWrap::EmptyOperatorStateManager(manager) => {
-                manager.create_state(job_id, task_number)
+                manager.create_state(job_name, task_number)
+            }
+            OperatorStateManagerWrap::DFSListStateManager(manager) => {
+                manager.create_state(job_name, task_number)
             }
         }
     }
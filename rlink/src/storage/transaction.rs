@@ -0,0 +1,70 @@
+use crate::api::checkpoint::CheckpointHandle;
+
+/// a transaction of the two-phase commit sink
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub(crate) struct Transaction {
+    /// the checkpoint whose barrier began the transaction
+    pub begin_checkpoint_id: u64,
+    /// the checkpoint whose barrier pre-committed the transaction, 0 if it's in progress
+    pub prepare_checkpoint_id: u64,
+    pub handle: String,
+}
+
+/// the transactions of a sink task when the barrier reached
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub(crate) struct TransactionSnapshot {
+    /// the pre-committed transactions, they are committed after the checkpoint is completed
+    pub pending: Vec<Transaction>,
+    /// the transaction began after the barrier, it's aborted after restore
+    pub current: Option<Transaction>,
+}
+
+impl TransactionSnapshot {
+    /// the snapshot is kept in the handle of the sink in the checkpoint of the task
+    pub fn to_handle(&self) -> CheckpointHandle {
+        CheckpointHandle {
+            handle: serde_json::to_string(self).unwrap(),
+        }
+    }
+
+    pub fn from_handle(handle: &CheckpointHandle) -> serde_json::Result<Self> {
+        serde_json::from_str(handle.handle.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::checkpoint::CheckpointHandle;
+    use crate::storage::transaction::{Transaction, TransactionSnapshot};
+
+    #[test]
+    pub fn transaction_snapshot_test() {
+        let snapshot = TransactionSnapshot {
+            pending: vec![Transaction {
+                begin_checkpoint_id: 1000,
+                prepare_checkpoint_id: 2000,
+                handle: "txn-2000".to_string(),
+            }],
+            current: Some(Transaction {
+                begin_checkpoint_id: 2000,
+                prepare_checkpoint_id: 0,
+                handle: "txn-3000".to_string(),
+            }),
+        };
+
+        let handle = snapshot.to_handle();
+        let restored = TransactionSnapshot::from_handle(&handle).unwrap();
+        assert_eq!(restored.pending, snapshot.pending);
+        assert_eq!(restored.current, snapshot.current);
+
+        let empty = TransactionSnapshot::default().to_handle();
+        let restored = TransactionSnapshot::from_handle(&empty).unwrap();
+        assert!(restored.pending.is_empty());
+        assert!(restored.current.is_none());
+
+        let illegal = CheckpointHandle {
+            handle: "1000".to_string(),
+        };
+        assert!(TransactionSnapshot::from_handle(&illegal).is_err());
+    }
+}